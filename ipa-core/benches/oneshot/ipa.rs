@@ -144,6 +144,7 @@ async fn run(args: Args) -> Result<(), Error> {
         args.per_user_cap,
         args.attribution_window(),
         args.breakdown_keys,
+        false,
         &order,
     );
 
@@ -22,6 +22,7 @@ track_steps!(
             prf_sharding::step,
             shuffle::step,
             aggregation::step,
+            logistic_regression::step,
             oprf_padding::step,
             step,
         },
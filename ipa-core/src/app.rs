@@ -12,7 +12,7 @@ use crate::{
     },
     hpke::{KeyRegistry, PrivateKeyOnly},
     protocol::QueryId,
-    query::{NewQueryError, QueryProcessor, QueryStatus},
+    query::{BudgetLedger, EvidenceLog, NewQueryError, PrivacyPolicy, QueryProcessor, QueryStatus},
     sync::Arc,
     utils::NonZeroU32PowerOfTwo,
 };
@@ -21,6 +21,9 @@ use crate::{
 pub struct AppConfig {
     active_work: Option<NonZeroU32PowerOfTwo>,
     key_registry: Option<KeyRegistry<PrivateKeyOnly>>,
+    privacy_policy: PrivacyPolicy,
+    budget_ledger: Option<Arc<BudgetLedger>>,
+    evidence_log: Option<Arc<EvidenceLog>>,
     runtime: IpaRuntime,
 }
 
@@ -37,6 +40,24 @@ impl AppConfig {
         self
     }
 
+    #[must_use]
+    pub fn with_privacy_policy(mut self, privacy_policy: PrivacyPolicy) -> Self {
+        self.privacy_policy = privacy_policy;
+        self
+    }
+
+    #[must_use]
+    pub fn with_budget_ledger(mut self, budget_ledger: BudgetLedger) -> Self {
+        self.budget_ledger = Some(Arc::new(budget_ledger));
+        self
+    }
+
+    #[must_use]
+    pub fn with_evidence_log(mut self, evidence_log: EvidenceLog) -> Self {
+        self.evidence_log = Some(Arc::new(evidence_log));
+        self
+    }
+
     #[must_use]
     pub fn with_runtime(mut self, runtime: IpaRuntime) -> Self {
         self.runtime = runtime;
@@ -69,7 +90,14 @@ impl Setup {
     #[must_use]
     pub fn new(config: AppConfig) -> (Self, HandlerRef) {
         let key_registry = config.key_registry.unwrap_or_else(KeyRegistry::empty);
-        let query_processor = QueryProcessor::new(key_registry, config.active_work, config.runtime);
+        let query_processor = QueryProcessor::new(
+            key_registry,
+            config.active_work,
+            config.privacy_policy,
+            config.budget_ledger,
+            config.evidence_log,
+            config.runtime,
+        );
         let handler = HandlerBox::empty();
         let this = Self {
             query_processor,
@@ -216,6 +244,7 @@ impl RequestHandler for Inner {
                 let query_id = ext_query_id(&req)?;
                 HelperResponse::from(qp.kill(query_id)?)
             }
+            RouteId::BudgetLedger => HelperResponse::from(qp.budget_status()),
         })
     }
 }
@@ -8,17 +8,24 @@ use std::{
 };
 
 use clap::{self, Parser, Subcommand};
+use futures::future::join;
 use hyper::http::uri::Scheme;
 use ipa_core::{
     cli::{
         client_config_setup, keygen, test_setup, ConfGenArgs, KeygenArgs, LoggingHandle,
         TestSetupArgs, Verbosity,
     },
-    config::{hpke_registry, HpkeServerConfig, NetworkConfig, ServerConfig, TlsConfig},
+    config::{
+        hpke_registry, ClientConfig, HpkeServerConfig, NetworkConfig, ServerConfig, TlsConfig,
+    },
     error::BoxError,
     executor::IpaRuntime,
     helpers::HelperIdentity,
-    net::{ClientIdentity, IpaHttpClient, MpcHttpTransport, ShardHttpTransport},
+    net::{
+        ClientIdentity, ConnectionFlavor, IpaHttpClient, MpcHttpTransport, Shard,
+        ShardHttpTransport,
+    },
+    query::{BudgetLedger, EvidenceKey, EvidenceLog, PrivacyPolicy},
     sharding::ShardIndex,
     AppConfig, AppSetup, NonZeroU32PowerOfTwo,
 };
@@ -97,6 +104,71 @@ struct ServerArgs {
     /// Override the amount of active work processed in parallel
     #[arg(long)]
     active_work: Option<NonZeroU32PowerOfTwo>,
+
+    /// File containing the minimum privacy guarantees this helper accepts for queries
+    #[arg(long)]
+    privacy_policy: Option<PathBuf>,
+
+    /// File where this helper keeps track of the privacy budget spent by queries. It is created
+    /// if it does not exist.
+    #[arg(long, requires = "epoch_budget")]
+    budget_ledger: Option<PathBuf>,
+
+    /// Privacy budget (epsilon) that queries may spend for each conversion site and epoch
+    #[arg(long, requires = "budget_ledger")]
+    epoch_budget: Option<f64>,
+
+    /// File where this helper records the evidence of failed malicious security checks. It is
+    /// created if it does not exist.
+    #[arg(long, requires = "evidence_key")]
+    evidence_log: Option<PathBuf>,
+
+    /// File with the hex-encoded 32 byte key that authenticates entries of the evidence log
+    #[arg(long, requires = "evidence_log")]
+    evidence_key: Option<PathBuf>,
+
+    #[clap(flatten)]
+    shard: ShardArgs,
+}
+
+/// Every helper party can be deployed as several cooperating shard processes. Each of them serves
+/// MPC traffic on `--port` and shard-to-shard traffic on `--shard-port`. Without a shard network
+/// configuration, the helper runs as a single shard.
+#[derive(Debug, clap::Args)]
+#[group(requires = "shard_network")]
+struct ShardArgs {
+    /// File containing the network configuration of all the shards of this helper, in shard
+    /// index order
+    #[arg(long, requires_all = ["shard_index", "shard_count"])]
+    shard_network: Option<PathBuf>,
+
+    /// Index of this shard, starting from 0
+    #[arg(long)]
+    shard_index: Option<u32>,
+
+    /// Number of shards of this helper. It must match the number of peers in the shard network
+    /// configuration and the number of shards of the other helpers.
+    #[arg(long)]
+    shard_count: Option<u32>,
+
+    /// Port to listen on for shard-to-shard traffic
+    #[arg(long, default_value = "6000")]
+    shard_port: Option<u16>,
+
+    /// Use the supplied prebound socket for shard-to-shard traffic instead of binding a new socket
+    ///
+    /// This is only intended for avoiding port conflicts in tests.
+    #[arg(hide = true, long)]
+    shard_server_socket_fd: Option<RawFd>,
+
+    /// TLS certificate for shard-to-shard communication. Defaults to the helper-to-helper
+    /// certificate.
+    #[arg(long, requires = "shard_tls_key")]
+    shard_tls_cert: Option<PathBuf>,
+
+    /// TLS key for shard-to-shard communication. Defaults to the helper-to-helper key.
+    #[arg(long, requires = "shard_tls_cert")]
+    shard_tls_key: Option<PathBuf>,
 }
 
 #[derive(Debug, Subcommand)]
@@ -114,34 +186,191 @@ fn read_file(path: &Path) -> Result<BufReader<fs::File>, BoxError> {
         .map_err(|e| format!("failed to open file {}: {e:?}", path.display()))?)
 }
 
-async fn server(args: ServerArgs, logging_handle: LoggingHandle) -> Result<(), BoxError> {
-    let my_identity = HelperIdentity::try_from(args.identity.expect("enforced by clap")).unwrap();
+/// Reads the TLS identity from the given certificate and key files.
+fn tls_identity<F: ConnectionFlavor>(
+    cert_file: PathBuf,
+    key_file: PathBuf,
+) -> Result<(ClientIdentity<F>, TlsConfig), BoxError> {
+    let mut key = read_file(&key_file)?;
+    let mut certs = read_file(&cert_file)?;
+    Ok((
+        ClientIdentity::from_pkcs8(&mut certs, &mut key)?,
+        TlsConfig::File {
+            certificate_file: cert_file,
+            private_key_file: key_file,
+        },
+    ))
+}
 
-    let (identity, server_tls) = match (args.tls_cert, args.tls_key) {
-        (Some(cert_file), Some(key_file)) => {
-            let mut key = read_file(&key_file)?;
-            let mut certs = read_file(&cert_file)?;
-            (
-                ClientIdentity::from_pkcs8(&mut certs, &mut key)?,
-                Some(TlsConfig::File {
-                    certificate_file: cert_file,
-                    private_key_file: key_file,
-                }),
+fn adopt_listener(fd: Option<RawFd>) -> Result<Option<TcpListener>, BoxError> {
+    fd.map(|fd| {
+        // SAFETY:
+        //  1. The `--server-socket-fd` and `--shard-server-socket-fd` options are only intended
+        //     for use in tests, not in production.
+        //  2. This must be the only call to from_raw_fd for this file descriptor, to ensure it has
+        //     only one owner.
+        let listener = unsafe { TcpListener::from_raw_fd(fd) };
+        if listener.local_addr().is_ok() {
+            info!("adopting fd {fd} as listening socket");
+            Ok(listener)
+        } else {
+            Err(BoxError::from(format!("the server was asked to listen on fd {fd}, but it does not appear to be a valid socket")))
+        }
+    })
+    .transpose()
+}
+
+/// Shard network of this helper, along with the identity of this shard in it.
+struct ShardSetup {
+    index: ShardIndex,
+    network_config: NetworkConfig<Shard>,
+    server_config: ServerConfig,
+    identity: ClientIdentity<Shard>,
+}
+
+impl ShardSetup {
+    /// Reads the shard configuration from the command line arguments.
+    ///
+    /// If this helper is not sharded, it is the only shard in its network. Its shard server is
+    /// never started, because there are no other shards to talk to.
+    fn new(
+        args: ShardArgs,
+        disable_https: bool,
+        helper_tls: Option<(PathBuf, PathBuf)>,
+        mpc_client_config: &ClientConfig,
+    ) -> Result<Self, BoxError> {
+        let Some(network_path) = args.shard_network else {
+            return Ok(Self {
+                index: ShardIndex::FIRST,
+                network_config: NetworkConfig::new_shards(vec![], mpc_client_config.clone()),
+                server_config: ServerConfig {
+                    port: args.shard_port,
+                    disable_https,
+                    tls: None,
+                    hpke_config: None,
+                },
+                identity: ClientIdentity::None,
+            });
+        };
+
+        let scheme = if disable_https {
+            Scheme::HTTP
+        } else {
+            Scheme::HTTPS
+        };
+        let network_config =
+            NetworkConfig::shards_from_toml_str(&fs::read_to_string(&network_path)?)?
+                .override_scheme(&scheme);
+        let index = ShardIndex::from(args.shard_index.expect("enforced by clap"));
+        let shard_count = ShardIndex::from(args.shard_count.expect("enforced by clap"));
+        if network_config.shard_count() != shard_count {
+            return Err(format!(
+                "shard count is {shard_count}, but {} lists {} shards",
+                network_path.display(),
+                network_config.shard_count()
             )
+            .into());
+        }
+        if index >= shard_count {
+            return Err(format!("shard index {index} must be less than {shard_count}").into());
         }
-        (None, None) => (ClientIdentity::Header(my_identity), None),
+
+        let (identity, tls) = match (args.shard_tls_cert, args.shard_tls_key, helper_tls) {
+            (Some(cert_file), Some(key_file), _) | (None, None, Some((cert_file, key_file))) => {
+                let (identity, tls) = tls_identity(cert_file, key_file)?;
+                (identity, Some(tls))
+            }
+            (None, None, None) => (ClientIdentity::Header(index), None),
+            _ => panic!("should have been rejected by clap"),
+        };
+
+        Ok(Self {
+            index,
+            network_config,
+            server_config: ServerConfig {
+                port: args.shard_port,
+                disable_https,
+                tls,
+                hpke_config: None,
+            },
+            identity,
+        })
+    }
+
+    fn is_sharded(&self) -> bool {
+        self.network_config.shard_count() > ShardIndex::from(1)
+    }
+}
+
+async fn server(args: ServerArgs, logging_handle: LoggingHandle) -> Result<(), BoxError> {
+    let my_identity = HelperIdentity::try_from(args.identity.expect("enforced by clap")).unwrap();
+
+    let helper_tls = match (args.tls_cert, args.tls_key) {
+        (Some(cert_file), Some(key_file)) => Some((cert_file, key_file)),
+        (None, None) => None,
         _ => panic!("should have been rejected by clap"),
     };
+    let (identity, server_tls) = match helper_tls.clone() {
+        Some((cert_file, key_file)) => {
+            let (identity, tls) = tls_identity(cert_file, key_file)?;
+            (identity, Some(tls))
+        }
+        None => (ClientIdentity::Header(my_identity), None),
+    };
 
     let mk_encryption = args.mk_private_key.map(|sk_path| HpkeServerConfig::File {
         private_key_file: sk_path,
     });
 
+    let privacy_policy = match args.privacy_policy {
+        Some(path) => toml::from_str::<PrivacyPolicy>(&fs::read_to_string(path)?)?,
+        None => PrivacyPolicy::default(),
+    };
+    info!("Privacy policy: {privacy_policy:?}");
+
+    let scheme = if args.disable_https {
+        Scheme::HTTP
+    } else {
+        Scheme::HTTPS
+    };
+    let network_config_path = args.network.as_deref().unwrap();
+    let network_config = NetworkConfig::from_toml_str(&fs::read_to_string(network_config_path)?)?
+        .override_scheme(&scheme);
+
+    let shard_server_socket_fd = args.shard.shard_server_socket_fd;
+    let shard_setup = ShardSetup::new(
+        args.shard,
+        args.disable_https,
+        helper_tls,
+        &network_config.client,
+    )?;
+    info!(
+        "Running as shard {} of {}",
+        shard_setup.index,
+        shard_setup
+            .network_config
+            .shard_count()
+            .max(ShardIndex::from(1))
+    );
+
     let query_runtime = new_query_runtime(&logging_handle);
-    let app_config = AppConfig::default()
+    let mut app_config = AppConfig::default()
         .with_key_registry(hpke_registry(mk_encryption.as_ref()).await?)
         .with_active_work(args.active_work)
+        .with_privacy_policy(privacy_policy)
         .with_runtime(IpaRuntime::from_tokio_runtime(&query_runtime));
+    if let (Some(path), Some(epoch_budget)) = (args.budget_ledger, args.epoch_budget) {
+        info!(
+            "Privacy budget ledger: {}, epoch budget: {epoch_budget}",
+            path.display()
+        );
+        app_config = app_config.with_budget_ledger(BudgetLedger::open(path, epoch_budget)?);
+    }
+    if let (Some(path), Some(key_path)) = (args.evidence_log, args.evidence_key) {
+        info!("Evidence log: {}", path.display());
+        let key = EvidenceKey::from_hex(&fs::read_to_string(key_path)?)?;
+        app_config = app_config.with_evidence_log(EvidenceLog::open(path, key)?);
+    }
 
     let (setup, handler) = AppSetup::new(app_config);
 
@@ -152,20 +381,6 @@ async fn server(args: ServerArgs, logging_handle: LoggingHandle) -> Result<(), B
         hpke_config: mk_encryption,
     };
 
-    let scheme = if args.disable_https {
-        Scheme::HTTP
-    } else {
-        Scheme::HTTPS
-    };
-    let network_config_path = args.network.as_deref().unwrap();
-    let network_config = NetworkConfig::from_toml_str(&fs::read_to_string(network_config_path)?)?
-        .override_scheme(&scheme);
-
-    // TODO: Following is just temporary until Shard Transport is actually used.
-    let shard_clients_config = network_config.client.clone();
-    let shard_server_config = server_config.clone();
-    // ---
-
     let http_runtime = new_http_runtime(&logging_handle);
     let clients = IpaHttpClient::from_conf(
         &IpaRuntime::from_tokio_runtime(&http_runtime),
@@ -181,35 +396,25 @@ async fn server(args: ServerArgs, logging_handle: LoggingHandle) -> Result<(), B
         Some(handler),
     );
 
-    // TODO: Following is just temporary until Shard Transport is actually used.
-    let shard_network_config = NetworkConfig::new_shards(vec![], shard_clients_config);
-    let (shard_transport, _shard_server) = ShardHttpTransport::new(
+    let is_sharded = shard_setup.is_sharded();
+    let shard_clients = IpaHttpClient::<Shard>::shards_from_conf(
+        &IpaRuntime::from_tokio_runtime(&http_runtime),
+        &shard_setup.network_config,
+        &shard_setup.identity,
+    );
+    let (shard_transport, shard_server) = ShardHttpTransport::new(
         IpaRuntime::from_tokio_runtime(&http_runtime),
-        ShardIndex::FIRST,
-        shard_server_config,
-        shard_network_config,
-        vec![],
+        shard_setup.index,
+        shard_setup.server_config,
+        shard_setup.network_config,
+        shard_clients,
         None,
     );
-    // ---
 
     let _app = setup.connect(transport.clone(), shard_transport.clone());
 
-    let listener = args.server_socket_fd
-        .map(|fd| {
-            // SAFETY:
-            //  1. The `--server-socket-fd` option is only intended for use in tests, not in production.
-            //  2. This must be the only call to from_raw_fd for this file descriptor, to ensure it has
-            //     only one owner.
-            let listener = unsafe { TcpListener::from_raw_fd(fd) };
-            if listener.local_addr().is_ok() {
-                info!("adopting fd {fd} as listening socket");
-                Ok(listener)
-            } else {
-                Err(BoxError::from(format!("the server was asked to listen on fd {fd}, but it does not appear to be a valid socket")))
-            }
-        })
-        .transpose()?;
+    let listener = adopt_listener(args.server_socket_fd)?;
+    let shard_listener = adopt_listener(shard_server_socket_fd)?;
 
     let (_addr, server_handle) = server
         .start_on(
@@ -220,7 +425,19 @@ async fn server(args: ServerArgs, logging_handle: LoggingHandle) -> Result<(), B
         )
         .await;
 
-    server_handle.await;
+    if is_sharded {
+        let (shard_addr, shard_server_handle) = shard_server
+            .start_on(
+                &IpaRuntime::from_tokio_runtime(&http_runtime),
+                shard_listener,
+                None as Option<()>,
+            )
+            .await;
+        info!("Listening for shard-to-shard traffic on {shard_addr}");
+        join(server_handle, shard_server_handle).await;
+    } else {
+        server_handle.await;
+    }
     [query_runtime, http_runtime].map(Runtime::shutdown_background);
 
     Ok(())
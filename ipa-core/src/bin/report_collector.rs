@@ -14,14 +14,14 @@ use hyper::http::uri::Scheme;
 use ipa_core::{
     cli::{
         playbook::{
-            make_clients, playbook_oprf_ipa, run_query_and_validate, validate, validate_dp,
-            InputSource,
+            create_sharded_query, make_sharded_clients, playbook_oprf_ipa, run_query_and_validate,
+            validate, validate_dp, InputSource, ShardDistribution,
         },
         CsvSerializer, IpaQueryResult, Verbosity,
     },
-    config::{KeyRegistries, NetworkConfig},
+    config::{KeyRegistries, ShardedNetworkConfig},
     ff::{boolean_array::BA32, FieldType},
-    helpers::query::{DpMechanism, IpaQueryConfig, QueryConfig, QuerySize, QueryType},
+    helpers::query::{IpaQueryConfig, QueryConfig, QuerySize, QueryType},
     net::{Helper, IpaHttpClient},
     report::{EncryptedOprfReportStreams, DEFAULT_KEY_ID},
     test_fixture::{
@@ -39,10 +39,15 @@ struct Args {
     #[clap(flatten)]
     logging: Verbosity,
 
-    /// Path to helper network configuration file
+    /// Path to helper network configuration file. If helpers are sharded, it must list the
+    /// MPC ring of every shard.
     #[arg(long)]
     network: Option<PathBuf>,
 
+    /// How to split input records between the shards of every helper
+    #[arg(long, value_enum, default_value_t = InputDistribution::RoundRobin)]
+    input_distribution: InputDistribution,
+
     /// Use insecure HTTP
     #[arg(short = 'k', long)]
     disable_https: bool,
@@ -62,6 +67,15 @@ struct Args {
     action: ReportCollectorCommand,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum InputDistribution {
+    /// Record `i` goes to shard `i mod shard_count`
+    RoundRobin,
+    /// All records of one user go to the same shard. Only supported for test data, because
+    /// encrypted reports do not reveal the user.
+    UserId,
+}
+
 #[derive(Debug, Parser)]
 pub struct CommandInput {
     #[arg(
@@ -169,7 +183,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Scheme::HTTPS
     };
 
-    let (clients, network) = make_clients(args.network.as_deref(), scheme, args.wait).await;
+    let (clients, network) = make_sharded_clients(args.network.as_deref(), scheme, args.wait).await;
     match args.action {
         ReportCollectorCommand::GenIpaInputs {
             count,
@@ -181,34 +195,34 @@ async fn main() -> Result<(), Box<dyn Error>> {
             seed,
             gen_args,
         } => gen_hybrid_inputs(count, seed, args.output_file, gen_args)?,
-        ReportCollectorCommand::SemiHonestOprfIpaTest(config) => {
+        ReportCollectorCommand::SemiHonestOprfIpaTest(ref config) => {
             ipa_test(
                 &args,
                 &network,
                 IpaSecurityModel::SemiHonest,
-                config,
+                config.clone(),
                 &clients,
             )
             .await?
         }
-        ReportCollectorCommand::MaliciousOprfIpaTest(config) => {
+        ReportCollectorCommand::MaliciousOprfIpaTest(ref config) => {
             ipa_test(
                 &args,
                 &network,
                 IpaSecurityModel::Malicious,
-                config,
+                config.clone(),
                 &clients,
             )
             .await?
         }
         ReportCollectorCommand::MaliciousOprfIpa {
             ref encrypted_inputs,
-            ipa_query_config,
+            ref ipa_query_config,
         } => {
             ipa(
                 &args,
                 IpaSecurityModel::Malicious,
-                ipa_query_config,
+                ipa_query_config.clone(),
                 &clients,
                 encrypted_inputs,
             )
@@ -216,12 +230,12 @@ async fn main() -> Result<(), Box<dyn Error>> {
         }
         ReportCollectorCommand::SemiHonestOprfIpa {
             ref encrypted_inputs,
-            ipa_query_config,
+            ref ipa_query_config,
         } => {
             ipa(
                 &args,
                 IpaSecurityModel::SemiHonest,
-                ipa_query_config,
+                ipa_query_config.clone(),
                 &clients,
                 encrypted_inputs,
             )
@@ -283,10 +297,20 @@ fn gen_inputs(
     Ok(())
 }
 
-fn get_query_type(security_model: IpaSecurityModel, ipa_query_config: IpaQueryConfig) -> QueryType {
-    match security_model {
-        IpaSecurityModel::SemiHonest => QueryType::SemiHonestOprfIpa(ipa_query_config),
-        IpaSecurityModel::Malicious => QueryType::MaliciousOprfIpa(ipa_query_config),
+/// Helpers split into several shards run the sharded version of IPA, which brings together the
+/// input of all shards.
+fn get_query_type(
+    security_model: IpaSecurityModel,
+    ipa_query_config: IpaQueryConfig,
+    shard_count: usize,
+) -> QueryType {
+    match (security_model, shard_count > 1) {
+        (IpaSecurityModel::SemiHonest, false) => QueryType::SemiHonestOprfIpa(ipa_query_config),
+        (IpaSecurityModel::Malicious, false) => QueryType::MaliciousOprfIpa(ipa_query_config),
+        (IpaSecurityModel::SemiHonest, true) => {
+            QueryType::SemiHonestShardedOprfIpa(ipa_query_config)
+        }
+        (IpaSecurityModel::Malicious, true) => QueryType::MaliciousShardedOprfIpa(ipa_query_config),
     }
 }
 
@@ -333,10 +357,14 @@ async fn ipa(
     args: &Args,
     security_model: IpaSecurityModel,
     ipa_query_config: IpaQueryConfig,
-    helper_clients: &[IpaHttpClient<Helper>; 3],
+    helper_clients: &[[IpaHttpClient<Helper>; 3]],
     encrypted_inputs: &EncryptedInputs,
 ) -> Result<(), Box<dyn Error>> {
-    let query_type = get_query_type(security_model, ipa_query_config);
+    let query_type = get_query_type(
+        security_model,
+        ipa_query_config.clone(),
+        helper_clients.len(),
+    );
 
     let files = [
         &encrypted_inputs.enc_input_file1,
@@ -344,31 +372,38 @@ async fn ipa(
         &encrypted_inputs.enc_input_file3,
     ];
 
-    let encrypted_oprf_report_streams = EncryptedOprfReportStreams::from(files);
+    if args.input_distribution != InputDistribution::RoundRobin {
+        return Err("encrypted inputs can only be distributed between shards round-robin".into());
+    }
+    let shard_streams = EncryptedOprfReportStreams::from_files_sharded(files, helper_clients.len());
+    let query_size = shard_streams
+        .iter()
+        .map(|streams| streams.query_size)
+        .sum::<usize>();
 
     let query_config = QueryConfig {
-        size: QuerySize::try_from(encrypted_oprf_report_streams.query_size).unwrap(),
+        size: QuerySize::try_from(query_size).unwrap(),
         field_type: FieldType::Fp32BitPrime,
         query_type,
     };
 
-    let query_id = helper_clients[0]
-        .create_query(query_config)
-        .await
-        .expect("Unable to create query!");
+    let query_id = create_sharded_query(helper_clients, query_config).await;
 
     tracing::info!("Starting query for OPRF");
     // the value for histogram values (BA32) must be kept in sync with the server-side
     // implementation, otherwise a runtime reconstruct error will be generated.
     // see ipa-core/src/query/executor.rs
     let actual = run_query_and_validate::<BA32>(
-        encrypted_oprf_report_streams.streams,
-        encrypted_oprf_report_streams.query_size,
+        shard_streams
+            .into_iter()
+            .map(|streams| streams.streams)
+            .collect(),
+        query_size,
         helper_clients,
         query_id,
         ipa_query_config,
     )
-    .await;
+    .await?;
 
     if let Some(ref path) = args.output_file {
         write_ipa_output_file(path, &actual)?;
@@ -380,13 +415,17 @@ async fn ipa(
 
 async fn ipa_test(
     args: &Args,
-    network: &NetworkConfig<Helper>,
+    network: &ShardedNetworkConfig,
     security_model: IpaSecurityModel,
     ipa_query_config: IpaQueryConfig,
-    helper_clients: &[IpaHttpClient<Helper>; 3],
+    helper_clients: &[[IpaHttpClient<Helper>; 3]],
 ) -> Result<(), Box<dyn Error>> {
     let input = InputSource::from(&args.input);
-    let query_type = get_query_type(security_model, ipa_query_config);
+    let query_type = get_query_type(
+        security_model,
+        ipa_query_config.clone(),
+        helper_clients.len(),
+    );
 
     let input_rows = input.iter::<TestRawDataRecord>().collect::<Vec<_>>();
     let query_config = QueryConfig {
@@ -394,10 +433,7 @@ async fn ipa_test(
         field_type: FieldType::Fp32BitPrime,
         query_type,
     };
-    let query_id = helper_clients[0]
-        .create_query(query_config)
-        .await
-        .expect("Unable to create query!");
+    let query_id = create_sharded_query(helper_clients, query_config).await;
 
     let expected = {
         let mut r = ipa_in_the_clear(
@@ -405,33 +441,42 @@ async fn ipa_test(
             ipa_query_config.per_user_credit_cap,
             ipa_query_config.attribution_window_seconds,
             ipa_query_config.max_breakdown_key,
+            ipa_query_config.unattributed_bucket,
             &CappingOrder::CapMostRecentFirst,
         );
 
         // pad the output vector to the max breakdown key, to make sure it is aligned with the MPC results
         // truncate shouldn't happen unless in_the_clear is badly broken
         r.resize(
-            usize::try_from(ipa_query_config.max_breakdown_key).unwrap(),
+            usize::try_from(ipa_query_config.max_breakdown_key).unwrap()
+                + usize::from(ipa_query_config.unattributed_bucket),
             0,
         );
         r
     };
 
     let mut key_registries = KeyRegistries::default();
-    let Some(key_registries) = key_registries.init_from(network) else {
+    // all shards of a helper share its match key encryption keys
+    let Some(key_registries) = key_registries.init_from(network.leader()) else {
         panic!("could not load network file")
     };
     // the value for histogram values (BA32) must be kept in sync with the server-side
     // implementation, otherwise a runtime reconstruct error will be generated.
     // see ipa-core/src/query/executor.rs
+    let user_id = |record: &TestRawDataRecord| record.user_id;
+    let distribution = match args.input_distribution {
+        InputDistribution::RoundRobin => ShardDistribution::RoundRobin,
+        InputDistribution::UserId => ShardDistribution::ByKey(&user_id),
+    };
     let actual = playbook_oprf_ipa::<BA32, _>(
         input_rows,
         helper_clients,
         query_id,
-        ipa_query_config,
+        ipa_query_config.clone(),
         Some((DEFAULT_KEY_ID, key_registries)),
+        &distribution,
     )
-    .await;
+    .await?;
 
     if let Some(ref path) = args.output_file {
         write_ipa_output_file(path, &actual)?;
@@ -449,9 +494,7 @@ async fn ipa_test(
                 actual.breakdowns,
                 ipa_query_config.epsilon,
                 ipa_query_config.per_user_credit_cap,
-                DpMechanism::DiscreteLaplace {
-                    epsilon: ipa_query_config.epsilon,
-                },
+                ipa_query_config.dp_mechanism()?,
             );
         }
     }
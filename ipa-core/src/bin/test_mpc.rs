@@ -5,13 +5,15 @@ use generic_array::ArrayLength;
 use hyper::http::uri::Scheme;
 use ipa_core::{
     cli::{
-        playbook::{make_clients, secure_add, secure_mul, validate, InputSource},
+        playbook::{make_clients, secure_add, secure_mul, secure_shuffle, validate, InputSource},
         Verbosity,
     },
-    ff::{Field, FieldType, Fp31, Fp32BitPrime, Serializable, U128Conversions},
+    ff::{
+        boolean_array::BA32, Field, FieldType, Fp31, Fp32BitPrime, Serializable, U128Conversions,
+    },
     helpers::query::{
         QueryConfig,
-        QueryType::{TestAddInPrimeField, TestMultiply},
+        QueryType::{TestAddInPrimeField, TestMultiply, TestShardedShuffle},
     },
     net::{Helper, IpaHttpClient},
     secret_sharing::{replicated::semi_honest::AdditiveShare, IntoShares},
@@ -89,7 +91,11 @@ enum TestAction {
     /// both shard-to-shard and helper-to-helper communication channels.
     /// This is exactly what shuffle does and that's why it is picked
     /// for this purpose.
-    ShardedShuffle,
+    ShardedShuffle {
+        /// Helper network configuration files of every shard, in the order of shard indices
+        #[arg(long, required = true, num_args = 1..)]
+        shard_networks: Vec<PathBuf>,
+    },
 }
 
 #[tokio::main]
@@ -103,11 +109,16 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Scheme::HTTPS
     };
 
+    if let TestAction::ShardedShuffle { shard_networks } = &args.action {
+        sharded_shuffle(&args, shard_networks, &scheme).await;
+        return Ok(());
+    }
+
     let (clients, _) = make_clients(args.network.as_deref(), scheme, args.wait).await;
     match args.action {
         TestAction::Multiply => multiply(&args, &clients).await,
         TestAction::AddInPrimeField => add(&args, &clients).await,
-        TestAction::ShardedShuffle => sharded_shuffle(&args, &clients).await,
+        TestAction::ShardedShuffle { .. } => unreachable!(),
     };
 
     Ok(())
@@ -166,6 +177,25 @@ async fn add(args: &Args, helper_clients: &[IpaHttpClient<Helper>; 3]) {
     };
 }
 
-async fn sharded_shuffle(_args: &Args, _helper_clients: &[IpaHttpClient<Helper>; 3]) {
-    unimplemented!()
+async fn sharded_shuffle(args: &Args, shard_networks: &[PathBuf], scheme: &Scheme) {
+    let mut clients = Vec::with_capacity(shard_networks.len());
+    for network in shard_networks {
+        let (shard_clients, _) = make_clients(Some(network), scheme.clone(), args.wait).await;
+        clients.push(shard_clients);
+    }
+
+    let input = InputSource::from(&args.input);
+    let input_rows = input
+        .known_size_iter()
+        .map(BA32::truncate_from)
+        .collect::<Vec<_>>();
+    let query_config =
+        QueryConfig::new(TestShardedShuffle, args.input.field, input_rows.len()).unwrap();
+    let mut actual = secure_shuffle(input_rows.clone(), &clients, query_config).await;
+
+    // shuffle output must be a permutation of the input
+    let mut expected = input_rows;
+    expected.sort_by_key(U128Conversions::as_u128);
+    actual.sort_by_key(U128Conversions::as_u128);
+    validate(&expected, &actual);
 }
@@ -204,7 +204,7 @@ mod tests {
         .await;
 
         assert_eq!(
-            results.reconstruct()[0..3]
+            results.map(|output| output.values).reconstruct()[0..3]
                 .iter()
                 .map(U128Conversions::as_u128)
                 .collect::<Vec<u128>>(),
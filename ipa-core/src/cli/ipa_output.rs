@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
-use crate::helpers::query::{IpaQueryConfig, QuerySize};
+use crate::helpers::query::{IpaQueryConfig, NoisyHistogram, QuerySize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct QueryResult {
@@ -13,5 +13,38 @@ pub struct QueryResult {
         deserialize_with = "crate::serde::duration::from_secs"
     )]
     pub latency: Duration,
+    /// Total value attributed to each breakdown. If the query has an unattributed bucket, the
+    /// total value of the trigger events that were not attributed is the last entry.
     pub breakdowns: Vec<u32>,
+    /// Noise added to `breakdowns`. Not set if the query ran without DP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise: Option<NoiseMetadata>,
+}
+
+/// Describes the DP noise in each breakdown, so results can be shown with error bars.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoiseMetadata {
+    /// Noise that helpers reported to have added to the histogram.
+    pub histogram: NoisyHistogram,
+    /// Mean of the noise added to each breakdown.
+    pub mean: f64,
+    /// Standard deviation of the noise added to each breakdown.
+    pub std: f64,
+    /// Value of each breakdown with the mean of the noise removed. Unlike `breakdowns`, negative
+    /// values are not wrapped around. Not set for suppressed breakdowns.
+    pub estimates: Vec<Option<f64>>,
+    /// 95% confidence interval for the true value of each breakdown, in the same order as
+    /// `breakdowns`. Not set for suppressed breakdowns.
+    pub confidence_intervals: Vec<Option<(f64, f64)>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppression: Option<Suppression>,
+}
+
+/// Breakdowns that helpers suppressed because they were sparse.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Suppression {
+    /// Breakdowns with an estimate below this value were reported as zero.
+    pub threshold: f64,
+    /// Breakdowns reported as zero, which may have been suppressed.
+    pub suppressed: Vec<usize>,
 }
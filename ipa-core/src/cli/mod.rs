@@ -16,7 +16,7 @@ mod verbosity;
 #[cfg(feature = "web-app")]
 pub use clientconf::{setup as client_config_setup, ConfGenArgs};
 pub use csv::Serializer as CsvSerializer;
-pub use ipa_output::QueryResult as IpaQueryResult;
+pub use ipa_output::{NoiseMetadata, QueryResult as IpaQueryResult, Suppression};
 #[cfg(feature = "web-app")]
 pub use keygen::{keygen, KeygenArgs};
 pub use metric_collector::{install_collector, CollectorHandle};
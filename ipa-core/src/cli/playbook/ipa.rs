@@ -5,7 +5,7 @@ use std::{
     time::{Duration, Instant},
 };
 
-use futures_util::future::try_join_all;
+use futures_util::future::{try_join, try_join_all};
 use generic_array::GenericArray;
 use rand::rngs::StdRng;
 use rand_core::SeedableRng;
@@ -14,12 +14,12 @@ use typenum::Unsigned;
 
 use crate::{
     cli::{
-        playbook::{BreakdownKey, Timestamp, TriggerValue},
-        IpaQueryResult,
+        playbook::{sharding::ShardDistribution, BreakdownKey, Timestamp, TriggerValue},
+        IpaQueryResult, NoiseMetadata, Suppression,
     },
     ff::{Serializable, U128Conversions},
     helpers::{
-        query::{IpaQueryConfig, QueryInput, QuerySize},
+        query::{IpaQueryConfig, OutputDp, QueryInput, QuerySize},
         BodyStream,
     },
     hpke::PublicKeyRegistry,
@@ -28,25 +28,51 @@ use crate::{
     query::QueryStatus,
     report::{KeyIdentifier, OprfReport},
     secret_sharing::{replicated::semi_honest::AdditiveShare, IntoShares, SharedValue},
+    sharding::ShardIndex,
     test_fixture::{ipa::TestRawDataRecord, Reconstruct},
 };
 
 /// Executes the IPA v3 protocol.
 ///
+/// `clients` has one set of helper clients per shard, in the order of shard indices. Records
+/// are split between shards according to `distribution`.
+///
+/// ## Errors
+/// If helpers report DP that does not describe the results, see [`run_query_and_validate`].
+///
 /// ## Panics
 /// If report encryption fails
 pub async fn playbook_oprf_ipa<HV, KR>(
     records: Vec<TestRawDataRecord>,
-    clients: &[IpaHttpClient<Helper>; 3],
+    clients: &[[IpaHttpClient<Helper>; 3]],
     query_id: QueryId,
     query_config: IpaQueryConfig,
     encryption: Option<(KeyIdentifier, [&KR; 3])>,
-) -> IpaQueryResult
+    distribution: &ShardDistribution<'_, TestRawDataRecord>,
+) -> Result<IpaQueryResult, OutputDpError>
 where
     HV: SharedValue + U128Conversions,
     AdditiveShare<HV>: Serializable,
     KR: PublicKeyRegistry,
 {
+    let query_size = records.len();
+    let shard_count = ShardIndex::try_from(clients.len()).unwrap();
+    let inputs = distribution
+        .split(records, shard_count)
+        .into_iter()
+        .map(|records| shard_input(&records, &query_config, encryption))
+        .collect();
+    tracing::info!("Starting query for OPRF");
+
+    run_query_and_validate::<HV>(inputs, query_size, clients, query_id, query_config).await
+}
+
+/// Shares or encrypts `records` for all helpers.
+fn shard_input<KR: PublicKeyRegistry>(
+    records: &[TestRawDataRecord],
+    query_config: &IpaQueryConfig,
+    encryption: Option<(KeyIdentifier, [&KR; 3])>,
+) -> [BodyStream; 3] {
     let mut buffers: [_; 3] = std::array::from_fn(|_| Vec::new());
     let query_size = records.len();
 
@@ -89,31 +115,47 @@ where
         )
     }
 
-    let inputs = buffers.map(BodyStream::from);
-    tracing::info!("Starting query for OPRF");
-
-    run_query_and_validate::<HV>(inputs, query_size, clients, query_id, query_config).await
+    buffers.map(BodyStream::from)
 }
 
+/// Sends `inputs` to every shard, waits for the query to complete and collects the results.
+///
+/// `inputs` and `clients` have one entry per shard, in the order of shard indices. Only the
+/// leader shard returns the histogram; results of the other shards are fetched to let them
+/// finish the query, and discarded. With more than one shard, the query must be one of the
+/// sharded IPA query types, which [`create_sharded_query`] enforces.
+///
+/// [`create_sharded_query`]: crate::cli::playbook::create_sharded_query
+///
+/// # Errors
+/// If helpers disagree on the DP they applied to the results, or report DP parameters that
+/// are out of bounds.
+///
 /// # Panics
-/// if results are invalid
+/// if the query fails on any helper or results are invalid
 #[allow(clippy::disallowed_methods)] // allow try_join_all
 pub async fn run_query_and_validate<HV>(
-    inputs: [BodyStream; 3],
+    inputs: Vec<[BodyStream; 3]>,
     query_size: usize,
-    clients: &[IpaHttpClient<Helper>; 3],
+    clients: &[[IpaHttpClient<Helper>; 3]],
     query_id: QueryId,
     query_config: IpaQueryConfig,
-) -> IpaQueryResult
+) -> Result<IpaQueryResult, OutputDpError>
 where
     HV: SharedValue + U128Conversions,
     AdditiveShare<HV>: Serializable,
 {
+    assert_eq!(
+        inputs.len(),
+        clients.len(),
+        "every shard must get its own input"
+    );
     let mpc_time = Instant::now();
     try_join_all(
         inputs
             .into_iter()
             .zip(clients)
+            .flat_map(|(shard_inputs, shard_clients)| zip(shard_inputs, shard_clients))
             .map(|(input_stream, client)| {
                 client.query_input(QueryInput {
                     query_id,
@@ -126,12 +168,18 @@ where
 
     let mut delay = Duration::from_millis(125);
     loop {
-        if try_join_all(clients.iter().map(|client| client.query_status(query_id)))
-            .await
-            .unwrap()
-            .into_iter()
-            .all(|status| status == QueryStatus::Completed)
+        if try_join_all(
+            clients
+                .iter()
+                .flatten()
+                .map(|client| client.query_status(query_id)),
+        )
+        .await
+        .unwrap()
+        .into_iter()
+        .all(|status| matches!(status, QueryStatus::Completed | QueryStatus::Failed))
         {
+            // if the query failed, requesting its results below reports the error
             break;
         }
 
@@ -141,12 +189,30 @@ where
         // the status API so we can check whether the query is making progress.
     }
 
-    // wait until helpers have processed the query and get the results from them
-    let results: [_; 3] = try_join_all(clients.iter().map(|client| client.query_results(query_id)))
-        .await
-        .unwrap()
-        .try_into()
-        .unwrap();
+    // wait until helpers have processed the query and get the results from them. Only the
+    // leader shard has the histogram, but every shard holds on to the query until its results
+    // are fetched.
+    let (leader, followers) = clients.split_first().unwrap();
+    let (results, _) = try_join(
+        try_join_all(
+            leader
+                .iter()
+                .map(|client| client.query_results_with_dp(query_id)),
+        ),
+        try_join_all(
+            followers
+                .iter()
+                .flatten()
+                .map(|client| client.query_results(query_id)),
+        ),
+    )
+    .await
+    .unwrap();
+    let (results, output_dp): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+    let results: [_; 3] = results.try_into().unwrap();
+    if output_dp.iter().any(|dp| dp != &output_dp[0]) {
+        return Err(OutputDpError::Mismatch);
+    }
 
     let results: Vec<HV> = results
         .map(|bytes| {
@@ -159,28 +225,178 @@ where
     let lat = mpc_time.elapsed();
 
     tracing::info!("Running IPA for {query_size:?} records took {t:?}", t = lat);
-    let mut breakdowns = vec![0; usize::try_from(query_config.max_breakdown_key).unwrap()];
+    // The unattributed bucket, if any, comes right after the last breakdown.
+    let num_breakdowns = usize::try_from(query_config.max_breakdown_key).unwrap()
+        + usize::from(query_config.unattributed_bucket);
+    let mut breakdowns = vec![0; num_breakdowns];
     for (breakdown_key, trigger_value) in results.into_iter().enumerate() {
         // TODO: make the data type used consistent with `ipa_in_the_clear`
         // I think using u32 is wrong, we should move to u128
         if query_config.with_dp == 0 {
             // otherwise if DP is added trigger_values will not be zero due to noise
             assert!(
-                breakdown_key < query_config.max_breakdown_key.try_into().unwrap()
-                    || trigger_value == HV::ZERO,
+                breakdown_key < num_breakdowns || trigger_value == HV::ZERO,
                 "trigger values were attributed to buckets more than max breakdown key"
             );
         }
 
-        if breakdown_key < query_config.max_breakdown_key.try_into().unwrap() {
+        if breakdown_key < num_breakdowns {
             breakdowns[breakdown_key] += u32::try_from(trigger_value.as_u128()).unwrap();
         }
     }
 
-    IpaQueryResult {
+    let noise = noise_metadata::<HV>(output_dp[0].as_ref(), &breakdowns)?;
+
+    Ok(IpaQueryResult {
         input_size: QuerySize::try_from(query_size).unwrap(),
         config: query_config,
         latency: lat,
         breakdowns,
+        noise,
+    })
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum OutputDpError {
+    #[error("helpers reported different DP for the same query results")]
+    Mismatch,
+    #[error("IPA results must have a single noisy histogram, helpers reported {0}")]
+    UnexpectedHistograms(usize),
+    #[error("helpers reported invalid DP parameters: {0}")]
+    InvalidParameters(#[from] crate::error::Error),
+}
+
+/// Describes the noise that helpers reported to have added to `breakdowns`.
+fn noise_metadata<HV: SharedValue>(
+    output_dp: Option<&OutputDp>,
+    breakdowns: &[u32],
+) -> Result<Option<NoiseMetadata>, OutputDpError> {
+    let Some(output_dp) = output_dp else {
+        return Ok(None);
+    };
+    let [histogram] = output_dp.histograms.as_slice() else {
+        return Err(OutputDpError::UnexpectedHistograms(
+            output_dp.histograms.len(),
+        ));
+    };
+    let Some(noise) = histogram.noise()? else {
+        return Ok(None);
+    };
+
+    // Negative noise wraps around, so large values are read as negative.
+    let modulus = 2_f64.powi(i32::try_from(HV::BITS).unwrap());
+    let noisy_values = breakdowns.iter().map(|&value| {
+        let value = f64::from(value);
+        if value >= modulus / 2.0 {
+            value - modulus
+        } else {
+            value
+        }
+    });
+    let (estimates, confidence_intervals) = noisy_values
+        .map(|value| {
+            (
+                Some(value - noise.mean),
+                Some(noise.confidence_interval_95(value)),
+            )
+        })
+        .unzip();
+    let mut noise = NoiseMetadata {
+        histogram: histogram.clone(),
+        mean: noise.mean,
+        std: noise.std,
+        estimates,
+        confidence_intervals,
+        suppression: None,
+    };
+
+    // Helpers report suppressed breakdowns as zero, so their noisy value carries no information.
+    if let Some(threshold) = histogram.suppression_threshold {
+        let suppressed = breakdowns
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &value)| (value == 0).then_some(i))
+            .collect::<Vec<_>>();
+        for &i in &suppressed {
+            noise.estimates[i] = None;
+            noise.confidence_intervals[i] = None;
+        }
+        noise.suppression = Some(Suppression {
+            threshold,
+            suppressed,
+        });
+    }
+
+    Ok(Some(noise))
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use super::{noise_metadata, OutputDpError};
+    use crate::{
+        ff::boolean_array::BA32,
+        helpers::query::{DpMechanism, NoisyHistogram, OutputDp},
+        protocol::dp::HistogramNoise,
+    };
+
+    fn output_dp(epsilon: f64, histograms: usize) -> OutputDp {
+        let mechanism = DpMechanism::DiscreteLaplace {
+            epsilon,
+            delta: 1e-6,
+        };
+        OutputDp {
+            mechanism,
+            histograms: vec![
+                NoisyHistogram {
+                    mechanism,
+                    sensitivity: 2,
+                    dimensions: 256,
+                    suppression_threshold: None,
+                };
+                histograms
+            ],
+        }
+    }
+
+    #[test]
+    fn uses_reported_noise() {
+        let dp = output_dp(1.0, 1);
+        let noise = noise_metadata::<BA32>(Some(&dp), &[10, 20])
+            .unwrap()
+            .unwrap();
+        let expected = HistogramNoise::new(dp.mechanism, 2, 256).unwrap().unwrap();
+
+        assert_eq!(noise.histogram, dp.histograms[0]);
+        assert!((noise.std - expected.std).abs() < f64::EPSILON);
+        assert_eq!(noise.estimates.len(), 2);
+        assert!(noise_metadata::<BA32>(None, &[10, 20]).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_output_dp() {
+        assert!(matches!(
+            noise_metadata::<BA32>(Some(&output_dp(1.0, 2)), &[10]),
+            Err(OutputDpError::UnexpectedHistograms(2))
+        ));
+        assert!(matches!(
+            noise_metadata::<BA32>(Some(&output_dp(-1.0, 1)), &[10]),
+            Err(OutputDpError::InvalidParameters(_))
+        ));
+    }
+
+    #[test]
+    fn marks_suppressed_breakdowns() {
+        let mut dp = output_dp(1.0, 1);
+        dp.histograms[0].suppression_threshold = Some(20.0);
+        let noise = noise_metadata::<BA32>(Some(&dp), &[100, 0, 0, 30])
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(noise.estimates[0], Some(100.0 - noise.mean));
+        assert_eq!(noise.estimates[1], None);
+        assert_eq!(noise.confidence_intervals[2], None);
+        let suppression = noise.suppression.unwrap();
+        assert!((suppression.threshold - 20.0).abs() < f64::EPSILON);
+        assert_eq!(suppression.suppressed, vec![1, 2]);
     }
 }
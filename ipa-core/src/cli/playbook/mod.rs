@@ -3,6 +3,8 @@ mod generator;
 mod input;
 mod ipa;
 mod multiply;
+mod sharded_shuffle;
+mod sharding;
 
 use core::fmt::Debug;
 use std::{fs, path::Path, time::Duration};
@@ -12,16 +14,21 @@ use comfy_table::{Cell, Color, Table};
 use hyper::http::uri::Scheme;
 pub use input::InputSource;
 pub use multiply::secure_mul;
+pub use sharded_shuffle::secure_shuffle;
+pub use sharding::{create_sharded_query, ShardDistribution};
 use tokio::time::sleep;
 
-pub use self::ipa::{playbook_oprf_ipa, run_query_and_validate};
+pub use self::ipa::{playbook_oprf_ipa, run_query_and_validate, OutputDpError};
 use crate::{
-    config::{ClientConfig, NetworkConfig, PeerConfig},
+    config::{ClientConfig, NetworkConfig, PeerConfig, ShardedNetworkConfig},
     executor::IpaRuntime,
     ff::boolean_array::{BA20, BA3, BA8},
     helpers::query::DpMechanism,
     net::{ClientIdentity, Helper, IpaHttpClient},
-    protocol::{dp::NoiseParams, ipa_prf::oprf_padding::insecure::OPRFPaddingDp},
+    protocol::{
+        dp::NoiseParams,
+        ipa_prf::oprf_padding::insecure::{DiscreteGaussianDp, OPRFPaddingDp},
+    },
 };
 
 pub type BreakdownKey = BA8;
@@ -93,6 +100,12 @@ pub fn validate_dp(
     let mut expected = expected.into_iter().fuse();
     let mut actual = actual.into_iter().fuse();
     let mut mismatch = Vec::new();
+    let delta = match dp_mechanism {
+        DpMechanism::Binomial { delta, .. }
+        | DpMechanism::DiscreteLaplace { delta, .. }
+        | DpMechanism::DiscreteGaussian { delta, .. } => delta,
+        DpMechanism::NoDp => DpMechanism::DEFAULT_DELTA,
+    };
 
     let mut table = Table::new();
     table.set_header(vec!["Row", "Expected", "Actual", "Diff?"]);
@@ -117,6 +130,7 @@ pub fn validate_dp(
 
         let noise_params = NoiseParams {
             epsilon,
+            delta,
             per_user_credit_cap,
             ell_1_sensitivity: per_user_credit_cap.into(),
             ell_2_sensitivity: per_user_credit_cap.into(),
@@ -125,12 +139,12 @@ pub fn validate_dp(
             ..Default::default()
         };
         let same = match dp_mechanism {
-            DpMechanism::Binomial { epsilon: _ } => {
+            DpMechanism::Binomial { .. } => {
                 let (mean, std) = crate::protocol::dp::binomial_noise_mean_std(&noise_params);
                 next_actual_f64 - mean > next_expected_f64 - 10.0 * std
                     && next_actual_f64 - mean < next_expected_f64 + 10.0 * std
             }
-            DpMechanism::DiscreteLaplace { epsilon: _ } => {
+            DpMechanism::DiscreteLaplace { .. } => {
                 let truncated_discrete_laplace = OPRFPaddingDp::new(
                     noise_params.epsilon,
                     noise_params.delta,
@@ -153,6 +167,25 @@ pub fn validate_dp(
                                              // println!("mean = {mean}, std = {std}, tolerance_factor * std = {}",tolerance_factor * std);
                 (next_actual_f64_shifted - next_expected_f64).abs() < tolerance_factor * 3.0 * std
             }
+            DpMechanism::DiscreteGaussian { .. } => {
+                let discrete_gaussian = DiscreteGaussianDp::new(
+                    noise_params.epsilon,
+                    noise_params.delta,
+                    noise_params.ell_2_sensitivity,
+                )
+                .unwrap();
+
+                // Same BA32 wrap around for negative noise as in the Laplace case.
+                let next_actual_f64_shifted = if next_actual_f64 > 2.0_f64.powf(31.0) {
+                    next_actual_f64 - 2.0_f64.powf(32.0)
+                } else {
+                    next_actual_f64
+                };
+
+                let tolerance_factor = 20.0;
+                (next_actual_f64_shifted - next_expected_f64).abs()
+                    < tolerance_factor * 3.0_f64.sqrt() * discrete_gaussian.std()
+            }
             DpMechanism::NoDp => next_expected == next_actual,
         };
 
@@ -199,14 +232,7 @@ pub async fn make_clients(
     let network = if let Some(path) = network_path {
         NetworkConfig::from_toml_str(&fs::read_to_string(path).unwrap()).unwrap()
     } else {
-        NetworkConfig::<Helper>::new_mpc(
-            vec![
-                PeerConfig::new("localhost:3000".parse().unwrap(), None),
-                PeerConfig::new("localhost:3001".parse().unwrap(), None),
-                PeerConfig::new("localhost:3002".parse().unwrap(), None),
-            ],
-            ClientConfig::default(),
-        )
+        default_network()
     };
     let network = network.override_scheme(&scheme);
 
@@ -221,6 +247,58 @@ pub async fn make_clients(
     (clients, network)
 }
 
+/// Creates clients to talk to every shard of MPC helpers. Returns 3 clients per shard, in the
+/// order of shard indices. A network configuration without shards yields a single set of
+/// clients, same as [`make_clients`].
+///
+/// ## Panics
+/// If configuration file `network_path` cannot be read from or if it does not conform to toml spec.
+pub async fn make_sharded_clients(
+    network_path: Option<&Path>,
+    scheme: Scheme,
+    wait: usize,
+) -> (Vec<[IpaHttpClient<Helper>; 3]>, ShardedNetworkConfig) {
+    let mut wait = wait;
+    let network = if let Some(path) = network_path {
+        ShardedNetworkConfig::from_toml_str(&fs::read_to_string(path).unwrap()).unwrap()
+    } else {
+        ShardedNetworkConfig::new(vec![default_network()])
+    };
+    let network = network.override_scheme(&scheme);
+
+    let clients = network
+        .rings()
+        .iter()
+        .map(|ring| IpaHttpClient::from_conf(&IpaRuntime::current(), ring, &ClientIdentity::None))
+        .collect::<Vec<_>>();
+    while wait > 0 && !all_shards_ready(&clients).await {
+        tracing::debug!("waiting for servers to come up");
+        sleep(Duration::from_secs(1)).await;
+        wait -= 1;
+    }
+    (clients, network)
+}
+
+fn default_network() -> NetworkConfig<Helper> {
+    NetworkConfig::<Helper>::new_mpc(
+        vec![
+            PeerConfig::new("localhost:3000".parse().unwrap(), None),
+            PeerConfig::new("localhost:3001".parse().unwrap(), None),
+            PeerConfig::new("localhost:3002".parse().unwrap(), None),
+        ],
+        ClientConfig::default(),
+    )
+}
+
+async fn all_shards_ready(clients: &[[IpaHttpClient<Helper>; 3]]) -> bool {
+    for shard_clients in clients {
+        if !clients_ready(shard_clients).await {
+            return false;
+        }
+    }
+    true
+}
+
 async fn clients_ready(clients: &[IpaHttpClient<Helper>; 3]) -> bool {
     clients[0].echo("").await.is_ok()
         && clients[1].echo("").await.is_ok()
@@ -0,0 +1,89 @@
+#![cfg(feature = "web-app")]
+
+use futures::future::try_join_all;
+use generic_array::GenericArray;
+use typenum::Unsigned;
+
+use crate::{
+    cli::playbook::sharding::{create_sharded_query, ShardDistribution},
+    ff::{boolean_array::BA32, Serializable},
+    helpers::{
+        query::{QueryConfig, QueryInput},
+        BodyStream,
+    },
+    net::{Helper, IpaHttpClient},
+    secret_sharing::{replicated::semi_honest::AdditiveShare as Replicated, IntoShares},
+    sharding::ShardIndex,
+    test_fixture::Reconstruct,
+};
+
+/// Secure sharded shuffle. `clients` has one set of helper clients per shard, in the order of
+/// shard indices. The input is split between shards round-robin, and the output is the
+/// concatenation of the rows every shard returned after shuffling.
+#[allow(clippy::missing_panics_doc, clippy::disallowed_methods)]
+pub async fn secure_shuffle(
+    input: Vec<BA32>,
+    clients: &[[IpaHttpClient<Helper>; 3]],
+    query_config: QueryConfig,
+) -> Vec<BA32> {
+    let query_id = create_sharded_query(clients, query_config).await;
+
+    // prepare inputs
+    let shard_count = ShardIndex::try_from(clients.len()).unwrap();
+    let shard_inputs = ShardDistribution::RoundRobin.split(input, shard_count);
+    let inputs = shard_inputs.into_iter().map(|shard_input| {
+        shard_input.into_iter().share().map(|vec| {
+            let r = vec
+                .into_iter()
+                .flat_map(|share| {
+                    let mut slice = vec![0u8; <Replicated<BA32> as Serializable>::Size::USIZE];
+                    share.serialize(GenericArray::from_mut_slice(&mut slice));
+                    slice
+                })
+                .collect::<Vec<_>>();
+
+            BodyStream::from(r)
+        })
+    });
+
+    // send inputs
+    try_join_all(
+        inputs
+            .zip(clients)
+            .flat_map(|(helper_inputs, shard_clients)| helper_inputs.into_iter().zip(shard_clients))
+            .map(|(input_stream, client)| {
+                client.query_input(QueryInput {
+                    query_id,
+                    input_stream,
+                })
+            }),
+    )
+    .await
+    .unwrap();
+
+    // wait until every shard has processed the query and get the results from them
+    let mut output = Vec::new();
+    for shard_clients in clients {
+        let results: [_; 3] = try_join_all(
+            shard_clients
+                .iter()
+                .map(|client| client.query_results(query_id)),
+        )
+        .await
+        .unwrap()
+        .try_into()
+        .unwrap();
+
+        output.extend(
+            results
+                .map(|bytes| {
+                    Replicated::<BA32>::from_byte_slice(&bytes)
+                        .collect::<Result<Vec<_>, _>>()
+                        .unwrap()
+                })
+                .reconstruct(),
+        );
+    }
+
+    output
+}
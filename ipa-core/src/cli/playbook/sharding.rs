@@ -0,0 +1,119 @@
+use crate::{
+    helpers::query::QueryConfig,
+    net::{Helper, IpaHttpClient},
+    protocol::QueryId,
+    sharding::ShardIndex,
+};
+
+/// Describes how a report collector splits input records between the shards of every helper.
+///
+/// All helpers must see their shares of a given record on the same shard, so the split is
+/// decided on the plaintext records, before they are secret-shared or encrypted.
+pub enum ShardDistribution<'a, T> {
+    /// Record `i` goes to shard `i mod shard_count`.
+    RoundRobin,
+    /// Record goes to shard `key mod shard_count`, so all records with the same key end up on
+    /// the same shard. Keys are expected to be uniformly distributed, otherwise some shards get
+    /// more records than others.
+    ByKey(&'a dyn Fn(&T) -> u64),
+}
+
+impl<T> ShardDistribution<'_, T> {
+    /// Splits `records` between `shard_count` shards, preserving the relative order of records
+    /// that end up on the same shard.
+    ///
+    /// ## Panics
+    /// If `shard_count` is zero.
+    #[must_use]
+    pub fn split<I: IntoIterator<Item = T>>(
+        &self,
+        records: I,
+        shard_count: ShardIndex,
+    ) -> Vec<Vec<T>> {
+        let shard_count = usize::from(shard_count);
+        assert!(shard_count > 0, "at least one shard is required");
+
+        let mut shards = (0..shard_count).map(|_| Vec::new()).collect::<Vec<_>>();
+        for (i, record) in records.into_iter().enumerate() {
+            let shard = match self {
+                Self::RoundRobin => i % shard_count,
+                Self::ByKey(key) => {
+                    usize::try_from(key(&record) % u64::try_from(shard_count).unwrap()).unwrap()
+                }
+            };
+            shards[shard].push(record);
+        }
+
+        shards
+    }
+}
+
+/// Creates the query on every shard. `clients` has one set of helper clients per shard, in the
+/// order of shard indices.
+///
+/// Shards of a helper do not coordinate query creation between themselves yet, so the report
+/// collector has to create the query on each of them.
+///
+/// ## Panics
+/// If `clients` is empty, if there is more than one shard and the query can't run on sharded
+/// helpers, if any of the shards fails to create the query, or if shards assign different ids
+/// to it.
+pub async fn create_sharded_query(
+    clients: &[[IpaHttpClient<Helper>; 3]],
+    query_config: QueryConfig,
+) -> QueryId {
+    assert!(!clients.is_empty(), "at least one shard is required");
+    assert!(
+        clients.len() == 1 || query_config.query_type.is_sharded(),
+        "{} query can't run on {} shards",
+        query_config.query_type.as_ref(),
+        clients.len()
+    );
+
+    // every shard runs the same query, so it must get the same query id
+    let mut query_ids = Vec::with_capacity(clients.len());
+    for shard_clients in clients {
+        query_ids.push(
+            shard_clients[0]
+                .create_query(query_config.clone())
+                .await
+                .expect("Unable to create query!"),
+        );
+    }
+    let query_id = query_ids[0];
+    assert!(
+        query_ids.iter().all(|&id| id == query_id),
+        "shards disagree on query id: {query_ids:?}"
+    );
+
+    query_id
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use super::ShardDistribution;
+    use crate::sharding::ShardIndex;
+
+    #[test]
+    fn round_robin() {
+        let shards = ShardDistribution::RoundRobin.split(0..7, ShardIndex::from(3));
+        assert_eq!(vec![vec![0, 3, 6], vec![1, 4], vec![2, 5]], shards);
+    }
+
+    #[test]
+    fn by_key() {
+        let key = |v: &(u64, char)| v.0;
+        let records = [(7, 'a'), (2, 'b'), (4, 'c'), (7, 'd'), (9, 'e')];
+        let shards = ShardDistribution::ByKey(&key).split(records, ShardIndex::from(2));
+        assert_eq!(
+            vec![vec![(2, 'b'), (4, 'c')], vec![(7, 'a'), (7, 'd'), (9, 'e')]],
+            shards
+        );
+    }
+
+    #[test]
+    fn single_shard() {
+        let shards = ShardDistribution::RoundRobin.split(0..4, ShardIndex::from(1));
+        assert_eq!(vec![vec![0, 1, 2, 3]], shards);
+    }
+}
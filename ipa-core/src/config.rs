@@ -131,6 +131,31 @@ impl NetworkConfig<Shard> {
             identities,
         }
     }
+
+    /// Reads the configuration of all the shards of one helper. Expects config to be toml
+    /// format, listing the peers in the order of their shard index.
+    ///
+    /// # Errors
+    /// if `input` is in an invalid format
+    pub fn shards_from_toml_str(input: &str) -> Result<Self, Error> {
+        use config::{Config, File, FileFormat};
+
+        let conf: Self = Config::builder()
+            .add_source(File::from_str(input, FileFormat::Toml))
+            .build()?
+            .try_deserialize()?;
+
+        Ok(Self::new_shards(conf.peers, conf.client))
+    }
+
+    /// Number of shards in this network.
+    ///
+    /// # Panics
+    /// In the unlikely event a usize cannot be turned into a u32
+    #[must_use]
+    pub fn shard_count(&self) -> ShardIndex {
+        ShardIndex::from(u32::try_from(self.peers.len()).unwrap())
+    }
 }
 
 impl NetworkConfig<Helper> {
@@ -177,6 +202,117 @@ impl NetworkConfig<Helper> {
     }
 }
 
+/// Network configuration of a sharded deployment, as seen by a client such as the report
+/// collector. Shard `i` of every helper forms one MPC ring, so the deployment is described by
+/// one ring per shard, in the order of shard indices.
+///
+/// In TOML, rings are listed as `[[shards]]` tables, each with exactly 3 `[[shards.peers]]`. A
+/// configuration with top-level `[[peers]]` instead describes a deployment with a single shard,
+/// which makes every non-sharded network configuration a valid sharded one.
+#[derive(Clone, Debug)]
+pub struct ShardedNetworkConfig {
+    rings: Vec<NetworkConfig<Helper>>,
+}
+
+impl ShardedNetworkConfig {
+    /// # Panics
+    /// If `rings` is empty.
+    #[must_use]
+    pub fn new(rings: Vec<NetworkConfig<Helper>>) -> Self {
+        assert!(!rings.is_empty(), "at least one shard is required");
+        Self { rings }
+    }
+
+    /// Reads config from string. Expects config to be toml format.
+    ///
+    /// # Errors
+    /// if `input` is in an invalid format, if it lists both `peers` and `shards`, or if any
+    /// of the shards does not have exactly 3 peers.
+    pub fn from_toml_str(input: &str) -> Result<Self, Error> {
+        use config::{Config, ConfigError, File, FileFormat};
+
+        #[derive(Deserialize)]
+        struct Ring {
+            peers: Vec<PeerConfig>,
+        }
+
+        #[derive(Deserialize)]
+        struct Network {
+            #[serde(default)]
+            client: ClientConfig,
+            #[serde(default)]
+            peers: Vec<PeerConfig>,
+            #[serde(default)]
+            shards: Vec<Ring>,
+        }
+
+        let conf: Network = Config::builder()
+            .add_source(File::from_str(input, FileFormat::Toml))
+            .build()?
+            .try_deserialize()?;
+
+        let rings = match (conf.peers.is_empty(), conf.shards.is_empty()) {
+            (false, true) => vec![conf.peers],
+            (true, false) => conf.shards.into_iter().map(|ring| ring.peers).collect(),
+            _ => {
+                return Err(ConfigError::Message(
+                    "network configuration must list either peers or shards".to_string(),
+                )
+                .into())
+            }
+        };
+        if let Some(ring) = rings.iter().find(|ring| ring.len() != 3) {
+            return Err(ConfigError::Message(format!(
+                "every shard must have exactly 3 peers, got {}",
+                ring.len()
+            ))
+            .into());
+        }
+
+        Ok(Self::new(
+            rings
+                .into_iter()
+                .map(|ring| NetworkConfig::new_mpc(ring, conf.client.clone()))
+                .collect(),
+        ))
+    }
+
+    /// # Panics
+    /// If `PathAndQuery::from_str("")` fails
+    #[must_use]
+    pub fn override_scheme(self, scheme: &Scheme) -> Self {
+        Self {
+            rings: self
+                .rings
+                .into_iter()
+                .map(|ring| ring.override_scheme(scheme))
+                .collect(),
+        }
+    }
+
+    /// Number of shards in this deployment.
+    ///
+    /// # Panics
+    /// In the unlikely event a usize cannot be turned into a u32
+    #[must_use]
+    pub fn shard_count(&self) -> ShardIndex {
+        ShardIndex::from(u32::try_from(self.rings.len()).unwrap())
+    }
+
+    /// The ring formed by the leader shards of every helper. Queries are coordinated and their
+    /// results are collected through the leader shards.
+    #[must_use]
+    pub fn leader(&self) -> &NetworkConfig<Helper> {
+        &self.rings[0]
+    }
+
+    /// MPC rings of this deployment, in the order of shard indices.
+    #[must_use]
+    pub fn rings(&self) -> &[NetworkConfig<Helper>] {
+        &self.rings
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct PeerConfig {
     /// Peer URL
@@ -523,11 +659,11 @@ mod tests {
     use rand::rngs::StdRng;
     use rand_core::SeedableRng;
 
-    use super::{NetworkConfig, PeerConfig};
+    use super::{NetworkConfig, PeerConfig, ShardedNetworkConfig};
     use crate::{
         config::{ClientConfig, HpkeClientConfig, Http2Configurator, HttpClientConfigurator},
         helpers::HelperIdentity,
-        net::test::TestConfigBuilder,
+        net::{test::TestConfigBuilder, Shard},
         sharding::ShardIndex,
     };
 
@@ -616,6 +752,100 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_shard_config() {
+        let conf = NetworkConfig::<Shard>::shards_from_toml_str(
+            r#"
+[[peers]]
+url = "http://localhost:6000"
+
+[[peers]]
+url = "http://localhost:6001"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(conf.shard_count(), ShardIndex::from(2));
+        assert_eq!(
+            conf.peers[ShardIndex(1)].url,
+            URI_2S.parse::<Uri>().unwrap()
+        );
+        assert_eq!(
+            conf.identities,
+            vec![ShardIndex::FIRST, ShardIndex::from(1)]
+        );
+    }
+
+    #[test]
+    fn parse_sharded_network_config() {
+        let conf = ShardedNetworkConfig::from_toml_str(
+            r#"
+[[shards]]
+[[shards.peers]]
+url = "http://localhost:3000"
+[[shards.peers]]
+url = "http://localhost:3001"
+[[shards.peers]]
+url = "http://localhost:3002"
+
+[[shards]]
+[[shards.peers]]
+url = "http://localhost:6000"
+[[shards.peers]]
+url = "http://localhost:6001"
+[[shards.peers]]
+url = "http://localhost:6002"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(conf.shard_count(), ShardIndex::from(2));
+        assert_eq!(
+            conf.leader().peers()[HelperIdentity::TWO].url,
+            URI_2.parse::<Uri>().unwrap()
+        );
+        assert_eq!(
+            conf.rings()[1].peers()[HelperIdentity::THREE].url,
+            URI_3S.parse::<Uri>().unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_unsharded_network_config() {
+        let conf = ShardedNetworkConfig::from_toml_str(
+            r#"
+[[peers]]
+url = "http://localhost:3000"
+[[peers]]
+url = "http://localhost:3001"
+[[peers]]
+url = "http://localhost:3002"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(conf.shard_count(), ShardIndex::from(1));
+        assert_eq!(
+            conf.leader().peers()[HelperIdentity::ONE].url,
+            URI_1.parse::<Uri>().unwrap()
+        );
+    }
+
+    #[test]
+    fn sharded_network_config_rejects_incomplete_rings() {
+        assert!(ShardedNetworkConfig::from_toml_str(
+            r#"
+[[shards]]
+[[shards.peers]]
+url = "http://localhost:3000"
+[[shards.peers]]
+url = "http://localhost:3001"
+"#,
+        )
+        .is_err());
+        assert!(ShardedNetworkConfig::from_toml_str("").is_err());
+    }
+
     #[test]
     fn indexing_peer_happy_case() {
         let uri1 = URI_1.parse::<Uri>().unwrap();
@@ -4,13 +4,14 @@ use std::{
     fmt::{Debug, Display},
 };
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
     helpers::{Role, ZeroRecordsError},
-    protocol::RecordId,
+    protocol::{Gate, RecordId},
     report::{hybrid::InvalidHybridReportError, InvalidReportError},
-    sharding::ShardIndex,
+    sharding::{ShardIndex, ShardedHelperIdentity},
     task::JoinError,
 };
 
@@ -42,8 +43,8 @@ pub enum Error {
     TooManyHelpers,
     #[error("failed to parse: {0}")]
     ParseError(BoxError),
-    #[error("malicious security check failed")]
-    MaliciousSecurityCheckFailed,
+    #[error("malicious security check failed: {0}")]
+    MaliciousSecurityCheckFailed(Box<ValidationFailure>),
     #[error("malicious reveal failed")]
     MaliciousRevealFailed,
     #[error("problem during IO: {0}")]
@@ -79,10 +80,10 @@ pub enum Error {
     LengthError(#[from] LengthError),
     #[error("Current Context is unsafe, call validate to make it safe: {0}")]
     ContextUnsafe(String),
-    #[error("DZKP Validation failed")]
-    DZKPValidationFailed,
+    #[error("DZKP Validation failed: {0}")]
+    DZKPValidationFailed(Box<ValidationFailure>),
     /// Because errors are not `Clone`, when a batch fails to verify, one record gets the actual
-    /// error (above, possibly with additional detail in the future), and the rest get this error.
+    /// error (above, with the evidence of the failure), and the rest get this error.
     #[error("Parallel DZKP Validation failed")]
     ParallelDZKPValidationFailed,
     #[error("Inconsistent shares")]
@@ -108,6 +109,8 @@ pub enum Error {
     ShuffleValidationFailed(String),
     #[error("Duplicate bytes found after {0} checks")]
     DuplicateBytes(usize),
+    #[error("Shard {0} failed, the query was aborted on all shards")]
+    ShardFailed(ShardedHelperIdentity),
 }
 
 impl Default for Error {
@@ -121,6 +124,18 @@ impl Error {
     pub fn path_parse_error(source: &str) -> Error {
         Error::ParseError(format!("unexpected value \"{source}\" in path").into())
     }
+
+    /// Returns the evidence collected by this helper if this error was caused by a failed
+    /// malicious security check.
+    #[must_use]
+    pub fn validation_failure(&self) -> Option<&ValidationFailure> {
+        match self {
+            Self::MaliciousSecurityCheckFailed(failure) | Self::DZKPValidationFailed(failure) => {
+                Some(failure)
+            }
+            _ => None,
+        }
+    }
 }
 
 impl From<std::num::ParseIntError> for Error {
@@ -153,6 +168,50 @@ impl Display for LengthError {
 
 impl std::error::Error for LengthError {}
 
+/// Malicious security checks that helpers run on the values they computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationCheck {
+    /// Verification of a distributed zero-knowledge proof for boolean multiplications.
+    Dzkp,
+    /// Comparison of information-theoretic MACs on shares of prime field values.
+    Mac,
+}
+
+/// What a helper knows about a malicious security check that failed.
+///
+/// Neither check can tell which helper cheated. A failed check only means that at least one of
+/// the messages it used was not computed honestly, so `involved` lists the helpers that sent them.
+/// For DZKP, the first helper is the prover whose proof did not verify and the second one is
+/// the other verifier of that proof. For MACs, both peers of the reporting helper are involved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationFailure {
+    pub check: ValidationCheck,
+    /// Gate where the check was run.
+    pub gate: Gate,
+    /// Index of the batch that failed to validate.
+    pub batch: usize,
+    /// Helper that detected the failure.
+    pub reported_by: Role,
+    pub involved: [Role; 2],
+}
+
+impl Display for ValidationFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{check:?} check failed on {reported_by:?} at {gate}, batch {batch}; \
+            involved messages from {first:?} and {second:?}",
+            check = self.check,
+            reported_by = self.reported_by,
+            gate = self.gate,
+            batch = self.batch,
+            first = self.involved[0],
+            second = self.involved[1],
+        )
+    }
+}
+
 /// Set up a global panic hook that dumps the panic information to our tracing subsystem if it is
 /// available and duplicates that to standard error output.
 ///
@@ -3,7 +3,7 @@ use thiserror::Error;
 use crate::{
     helpers::{
         buffers::{DeserializeError, EndOfStreamError},
-        ChannelId, TotalRecords, TransportIdentity,
+        ChannelId, Role, TotalRecords, TransportIdentity,
     },
     protocol::RecordId,
 };
@@ -21,6 +21,8 @@ pub enum Error<I: TransportIdentity> {
         channel_id: ChannelId<I>,
         inner: DeserializeError,
     },
+    #[error("Query was aborted by {by:?} while receiving from {channel_id:?}")]
+    Aborted { channel_id: ChannelId<I>, by: Role },
     #[error("record ID {record_id:?} is out of range for {channel_id:?} (expected {total_records:?} records)")]
     TooManyRecords {
         record_id: RecordId,
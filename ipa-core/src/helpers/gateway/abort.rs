@@ -0,0 +1,121 @@
+use std::{convert::Infallible, future::pending};
+
+use futures::{
+    channel::oneshot,
+    future::{BoxFuture, Shared},
+    stream::FuturesUnordered,
+    FutureExt, StreamExt,
+};
+use generic_array::GenericArray;
+use typenum::U1;
+
+use crate::{
+    ff::Serializable,
+    helpers::{gateway::receive::UR, MpcMessage, Role},
+    protocol::RecordId,
+    sync::{Arc, Mutex},
+};
+
+/// Message sent by a helper that aborted the query to its peers.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Poison;
+
+impl Serializable for Poison {
+    type Size = U1;
+    type DeserializationError = Infallible;
+
+    fn serialize(&self, buf: &mut GenericArray<u8, Self::Size>) {
+        buf[0] = 1;
+    }
+
+    fn deserialize(
+        _buf: &GenericArray<u8, Self::Size>,
+    ) -> Result<Self, Self::DeserializationError> {
+        Ok(Self)
+    }
+}
+
+impl MpcMessage for Poison {}
+
+/// Abort state shared by all MPC channels of a gateway.
+///
+/// The query is aborted when this helper calls [`Gateway::abort`] or when a poison message is
+/// received from one of the peers. The signal is only polled by receive operations that wait for
+/// data, which is where helpers get stuck if a peer stopped running the query.
+///
+/// [`Gateway::abort`]: crate::helpers::Gateway::abort
+#[derive(Clone)]
+pub(super) struct Abort {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    local: Mutex<Option<oneshot::Sender<Role>>>,
+    /// Resolves to the helper that aborted the query.
+    signal: Shared<BoxFuture<'static, Role>>,
+}
+
+impl Abort {
+    /// Creates the abort state for a helper that listens for poison messages on the given
+    /// receivers, one per peer.
+    pub fn new(poison_receivers: [(Role, UR); 2]) -> Self {
+        let (tx, rx) = oneshot::channel();
+        let mut signals = poison_receivers
+            .into_iter()
+            .map(|(peer, rx)| {
+                async move {
+                    // A peer that did not abort never sends poison, errors mean the channel is gone.
+                    rx.recv::<Poison, _>(RecordId::FIRST)
+                        .await
+                        .ok()
+                        .map(|_| peer)
+                }
+                .boxed()
+            })
+            .collect::<FuturesUnordered<_>>();
+        signals.push(rx.map(Result::ok).boxed());
+
+        let signal = async move {
+            while let Some(signal) = signals.next().await {
+                if let Some(role) = signal {
+                    return role;
+                }
+            }
+            pending().await
+        }
+        .boxed()
+        .shared();
+
+        Self {
+            inner: Arc::new(Inner {
+                local: Mutex::new(Some(tx)),
+                signal,
+            }),
+        }
+    }
+
+    /// Aborts the query on behalf of this helper. Returns `false` if this helper has already
+    /// done so.
+    ///
+    /// ## Panics
+    /// If the mutex is poisoned.
+    pub fn trigger(&self, me: Role) -> bool {
+        if let Some(tx) = self.inner.local.lock().unwrap().take() {
+            // receiving end is owned by the signal future that is never dropped before `self`
+            let _ = tx.send(me);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the helper that aborted the query, if it is known to be aborted.
+    pub fn aborted_by(&self) -> Option<Role> {
+        self.inner.signal.peek().copied()
+    }
+
+    /// Resolves to the helper that aborted the query, once it is aborted.
+    pub fn wait(&self) -> Shared<BoxFuture<'static, Role>> {
+        self.inner.signal.clone()
+    }
+}
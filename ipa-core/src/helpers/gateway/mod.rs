@@ -1,3 +1,4 @@
+mod abort;
 mod receive;
 mod send;
 #[cfg(feature = "stall-detection")]
@@ -7,8 +8,12 @@ mod transport;
 use std::{
     cmp::{max, min},
     num::NonZeroUsize,
+    time::Duration,
 };
 
+use futures::future::join;
+use ipa_step::StepNarrow;
+
 pub(super) use receive::{MpcReceivingEnd, ShardReceivingEnd};
 pub(super) use send::SendingEnd;
 #[cfg(feature = "stall-detection")]
@@ -19,16 +24,17 @@ use crate::{
     helpers::{
         buffers::UnorderedReceiver,
         gateway::{
+            abort::{Abort, Poison},
             receive::{GatewayReceivers, ShardReceiveStream, UR},
             send::GatewaySenders,
             transport::Transports,
         },
         query::QueryConfig,
-        HelperChannelId, LogErrors, Message, MpcMessage, RecordsStream, Role, RoleAssignment,
-        ShardChannelId, TotalRecords, Transport,
+        ChannelId, Direction, HelperChannelId, LogErrors, Message, MpcMessage, RecordsStream, Role,
+        RoleAssignment, ShardChannelId, ShardedTransport, TotalRecords, Transport,
     },
-    protocol::QueryId,
-    sharding::ShardIndex,
+    protocol::{step::ProtocolStep, Gate, QueryId, RecordId},
+    sharding::{ShardIndex, Sharded, ShardedHelperIdentity},
     sync::{Arc, Mutex},
     utils::NonZeroU32PowerOfTwo,
 };
@@ -56,6 +62,7 @@ pub struct Gateway {
     config: GatewayConfig,
     transports: Transports<RoleResolvingTransport, ShardTransportImpl>,
     query_id: QueryId,
+    abort: Abort,
     #[cfg(feature = "stall-detection")]
     inner: crate::sync::Arc<State>,
     #[cfg(not(feature = "stall-detection"))]
@@ -103,6 +110,16 @@ pub struct GatewayConfig {
     /// send/receive requests
     #[cfg(feature = "stall-detection")]
     pub progress_check_interval: std::time::Duration,
+
+    /// How often shards of a sharded helper send heartbeats to each other while a query is
+    /// running. Not used if this helper is not sharded.
+    pub heartbeat_interval: Duration,
+
+    /// A shard that stopped sending heartbeats for this long is considered failed, and the query
+    /// is aborted on every shard of all three helpers. This must be considerably larger than
+    /// [`Self::heartbeat_interval`] because heartbeats are sent by the same task that runs the
+    /// query, so they are delayed by long computations.
+    pub shard_failure_timeout: Duration,
 }
 
 impl Gateway {
@@ -115,17 +132,31 @@ impl Gateway {
         shard_transport: ShardTransportImpl,
     ) -> Self {
         tracing::debug!("active_work = {}", config.active);
+        let mpc = RoleResolvingTransport {
+            roles,
+            inner: mpc_transport,
+        };
+        // Poison receivers are kept out of the receivers map, so they are not reported as stalled.
+        let abort = Abort::new([Direction::Left, Direction::Right].map(|direction| {
+            let peer = mpc.identity().peer(direction);
+            let rx = UnorderedReceiver::new(
+                Box::pin(LogErrors::new(
+                    mpc.receive(peer, (query_id, Self::abort_gate())),
+                )),
+                config.active_work(),
+            );
+            (peer, rx)
+        }));
+
         #[allow(clippy::useless_conversion)] // not useless in stall-detection build
         Self {
             query_id,
             config,
             transports: Transports {
-                mpc: RoleResolvingTransport {
-                    roles,
-                    inner: mpc_transport,
-                },
+                mpc,
                 shard: shard_transport,
             },
+            abort,
             inner: State::default().into(),
         }
     }
@@ -140,6 +171,57 @@ impl Gateway {
         &self.config
     }
 
+    /// Returns the index of the shard this gateway belongs to, along with the total number of
+    /// shards on this helper.
+    #[must_use]
+    pub fn shard_config(&self) -> Sharded {
+        Sharded {
+            shard_id: self.transports.shard.identity(),
+            shard_count: self.transports.shard.shard_count(),
+        }
+    }
+
+    /// Returns the identity of the helper shard this gateway belongs to.
+    #[must_use]
+    pub fn sharded_identity(&self) -> ShardedHelperIdentity {
+        ShardedHelperIdentity::new(
+            self.transports.mpc.inner.identity(),
+            self.transports.shard.identity(),
+        )
+    }
+
+    /// Aborts the query on this helper and tells both peers to do the same, so that no helper
+    /// keeps waiting for messages from a helper that stopped running the query. Once the query is
+    /// aborted, all pending and future receive operations on MPC channels fail with
+    /// [`Error::Aborted`]. Abort cannot be undone.
+    ///
+    /// Only the first call sends the abort signal to peers, subsequent calls do nothing.
+    ///
+    /// [`Error::Aborted`]: crate::helpers::Error::Aborted
+    pub async fn abort(&self) {
+        if !self.abort.trigger(self.role()) {
+            return;
+        }
+        tracing::warn!("aborting query {:?} on {:?}", self.query_id, self.role());
+
+        let send_poison = |direction: Direction| async move {
+            // peers that already stopped running the query may not receive it
+            let _ = self
+                .get_mpc_sender::<Poison>(
+                    &ChannelId::new(self.role().peer(direction), Self::abort_gate()),
+                    TotalRecords::ONE,
+                    self.config.active_work_as_power_of_two(),
+                )
+                .send(RecordId::FIRST, Poison)
+                .await;
+        };
+        join(send_poison(Direction::Left), send_poison(Direction::Right)).await;
+    }
+
+    fn abort_gate() -> Gate {
+        Gate::default().narrow(&ProtocolStep::Abort)
+    }
+
     /// Returns a sender suitable for sending data between MPC helpers. The data must be approved
     /// for sending by implementing [`MpcMessage`] trait.
     ///
@@ -209,6 +291,7 @@ impl Gateway {
     ) -> receive::MpcReceivingEnd<M> {
         receive::MpcReceivingEnd::new(
             channel_id.clone(),
+            self.abort.clone(),
             self.inner.mpc_receivers.get_or_create(channel_id, || {
                 UnorderedReceiver::new(
                     Box::pin(LogErrors::new(self.transports.mpc.receive(
@@ -264,6 +347,8 @@ impl Default for GatewayConfig {
             } else {
                 30
             }),
+            heartbeat_interval: Duration::from_secs(5),
+            shard_failure_timeout: Duration::from_secs(60),
         }
     }
 }
@@ -564,6 +649,32 @@ mod tests {
         });
     }
 
+    #[test]
+    fn abort_wakes_up_receivers() {
+        run(|| async move {
+            let world = TestWorld::default();
+            world
+                .semi_honest((), |ctx, ()| async move {
+                    let ctx = ctx.set_total_records(1);
+                    if ctx.role() == Role::H1 {
+                        // H1 never sends anything, so peers would be stuck without the abort.
+                        ctx.abort().await;
+                        ctx.abort().await;
+                    } else {
+                        let r = ctx
+                            .recv_channel::<Fp31>(Role::H1)
+                            .receive(RecordId::FIRST)
+                            .await;
+                        assert!(matches!(
+                            r,
+                            Err(crate::helpers::Error::Aborted { by: Role::H1, .. })
+                        ));
+                    }
+                })
+                .await;
+        });
+    }
+
     #[test]
     fn custom_active_work() {
         run(|| async move {
@@ -1,19 +1,22 @@
 use std::{
     marker::PhantomData,
-    pin::Pin,
+    pin::{pin, Pin},
     task::{Context, Poll},
 };
 
 use bytes::Bytes;
 use dashmap::{mapref::entry::Entry, DashMap};
-use futures::Stream;
+use futures::{
+    future::{select, Either},
+    Stream,
+};
 use pin_project::pin_project;
 
 use crate::{
     error::BoxError,
     helpers::{
         buffers::{UnorderedReceiver, UnorderedReceiverError},
-        gateway::transport::RoleResolvingTransport,
+        gateway::{abort::Abort, transport::RoleResolvingTransport},
         transport::SingleRecordStream,
         ChannelId, Error, HelperChannelId, LogErrors, Message, MpcMessage, Role, ShardChannelId,
         ShardTransportImpl, Transport, TransportIdentity,
@@ -30,6 +33,7 @@ use crate::{
 /// [`gat`]: https://github.com/rust-lang/rust/issues/100013
 pub struct MpcReceivingEnd<M> {
     channel_id: HelperChannelId,
+    abort: Abort,
     unordered_rx: UR,
     _phantom: PhantomData<fn() -> M>,
 }
@@ -62,38 +66,56 @@ pub struct ShardReceiveStream(
 );
 
 impl<M: MpcMessage> MpcReceivingEnd<M> {
-    pub(super) fn new(channel_id: HelperChannelId, rx: UR) -> Self {
+    pub(super) fn new(channel_id: HelperChannelId, abort: Abort, rx: UR) -> Self {
         Self {
             channel_id,
+            abort,
             unordered_rx: rx,
             _phantom: PhantomData,
         }
     }
 
     /// Receive message associated with the given record id. This method does not return until
-    /// message is actually received and deserialized.
+    /// message is actually received and deserialized, or the query is aborted.
     ///
     /// ## Errors
-    /// Returns an error if receiving fails
+    /// Returns an error if receiving fails or the query has been aborted by any helper.
     ///
     /// ## Panics
     /// This will panic if message size does not fit into 8 bytes and it somehow got serialized
     /// and sent to this helper.
     #[tracing::instrument(level = "trace", "receive", skip_all, fields(i = %record_id, from = ?self.channel_id.peer, gate = ?self.channel_id.gate.as_ref()))]
     pub async fn receive(&self, record_id: RecordId) -> Result<M, Error<Role>> {
-        self.unordered_rx
-            .recv::<M, _>(record_id)
-            .await
-            .map_err(|e| match e {
-                UnorderedReceiverError::DeserializeFailed(inner) => Error::DeserializeFailed {
-                    channel_id: self.channel_id.clone(),
-                    inner,
-                },
-                UnorderedReceiverError::EndOfStream(inner) => Error::EndOfStream {
-                    channel_id: self.channel_id.clone(),
-                    inner,
-                },
-            })
+        let aborted = |by| Error::Aborted {
+            channel_id: self.channel_id.clone(),
+            by,
+        };
+        if let Some(by) = self.abort.aborted_by() {
+            return Err(aborted(by));
+        }
+
+        // Data is polled first, so receiving messages that already arrived does not need to
+        // check the abort signal.
+        let received = match select(
+            pin!(self.unordered_rx.recv::<M, _>(record_id)),
+            self.abort.wait(),
+        )
+        .await
+        {
+            Either::Left((received, _)) => received,
+            Either::Right((by, _)) => return Err(aborted(by)),
+        };
+
+        received.map_err(|e| match e {
+            UnorderedReceiverError::DeserializeFailed(inner) => Error::DeserializeFailed {
+                channel_id: self.channel_id.clone(),
+                inner,
+            },
+            UnorderedReceiverError::EndOfStream(inner) => Error::EndOfStream {
+                channel_id: self.channel_id.clone(),
+                inner,
+            },
+        })
     }
 }
 
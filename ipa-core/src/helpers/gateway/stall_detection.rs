@@ -78,7 +78,7 @@ mod gateway {
             Role, RoleAssignment, SendingEnd, ShardChannelId, ShardReceivingEnd, TotalRecords,
         },
         protocol::QueryId,
-        sharding::ShardIndex,
+        sharding::{ShardIndex, Sharded, ShardedHelperIdentity},
         sync::Arc,
         utils::NonZeroU32PowerOfTwo,
     };
@@ -99,6 +99,14 @@ mod gateway {
 
                 #[inline]
                 pub fn config(&self) -> &GatewayConfig;
+
+                #[inline]
+                pub fn shard_config(&self) -> Sharded;
+
+                #[inline]
+                pub fn sharded_identity(&self) -> ShardedHelperIdentity;
+
+                pub async fn abort(&self);
             }
         }
 
@@ -74,8 +74,8 @@ pub use transport::{
     make_owned_handler, query, routing, ApiError, BodyStream, BytesStream, HandlerBox, HandlerRef,
     HelperResponse, Identity as TransportIdentity, LengthDelimitedStream, LogErrors, NoQueryId,
     NoResourceIdentifier, NoStep, QueryIdBinding, ReceiveRecords, RecordsStream, RequestHandler,
-    RouteParams, SingleRecordStream, StepBinding, StreamCollection, StreamKey, Transport,
-    WrappedBoxBodyStream,
+    RouteParams, ShardedTransport, SingleRecordStream, StepBinding, StreamCollection, StreamKey,
+    Transport, WrappedBoxBodyStream,
 };
 use typenum::{Const, ToUInt, Unsigned, U8};
 use x25519_dalek::PublicKey;
@@ -7,12 +7,13 @@ use serde_json::json;
 use crate::{
     error::BoxError,
     helpers::{
-        query::PrepareQuery, transport::routing::Addr, BodyStream, HelperIdentity,
-        TransportIdentity,
+        query::{OutputDp, PrepareQuery},
+        transport::routing::Addr,
+        BodyStream, HelperIdentity, TransportIdentity,
     },
     query::{
-        NewQueryError, PrepareQueryError, ProtocolResult, QueryCompletionError, QueryInputError,
-        QueryKillStatus, QueryKilled, QueryStatus, QueryStatusError,
+        BudgetLedgerStatus, NewQueryError, PrepareQueryError, ProtocolResult, QueryCompletionError,
+        QueryInputError, QueryKillStatus, QueryKilled, QueryStatus, QueryStatusError,
     },
     sync::{Arc, Mutex, Weak},
 };
@@ -29,6 +30,8 @@ use crate::{
 ///
 pub struct HelperResponse {
     body: Vec<u8>,
+    /// Set for the results of differentially private queries.
+    output_dp: Option<OutputDp>,
 }
 
 /// The lifecycle of request handlers is somewhat complicated. First, to initialize [`Transport`],
@@ -98,7 +101,20 @@ impl HelperResponse {
     /// Returns an empty response that indicates that incoming request has been processed successfully
     #[must_use]
     pub fn ok() -> Self {
-        Self { body: Vec::new() }
+        Self::from_body(Vec::new())
+    }
+
+    fn from_body(body: Vec<u8>) -> Self {
+        Self {
+            body,
+            output_dp: None,
+        }
+    }
+
+    /// Returns the differential privacy applied to query results carried by this response.
+    #[must_use]
+    pub fn output_dp(&self) -> Option<&OutputDp> {
+        self.output_dp.as_ref()
     }
 
     /// Consumes [`Self`] and returns the body of the response.
@@ -118,7 +134,7 @@ impl HelperResponse {
 impl From<PrepareQuery> for HelperResponse {
     fn from(value: PrepareQuery) -> Self {
         let v = serde_json::to_vec(&json!({"query_id": value.query_id})).unwrap();
-        Self { body: v }
+        Self::from_body(v)
     }
 }
 
@@ -131,21 +147,31 @@ impl From<()> for HelperResponse {
 impl From<QueryStatus> for HelperResponse {
     fn from(value: QueryStatus) -> Self {
         let v = serde_json::to_vec(&json!({"status": value})).unwrap();
-        Self { body: v }
+        Self::from_body(v)
     }
 }
 
 impl From<QueryKilled> for HelperResponse {
     fn from(value: QueryKilled) -> Self {
         let v = serde_json::to_vec(&json!({"query_id": value.0, "status": "killed"})).unwrap();
-        Self { body: v }
+        Self::from_body(v)
+    }
+}
+
+impl From<BudgetLedgerStatus> for HelperResponse {
+    fn from(value: BudgetLedgerStatus) -> Self {
+        let v = serde_json::to_vec(&value).unwrap();
+        Self::from_body(v)
     }
 }
 
 impl<R: AsRef<dyn ProtocolResult>> From<R> for HelperResponse {
     fn from(value: R) -> Self {
         let v = value.as_ref().to_bytes();
-        Self { body: v }
+        Self {
+            body: v,
+            output_dp: value.as_ref().output_dp().cloned(),
+        }
     }
 }
 
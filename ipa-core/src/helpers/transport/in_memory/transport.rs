@@ -25,8 +25,8 @@ use crate::{
         in_memory_config::DynStreamInterceptor,
         transport::routing::{Addr, RouteId},
         ApiError, BodyStream, HandlerRef, HelperIdentity, HelperResponse, NoResourceIdentifier,
-        QueryIdBinding, ReceiveRecords, RequestHandler, RouteParams, StepBinding, StreamCollection,
-        Transport, TransportIdentity,
+        QueryIdBinding, ReceiveRecords, RequestHandler, RouteParams, ShardedTransport, StepBinding,
+        StreamCollection, Transport, TransportIdentity,
     },
     protocol::{Gate, QueryId},
     sharding::ShardIndex,
@@ -117,7 +117,8 @@ impl<I: TransportIdentity> InMemoryTransport<I> {
                             | RouteId::QueryInput
                             | RouteId::QueryStatus
                             | RouteId::CompleteQuery
-                            | RouteId::KillQuery => {
+                            | RouteId::KillQuery
+                            | RouteId::BudgetLedger => {
                                 handler
                                     .as_ref()
                                     .expect("Handler is set")
@@ -152,6 +153,13 @@ impl<I: TransportIdentity> InMemoryTransport<I> {
     }
 }
 
+impl ShardedTransport for Weak<InMemoryTransport<ShardIndex>> {
+    fn shard_count(&self) -> ShardIndex {
+        let connections = self.upgrade().unwrap().connections.len();
+        ShardIndex::try_from(connections + 1).unwrap()
+    }
+}
+
 #[async_trait]
 impl<I: TransportIdentity> Transport for Weak<InMemoryTransport<I>> {
     type Identity = I;
@@ -408,7 +416,7 @@ mod tests {
                     .unwrap()
                     .take()
                     .expect("query callback invoked more than once")
-                    .send(query_config)
+                    .send(query_config.clone())
                     .unwrap();
                 Ok(HelperResponse::from(PrepareQuery {
                     query_id: QueryId,
@@ -423,7 +431,7 @@ mod tests {
 
         send_and_ack(
             &tx,
-            Addr::from_route(Some(HelperIdentity::TWO), expected),
+            Addr::from_route(Some(HelperIdentity::TWO), expected.clone()),
             stream::empty(),
         )
         .await;
@@ -333,6 +333,12 @@ pub trait Transport: Clone + Send + Sync + 'static {
     }
 }
 
+/// Transport that connects all the shards of one helper party.
+pub trait ShardedTransport: Transport<Identity = ShardIndex> {
+    /// Returns the number of shards this helper party runs, including this one.
+    fn shard_count(&self) -> ShardIndex;
+}
+
 #[cfg(all(test, unit_test))]
 mod tests {
     use crate::{
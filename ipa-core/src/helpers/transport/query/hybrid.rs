@@ -1,6 +1,14 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+use super::{
+    default_delta, default_padding_delta, default_padding_epsilon, default_padding_sensitivity,
+    DpConfigError, DpMechanism, DpMechanismKind,
+};
+use crate::protocol::ipa_prf::oprf_padding::{
+    AggregationPadding, OPRFPadding, PaddingGeneration, PaddingParameters,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[cfg_attr(feature = "clap", derive(clap::Args))]
 pub struct HybridQueryParams {
     #[cfg_attr(feature = "clap", arg(long, default_value = "8"))]
@@ -11,6 +19,42 @@ pub struct HybridQueryParams {
     pub with_dp: u32,
     #[cfg_attr(feature = "clap", arg(short = 'e', long, default_value = "5.0"))]
     pub epsilon: f64,
+    /// Noise mechanism applied to the output, if `with_dp` is set.
+    #[cfg_attr(feature = "clap", arg(long, value_enum, default_value_t))]
+    #[serde(default)]
+    pub dp_mechanism: DpMechanismKind,
+    #[cfg_attr(feature = "clap", arg(long, default_value = "1e-6"))]
+    #[serde(default = "default_delta")]
+    pub delta: f64,
+    /// Epsilon of the fake rows added to hide how many reports share a match key.
+    #[cfg_attr(feature = "clap", arg(long, default_value = "5.0"))]
+    #[serde(default = "default_padding_epsilon")]
+    pub oprf_padding_epsilon: f64,
+    #[cfg_attr(feature = "clap", arg(long, default_value = "1e-6"))]
+    #[serde(default = "default_padding_delta")]
+    pub oprf_padding_delta: f64,
+    /// Largest number of reports sharing a match key whose count is hidden by padding.
+    #[cfg_attr(feature = "clap", arg(long, default_value = "10"))]
+    #[serde(default = "default_padding_sensitivity")]
+    pub matchkey_cardinality_cap: u32,
+    /// Epsilon of the fake rows added to hide how many rows are revealed for each breakdown.
+    #[cfg_attr(feature = "clap", arg(long, default_value = "5.0"))]
+    #[serde(default = "default_padding_epsilon")]
+    pub aggregation_padding_epsilon: f64,
+    #[cfg_attr(feature = "clap", arg(long, default_value = "1e-6"))]
+    #[serde(default = "default_padding_delta")]
+    pub aggregation_padding_delta: f64,
+    #[cfg_attr(feature = "clap", arg(long, default_value = "10"))]
+    #[serde(default = "default_padding_sensitivity")]
+    pub aggregation_padding_sensitivity: u32,
+    /// Domain of the conversion site whose reports are queried. Together with `epoch`, it
+    /// selects the privacy budget that this query is charged against.
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub conversion_site: Option<String>,
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub epoch: Option<u32>,
     #[cfg_attr(feature = "clap", arg(long))]
     #[serde(default)]
     pub plaintext_match_keys: bool,
@@ -26,7 +70,46 @@ impl Default for HybridQueryParams {
             max_breakdown_key: 20,
             with_dp: 1,
             epsilon: 0.10,
+            dp_mechanism: DpMechanismKind::default(),
+            delta: DpMechanism::DEFAULT_DELTA,
+            oprf_padding_epsilon: default_padding_epsilon(),
+            oprf_padding_delta: default_padding_delta(),
+            matchkey_cardinality_cap: default_padding_sensitivity(),
+            aggregation_padding_epsilon: default_padding_epsilon(),
+            aggregation_padding_delta: default_padding_delta(),
+            aggregation_padding_sensitivity: default_padding_sensitivity(),
+            conversion_site: None,
+            epoch: None,
             plaintext_match_keys: false,
         }
     }
 }
+
+impl HybridQueryParams {
+    /// Returns the DP mechanism requested by these parameters.
+    ///
+    /// ## Errors
+    /// If the requested epsilon or delta are out of bounds.
+    pub fn dp_mechanism(&self) -> Result<DpMechanism, DpConfigError> {
+        DpMechanism::from_query_params(self.with_dp, self.dp_mechanism, self.epsilon, self.delta)
+    }
+
+    /// Returns the padding requested by these parameters.
+    #[must_use]
+    pub fn padding_params(&self) -> PaddingParameters {
+        PaddingParameters {
+            oprf_padding: OPRFPadding::Parameters {
+                oprf_epsilon: self.oprf_padding_epsilon,
+                oprf_delta: self.oprf_padding_delta,
+                matchkey_cardinality_cap: self.matchkey_cardinality_cap,
+                oprf_padding_sensitivity: OPRFPadding::SENSITIVITY,
+            },
+            aggregation_padding: AggregationPadding::Parameters {
+                aggregation_epsilon: self.aggregation_padding_epsilon,
+                aggregation_delta: self.aggregation_padding_delta,
+                aggregation_padding_sensitivity: self.aggregation_padding_sensitivity,
+            },
+            padding_generation: PaddingGeneration::Pairwise,
+        }
+    }
+}
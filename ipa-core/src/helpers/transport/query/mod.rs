@@ -1,11 +1,19 @@
+mod feature_label;
 mod hybrid;
+mod lift;
+mod logistic_regression;
+mod reach_frequency;
 
 use std::{
     fmt::{Debug, Display, Formatter},
     num::NonZeroU32,
 };
 
+pub use feature_label::FeatureLabelQueryParams;
 pub use hybrid::HybridQueryParams;
+pub use lift::LiftQueryParams;
+pub use logistic_regression::LogisticRegressionQueryParams;
+pub use reach_frequency::ReachFrequencyQueryParams;
 use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::{
@@ -14,7 +22,17 @@ use crate::{
         transport::{routing::RouteId, BodyStream, NoQueryId, NoStep},
         RoleAssignment, RouteParams,
     },
-    protocol::QueryId,
+    protocol::{
+        dp::{check_threshold_stds, HistogramNoise, SuppressionThresholdError, MAX_EPSILON},
+        ipa_prf::{
+            oprf_padding::{
+                insecure::Error as PaddingConfigError, AggregationPadding, OPRFPadding,
+                PaddingGeneration, PaddingParameters,
+            },
+            prf_sharding::reach_frequency::MAX_FREQUENCY_CAP,
+        },
+        QueryId,
+    },
 };
 
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Serialize)]
@@ -80,7 +98,7 @@ impl From<QuerySize> for usize {
     }
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 pub struct QueryConfig {
     pub size: QuerySize,
@@ -92,6 +110,16 @@ pub struct QueryConfig {
 pub enum QueryConfigError {
     #[error(transparent)]
     BadQuerySize(#[from] BadQuerySizeError),
+    #[error(transparent)]
+    BadDpParameters(#[from] DpConfigError),
+    #[error("invalid padding parameters: {0}")]
+    BadPaddingParameters(#[from] PaddingConfigError),
+    #[error("{0:?} is not a valid conversion site domain")]
+    BadConversionSite(String),
+    #[error("frequency cap must be between 1 and {MAX_FREQUENCY_CAP}, got {0}")]
+    BadFrequencyCap(u32),
+    #[error(transparent)]
+    BadSuppressionThreshold(#[from] SuppressionThresholdError),
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -146,7 +174,8 @@ impl QueryConfig {
     /// Initialize new query configuration.
     ///
     /// ## Errors
-    /// If query size is too large or 0.
+    /// If query size is too large or 0, or if the query requests invalid DP or padding
+    /// parameters.
     pub fn new<S>(
         query_type: QueryType,
         field_type: FieldType,
@@ -155,11 +184,82 @@ impl QueryConfig {
     where
         S: TryInto<QuerySize, Error = BadQuerySizeError>,
     {
-        Ok(Self {
+        let config = Self {
             size: size.try_into()?,
             field_type,
             query_type,
-        })
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the parameters of the query that are not validated when they are deserialized.
+    ///
+    /// ## Errors
+    /// If the query requests invalid DP or padding parameters.
+    pub fn validate(&self) -> Result<(), QueryConfigError> {
+        match &self.query_type {
+            QueryType::SemiHonestOprfIpa(config)
+            | QueryType::MaliciousOprfIpa(config)
+            | QueryType::SemiHonestShardedOprfIpa(config)
+            | QueryType::MaliciousShardedOprfIpa(config) => {
+                config.dp_mechanism()?;
+                config.padding_params().validate()?;
+                validate_conversion_site(config.conversion_site.as_deref())?;
+                if let Some(threshold_stds) = config.suppression_threshold_stds {
+                    check_threshold_stds(threshold_stds)?;
+                }
+            }
+            QueryType::SemiHonestHybrid(config) => {
+                config.dp_mechanism()?;
+                config.padding_params().validate()?;
+                validate_conversion_site(config.conversion_site.as_deref())?;
+            }
+            QueryType::SemiHonestFeatureLabelDotProduct(config) => {
+                config.dp_mechanism()?;
+                validate_conversion_site(config.conversion_site.as_deref())?;
+            }
+            QueryType::SemiHonestLogisticRegression(config) => {
+                config.dp_mechanism()?;
+                validate_conversion_site(config.conversion_site.as_deref())?;
+            }
+            QueryType::SemiHonestReachFrequency(config) => {
+                config.dp_mechanism()?;
+                validate_frequency_cap(config.frequency_cap)?;
+                validate_conversion_site(config.conversion_site.as_deref())?;
+            }
+            QueryType::SemiHonestLift(config) => {
+                config.dp_mechanism()?;
+                validate_conversion_site(config.conversion_site.as_deref())?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Conversion sites are identified by their domain, which is also how they appear in
+/// hybrid conversion reports.
+fn validate_conversion_site(site: Option<&str>) -> Result<(), QueryConfigError> {
+    match site {
+        Some(site)
+            if site.is_empty()
+                || !site
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'-') =>
+        {
+            Err(QueryConfigError::BadConversionSite(site.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Users that saw more impressions than the frequency cap are counted in the last bucket of the
+/// frequency distribution, so the cap is the number of buckets.
+fn validate_frequency_cap(frequency_cap: u32) -> Result<(), QueryConfigError> {
+    match usize::try_from(frequency_cap) {
+        Ok(cap) if (1..=MAX_FREQUENCY_CAP).contains(&cap) => Ok(()),
+        _ => Err(QueryConfigError::BadFrequencyCap(frequency_cap)),
     }
 }
 
@@ -194,7 +294,7 @@ impl Debug for QueryInput {
     }
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 pub enum QueryType {
     #[cfg(any(test, feature = "test-fixture", feature = "cli"))]
@@ -205,7 +305,14 @@ pub enum QueryType {
     TestShardedShuffle,
     SemiHonestOprfIpa(IpaQueryConfig),
     MaliciousOprfIpa(IpaQueryConfig),
+    /// OPRF IPA on helpers that are split into several shards.
+    SemiHonestShardedOprfIpa(IpaQueryConfig),
+    MaliciousShardedOprfIpa(IpaQueryConfig),
     SemiHonestHybrid(HybridQueryParams),
+    SemiHonestFeatureLabelDotProduct(FeatureLabelQueryParams),
+    SemiHonestLogisticRegression(LogisticRegressionQueryParams),
+    SemiHonestReachFrequency(ReachFrequencyQueryParams),
+    SemiHonestLift(LiftQueryParams),
 }
 
 impl QueryType {
@@ -215,7 +322,27 @@ impl QueryType {
     pub const TEST_SHARDED_SHUFFLE_STR: &'static str = "test-sharded-shuffle";
     pub const SEMI_HONEST_OPRF_IPA_STR: &'static str = "semi-honest-oprf-ipa";
     pub const MALICIOUS_OPRF_IPA_STR: &'static str = "malicious-oprf-ipa";
+    pub const SEMI_HONEST_SHARDED_OPRF_IPA_STR: &'static str = "semi-honest-sharded-oprf-ipa";
+    pub const MALICIOUS_SHARDED_OPRF_IPA_STR: &'static str = "malicious-sharded-oprf-ipa";
     pub const SEMI_HONEST_HYBRID_STR: &'static str = "semi-honest-hybrid";
+    pub const SEMI_HONEST_FEATURE_LABEL_DOT_PRODUCT_STR: &'static str =
+        "semi-honest-feature-label-dot-product";
+    pub const SEMI_HONEST_LOGISTIC_REGRESSION_STR: &'static str = "semi-honest-logistic-regression";
+    pub const SEMI_HONEST_REACH_FREQUENCY_STR: &'static str = "semi-honest-reach-frequency";
+    pub const SEMI_HONEST_LIFT_STR: &'static str = "semi-honest-lift";
+
+    /// Returns `true` if this query can run on helpers that are split into several shards.
+    /// Other queries only see the input of the shard they run on, so running them on more than
+    /// one shard gives wrong results.
+    #[must_use]
+    pub fn is_sharded(&self) -> bool {
+        match self {
+            #[cfg(any(test, feature = "cli", feature = "test-fixture"))]
+            QueryType::TestShardedShuffle => true,
+            QueryType::SemiHonestShardedOprfIpa(_) | QueryType::MaliciousShardedOprfIpa(_) => true,
+            _ => false,
+        }
+    }
 }
 
 /// TODO: should this `AsRef` impl (used for `Substep`) take into account config of IPA?
@@ -230,22 +357,197 @@ impl AsRef<str> for QueryType {
             QueryType::TestShardedShuffle => Self::TEST_SHARDED_SHUFFLE_STR,
             QueryType::SemiHonestOprfIpa(_) => Self::SEMI_HONEST_OPRF_IPA_STR,
             QueryType::MaliciousOprfIpa(_) => Self::MALICIOUS_OPRF_IPA_STR,
+            QueryType::SemiHonestShardedOprfIpa(_) => Self::SEMI_HONEST_SHARDED_OPRF_IPA_STR,
+            QueryType::MaliciousShardedOprfIpa(_) => Self::MALICIOUS_SHARDED_OPRF_IPA_STR,
             QueryType::SemiHonestHybrid(_) => Self::SEMI_HONEST_HYBRID_STR,
+            QueryType::SemiHonestFeatureLabelDotProduct(_) => {
+                Self::SEMI_HONEST_FEATURE_LABEL_DOT_PRODUCT_STR
+            }
+            QueryType::SemiHonestLogisticRegression(_) => Self::SEMI_HONEST_LOGISTIC_REGRESSION_STR,
+            QueryType::SemiHonestReachFrequency(_) => Self::SEMI_HONEST_REACH_FREQUENCY_STR,
+            QueryType::SemiHonestLift(_) => Self::SEMI_HONEST_LIFT_STR,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "kind")]
 pub enum DpMechanism {
     NoDp,
-    Binomial { epsilon: f64 },
-    DiscreteLaplace { epsilon: f64 },
+    Binomial { epsilon: f64, delta: f64 },
+    DiscreteLaplace { epsilon: f64, delta: f64 },
+    DiscreteGaussian { epsilon: f64, delta: f64 },
+}
+
+impl DpMechanism {
+    /// Delta used by queries that do not specify one.
+    pub const DEFAULT_DELTA: f64 = 1e-6;
+
+    /// Builds the mechanism requested by a query config. `with_dp` set to 0 disables DP, and
+    /// `kind`, `epsilon` and `delta` are ignored in that case.
+    ///
+    /// ## Errors
+    /// If `epsilon` or `delta` are out of bounds.
+    pub fn from_query_params(
+        with_dp: u32,
+        kind: DpMechanismKind,
+        epsilon: f64,
+        delta: f64,
+    ) -> Result<Self, DpConfigError> {
+        if with_dp == 0 {
+            return Ok(Self::NoDp);
+        }
+        if !(epsilon > 0.0 && epsilon <= MAX_EPSILON) {
+            return Err(DpConfigError::Epsilon(epsilon));
+        }
+        if !(delta > 0.0 && delta < 1.0) {
+            return Err(DpConfigError::Delta(delta));
+        }
+        Ok(match kind {
+            DpMechanismKind::DiscreteLaplace => Self::DiscreteLaplace { epsilon, delta },
+            DpMechanismKind::Binomial => Self::Binomial { epsilon, delta },
+            DpMechanismKind::DiscreteGaussian => Self::DiscreteGaussian { epsilon, delta },
+        })
+    }
+
+    /// Returns the mechanism for each of `parts` releases that evenly share the privacy budget
+    /// of this mechanism.
+    #[must_use]
+    pub fn split_budget(self, parts: u32) -> Self {
+        let parts = f64::from(parts);
+        match self {
+            Self::NoDp => Self::NoDp,
+            Self::Binomial { epsilon, delta } => Self::Binomial {
+                epsilon: epsilon / parts,
+                delta: delta / parts,
+            },
+            Self::DiscreteLaplace { epsilon, delta } => Self::DiscreteLaplace {
+                epsilon: epsilon / parts,
+                delta: delta / parts,
+            },
+            Self::DiscreteGaussian { epsilon, delta } => Self::DiscreteGaussian {
+                epsilon: epsilon / parts,
+                delta: delta / parts,
+            },
+        }
+    }
+}
+
+/// Differential privacy that helpers applied to the output of a query. It is returned together
+/// with the query results, so report collectors don't have to infer it from the query config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputDp {
+    /// Mechanism requested for the whole query.
+    pub mechanism: DpMechanism,
+    /// Noisy histograms released in the output, in the order they appear in it.
+    pub histograms: Vec<NoisyHistogram>,
+}
+
+#[cfg(test)]
+impl Eq for OutputDp {}
+
+/// Noise added to one histogram in the output of a query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoisyHistogram {
+    /// Mechanism applied to this histogram, with its share of the query privacy budget.
+    pub mechanism: DpMechanism,
+    /// Bound on the total contribution of a single user to the histogram, which the noise is
+    /// calibrated to.
+    pub sensitivity: u32,
+    /// Number of buckets that noise is added to.
+    pub dimensions: u32,
+    /// Buckets whose noisy value, with the mean of the noise removed, was below this threshold
+    /// were reported as zero. Not set if sparse buckets were not suppressed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppression_threshold: Option<f64>,
+}
+
+#[cfg(test)]
+impl Eq for NoisyHistogram {}
+
+impl NoisyHistogram {
+    /// Describes the noise in every bucket of this histogram. Returns `None` if no noise was
+    /// added.
+    ///
+    /// ## Errors
+    /// If the DP parameters are out of bounds.
+    pub fn noise(&self) -> Result<Option<HistogramNoise>, crate::error::Error> {
+        HistogramNoise::new(self.mechanism, self.sensitivity, self.dimensions)
+    }
+}
+
+/// Noise mechanism that a query requests for its output, when DP is enabled.
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum DpMechanismKind {
+    #[default]
+    DiscreteLaplace,
+    Binomial,
+    DiscreteGaussian,
+}
+
+impl Display for DpMechanismKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DiscreteLaplace => write!(f, "discrete-laplace"),
+            Self::Binomial => write!(f, "binomial"),
+            Self::DiscreteGaussian => write!(f, "discrete-gaussian"),
+        }
+    }
+}
+
+/// How helpers agree on the challenges of the distributed zero-knowledge proofs that validate
+/// multiplications in malicious queries.
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum DzkpMode {
+    /// Both verifiers of a proof exchange hashes of their proof shares after receiving the proof.
+    #[default]
+    Interactive,
+    /// Verifiers derive challenges from proof hashes that are sent together with the proof
+    /// (Fiat-Shamir), which saves one round trip for every validated batch.
+    NonInteractive,
+}
+
+impl Display for DzkpMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Interactive => write!(f, "interactive"),
+            Self::NonInteractive => write!(f, "non-interactive"),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DpConfigError {
+    #[error("epsilon must be within (0, {MAX_EPSILON}], got: {0}")]
+    Epsilon(f64),
+    #[error("delta must be within (0, 1), got: {0}")]
+    Delta(f64),
+}
+
+fn default_delta() -> f64 {
+    DpMechanism::DEFAULT_DELTA
+}
+
+fn default_padding_epsilon() -> f64 {
+    5.0
+}
+
+fn default_padding_delta() -> f64 {
+    1e-6
+}
+
+fn default_padding_sensitivity() -> u32 {
+    10
 }
 
 #[cfg(test)]
 impl Eq for IpaQueryConfig {}
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[cfg_attr(feature = "clap", derive(clap::Args))]
 pub struct IpaQueryConfig {
     #[cfg_attr(feature = "clap", arg(long, default_value = "8"))]
@@ -254,10 +556,70 @@ pub struct IpaQueryConfig {
     pub max_breakdown_key: u32,
     #[cfg_attr(feature = "clap", arg(long))]
     pub attribution_window_seconds: Option<NonZeroU32>,
+    /// Largest value of a single trigger event. Larger values are lowered to this cap before
+    /// attribution, which also bounds the sensitivity of the output if the cap is small.
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub trigger_value_cap: Option<u32>,
+    /// Credit trigger values that are not attributed to any source event to an extra breakdown,
+    /// right after the last one (`max_breakdown_key`). It is noised like any other breakdown.
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub unattributed_bucket: bool,
+    /// Helpers report breakdowns as zero if their noisy value, with the mean of the noise
+    /// removed, is below this many standard deviations of the DP noise. Ignored without DP.
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub suppression_threshold_stds: Option<f64>,
     #[arg(short = 'd', long, default_value = "1")]
     pub with_dp: u32,
     #[arg(short = 'e', long, default_value = "5.0")]
     pub epsilon: f64,
+    /// Noise mechanism applied to the output, if `with_dp` is set.
+    #[cfg_attr(feature = "clap", arg(long, value_enum, default_value_t))]
+    #[serde(default)]
+    pub dp_mechanism: DpMechanismKind,
+    #[cfg_attr(feature = "clap", arg(long, default_value = "1e-6"))]
+    #[serde(default = "default_delta")]
+    pub delta: f64,
+    /// Epsilon of the fake rows added to hide how many reports share a match key.
+    #[cfg_attr(feature = "clap", arg(long, default_value = "5.0"))]
+    #[serde(default = "default_padding_epsilon")]
+    pub oprf_padding_epsilon: f64,
+    #[cfg_attr(feature = "clap", arg(long, default_value = "1e-6"))]
+    #[serde(default = "default_padding_delta")]
+    pub oprf_padding_delta: f64,
+    /// Largest number of reports sharing a match key whose count is hidden by padding.
+    #[cfg_attr(feature = "clap", arg(long, default_value = "10"))]
+    #[serde(default = "default_padding_sensitivity")]
+    pub matchkey_cardinality_cap: u32,
+    /// Epsilon of the fake rows added to hide how many rows are revealed for each breakdown.
+    #[cfg_attr(feature = "clap", arg(long, default_value = "5.0"))]
+    #[serde(default = "default_padding_epsilon")]
+    pub aggregation_padding_epsilon: f64,
+    #[cfg_attr(feature = "clap", arg(long, default_value = "1e-6"))]
+    #[serde(default = "default_padding_delta")]
+    pub aggregation_padding_delta: f64,
+    #[cfg_attr(feature = "clap", arg(long, default_value = "10"))]
+    #[serde(default = "default_padding_sensitivity")]
+    pub aggregation_padding_sensitivity: u32,
+    /// Generate the OPRF and aggregation padding noise in MPC, so that no pair of helpers knows
+    /// it. This is slower than the default, where each pair of helpers samples the noise it adds.
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub joint_padding: bool,
+    /// Domain of the conversion site whose reports are queried. Together with `epoch`, it
+    /// selects the privacy budget that this query is charged against.
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub conversion_site: Option<String>,
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub epoch: Option<u32>,
+    /// How malicious multiplications are validated. Ignored by semi-honest queries.
+    #[cfg_attr(feature = "clap", arg(long, value_enum, default_value_t))]
+    #[serde(default)]
+    pub dzkp_mode: DzkpMode,
 
     /// If false, IPA decrypts match key shares in the input reports. If true, IPA uses match key
     /// shares from input reports directly. Setting this to true also activates an alternate
@@ -274,14 +636,60 @@ impl Default for IpaQueryConfig {
             per_user_credit_cap: 8,
             max_breakdown_key: 20,
             attribution_window_seconds: None,
+            trigger_value_cap: None,
+            unattributed_bucket: false,
+            suppression_threshold_stds: None,
             with_dp: 1,
             epsilon: 0.10,
+            dp_mechanism: DpMechanismKind::default(),
+            delta: DpMechanism::DEFAULT_DELTA,
+            oprf_padding_epsilon: default_padding_epsilon(),
+            oprf_padding_delta: default_padding_delta(),
+            matchkey_cardinality_cap: default_padding_sensitivity(),
+            aggregation_padding_epsilon: default_padding_epsilon(),
+            aggregation_padding_delta: default_padding_delta(),
+            aggregation_padding_sensitivity: default_padding_sensitivity(),
+            joint_padding: false,
+            conversion_site: None,
+            epoch: None,
+            dzkp_mode: DzkpMode::default(),
             plaintext_match_keys: false,
         }
     }
 }
 
 impl IpaQueryConfig {
+    /// Returns the DP mechanism requested by this config.
+    ///
+    /// ## Errors
+    /// If the requested epsilon or delta are out of bounds.
+    pub fn dp_mechanism(&self) -> Result<DpMechanism, DpConfigError> {
+        DpMechanism::from_query_params(self.with_dp, self.dp_mechanism, self.epsilon, self.delta)
+    }
+
+    /// Returns the padding requested by this config.
+    #[must_use]
+    pub fn padding_params(&self) -> PaddingParameters {
+        PaddingParameters {
+            oprf_padding: OPRFPadding::Parameters {
+                oprf_epsilon: self.oprf_padding_epsilon,
+                oprf_delta: self.oprf_padding_delta,
+                matchkey_cardinality_cap: self.matchkey_cardinality_cap,
+                oprf_padding_sensitivity: OPRFPadding::SENSITIVITY,
+            },
+            aggregation_padding: AggregationPadding::Parameters {
+                aggregation_epsilon: self.aggregation_padding_epsilon,
+                aggregation_delta: self.aggregation_padding_delta,
+                aggregation_padding_sensitivity: self.aggregation_padding_sensitivity,
+            },
+            padding_generation: if self.joint_padding {
+                PaddingGeneration::Joint
+            } else {
+                PaddingGeneration::Pairwise
+            },
+        }
+    }
+
     /// ## Panics
     /// If attribution window is 0
     #[must_use]
@@ -299,9 +707,23 @@ impl IpaQueryConfig {
                 NonZeroU32::new(attribution_window_seconds)
                     .expect("attribution window must be a positive value > 0"),
             ),
+            trigger_value_cap: None,
+            unattributed_bucket: false,
+            suppression_threshold_stds: None,
             with_dp,
             epsilon,
-            // dp_params,
+            dp_mechanism: DpMechanismKind::default(),
+            delta: DpMechanism::DEFAULT_DELTA,
+            oprf_padding_epsilon: default_padding_epsilon(),
+            oprf_padding_delta: default_padding_delta(),
+            matchkey_cardinality_cap: default_padding_sensitivity(),
+            aggregation_padding_epsilon: default_padding_epsilon(),
+            aggregation_padding_delta: default_padding_delta(),
+            aggregation_padding_sensitivity: default_padding_sensitivity(),
+            joint_padding: false,
+            conversion_site: None,
+            epoch: None,
+            dzkp_mode: DzkpMode::default(),
             plaintext_match_keys: false,
         }
     }
@@ -321,8 +743,23 @@ impl IpaQueryConfig {
             per_user_credit_cap,
             max_breakdown_key,
             attribution_window_seconds: None,
+            trigger_value_cap: None,
+            unattributed_bucket: false,
+            suppression_threshold_stds: None,
             with_dp,
             epsilon,
+            dp_mechanism: DpMechanismKind::default(),
+            delta: DpMechanism::DEFAULT_DELTA,
+            oprf_padding_epsilon: default_padding_epsilon(),
+            oprf_padding_delta: default_padding_delta(),
+            matchkey_cardinality_cap: default_padding_sensitivity(),
+            aggregation_padding_epsilon: default_padding_epsilon(),
+            aggregation_padding_delta: default_padding_delta(),
+            aggregation_padding_sensitivity: default_padding_sensitivity(),
+            joint_padding: false,
+            conversion_site: None,
+            epoch: None,
+            dzkp_mode: DzkpMode::default(),
             plaintext_match_keys: false,
         }
     }
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+use super::{default_delta, DpConfigError, DpMechanism, DpMechanismKind};
+use crate::protocol::ipa_prf::oprf_padding::PaddingParameters;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct ReachFrequencyQueryParams {
+    /// Users that saw this many impressions or more share the last bucket of the frequency
+    /// distribution.
+    #[cfg_attr(feature = "clap", arg(long, default_value = "3"))]
+    pub frequency_cap: u32,
+    #[cfg_attr(feature = "clap", arg(short = 'd', long, default_value = "1"))]
+    pub with_dp: u32,
+    /// Privacy budget of the query, split evenly between reach and frequency.
+    #[cfg_attr(feature = "clap", arg(short = 'e', long, default_value = "5.0"))]
+    pub epsilon: f64,
+    /// Noise mechanism applied to the output, if `with_dp` is set.
+    #[cfg_attr(feature = "clap", arg(long, value_enum, default_value_t))]
+    #[serde(default)]
+    pub dp_mechanism: DpMechanismKind,
+    #[cfg_attr(feature = "clap", arg(long, default_value = "1e-6"))]
+    #[serde(default = "default_delta")]
+    pub delta: f64,
+    /// Domain of the conversion site whose reports are queried. Together with `epoch`, it
+    /// selects the privacy budget that this query is charged against.
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub conversion_site: Option<String>,
+    #[cfg_attr(feature = "clap", arg(long))]
+    #[serde(default)]
+    pub epoch: Option<u32>,
+}
+
+#[cfg(test)]
+impl Eq for ReachFrequencyQueryParams {}
+
+impl Default for ReachFrequencyQueryParams {
+    fn default() -> Self {
+        Self {
+            frequency_cap: 3,
+            with_dp: 1,
+            epsilon: 5.0,
+            dp_mechanism: DpMechanismKind::default(),
+            delta: DpMechanism::DEFAULT_DELTA,
+            conversion_site: None,
+            epoch: None,
+        }
+    }
+}
+
+impl ReachFrequencyQueryParams {
+    /// Returns the DP mechanism requested by these parameters.
+    ///
+    /// ## Errors
+    /// If the requested epsilon or delta are out of bounds.
+    pub fn dp_mechanism(&self) -> Result<DpMechanism, DpConfigError> {
+        DpMechanism::from_query_params(self.with_dp, self.dp_mechanism, self.epsilon, self.delta)
+    }
+
+    /// Returns the padding used by this query. The padding can't be configured yet, so it is
+    /// always the default one.
+    #[must_use]
+    pub fn padding_params(&self) -> PaddingParameters {
+        PaddingParameters::default()
+    }
+}
@@ -118,6 +118,10 @@ impl ResponseFromEndpoint {
         self.inner.status()
     }
 
+    pub fn headers(&self) -> &hyper::HeaderMap {
+        self.inner.headers()
+    }
+
     pub fn into_body(self) -> Body {
         self.inner.into_body()
     }
@@ -460,12 +464,31 @@ impl IpaHttpClient<Helper> {
     /// If the request has illegal arguments, or fails to deliver to helper
     #[cfg(any(all(test, not(feature = "shuttle")), feature = "cli"))]
     pub async fn query_results(&self, query_id: QueryId) -> Result<bytes::Bytes, Error> {
+        let (body, _) = self.query_results_with_dp(query_id).await?;
+        Ok(body)
+    }
+
+    /// Same as [`Self::query_results`], but also returns the differential privacy that this helper
+    /// applied to the results. It is `None` for queries that are not differentially private.
+    ///
+    /// ## Errors
+    /// If the request has illegal arguments, or fails to deliver to helper
+    #[cfg(any(all(test, not(feature = "shuttle")), feature = "cli"))]
+    pub async fn query_results_with_dp(
+        &self,
+        query_id: QueryId,
+    ) -> Result<(bytes::Bytes, Option<crate::helpers::query::OutputDp>), Error> {
         let req = http_serde::query::results::Request::new(query_id);
         let req = req.try_into_http_request(self.scheme.clone(), self.authority.clone())?;
         let resp = self.request(req).await?;
         if resp.status().is_success() {
+            let output_dp = resp
+                .headers()
+                .get(&crate::net::HTTP_OUTPUT_DP_HEADER)
+                .map(|value| serde_json::from_slice(value.as_bytes()))
+                .transpose()?;
             let body = resp.into_body().collect().await?.to_bytes();
-            Ok(body)
+            Ok((body, output_dp))
         } else {
             Err(Error::from_failed_resp(resp).await)
         }
@@ -524,12 +547,14 @@ pub(crate) mod tests {
     use crate::{
         ff::{FieldType, Fp31},
         helpers::{
-            make_owned_handler, query::QueryType::TestMultiply, BytesStream, HelperIdentity,
-            HelperResponse, RequestHandler, RoleAssignment, Transport, MESSAGE_PAYLOAD_SIZE_BYTES,
+            make_owned_handler,
+            query::{DpMechanism, NoisyHistogram, OutputDp, QueryType::TestMultiply},
+            BytesStream, HelperIdentity, HelperResponse, RequestHandler, RoleAssignment, Transport,
+            MESSAGE_PAYLOAD_SIZE_BYTES,
         },
         net::test::TestServer,
         protocol::step::TestExecutionStep,
-        query::ProtocolResult,
+        query::{DpQueryOutput, ProtocolResult},
         secret_sharing::replicated::semi_honest::AdditiveShare as Replicated,
         sync::Arc,
     };
@@ -759,4 +784,50 @@ pub(crate) mod tests {
                 .to_bytes()
         );
     }
+
+    #[tokio::test]
+    async fn results_with_dp() {
+        let expected_results = [
+            Fp31::try_from(1u128).unwrap(),
+            Fp31::try_from(2u128).unwrap(),
+        ];
+        let mechanism = DpMechanism::DiscreteLaplace {
+            epsilon: 1.0,
+            delta: 1e-6,
+        };
+        let expected_dp = OutputDp {
+            mechanism,
+            histograms: vec![NoisyHistogram {
+                mechanism,
+                sensitivity: 8,
+                dimensions: 256,
+                suppression_threshold: None,
+            }],
+        };
+        let handler = move || {
+            let expected_dp = expected_dp.clone();
+            make_owned_handler(move |_, _| {
+                let expected_dp = expected_dp.clone();
+                async move {
+                    let results: Box<dyn ProtocolResult> = Box::new(DpQueryOutput {
+                        values: vec![Replicated::from((expected_results[0], expected_results[1]))],
+                        dp: expected_dp,
+                    });
+                    Ok(HelperResponse::from(results))
+                }
+            })
+        };
+        let (results, output_dp) = test_query_command(
+            |client| async move { client.query_results_with_dp(QueryId).await.unwrap() },
+            handler,
+        )
+        .await;
+        assert_eq!(
+            results.to_vec(),
+            [Replicated::from((expected_results[0], expected_results[1]))]
+                .to_vec()
+                .to_bytes()
+        );
+        assert_eq!(output_dp, Some(expected_dp));
+    }
 }
@@ -81,7 +81,7 @@ pub mod query {
 
     use crate::{
         ff::FieldType,
-        helpers::query::{QueryConfig, QuerySize, QueryType},
+        helpers::query::{DzkpMode, QueryConfig, QuerySize, QueryType},
         net::Error,
     };
 
@@ -122,6 +122,8 @@ pub mod query {
                 QueryType::TEST_MULTIPLY_STR => Ok(QueryType::TestMultiply),
                 #[cfg(any(test, feature = "cli", feature = "test-fixture"))]
                 QueryType::TEST_ADD_STR => Ok(QueryType::TestAddInPrimeField),
+                #[cfg(any(test, feature = "cli", feature = "test-fixture"))]
+                QueryType::TEST_SHARDED_SHUFFLE_STR => Ok(QueryType::TestShardedShuffle),
                 QueryType::SEMI_HONEST_OPRF_IPA_STR => {
                     let Query(q) = req.extract().await?;
                     Ok(QueryType::SemiHonestOprfIpa(q))
@@ -130,17 +132,46 @@ pub mod query {
                     let Query(q) = req.extract().await?;
                     Ok(QueryType::MaliciousOprfIpa(q))
                 }
+                QueryType::SEMI_HONEST_SHARDED_OPRF_IPA_STR => {
+                    let Query(q) = req.extract().await?;
+                    Ok(QueryType::SemiHonestShardedOprfIpa(q))
+                }
+                QueryType::MALICIOUS_SHARDED_OPRF_IPA_STR => {
+                    let Query(q) = req.extract().await?;
+                    Ok(QueryType::MaliciousShardedOprfIpa(q))
+                }
+                QueryType::SEMI_HONEST_FEATURE_LABEL_DOT_PRODUCT_STR => {
+                    let Query(q) = req.extract().await?;
+                    Ok(QueryType::SemiHonestFeatureLabelDotProduct(q))
+                }
+                QueryType::SEMI_HONEST_LOGISTIC_REGRESSION_STR => {
+                    let Query(q) = req.extract().await?;
+                    Ok(QueryType::SemiHonestLogisticRegression(q))
+                }
+                QueryType::SEMI_HONEST_REACH_FREQUENCY_STR => {
+                    let Query(q) = req.extract().await?;
+                    Ok(QueryType::SemiHonestReachFrequency(q))
+                }
+                QueryType::SEMI_HONEST_LIFT_STR => {
+                    let Query(q) = req.extract().await?;
+                    Ok(QueryType::SemiHonestLift(q))
+                }
                 other => Err(Error::bad_query_value("query_type", other)),
             }?;
-            Ok(QueryConfigQueryParams(QueryConfig {
+            let query_config = QueryConfig {
                 size,
                 field_type,
                 query_type,
-            }))
+            };
+            query_config
+                .validate()
+                .map_err(|e| Error::BadQueryString(Box::new(e)))?;
+            Ok(QueryConfigQueryParams(query_config))
         }
     }
 
     impl Display for QueryConfigQueryParams {
+        #[allow(clippy::too_many_lines)]
         fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
             write!(
                 f,
@@ -149,51 +180,163 @@ pub mod query {
                 f = self.field_type,
                 size = self.size
             )?;
-            match self.query_type {
+            match &self.query_type {
                 #[cfg(any(test, feature = "test-fixture", feature = "cli"))]
                 QueryType::TestMultiply | QueryType::TestAddInPrimeField => Ok(()),
                 #[cfg(any(test, feature = "test-fixture", feature = "cli"))]
                 QueryType::TestShardedShuffle => Ok(()),
-                QueryType::SemiHonestOprfIpa(config) | QueryType::MaliciousOprfIpa(config) => {
+                QueryType::SemiHonestOprfIpa(config)
+                | QueryType::MaliciousOprfIpa(config)
+                | QueryType::SemiHonestShardedOprfIpa(config)
+                | QueryType::MaliciousShardedOprfIpa(config) => {
                     write!(
                         f,
-                        "&per_user_credit_cap={}&max_breakdown_key={}&with_dp={}&epsilon={}",
+                        "&per_user_credit_cap={}&max_breakdown_key={}&with_dp={}&epsilon={}\
+                        &dp_mechanism={}&delta={}&oprf_padding_epsilon={}&oprf_padding_delta={}\
+                        &matchkey_cardinality_cap={}&aggregation_padding_epsilon={}\
+                        &aggregation_padding_delta={}&aggregation_padding_sensitivity={}",
                         config.per_user_credit_cap,
                         config.max_breakdown_key,
                         config.with_dp,
                         config.epsilon,
+                        config.dp_mechanism,
+                        config.delta,
+                        config.oprf_padding_epsilon,
+                        config.oprf_padding_delta,
+                        config.matchkey_cardinality_cap,
+                        config.aggregation_padding_epsilon,
+                        config.aggregation_padding_delta,
+                        config.aggregation_padding_sensitivity,
                     )?;
 
                     if config.plaintext_match_keys {
                         write!(f, "&plaintext_match_keys=true")?;
                     }
 
+                    if config.joint_padding {
+                        write!(f, "&joint_padding=true")?;
+                    }
+
+                    write_budget_key(f, config.conversion_site.as_deref(), config.epoch)?;
+
                     if let Some(window) = config.attribution_window_seconds {
                         write!(f, "&attribution_window_seconds={}", window.get())?;
                     }
 
+                    if let Some(cap) = config.trigger_value_cap {
+                        write!(f, "&trigger_value_cap={cap}")?;
+                    }
+
+                    if config.unattributed_bucket {
+                        write!(f, "&unattributed_bucket=true")?;
+                    }
+
+                    if let Some(threshold_stds) = config.suppression_threshold_stds {
+                        write!(f, "&suppression_threshold_stds={threshold_stds}")?;
+                    }
+
+                    if config.dzkp_mode != DzkpMode::default() {
+                        write!(f, "&dzkp_mode={}", config.dzkp_mode)?;
+                    }
+
                     Ok(())
                 }
                 QueryType::SemiHonestHybrid(config) => {
                     write!(
                         f,
-                        "&per_user_credit_cap={}&max_breakdown_key={}&with_dp={}&epsilon={}",
+                        "&per_user_credit_cap={}&max_breakdown_key={}&with_dp={}&epsilon={}\
+                        &dp_mechanism={}&delta={}&oprf_padding_epsilon={}&oprf_padding_delta={}\
+                        &matchkey_cardinality_cap={}&aggregation_padding_epsilon={}\
+                        &aggregation_padding_delta={}&aggregation_padding_sensitivity={}",
                         config.per_user_credit_cap,
                         config.max_breakdown_key,
                         config.with_dp,
                         config.epsilon,
+                        config.dp_mechanism,
+                        config.delta,
+                        config.oprf_padding_epsilon,
+                        config.oprf_padding_delta,
+                        config.matchkey_cardinality_cap,
+                        config.aggregation_padding_epsilon,
+                        config.aggregation_padding_delta,
+                        config.aggregation_padding_sensitivity,
                     )?;
 
                     if config.plaintext_match_keys {
                         write!(f, "&plaintext_match_keys=true")?;
                     }
 
+                    write_budget_key(f, config.conversion_site.as_deref(), config.epoch)?;
+
+                    Ok(())
+                }
+                QueryType::SemiHonestFeatureLabelDotProduct(config) => {
+                    write!(
+                        f,
+                        "&with_dp={}&epsilon={}&dp_mechanism={}&delta={}",
+                        config.with_dp, config.epsilon, config.dp_mechanism, config.delta,
+                    )?;
+                    write_budget_key(f, config.conversion_site.as_deref(), config.epoch)
+                }
+                QueryType::SemiHonestLogisticRegression(config) => {
+                    write!(
+                        f,
+                        "&iterations={}&learning_rate_shift={}&with_dp={}&epsilon={}\
+                        &dp_mechanism={}&delta={}",
+                        config.iterations,
+                        config.learning_rate_shift,
+                        config.with_dp,
+                        config.epsilon,
+                        config.dp_mechanism,
+                        config.delta,
+                    )?;
+                    write_budget_key(f, config.conversion_site.as_deref(), config.epoch)
+                }
+                QueryType::SemiHonestReachFrequency(config) => {
+                    write!(
+                        f,
+                        "&frequency_cap={}&with_dp={}&epsilon={}&dp_mechanism={}&delta={}",
+                        config.frequency_cap,
+                        config.with_dp,
+                        config.epsilon,
+                        config.dp_mechanism,
+                        config.delta,
+                    )?;
+                    write_budget_key(f, config.conversion_site.as_deref(), config.epoch)
+                }
+                QueryType::SemiHonestLift(config) => {
+                    write!(
+                        f,
+                        "&with_dp={}&epsilon={}&dp_mechanism={}&delta={}",
+                        config.with_dp, config.epsilon, config.dp_mechanism, config.delta,
+                    )?;
+                    write_budget_key(f, config.conversion_site.as_deref(), config.epoch)?;
+
+                    if let Some(window) = config.attribution_window_seconds {
+                        write!(f, "&attribution_window_seconds={}", window.get())?;
+                    }
+
                     Ok(())
                 }
             }
         }
     }
 
+    fn write_budget_key(
+        f: &mut Formatter<'_>,
+        conversion_site: Option<&str>,
+        epoch: Option<u32>,
+    ) -> std::fmt::Result {
+        if let Some(site) = conversion_site {
+            write!(f, "&conversion_site={site}")?;
+        }
+        if let Some(epoch) = epoch {
+            write!(f, "&epoch={epoch}")?;
+        }
+
+        Ok(())
+    }
+
     pub const BASE_AXUM_PATH: &str = "/query";
 
     pub mod create {
@@ -611,4 +754,65 @@ pub mod query {
 
         pub const AXUM_PATH: &str = "/:query_id/kill";
     }
+
+    pub mod budget {
+        use crate::{
+            helpers::{routing::RouteId, HelperResponse, NoQueryId, NoStep, RouteParams},
+            query::BudgetLedgerStatus,
+        };
+
+        /// Request to inspect the privacy budget ledger of a helper. It is meant for helper
+        /// operators, so report collector does not use it.
+        pub struct Request;
+
+        impl RouteParams<RouteId, NoQueryId, NoStep> for Request {
+            type Params = String;
+
+            fn resource_identifier(&self) -> RouteId {
+                RouteId::BudgetLedger
+            }
+
+            fn query_id(&self) -> NoQueryId {
+                NoQueryId
+            }
+
+            fn gate(&self) -> NoStep {
+                NoStep
+            }
+
+            fn extra(&self) -> Self::Params {
+                String::new()
+            }
+        }
+
+        impl Request {
+            #[cfg(all(test, unit_test))]
+            #[allow(clippy::unused_self)] // to conform with other requests
+            pub fn try_into_http_request(
+                self,
+                scheme: axum::http::uri::Scheme,
+                authority: axum::http::uri::Authority,
+            ) -> crate::net::http_serde::OutgoingRequest {
+                let uri = axum::http::uri::Uri::builder()
+                    .scheme(scheme)
+                    .authority(authority)
+                    .path_and_query(format!(
+                        "{}/budget",
+                        crate::net::http_serde::query::BASE_AXUM_PATH,
+                    ))
+                    .build()?;
+                Ok(hyper::Request::get(uri).body(axum::body::Body::empty())?)
+            }
+        }
+
+        pub type ResponseBody = BudgetLedgerStatus;
+
+        impl From<HelperResponse> for ResponseBody {
+            fn from(value: HelperResponse) -> Self {
+                serde_json::from_slice(value.into_body().as_slice()).unwrap()
+            }
+        }
+
+        pub const AXUM_PATH: &str = "/budget";
+    }
 }
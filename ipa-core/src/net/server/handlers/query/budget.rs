@@ -0,0 +1,77 @@
+use axum::{routing::get, Extension, Json, Router};
+use hyper::StatusCode;
+
+use crate::{
+    helpers::BodyStream,
+    net::{
+        http_serde::query::budget::{self, Request},
+        server::Error,
+        transport::MpcHttpTransport,
+    },
+};
+
+async fn handler(
+    transport: Extension<MpcHttpTransport>,
+) -> Result<Json<budget::ResponseBody>, Error> {
+    match transport.dispatch(Request, BodyStream::empty()).await {
+        Ok(resp) => Ok(Json(budget::ResponseBody::from(resp))),
+        Err(e) => Err(Error::application(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+pub fn router(transport: MpcHttpTransport) -> Router {
+    Router::new()
+        .route(budget::AXUM_PATH, get(handler))
+        .layer(Extension(transport))
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use axum::http::uri::{Authority, Scheme};
+
+    use crate::{
+        helpers::{
+            make_owned_handler,
+            routing::{Addr, RouteId},
+            BodyStream, HelperIdentity, HelperResponse,
+        },
+        net::{http_serde, server::handlers::query::test_helpers::assert_success_with},
+        query::{BudgetEntry, BudgetLedgerStatus},
+    };
+
+    #[tokio::test]
+    async fn budget_test() {
+        let expected = BudgetLedgerStatus {
+            epoch_budget: Some(1.0),
+            entries: vec![BudgetEntry {
+                conversion_site: "shop.example".to_string(),
+                epoch: 7,
+                spent: 0.25,
+                reserved: 0.5,
+                remaining: 0.25,
+            }],
+        };
+
+        let handler = make_owned_handler({
+            let expected = expected.clone();
+            move |addr: Addr<HelperIdentity>, _data: BodyStream| {
+                let expected = expected.clone();
+                async move {
+                    let RouteId::BudgetLedger = addr.route else {
+                        panic!("unexpected call: {addr:?}");
+                    };
+                    assert_eq!(addr.query_id, None);
+                    Ok(HelperResponse::from(expected))
+                }
+            }
+        });
+
+        let req = http_serde::query::budget::Request
+            .try_into_http_request(Scheme::HTTP, Authority::from_static("localhost"))
+            .unwrap();
+        let resp = assert_success_with(req, handler).await;
+        let status: BudgetLedgerStatus = serde_json::from_slice(&resp).unwrap();
+        assert_eq!(expected.entries, status.entries);
+        assert_eq!(expected.epoch_budget, status.epoch_budget);
+    }
+}
@@ -22,6 +22,9 @@ async fn handler(
         Err(err @ ApiError::NewQuery(NewQueryError::State { .. })) => {
             Err(Error::application(StatusCode::CONFLICT, err))
         }
+        Err(
+            err @ ApiError::NewQuery(NewQueryError::PolicyViolation(_) | NewQueryError::Budget(_)),
+        ) => Err(Error::application(StatusCode::FORBIDDEN, err)),
         Err(err) => Err(Error::application(StatusCode::INTERNAL_SERVER_ERROR, err)),
     }
 }
@@ -34,7 +37,7 @@ pub fn router(transport: MpcHttpTransport) -> Router {
 
 #[cfg(all(test, unit_test))]
 mod tests {
-    use std::num::NonZeroU32;
+    use std::{fmt::Write, num::NonZeroU32};
 
     use axum::body::Body;
     use hyper::{
@@ -46,7 +49,11 @@ mod tests {
         ff::FieldType,
         helpers::{
             make_owned_handler,
-            query::{IpaQueryConfig, PrepareQuery, QueryConfig, QueryType},
+            query::{
+                DpMechanismKind, FeatureLabelQueryParams, IpaQueryConfig, LiftQueryParams,
+                LogisticRegressionQueryParams, PrepareQuery, QueryConfig, QueryType,
+                ReachFrequencyQueryParams,
+            },
             routing::RouteId,
             HelperResponse, Role, RoleAssignment,
         },
@@ -58,21 +65,24 @@ mod tests {
     };
 
     async fn create_test(expected_query_config: QueryConfig) {
-        let req = http_serde::query::create::Request::new(expected_query_config)
+        let req = http_serde::query::create::Request::new(expected_query_config.clone())
             .try_into_http_request(Scheme::HTTP, Authority::from_static("localhost"))
             .unwrap();
-        let handler = make_owned_handler(move |addr, _| async move {
-            let RouteId::ReceiveQuery = addr.route else {
-                panic!("unexpected call");
-            };
+        let handler = make_owned_handler(move |addr, _| {
+            let expected_query_config = expected_query_config.clone();
+            async move {
+                let RouteId::ReceiveQuery = addr.route else {
+                    panic!("unexpected call");
+                };
 
-            let query_config = addr.into().unwrap();
-            assert_eq!(query_config, expected_query_config);
-            Ok(HelperResponse::from(PrepareQuery {
-                query_id: QueryId,
-                config: query_config,
-                roles: RoleAssignment::try_from([Role::H1, Role::H2, Role::H3]).unwrap(),
-            }))
+                let query_config = addr.into().unwrap();
+                assert_eq!(query_config, expected_query_config);
+                Ok(HelperResponse::from(PrepareQuery {
+                    query_id: QueryId,
+                    config: query_config,
+                    roles: RoleAssignment::try_from([Role::H1, Role::H2, Role::H3]).unwrap(),
+                }))
+            }
         });
         let resp = assert_success_with(req, handler).await;
         let http_serde::query::create::ResponseBody { query_id } =
@@ -95,7 +105,10 @@ mod tests {
                     attribution_window_seconds: None,
                     with_dp: 0,
                     epsilon: 5.0,
+                    dp_mechanism: DpMechanismKind::DiscreteLaplace,
+                    delta: 1e-6,
                     plaintext_match_keys: true,
+                    ..Default::default()
                 }),
                 FieldType::Fp32BitPrime,
                 1,
@@ -115,7 +128,10 @@ mod tests {
                     attribution_window_seconds: None,
                     with_dp: 1,
                     epsilon: 5.0,
+                    dp_mechanism: DpMechanismKind::DiscreteLaplace,
+                    delta: 1e-6,
                     plaintext_match_keys: true,
+                    ..Default::default()
                 }),
                 FieldType::Fp32BitPrime,
                 1,
@@ -135,7 +151,71 @@ mod tests {
                     attribution_window_seconds: None,
                     with_dp: 1,
                     epsilon: 5.0,
+                    dp_mechanism: DpMechanismKind::DiscreteLaplace,
+                    delta: 1e-6,
                     plaintext_match_keys: true,
+                    ..Default::default()
+                }),
+                FieldType::Fp32BitPrime,
+                1,
+            )
+            .unwrap(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn create_test_malicious_ipa_with_binomial_dp() {
+        create_test(
+            QueryConfig::new(
+                QueryType::MaliciousOprfIpa(IpaQueryConfig {
+                    per_user_credit_cap: 8,
+                    max_breakdown_key: 20,
+                    attribution_window_seconds: None,
+                    with_dp: 1,
+                    epsilon: 1.0,
+                    dp_mechanism: DpMechanismKind::Binomial,
+                    delta: 1e-8,
+                    plaintext_match_keys: true,
+                    ..Default::default()
+                }),
+                FieldType::Fp32BitPrime,
+                1,
+            )
+            .unwrap(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn create_test_ipa_with_custom_padding() {
+        create_test(
+            QueryConfig::new(
+                QueryType::SemiHonestOprfIpa(IpaQueryConfig {
+                    oprf_padding_epsilon: 10.0,
+                    oprf_padding_delta: 1e-4,
+                    matchkey_cardinality_cap: 3,
+                    aggregation_padding_epsilon: 2.5,
+                    aggregation_padding_delta: 1e-8,
+                    aggregation_padding_sensitivity: 4,
+                    ..Default::default()
+                }),
+                FieldType::Fp32BitPrime,
+                1,
+            )
+            .unwrap(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn create_test_ipa_with_budget_key() {
+        create_test(
+            QueryConfig::new(
+                QueryType::MaliciousOprfIpa(IpaQueryConfig {
+                    conversion_site: Some("shop.example".to_string()),
+                    epoch: Some(42),
+                    ..Default::default()
                 }),
                 FieldType::Fp32BitPrime,
                 1,
@@ -156,12 +236,107 @@ mod tests {
                 attribution_window_seconds: NonZeroU32::new(86_400),
                 with_dp: 0,
                 epsilon: 5.0,
+                dp_mechanism: DpMechanismKind::DiscreteLaplace,
+                delta: 1e-6,
                 plaintext_match_keys: true,
+                ..Default::default()
+            }),
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn create_test_ipa_with_trigger_value_cap() {
+        create_test(QueryConfig {
+            size: 1.try_into().unwrap(),
+            field_type: FieldType::Fp32BitPrime,
+            query_type: QueryType::MaliciousOprfIpa(IpaQueryConfig {
+                per_user_credit_cap: 8,
+                max_breakdown_key: 20,
+                with_dp: 1,
+                epsilon: 5.0,
+                trigger_value_cap: Some(4),
+                ..Default::default()
             }),
         })
         .await;
     }
 
+    #[tokio::test]
+    async fn create_test_semi_honest_feature_label_dot_product() {
+        create_test(
+            QueryConfig::new(
+                QueryType::SemiHonestFeatureLabelDotProduct(FeatureLabelQueryParams {
+                    with_dp: 1,
+                    epsilon: 3.0,
+                    ..Default::default()
+                }),
+                FieldType::Fp32BitPrime,
+                1,
+            )
+            .unwrap(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn create_test_semi_honest_logistic_regression() {
+        create_test(
+            QueryConfig::new(
+                QueryType::SemiHonestLogisticRegression(LogisticRegressionQueryParams {
+                    iterations: 5,
+                    learning_rate_shift: 8,
+                    with_dp: 1,
+                    epsilon: 3.0,
+                    ..Default::default()
+                }),
+                FieldType::Fp32BitPrime,
+                1,
+            )
+            .unwrap(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn create_test_semi_honest_reach_frequency() {
+        create_test(
+            QueryConfig::new(
+                QueryType::SemiHonestReachFrequency(ReachFrequencyQueryParams {
+                    frequency_cap: 5,
+                    with_dp: 1,
+                    epsilon: 3.0,
+                    ..Default::default()
+                }),
+                FieldType::Fp32BitPrime,
+                1,
+            )
+            .unwrap(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn create_test_semi_honest_lift() {
+        create_test(
+            QueryConfig::new(
+                QueryType::SemiHonestLift(LiftQueryParams {
+                    attribution_window_seconds: NonZeroU32::new(86_400),
+                    with_dp: 1,
+                    epsilon: 3.0,
+                    dp_mechanism: DpMechanismKind::DiscreteGaussian,
+                    delta: 1e-7,
+                    conversion_site: Some("shop.example".to_string()),
+                    epoch: Some(3),
+                }),
+                FieldType::Fp32BitPrime,
+                1,
+            )
+            .unwrap(),
+        )
+        .await;
+    }
+
     struct OverrideReq {
         field_type: String,
         query_type_params: String,
@@ -229,6 +404,9 @@ mod tests {
         attribution_window_seconds: Option<String>,
         with_dp: String,
         epsilon: String,
+        dp_mechanism: Option<String>,
+        delta: Option<String>,
+        conversion_site: Option<String>,
     }
 
     impl From<OverrideIPAReq> for hyper::Request<Body> {
@@ -243,7 +421,16 @@ mod tests {
             );
 
             if let Some(window) = val.attribution_window_seconds {
-                query.push_str(&format!("&attribution_window_seconds={window}"));
+                write!(query, "&attribution_window_seconds={window}").unwrap();
+            }
+            if let Some(dp_mechanism) = val.dp_mechanism {
+                write!(query, "&dp_mechanism={dp_mechanism}").unwrap();
+            }
+            if let Some(delta) = val.delta {
+                write!(query, "&delta={delta}").unwrap();
+            }
+            if let Some(site) = val.conversion_site {
+                write!(query, "&conversion_site={site}").unwrap();
             }
             OverrideReq {
                 field_type: val.field_type,
@@ -263,6 +450,9 @@ mod tests {
                 attribution_window_seconds: None,
                 with_dp: "1".into(),
                 epsilon: "3.0".into(),
+                dp_mechanism: None,
+                delta: None,
+                conversion_site: None,
             }
         }
     }
@@ -311,4 +501,54 @@ mod tests {
         };
         assert_fails_with(req.into(), StatusCode::UNPROCESSABLE_ENTITY).await;
     }
+
+    #[tokio::test]
+    async fn malformed_dp_mechanism_ipa() {
+        let req = OverrideIPAReq {
+            dp_mechanism: Some("gaussian".into()),
+            ..Default::default()
+        };
+        assert_fails_with(req.into(), StatusCode::UNPROCESSABLE_ENTITY).await;
+    }
+
+    #[tokio::test]
+    async fn out_of_range_epsilon_ipa() {
+        let req = OverrideIPAReq {
+            epsilon: "0".into(),
+            ..Default::default()
+        };
+        assert_fails_with(req.into(), StatusCode::UNPROCESSABLE_ENTITY).await;
+    }
+
+    #[tokio::test]
+    async fn out_of_range_delta_ipa() {
+        let req = OverrideIPAReq {
+            delta: Some("1.5".into()),
+            ..Default::default()
+        };
+        assert_fails_with(req.into(), StatusCode::UNPROCESSABLE_ENTITY).await;
+    }
+
+    #[tokio::test]
+    async fn malformed_conversion_site_ipa() {
+        let req = OverrideIPAReq {
+            conversion_site: Some("shop_example".into()),
+            ..Default::default()
+        };
+        assert_fails_with(req.into(), StatusCode::UNPROCESSABLE_ENTITY).await;
+    }
+
+    #[tokio::test]
+    async fn out_of_range_frequency_cap() {
+        for frequency_cap in [0, 9] {
+            let req = OverrideReq {
+                field_type: format!("{:?}", FieldType::Fp32BitPrime),
+                query_type_params: format!(
+                    "query_type={}&frequency_cap={frequency_cap}&with_dp=1&epsilon=3.0",
+                    QueryType::SEMI_HONEST_REACH_FREQUENCY_STR,
+                ),
+            };
+            assert_fails_with(req.into(), StatusCode::UNPROCESSABLE_ENTITY).await;
+        }
+    }
 }
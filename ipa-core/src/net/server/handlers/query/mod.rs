@@ -1,3 +1,4 @@
+mod budget;
 mod create;
 mod input;
 mod kill;
@@ -6,7 +7,7 @@ mod results;
 mod status;
 mod step;
 
-use std::marker::PhantomData;
+use std::{marker::PhantomData, sync::Arc};
 
 use axum::{
     response::{IntoResponse, Response},
@@ -35,6 +36,7 @@ pub fn query_router(transport: MpcHttpTransport) -> Router {
         .merge(input::router(transport.clone()))
         .merge(status::router(transport.clone()))
         .merge(kill::router(transport.clone()))
+        .merge(budget::router(transport.clone()))
         .merge(results::router(transport))
 }
 
@@ -47,7 +49,7 @@ pub fn query_router(transport: MpcHttpTransport) -> Router {
 // It might make sense to split the query and h2h handlers into two modules.
 pub fn h2h_router(transport: MpcHttpTransport) -> Router {
     Router::new()
-        .merge(step::router(transport.clone()))
+        .merge(step::router(Arc::clone(&transport.inner_transport)))
         .merge(prepare::router(transport.inner_transport))
         .layer(layer_fn(HelperAuthentication::<_, Helper>::new))
 }
@@ -55,6 +57,7 @@ pub fn h2h_router(transport: MpcHttpTransport) -> Router {
 /// Construct router for shard-to-shard communications similar to [`h2h_router`].
 pub fn s2s_router(transport: ShardHttpTransport) -> Router {
     Router::new()
+        .merge(step::router(Arc::clone(&transport.inner_transport)))
         .merge(prepare::router(transport.inner_transport))
         .layer(layer_fn(HelperAuthentication::<_, Shard>::new))
 }
@@ -1,5 +1,5 @@
 use axum::{extract::Path, routing::get, Extension, Router};
-use hyper::StatusCode;
+use hyper::{http::HeaderValue, HeaderMap, StatusCode};
 
 use crate::{
     helpers::BodyStream,
@@ -7,19 +7,30 @@ use crate::{
         http_serde::{self, query::results::Request},
         server::Error,
         transport::MpcHttpTransport,
+        HTTP_OUTPUT_DP_HEADER,
     },
     protocol::QueryId,
 };
 
 /// Handles the completion of the query by blocking the sender until query is completed.
+/// Differential privacy applied to the results, if any, is returned in a header.
 async fn handler(
     transport: Extension<MpcHttpTransport>,
     Path(query_id): Path<QueryId>,
-) -> Result<Vec<u8>, Error> {
+) -> Result<(HeaderMap, Vec<u8>), Error> {
     let req = Request { query_id };
     // TODO: we may be able to stream the response
     match transport.dispatch(req, BodyStream::empty()).await {
-        Ok(resp) => Ok(resp.into_body()),
+        Ok(resp) => {
+            let mut headers = HeaderMap::new();
+            if let Some(output_dp) = resp.output_dp() {
+                let value = serde_json::to_string(output_dp)?;
+                let value =
+                    HeaderValue::try_from(value).map_err(|e| Error::InvalidHeader(e.into()))?;
+                headers.insert(HTTP_OUTPUT_DP_HEADER.clone(), value);
+            }
+            Ok((headers, resp.into_body()))
+        }
         Err(e) => Err(Error::application(StatusCode::INTERNAL_SERVER_ERROR, e)),
     }
 }
@@ -18,7 +18,7 @@ use crate::{
         routing::{Addr, RouteId},
         ApiError, BodyStream, HandlerRef, HelperIdentity, HelperResponse, NoQueryId,
         NoResourceIdentifier, NoStep, QueryIdBinding, ReceiveRecords, RequestHandler, RouteParams,
-        StepBinding, StreamCollection, Transport, TransportIdentity,
+        ShardedTransport, StepBinding, StreamCollection, Transport, TransportIdentity,
     },
     net::{client::IpaHttpClient, error::Error, IpaHttpServer},
     protocol::{Gate, QueryId},
@@ -109,7 +109,8 @@ impl<F: ConnectionFlavor> HttpTransport<F> {
             | RouteId::ReceiveQuery
             | RouteId::QueryStatus
             | RouteId::CompleteQuery
-            | RouteId::KillQuery) => {
+            | RouteId::KillQuery
+            | RouteId::BudgetLedger) => {
                 unimplemented!(
                     "attempting to send client-specific request {evt:?} to another helper"
                 )
@@ -128,6 +129,20 @@ impl<F: ConnectionFlavor> HttpTransport<F> {
         )
     }
 
+    /// Connect an inbound stream of record data.
+    ///
+    /// This is called by peer helpers or shards via the HTTP server.
+    pub fn receive_stream(
+        &self,
+        query_id: QueryId,
+        gate: Gate,
+        from: F::Identity,
+        stream: BodyStream,
+    ) {
+        self.record_streams
+            .add_stream((query_id, from, gate), stream);
+    }
+
     /// Dispatches the given request to the [`RequestHandler`] connected to this transport.
     ///
     /// ## Errors
@@ -224,8 +239,7 @@ impl MpcHttpTransport {
         stream: BodyStream,
     ) {
         self.inner_transport
-            .record_streams
-            .add_stream((query_id, from, gate), stream);
+            .receive_stream(query_id, gate, from, stream);
     }
 
     /// Dispatches the given request to the [`RequestHandler`] connected to this transport.
@@ -310,6 +324,14 @@ impl ShardHttpTransport {
     }
 }
 
+impl ShardedTransport for ShardHttpTransport {
+    fn shard_count(&self) -> ShardIndex {
+        // A helper that is not sharded does not have clients for its only shard.
+        let clients = self.inner_transport.clients.len();
+        ShardIndex::try_from(clients.max(1)).unwrap()
+    }
+}
+
 #[async_trait]
 impl Transport for ShardHttpTransport {
     type Identity = ShardIndex;
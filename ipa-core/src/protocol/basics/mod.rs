@@ -58,7 +58,8 @@ impl<'a, B: ShardBinding>
 {
 }
 
-impl<'a, const N: usize> BasicProtocols<UpgradedMaliciousContext<'a, Fp25519>, Fp25519, N>
+impl<'a, B: ShardBinding, const N: usize>
+    BasicProtocols<UpgradedMaliciousContext<'a, Fp25519, B>, Fp25519, N>
     for malicious::AdditiveShare<Fp25519, N>
 where
     Fp25519: FieldSimd<N>,
@@ -313,7 +313,7 @@ mod tests {
                             match m_ctx.validate_record(RecordId::FIRST).await {
                                 Ok(result) => panic!("Got a result {result:?}"),
                                 Err(err) => {
-                                    assert!(matches!(err, Error::MaliciousSecurityCheckFailed));
+                                    assert!(matches!(err, Error::MaliciousSecurityCheckFailed(_)));
                                 }
                             }
                         })
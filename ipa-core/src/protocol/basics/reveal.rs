@@ -286,45 +286,51 @@ where
         if share_from_left == share_from_right {
             Ok(Some(share_from_left + left + right))
         } else {
+            // Peers may be waiting on messages from this helper that will never come.
+            ctx.abort().await;
             Err(Error::MaliciousRevealFailed)
         }
     }
 }
 
-impl<'a, V, const N: usize, CtxF> Reveal<UpgradedMaliciousContext<'a, CtxF>> for Replicated<V, N>
+impl<'a, V, B, const N: usize, CtxF> Reveal<UpgradedMaliciousContext<'a, CtxF, B>>
+    for Replicated<V, N>
 where
     CtxF: ExtendableField,
+    B: ShardBinding,
     V: SharedValue + Vectorizable<N>,
 {
     type Output = <V as Vectorizable<N>>::Array;
 
     async fn generic_reveal<'fut>(
         &'fut self,
-        ctx: UpgradedMaliciousContext<'a, CtxF>,
+        ctx: UpgradedMaliciousContext<'a, CtxF, B>,
         record_id: RecordId,
         excluded: Option<Role>,
     ) -> Result<Option<<V as Vectorizable<N>>::Array>, Error>
     where
-        UpgradedMaliciousContext<'a, CtxF>: 'fut,
+        UpgradedMaliciousContext<'a, CtxF, B>: 'fut,
     {
         malicious_reveal(ctx, record_id, excluded, self).await
     }
 }
 
-impl<'a, F, const N: usize> Reveal<UpgradedMaliciousContext<'a, F>> for MaliciousReplicated<F, N>
+impl<'a, F, B, const N: usize> Reveal<UpgradedMaliciousContext<'a, F, B>>
+    for MaliciousReplicated<F, N>
 where
     F: ExtendableFieldSimd<N>,
+    B: ShardBinding,
 {
     type Output = <F as Vectorizable<N>>::Array;
 
     async fn generic_reveal<'fut>(
         &'fut self,
-        ctx: UpgradedMaliciousContext<'a, F>,
+        ctx: UpgradedMaliciousContext<'a, F, B>,
         record_id: RecordId,
         excluded: Option<Role>,
     ) -> Result<Option<<F as Vectorizable<N>>::Array>, Error>
     where
-        UpgradedMaliciousContext<'a, F>: 'fut,
+        UpgradedMaliciousContext<'a, F, B>: 'fut,
     {
         use crate::secret_sharing::replicated::malicious::ThisCodeIsAuthorizedToDowngradeFromMalicious;
 
@@ -404,7 +410,10 @@ where
     C: DZKPContext + 'fut,
     S: Reveal<C> + Send + Sync + ?Sized,
 {
-    ctx.validate_record(record_id).await?;
+    if let Err(e) = ctx.validate_record(record_id).await {
+        ctx.abort().await;
+        return Err(e);
+    }
     partial_reveal(ctx, record_id, excluded, v).await
 }
 
@@ -418,6 +427,7 @@ mod tests {
         error::Error,
         ff::{boolean::Boolean, Field, Fp31, Fp32BitPrime},
         helpers::{
+            self,
             in_memory_config::{MaliciousHelper, MaliciousHelperContext},
             Role,
         },
@@ -622,12 +632,19 @@ mod tests {
             reveal(ctx, RecordId::FIRST, &share).await.map(Some)
         };
 
-        // H1 should be able to see the mismatch
+        // H1 should be able to see the mismatch and abort the query on other helpers,
+        // which may or may not have finished the reveal by then.
         if my_role == Role::H1 {
             assert!(matches!(r, Err(Error::MaliciousRevealFailed)));
         } else {
-            // sanity check
-            r.unwrap();
+            assert!(matches!(
+                r,
+                Ok(_)
+                    | Err(Error::MpcInfraError(helpers::Error::Aborted {
+                        by: Role::H1,
+                        ..
+                    }))
+            ));
         }
     }
 
@@ -652,11 +669,14 @@ mod tests {
 
             let world = TestWorld::new_with(config);
             let input: Fp31 = rng.gen();
+            // Validation fails once the query is aborted, so this can't use `upgraded_malicious`
             world
-                .upgraded_malicious(
-                    vec![input].into_iter(),
-                    |ctx, _record_id: RecordId, share| do_malicious_reveal(ctx, partial, share),
-                )
+                .malicious(input, |ctx, share| async move {
+                    let v = ctx.set_total_records(1).validator::<Fp31>();
+                    let m_ctx = v.context();
+                    let m_share = share.upgrade(m_ctx.clone(), RecordId::FIRST).await.unwrap();
+                    do_malicious_reveal(m_ctx, partial, m_share).await;
+                })
                 .await;
         });
     }
@@ -672,11 +692,14 @@ mod tests {
 
             let world = TestWorld::new_with(config);
             let input: Fp31 = rng.gen();
+            // Validation fails once the query is aborted, so this can't use `upgraded_malicious`
             world
-                .upgraded_malicious(
-                    vec![input].into_iter(),
-                    |ctx, _record_id: RecordId, share| do_malicious_reveal(ctx, partial, share),
-                )
+                .malicious(input, |ctx, share| async move {
+                    let v = ctx.set_total_records(1).validator::<Fp31>();
+                    let m_ctx = v.context();
+                    let m_share = share.upgrade(m_ctx.clone(), RecordId::FIRST).await.unwrap();
+                    do_malicious_reveal(m_ctx, partial, m_share).await;
+                })
                 .await;
         });
     }
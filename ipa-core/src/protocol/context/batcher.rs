@@ -232,8 +232,8 @@ impl<'a, B> Batcher<'a, B> {
                     } else {
                         // Because errors are not `Clone`, only the validate_record call that actually
                         // did the validation returns the actual error (of type
-                        // `Error::DZKPValidationFailed`, possibly with additional detail in the
-                        // future). The rest get this error.
+                        // `Error::DZKPValidationFailed`, with the evidence of the failure). The
+                        // rest get this error.
                         Err(Error::ParallelDZKPValidationFailed)
                     }
                 }
@@ -291,6 +291,11 @@ mod tests {
     };
 
     use super::*;
+    use crate::{
+        error::{ValidationCheck, ValidationFailure},
+        helpers::Role,
+        protocol::Gate,
+    };
 
     #[test]
     fn makes_batches() {
@@ -471,7 +476,15 @@ mod tests {
             .unwrap()
             .validate_record(RecordId::from(1), |i, b| {
                 assert!(i == 0 && b.as_slice() == [0, 1]);
-                ready(Err(Error::DZKPValidationFailed))
+                ready(Err(Error::DZKPValidationFailed(Box::new(
+                    ValidationFailure {
+                        check: ValidationCheck::Dzkp,
+                        gate: Gate::default(),
+                        batch: i,
+                        reported_by: Role::H1,
+                        involved: [Role::H2, Role::H3],
+                    },
+                ))))
             }));
         let mut fut2 = pin!(batcher
             .lock()
@@ -488,7 +501,7 @@ mod tests {
         assert!(poll_immediate(&mut fut0).await.is_none());
         assert!(poll_immediate(&mut fut2).await.is_none());
 
-        assert!(matches!(fut1.await, Err(Error::DZKPValidationFailed)));
+        assert!(matches!(fut1.await, Err(Error::DZKPValidationFailed(_))));
         assert!(matches!(
             poll_immediate(&mut fut0).await,
             Some(Err(Error::ParallelDZKPValidationFailed))
@@ -1,5 +1,6 @@
 use std::{
     fmt::{Debug, Formatter},
+    future::Future,
     num::NonZeroUsize,
 };
 
@@ -8,18 +9,20 @@ use ipa_step::{Step, StepNarrow};
 
 use crate::{
     error::Error,
-    helpers::{MpcMessage, MpcReceivingEnd, Role, SendingEnd, TotalRecords},
+    helpers::{
+        Message, MpcMessage, MpcReceivingEnd, Role, SendingEnd, ShardReceivingEnd, TotalRecords,
+    },
     protocol::{
         context::{
             dzkp_validator::{Batch, MaliciousDZKPValidatorInner, Segment},
             prss::InstrumentedIndexedSharedRandomness,
             Context as ContextTrait, DZKPContext, InstrumentedSequentialSharedRandomness,
-            MaliciousContext,
+            MaliciousContext, ShardedContext,
         },
         Gate, RecordId,
     },
     seq_join::SeqJoin,
-    sharding::ShardBinding,
+    sharding::{ShardBinding, ShardConfiguration, ShardIndex, Sharded},
     sync::{Arc, Weak},
 };
 
@@ -88,6 +91,26 @@ impl<'a, B: ShardBinding> DZKPUpgraded<'a, B> {
     }
 }
 
+impl ShardConfiguration for DZKPUpgraded<'_, Sharded> {
+    fn shard_id(&self) -> ShardIndex {
+        self.base_ctx.shard_id()
+    }
+
+    fn shard_count(&self) -> ShardIndex {
+        self.base_ctx.shard_count()
+    }
+}
+
+impl ShardedContext for DZKPUpgraded<'_, Sharded> {
+    fn shard_send_channel<M: Message>(&self, dest_shard: ShardIndex) -> SendingEnd<ShardIndex, M> {
+        self.base_ctx.shard_send_channel(dest_shard)
+    }
+
+    fn shard_recv_channel<M: Message>(&self, origin: ShardIndex) -> ShardReceivingEnd<M> {
+        self.base_ctx.shard_recv_channel(origin)
+    }
+}
+
 #[async_trait]
 impl<'a, B: ShardBinding> DZKPContext for DZKPUpgraded<'a, B> {
     async fn validate_record(&self, record_id: RecordId) -> Result<(), Error> {
@@ -155,6 +178,10 @@ impl<'a, B: ShardBinding> super::Context for DZKPUpgraded<'a, B> {
     fn recv_channel<M: MpcMessage>(&self, role: Role) -> MpcReceivingEnd<M> {
         self.base_ctx.recv_channel(role)
     }
+
+    fn abort(&self) -> impl Future<Output = ()> + Send {
+        self.base_ctx.abort()
+    }
 }
 
 impl<'a, B: ShardBinding> SeqJoin for DZKPUpgraded<'a, B> {
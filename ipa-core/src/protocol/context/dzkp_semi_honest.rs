@@ -1,6 +1,7 @@
 use std::{
     any::type_name,
     fmt::{Debug, Formatter},
+    future::Future,
     num::NonZeroUsize,
 };
 
@@ -98,6 +99,10 @@ impl<'a, B: ShardBinding> super::Context for DZKPUpgraded<'a, B> {
     fn recv_channel<M: MpcMessage>(&self, role: Role) -> MpcReceivingEnd<M> {
         self.inner.recv_channel(role)
     }
+
+    fn abort(&self) -> impl Future<Output = ()> + Send {
+        self.inner.abort()
+    }
 }
 
 impl<'a, B: ShardBinding> SeqJoin for DZKPUpgraded<'a, B> {
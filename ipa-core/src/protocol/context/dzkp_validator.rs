@@ -2,13 +2,13 @@ use std::{collections::BTreeMap, fmt::Debug, future::ready};
 
 use async_trait::async_trait;
 use bitvec::prelude::{BitArray, BitSlice, Lsb0};
-use futures::{stream, Future, FutureExt, Stream, StreamExt};
+use futures::{future::join, stream, Future, FutureExt, Stream, StreamExt};
 use ipa_step::StepNarrow;
 
 use crate::{
     error::{BoxError, Error},
     ff::{Fp61BitPrime, U128Conversions},
-    helpers::TotalRecords,
+    helpers::{query::DzkpMode, TotalRecords},
     protocol::{
         context::{
             batcher::Batcher,
@@ -626,20 +626,45 @@ impl Batch {
             ProofBatch::generate(&proof_ctx, prss_record_ids, self.get_field_values_prover())
         };
 
-        let chunk_batch = BatchToVerify::generate_batch_to_verify(
-            proof_ctx,
-            record_id,
-            my_batch_left_shares,
-            shares_of_batch_from_left_prover,
-            p_mask_from_right_prover,
-            q_mask_from_left_prover,
-        )
-        .await;
+        let dzkp_mode = ctx.dzkp_mode();
+        let (chunk_batch, challenges_for_left_prover, challenges_for_right_prover) = match dzkp_mode
+        {
+            DzkpMode::Interactive => {
+                let chunk_batch = BatchToVerify::generate_batch_to_verify(
+                    proof_ctx,
+                    record_id,
+                    my_batch_left_shares,
+                    shares_of_batch_from_left_prover,
+                    p_mask_from_right_prover,
+                    q_mask_from_left_prover,
+                )
+                .await;
 
-        // generate challenges
-        let (challenges_for_left_prover, challenges_for_right_prover) = chunk_batch
-            .generate_challenges(ctx.narrow(&Step::Challenge), record_id)
-            .await;
+                // generate challenges
+                let (challenges_for_left_prover, challenges_for_right_prover) = chunk_batch
+                    .generate_challenges(ctx.narrow(&Step::Challenge), record_id)
+                    .await;
+
+                (
+                    chunk_batch,
+                    challenges_for_left_prover,
+                    challenges_for_right_prover,
+                )
+            }
+            // challenges are derived from hashes sent with the proof, saving a round trip
+            DzkpMode::NonInteractive => {
+                BatchToVerify::generate_batch_and_challenges(
+                    proof_ctx,
+                    ctx.narrow(&Step::ProofHashes),
+                    record_id,
+                    my_batch_left_shares,
+                    shares_of_batch_from_left_prover,
+                    p_mask_from_right_prover,
+                    q_mask_from_left_prover,
+                )
+                .await?
+            }
+        };
 
         let (sum_of_uv, p_r_right_prover, q_r_left_prover) = {
             // get number of multiplications
@@ -669,17 +694,34 @@ impl Batch {
         };
 
         // verify BatchToVerify, return result
-        chunk_batch
-            .verify(
-                ctx.narrow(&Step::VerifyProof),
-                record_id,
-                sum_of_uv,
-                p_r_right_prover,
-                q_r_left_prover,
-                &challenges_for_left_prover,
-                &challenges_for_right_prover,
-            )
-            .await
+        let verify_ctx = ctx.narrow(&Step::VerifyProof);
+        let verified = chunk_batch.verify(
+            verify_ctx.clone(),
+            record_id,
+            sum_of_uv,
+            p_r_right_prover,
+            q_r_left_prover,
+            &challenges_for_left_prover,
+            &challenges_for_right_prover,
+        );
+        match dzkp_mode {
+            DzkpMode::Interactive => verified.await,
+            DzkpMode::NonInteractive => {
+                // The prover could have sent different hashes to its verifiers, so they must
+                // make sure they used the same challenges before accepting the proof.
+                let (verified, challenges_match) = join(
+                    verified,
+                    BatchToVerify::check_challenges(
+                        verify_ctx,
+                        record_id,
+                        &challenges_for_left_prover,
+                        &challenges_for_right_prover,
+                    ),
+                )
+                .await;
+                challenges_match.and(verified)
+            }
+        }
     }
 }
 
@@ -948,7 +990,7 @@ mod tests {
     };
 
     use bitvec::{order::Lsb0, prelude::BitArray, vec::BitVec};
-    use futures::{stream, StreamExt, TryStreamExt};
+    use futures::{future::join_all, stream, StreamExt, TryStreamExt};
     use futures_util::stream::iter;
     use proptest::{
         prelude::{Just, Strategy},
@@ -958,12 +1000,13 @@ mod tests {
     use rand::{distributions::Standard, prelude::Distribution};
 
     use crate::{
-        error::Error,
+        error::{Error, ValidationCheck},
         ff::{
             boolean::Boolean,
             boolean_array::{BooleanArray, BA16, BA20, BA256, BA3, BA32, BA64, BA8},
             Fp61BitPrime,
         },
+        helpers::{in_memory_config::MaliciousHelper, query::DzkpMode, Direction, Role},
         protocol::{
             basics::{select, BooleanArrayMul, SecureMul},
             context::{
@@ -971,6 +1014,7 @@ mod tests {
                 dzkp_validator::{
                     Batch, DZKPValidator, Segment, SegmentEntry, BIT_ARRAY_LEN, TARGET_PROOF_SIZE,
                 },
+                step::{DzkpProofHashesStep, MaliciousProtocolStep},
                 Context, DZKPUpgradedMaliciousContext, DZKPUpgradedSemiHonestContext,
                 UpgradableContext, TEST_DZKP_STEPS,
             },
@@ -983,7 +1027,7 @@ mod tests {
         },
         seq_join::{seq_join, SeqJoin},
         sharding::NotSharded,
-        test_fixture::{join3v, Reconstruct, Runner, TestWorld},
+        test_fixture::{join3v, Reconstruct, Runner, TestWorld, TestWorldConfig},
     };
 
     async fn test_select_semi_honest<V>()
@@ -1042,7 +1086,7 @@ mod tests {
         test_select_semi_honest::<BA256>().await;
     }
 
-    async fn test_select_malicious<V>()
+    async fn test_select_malicious<V>(dzkp_mode: DzkpMode)
     where
         V: BooleanArray,
         for<'a> Replicated<V>: BooleanArrayMul<DZKPUpgradedMaliciousContext<'a, NotSharded>>,
@@ -1062,7 +1106,10 @@ mod tests {
 
         let futures = zip(context.iter(), zip(bit_shares, zip(a_shares, b_shares))).map(
             |(ctx, (bit_share, (a_share, b_share)))| async move {
-                let v = ctx.clone().dzkp_validator(TEST_DZKP_STEPS, 1);
+                let v = ctx
+                    .clone()
+                    .set_dzkp_mode(dzkp_mode)
+                    .dzkp_validator(TEST_DZKP_STEPS, 1);
                 let m_ctx = v.context();
 
                 let result = select(
@@ -1089,13 +1136,93 @@ mod tests {
 
     #[tokio::test]
     async fn select_malicious() {
-        test_select_malicious::<BA3>().await;
-        test_select_malicious::<BA8>().await;
-        test_select_malicious::<BA16>().await;
-        test_select_malicious::<BA20>().await;
-        test_select_malicious::<BA32>().await;
-        test_select_malicious::<BA64>().await;
-        test_select_malicious::<BA256>().await;
+        for mode in [DzkpMode::Interactive, DzkpMode::NonInteractive] {
+            test_select_malicious::<BA3>(mode).await;
+            test_select_malicious::<BA8>(mode).await;
+            test_select_malicious::<BA16>(mode).await;
+            test_select_malicious::<BA20>(mode).await;
+            test_select_malicious::<BA32>(mode).await;
+            test_select_malicious::<BA64>(mode).await;
+            test_select_malicious::<BA256>(mode).await;
+        }
+    }
+
+    /// Runs a select with `attacker` flipping a bit of every message it sends on gates that
+    /// contain `corrupted_step` and checks that only the left peer of the attacker detects it.
+    async fn select_malicious_tweaked(dzkp_mode: DzkpMode, corrupted_step: &'static str) {
+        for attacker in Role::all() {
+            let mut config = TestWorldConfig::default();
+            config.stream_interceptor =
+                MaliciousHelper::new(*attacker, config.role_assignment(), move |ctx, data| {
+                    if ctx.gate.as_ref().contains(corrupted_step) {
+                        data[0] ^= 1;
+                    }
+                });
+            let world = TestWorld::new_with(&config);
+            let context = world.malicious_contexts();
+            let mut rng = thread_rng();
+
+            let bit_shares = rng.gen::<Boolean>().share_with(&mut rng);
+            let a_shares = rng.gen::<BA8>().share_with(&mut rng);
+            let b_shares = rng.gen::<BA8>().share_with(&mut rng);
+
+            let futures = zip(context.iter(), zip(bit_shares, zip(a_shares, b_shares))).map(
+                |(ctx, (bit_share, (a_share, b_share)))| async move {
+                    let v = ctx
+                        .clone()
+                        .set_dzkp_mode(dzkp_mode)
+                        .dzkp_validator(TEST_DZKP_STEPS, 1);
+                    select(
+                        v.context().set_total_records(1),
+                        RecordId::FIRST,
+                        &bit_share,
+                        &a_share,
+                        &b_share,
+                    )
+                    .await
+                    .unwrap();
+
+                    v.validate().await
+                },
+            );
+            let results = join_all(futures).await;
+
+            // Only the helper that verifies the attacker's proof together with the corrupted
+            // value can tell that something is wrong.
+            for (role, result) in zip(Role::all(), results) {
+                match result {
+                    Err(Error::DZKPValidationFailed(failure))
+                        if *role == attacker.peer(Direction::Left) =>
+                    {
+                        assert_eq!(ValidationCheck::Dzkp, failure.check);
+                        assert_eq!(*role, failure.reported_by);
+                        assert_eq!(0, failure.batch);
+                        assert_eq!(*attacker, failure.involved[0]);
+                    }
+                    Ok(()) if *role != attacker.peer(Direction::Left) => {}
+                    result => panic!("unexpected result on {role:?}: {result:?}"),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn select_malicious_corrupted_multiplication() {
+        // corrupt the multiplication, but not the validation protocol
+        for mode in [DzkpMode::Interactive, DzkpMode::NonInteractive] {
+            select_malicious_tweaked(mode, MaliciousProtocolStep::MaliciousProtocol.as_ref()).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn select_malicious_corrupted_proof_hashes() {
+        // the prover lies to its right verifier about the left share of its proof, so the
+        // verifiers of its proof derive different challenges
+        select_malicious_tweaked(
+            DzkpMode::NonInteractive,
+            DzkpProofHashesStep::Prover.as_ref(),
+        )
+        .await;
     }
 
     #[tokio::test]
@@ -1,6 +1,7 @@
 use std::{
     any::type_name,
     fmt::{Debug, Formatter},
+    future::Future,
     num::NonZeroUsize,
 };
 
@@ -10,8 +11,8 @@ use ipa_step::{Step, StepNarrow};
 use crate::{
     error::Error,
     helpers::{
-        Gateway, Message, MpcMessage, MpcReceivingEnd, Role, SendingEnd, ShardReceivingEnd,
-        TotalRecords,
+        query::DzkpMode, Gateway, Message, MpcMessage, MpcReceivingEnd, Role, SendingEnd,
+        ShardReceivingEnd, TotalRecords,
     },
     protocol::{
         basics::mul::{semi_honest_multiply, step::MaliciousMultiplyStep::RandomnessForValidation},
@@ -109,6 +110,14 @@ impl<'a, B: ShardBinding> Context<'a, B> {
             inner: self.inner.set_active_work(new_active_work),
         }
     }
+
+    /// Selects how DZKP validators created from this context agree on proof challenges.
+    #[must_use]
+    pub fn set_dzkp_mode(self, dzkp_mode: DzkpMode) -> Self {
+        Self {
+            inner: self.inner.set_dzkp_mode(dzkp_mode),
+        }
+    }
 }
 
 impl<'a, B: ShardBinding> super::Context for Context<'a, B> {
@@ -159,6 +168,10 @@ impl<'a, B: ShardBinding> super::Context for Context<'a, B> {
     fn recv_channel<M: MpcMessage>(&self, role: Role) -> MpcReceivingEnd<M> {
         self.inner.recv_channel(role)
     }
+
+    fn abort(&self) -> impl Future<Output = ()> + Send {
+        self.inner.abort()
+    }
 }
 
 impl<'a, B: ShardBinding> UpgradableContext for Context<'a, B> {
@@ -337,6 +350,10 @@ impl<'a, F: ExtendableField, B: ShardBinding> super::Context for Upgraded<'a, F,
     fn recv_channel<M: MpcMessage>(&self, role: Role) -> MpcReceivingEnd<M> {
         self.base_ctx.recv_channel(role)
     }
+
+    fn abort(&self) -> impl Future<Output = ()> + Send {
+        self.base_ctx.abort()
+    }
 }
 
 impl<'a, F: ExtendableField, B: ShardBinding> SeqJoin for Upgraded<'a, F, B> {
@@ -11,7 +11,7 @@ pub mod upgrade;
 mod batcher;
 pub mod validator;
 
-use std::{collections::HashMap, num::NonZeroUsize, pin::pin};
+use std::{collections::HashMap, future::Future, num::NonZeroUsize, pin::pin};
 
 use async_trait::async_trait;
 pub use dzkp_malicious::DZKPUpgraded as DZKPUpgradedMaliciousContext;
@@ -35,8 +35,8 @@ pub(crate) use malicious::TEST_DZKP_STEPS;
 use crate::{
     error::Error,
     helpers::{
-        stream::ExactSizeStream, ChannelId, Direction, Gateway, Message, MpcMessage,
-        MpcReceivingEnd, Role, SendingEnd, ShardReceivingEnd, TotalRecords,
+        query::DzkpMode, stream::ExactSizeStream, ChannelId, Direction, Gateway, Message,
+        MpcMessage, MpcReceivingEnd, Role, SendingEnd, ShardReceivingEnd, TotalRecords,
     },
     protocol::{
         context::dzkp_validator::DZKPValidator,
@@ -105,6 +105,11 @@ pub trait Context: Clone + Send + Sync + SeqJoin {
     /// Requests data to be received from another MPC helper. Receive requests [`MpcReceivingEnd::receive`]
     /// can be issued from multiple threads.
     fn recv_channel<M: MpcMessage>(&self, role: Role) -> MpcReceivingEnd<M>;
+
+    /// Aborts the query on all helpers. This must be used when this helper cannot continue
+    /// running the query, so that its peers do not wait for messages from it forever. See
+    /// [`Gateway::abort`] for details.
+    fn abort(&self) -> impl Future<Output = ()> + Send;
 }
 
 pub trait UpgradableContext: Context {
@@ -166,6 +171,8 @@ pub struct Base<'a, B: ShardBinding = NotSharded> {
     gate: Gate,
     total_records: TotalRecords,
     active_work: NonZeroU32PowerOfTwo,
+    /// How DZKP validators created from this context agree on proof challenges.
+    dzkp_mode: DzkpMode,
     /// This indicates whether the system uses sharding or no. It's not ideal that we keep it here
     /// because it gets cloned often, a potential solution to that, if this shows up on flame graph,
     /// would be to move it to [`Inner`] struct.
@@ -185,6 +192,7 @@ impl<'a, B: ShardBinding> Base<'a, B> {
             gate,
             total_records,
             active_work: gateway.config().active_work_as_power_of_two(),
+            dzkp_mode: DzkpMode::default(),
             sharding,
         }
     }
@@ -196,6 +204,15 @@ impl<'a, B: ShardBinding> Base<'a, B> {
             ..self.clone()
         }
     }
+
+    #[must_use]
+    pub fn set_dzkp_mode(self, dzkp_mode: DzkpMode) -> Self {
+        Self { dzkp_mode, ..self }
+    }
+
+    pub(crate) fn dzkp_mode(&self) -> DzkpMode {
+        self.dzkp_mode
+    }
 }
 
 impl ShardedContext for Base<'_, Sharded> {
@@ -231,6 +248,7 @@ impl<'a, B: ShardBinding> Context for Base<'a, B> {
             gate: self.gate.narrow(step),
             total_records: self.total_records,
             active_work: self.active_work,
+            dzkp_mode: self.dzkp_mode,
             sharding: self.sharding.clone(),
         }
     }
@@ -241,6 +259,7 @@ impl<'a, B: ShardBinding> Context for Base<'a, B> {
             gate: self.gate.clone(),
             total_records: self.total_records.overwrite(total_records),
             active_work: self.active_work,
+            dzkp_mode: self.dzkp_mode,
             sharding: self.sharding.clone(),
         }
     }
@@ -281,6 +300,10 @@ impl<'a, B: ShardBinding> Context for Base<'a, B> {
             .gateway
             .get_mpc_receiver(&ChannelId::new(role, self.gate.clone()))
     }
+
+    fn abort(&self) -> impl Future<Output = ()> + Send {
+        self.inner.gateway.abort()
+    }
 }
 
 /// Context for MPC circuits that can operate on multiple shards. Provides access to shard information
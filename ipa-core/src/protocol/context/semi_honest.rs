@@ -1,6 +1,7 @@
 use std::{
     any::type_name,
     fmt::{Debug, Formatter},
+    future::Future,
     marker::PhantomData,
     num::NonZeroUsize,
 };
@@ -147,6 +148,10 @@ impl<'a, B: ShardBinding> super::Context for Context<'a, B> {
     fn recv_channel<M: MpcMessage>(&self, role: Role) -> MpcReceivingEnd<M> {
         self.inner.recv_channel(role)
     }
+
+    fn abort(&self) -> impl Future<Output = ()> + Send {
+        self.inner.abort()
+    }
 }
 
 impl<'a, B: ShardBinding> UpgradableContext for Context<'a, B> {
@@ -264,6 +269,10 @@ impl<'a, B: ShardBinding, F: ExtendableField> super::Context for Upgraded<'a, B,
     fn recv_channel<M: MpcMessage>(&self, role: Role) -> MpcReceivingEnd<M> {
         self.inner.recv_channel(role)
     }
+
+    fn abort(&self) -> impl Future<Output = ()> + Send {
+        self.inner.abort()
+    }
 }
 
 impl<'a, B: ShardBinding, F: ExtendableField> SeqJoin for Upgraded<'a, B, F> {
@@ -34,15 +34,29 @@ pub(crate) enum DzkpValidationProtocolStep {
     GenerateProof,
     /// Step for producing challenge between proof verifiers
     Challenge,
+    /// Step for sending proof hashes together with the proof, used instead of `Challenge`
+    /// in non-interactive mode
+    #[step(child = DzkpProofHashesStep)]
+    ProofHashes,
     /// Step for proof verification
     #[step(child = DzkpProofVerifyStep)]
     VerifyProof,
 }
 
+#[derive(CompactStep)]
+pub(crate) enum DzkpProofHashesStep {
+    /// Step for sending hashes of the proof share that the prover sends to the left
+    Prover,
+    /// Step for sending hashes of the proof share that the verifier generates from PRSS
+    Verifier,
+}
+
 #[derive(CompactStep)]
 pub(crate) enum DzkpProofVerifyStep {
     /// Step for computing `p * q` between proof verifiers
     PTimesQ,
     /// Step for computing `G_diff` between proof verifiers
     Diff,
+    /// Step for checking that proof verifiers derived the same challenges in non-interactive mode
+    ChallengeCheck,
 }
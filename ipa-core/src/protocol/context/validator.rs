@@ -5,7 +5,7 @@ use std::{
 };
 
 use crate::{
-    error::Error,
+    error::{Error, ValidationCheck, ValidationFailure},
     ff::Field,
     helpers::{Direction, TotalRecords},
     protocol::{
@@ -289,7 +289,17 @@ impl<F: ExtendableField, B: ShardBinding> Malicious<'_, F, B> {
 
             Ok(())
         } else {
-            Err(Error::MaliciousSecurityCheckFailed)
+            // Any helper could have launched the additive attack, so both peers are reported.
+            let role = self.validate_ctx.role();
+            Err(Error::MaliciousSecurityCheckFailed(Box::new(
+                ValidationFailure {
+                    check: ValidationCheck::Mac,
+                    gate: self.validate_ctx.gate().clone(),
+                    batch: self.offset,
+                    reported_by: role,
+                    involved: [role.peer(Direction::Left), role.peer(Direction::Right)],
+                },
+            )))
         }
     }
 }
@@ -397,7 +407,7 @@ mod tests {
     use std::iter::{repeat, zip};
 
     use crate::{
-        error::Error,
+        error::{Error, ValidationCheck},
         ff::{Field, Fp31, Fp32BitPrime},
         helpers::Role,
         protocol::{
@@ -519,12 +529,18 @@ mod tests {
                     } else {
                         a
                     };
+                    let role = ctx.role();
                     let ctx = ctx.set_total_records(1);
                     let v = ctx.validator();
                     let _ = a.upgrade(v.context(), RecordId::FIRST).await.unwrap();
                     match v.context().validate_record(RecordId::FIRST).await {
                         Ok(result) => panic!("Got a result {result:?}"),
-                        Err(err) => assert!(matches!(err, Error::MaliciousSecurityCheckFailed)),
+                        Err(Error::MaliciousSecurityCheckFailed(failure)) => {
+                            assert_eq!(ValidationCheck::Mac, failure.check);
+                            assert_eq!(role, failure.reported_by);
+                            assert!(!failure.involved.contains(&role));
+                        }
+                        Err(err) => panic!("unexpected error {err:?}"),
                     }
                 })
                 .await;
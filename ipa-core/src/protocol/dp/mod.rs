@@ -3,7 +3,8 @@ pub mod step;
 
 use std::{convert::Infallible, f64};
 
-use futures_util::{stream, StreamExt};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use ipa_step::{Step, StepNarrow};
 use rand_core::{CryptoRng, RngCore};
 
 use crate::{
@@ -11,23 +12,27 @@ use crate::{
         Error::{self, EpsilonOutOfBounds},
         LengthError,
     },
-    ff::{boolean::Boolean, boolean_array::BooleanArray, U128Conversions},
+    ff::{boolean::Boolean, boolean_array::BooleanArray, ArrayAccess, U128Conversions},
     helpers::{query::DpMechanism, Direction, Role, TotalRecords},
     protocol::{
-        boolean::step::ThirtyTwoBitStep,
+        basics::{select, BooleanArrayMul, ShareKnownValue},
+        boolean::{step::ThirtyTwoBitStep, NBitStep},
         context::{
-            dzkp_validator::DZKPValidator, Context, DZKPUpgraded, MaliciousProtocolSteps,
-            UpgradableContext,
+            dzkp_validator::{validated_seq_join, DZKPValidator, TARGET_PROOF_SIZE},
+            Context, DZKPUpgraded, MaliciousProtocolSteps, UpgradableContext,
         },
-        dp::step::{ApplyDpNoise, DPStep},
+        dp::step::{ApplyDpNoise, DPStep, SuppressionStep},
         ipa_prf::{
             aggregation::{aggregate_values, aggregate_values_proof_chunk},
-            boolean_ops::addition_sequential::integer_add,
-            oprf_padding::insecure::OPRFPaddingDp,
+            boolean_ops::{
+                addition_sequential::integer_add,
+                comparison_and_subtraction_sequential::compare_geq,
+            },
+            oprf_padding::insecure::{DiscreteGaussianDp, OPRFPaddingDp},
             step::IpaPrfStep,
         },
         prss::{FromPrss, SharedRandomness},
-        BooleanProtocols, RecordId,
+        BooleanProtocols, Gate, RecordId,
     },
     secret_sharing::{
         replicated::{
@@ -75,7 +80,8 @@ impl Default for NoiseParams {
     }
 }
 const MAX_PROBABILITY: f64 = 1.0;
-const MAX_EPSILON: f64 = 20.0;
+/// Largest epsilon that queries can request.
+pub const MAX_EPSILON: f64 = 20.0;
 
 impl NoiseParams {
     /// # Errors
@@ -228,7 +234,6 @@ where
 /// # Panics
 /// may panic from asserts down in  `gen_binomial_noise`
 ///
-#[allow(clippy::too_many_lines)]
 pub async fn dp_for_histogram<C, const B: usize, OV, const SS_BITS: usize>(
     ctx: C,
     histogram_bin_values: BitDecomposed<Replicated<Boolean, B>>,
@@ -249,19 +254,89 @@ where
         protocol: &IpaPrfStep::DifferentialPrivacy,
         validate: &IpaPrfStep::DifferentialPrivacyValidate,
     };
+    dp_for_histogram_with_steps::<_, _, B, OV, SS_BITS>(ctx, steps, histogram_bin_values, dp_params)
+        .await
+}
+
+/// Same as [`dp_for_histogram`], but noise generation and its validation run under the
+/// provided steps. This allows protocols that add noise more than once, like the
+/// iterations of logistic regression training, to do so under distinct steps.
+///
+/// # Errors
+/// See [`dp_for_histogram`].
+/// # Panics
+/// See [`dp_for_histogram`].
+pub async fn dp_for_histogram_with_steps<C, S, const B: usize, OV, const SS_BITS: usize>(
+    ctx: C,
+    steps: MaliciousProtocolSteps<'_, S>,
+    histogram_bin_values: BitDecomposed<Replicated<Boolean, B>>,
+    dp_params: DpMechanism,
+) -> Result<Vec<Replicated<OV>>, Error>
+where
+    C: UpgradableContext,
+    S: Step + ?Sized,
+    Gate: StepNarrow<S>,
+    Boolean: Vectorizable<B> + FieldSimd<B>,
+    BitDecomposed<Replicated<Boolean, B>>: FromPrss<usize>,
+    OV: BooleanArray + U128Conversions,
+    Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
+    Vec<Replicated<OV>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
+    BitDecomposed<AdditiveShare<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [AdditiveShare<OV>; B], Error = Infallible>,
+{
+    dp_for_histogram_with_sensitivity::<_, _, B, OV>(
+        ctx,
+        steps,
+        histogram_bin_values,
+        dp_params,
+        2_u32.pow(u32::try_from(SS_BITS).unwrap()),
+    )
+    .await
+}
+
+/// Same as [`dp_for_histogram_with_steps`], but the noise is calibrated to a per-user
+/// sensitivity of `per_user_credit_cap`, rather than `2^SS_BITS`. This is useful when a user's
+/// contribution is bounded more tightly than by the saturating sum, e.g. because individual
+/// trigger values are capped.
+///
+/// # Errors
+/// See [`dp_for_histogram`].
+/// # Panics
+/// See [`dp_for_histogram`].
+#[allow(clippy::too_many_lines)]
+pub async fn dp_for_histogram_with_sensitivity<C, S, const B: usize, OV>(
+    ctx: C,
+    steps: MaliciousProtocolSteps<'_, S>,
+    histogram_bin_values: BitDecomposed<Replicated<Boolean, B>>,
+    dp_params: DpMechanism,
+    per_user_credit_cap: u32,
+) -> Result<Vec<Replicated<OV>>, Error>
+where
+    C: UpgradableContext,
+    S: Step + ?Sized,
+    Gate: StepNarrow<S>,
+    Boolean: Vectorizable<B> + FieldSimd<B>,
+    BitDecomposed<Replicated<Boolean, B>>: FromPrss<usize>,
+    OV: BooleanArray + U128Conversions,
+    Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
+    Vec<Replicated<OV>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
+    BitDecomposed<AdditiveShare<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [AdditiveShare<OV>; B], Error = Infallible>,
+{
     match dp_params {
         DpMechanism::NoDp => Ok(Vec::transposed_from(&histogram_bin_values)?),
-        DpMechanism::Binomial { epsilon } => {
+        DpMechanism::Binomial { epsilon, delta } => {
             if epsilon <= 0.0 || epsilon > MAX_EPSILON {
                 return Err(EpsilonOutOfBounds);
             }
 
-            let per_user_credit_cap = 2_u32.pow(u32::try_from(SS_BITS).unwrap());
-
             let dimensions = f64::from(u32::try_from(B).unwrap());
 
             let noise_params = NoiseParams {
                 epsilon,
+                delta,
                 per_user_credit_cap,
                 ell_1_sensitivity: f64::from(per_user_credit_cap),
                 ell_2_sensitivity: f64::from(per_user_credit_cap),
@@ -310,10 +385,11 @@ where
 
             Ok(noisy_histogram)
         }
-        DpMechanism::DiscreteLaplace { epsilon } => {
+        DpMechanism::DiscreteLaplace { epsilon, delta } => {
             let noise_params = NoiseParams {
                 epsilon,
-                per_user_credit_cap: 2_u32.pow(u32::try_from(SS_BITS).unwrap()),
+                delta,
+                per_user_credit_cap,
                 ..Default::default()
             };
 
@@ -341,7 +417,9 @@ where
             let dp_validator = ctx.dzkp_validator(steps, 1);
 
             let noised_output = apply_laplace_noise_pass::<_, OV, B>(
-                &dp_validator.context().narrow(&DPStep::LaplacePass1),
+                &dp_validator
+                    .context()
+                    .narrow::<DPStep>(&DPStep::LaplacePass1),
                 histogram_bin_values,
                 Role::H1,
                 &noise_params,
@@ -349,7 +427,9 @@ where
             .await?;
 
             let noised_output = apply_laplace_noise_pass::<_, OV, B>(
-                &dp_validator.context().narrow(&DPStep::LaplacePass2),
+                &dp_validator
+                    .context()
+                    .narrow::<DPStep>(&DPStep::LaplacePass2),
                 noised_output,
                 Role::H2,
                 &noise_params,
@@ -357,7 +437,9 @@ where
             .await?;
 
             let noised_output = apply_laplace_noise_pass::<_, OV, B>(
-                &dp_validator.context().narrow(&DPStep::LaplacePass3),
+                &dp_validator
+                    .context()
+                    .narrow::<DPStep>(&DPStep::LaplacePass3),
                 noised_output,
                 Role::H3,
                 &noise_params,
@@ -366,6 +448,53 @@ where
 
             dp_validator.validate().await?;
 
+            Ok(Vec::transposed_from(&noised_output)?)
+        }
+        DpMechanism::DiscreteGaussian { epsilon, delta } => {
+            let noise_params = NoiseParams {
+                epsilon,
+                delta,
+                per_user_credit_cap,
+                ell_2_sensitivity: f64::from(per_user_credit_cap),
+                ..Default::default()
+            };
+
+            let discrete_gaussian = DiscreteGaussianDp::new(
+                noise_params.epsilon,
+                noise_params.delta,
+                noise_params.ell_2_sensitivity,
+            )?;
+            tracing::info!(
+                "In dp_for_histogram with Discrete Gaussian noise: \
+                epsilon = {epsilon}, \
+                delta = {delta}, \
+                ell_2_sensitivity = {}, \
+                noise std (for each of the three pairs of noise) = {}, \
+                OV::BITS = {}",
+                noise_params.ell_2_sensitivity,
+                discrete_gaussian.std(),
+                OV::BITS,
+            );
+
+            let dp_validator = ctx.dzkp_validator(steps, 1);
+
+            let mut noised_output = histogram_bin_values;
+            for (step, excluded_helper) in [
+                (DPStep::GaussianPass1, Role::H1),
+                (DPStep::GaussianPass2, Role::H2),
+                (DPStep::GaussianPass3, Role::H3),
+            ] {
+                noised_output = apply_gaussian_noise_pass::<_, OV, B>(
+                    &dp_validator.context().narrow::<DPStep>(&step),
+                    noised_output,
+                    excluded_helper,
+                    &noise_params,
+                )
+                .await?;
+            }
+
+            dp_validator.validate().await?;
+
             Ok(Vec::transposed_from(&noised_output)?)
         }
     }
@@ -486,6 +615,73 @@ where
     Ok(histogram_noised)
 }
 
+/// Adds discrete Gaussian noise, calibrated from the L2 sensitivity in `noise_params`, to the
+/// histogram. Like [`apply_laplace_noise_pass`], the two helpers other than `excluded_helper`
+/// sample the same noise from PRSS and the excluded helper contributes zero shares.
+///
+/// # Errors
+/// will propagate errors from constructing a discrete Gaussian distribution.
+/// # Panics
+/// if `OV::BITS > 32`
+pub async fn apply_gaussian_noise_pass<C, OV, const B: usize>(
+    ctx: &C,
+    histogram_bin_values: BitDecomposed<Replicated<Boolean, B>>,
+    excluded_helper: Role,
+    noise_params: &NoiseParams,
+) -> Result<BitDecomposed<Replicated<Boolean, B>>, Error>
+where
+    C: Context,
+    OV: BooleanArray + U128Conversions,
+    Boolean: Vectorizable<B> + FieldSimd<B>,
+    Replicated<Boolean, B>: BooleanProtocols<C, B>,
+    BitDecomposed<AdditiveShare<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [AdditiveShare<OV>; B], Error = Infallible>,
+    AdditiveShare<OV>: ReplicatedSecretSharing<OV>,
+{
+    assert!(OV::BITS <= 32);
+    let noise_values_array: [AdditiveShare<OV>; B] =
+        if let Some(direction_to_excluded_helper) = ctx.role().direction_to(excluded_helper) {
+            let (mut left, mut right) = ctx.prss_rng();
+            let rng = match direction_to_excluded_helper {
+                Direction::Left => &mut right,
+                Direction::Right => &mut left,
+            };
+            let discrete_gaussian = DiscreteGaussianDp::new(
+                noise_params.epsilon,
+                noise_params.delta,
+                noise_params.ell_2_sensitivity,
+            )?;
+            std::array::from_fn(|_i| {
+                // Negative samples wrap around, which is what two's complement addition
+                // modulo 2^OV::BITS expects.
+                #[allow(clippy::cast_sign_loss)]
+                let sample = OV::truncate_from(u128::from(discrete_gaussian.sample(rng) as u32));
+                match direction_to_excluded_helper {
+                    Direction::Left => AdditiveShare::new(OV::ZERO, sample),
+                    Direction::Right => AdditiveShare::new(sample, OV::ZERO),
+                }
+            })
+        } else {
+            std::array::from_fn(|_i| AdditiveShare::new(OV::ZERO, OV::ZERO))
+        };
+
+    let noise_shares_vectorized: BitDecomposed<AdditiveShare<Boolean, B>> =
+        BitDecomposed::transposed_from(&noise_values_array).unwrap();
+
+    let apply_noise_ctx = ctx
+        .narrow(&ApplyDpNoise::ApplyNoise)
+        .set_total_records(TotalRecords::ONE);
+    let (histogram_noised, _) = integer_add::<_, ThirtyTwoBitStep, B>(
+        apply_noise_ctx,
+        RecordId::FIRST,
+        &noise_shares_vectorized,
+        &histogram_bin_values,
+    )
+    .await
+    .unwrap();
+    Ok(histogram_noised)
+}
+
 // implement calculations to instantiation Thm 1 of https://arxiv.org/pdf/1805.10559
 // which lets us determine the minimum necessary num_bernoulli for a given epsilon, delta
 // and other parameters
@@ -597,6 +793,218 @@ pub fn binomial_noise_mean_std(noise_params: &NoiseParams) -> (f64, f64) {
     (mean, standard_deviation)
 }
 
+/// Two-sided z-score of a 95% interval under the normal approximation.
+const Z_95: f64 = 1.959_963_984_540_054;
+
+/// Mean and standard deviation of the noise that [`dp_for_histogram`] adds to every bucket of a
+/// histogram. Report collectors use it to put error bars on noisy results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistogramNoise {
+    pub mean: f64,
+    pub std: f64,
+}
+
+impl HistogramNoise {
+    /// Describes the noise added by `dp_params` to a histogram with `dimensions` buckets, when
+    /// every user contributes at most `per_user_credit_cap` in total. Returns `None` if no noise
+    /// is added.
+    ///
+    /// # Errors
+    /// If the parameters of `dp_params` are out of bounds.
+    pub fn new(
+        dp_params: DpMechanism,
+        per_user_credit_cap: u32,
+        dimensions: u32,
+    ) -> Result<Option<Self>, Error> {
+        Ok(match dp_params {
+            DpMechanism::NoDp => None,
+            DpMechanism::Binomial { epsilon, delta } => {
+                if epsilon <= 0.0 || epsilon > MAX_EPSILON {
+                    return Err(EpsilonOutOfBounds);
+                }
+                let noise_params = NoiseParams {
+                    epsilon,
+                    delta,
+                    per_user_credit_cap,
+                    ell_1_sensitivity: f64::from(per_user_credit_cap),
+                    ell_2_sensitivity: f64::from(per_user_credit_cap),
+                    ell_infty_sensitivity: f64::from(per_user_credit_cap),
+                    dimensions: f64::from(dimensions),
+                    ..Default::default()
+                };
+                let (mean, std) = binomial_noise_mean_std(&noise_params);
+                Some(Self { mean, std })
+            }
+            // Each of the three pairs of helpers adds an independent sample.
+            DpMechanism::DiscreteLaplace { epsilon, delta } => {
+                let truncated_discrete_laplace =
+                    OPRFPaddingDp::new(epsilon, delta, per_user_credit_cap)?;
+                let (mean, std) = truncated_discrete_laplace.mean_and_std();
+                Some(Self {
+                    mean: 3.0 * (mean - f64::from(truncated_discrete_laplace.get_shift())),
+                    std: 3.0_f64.sqrt() * std,
+                })
+            }
+            DpMechanism::DiscreteGaussian { epsilon, delta } => {
+                let discrete_gaussian =
+                    DiscreteGaussianDp::new(epsilon, delta, f64::from(per_user_credit_cap))?;
+                Some(Self {
+                    mean: 0.0,
+                    std: 3.0_f64.sqrt() * discrete_gaussian.std(),
+                })
+            }
+        })
+    }
+
+    /// Returns the 95% confidence interval for the true value of a bucket whose noisy value is
+    /// `noisy_value`. The interval uses the normal approximation of the noise distribution.
+    #[must_use]
+    pub fn confidence_interval_95(&self, noisy_value: f64) -> (f64, f64) {
+        let estimate = noisy_value - self.mean;
+        (estimate - Z_95 * self.std, estimate + Z_95 * self.std)
+    }
+
+    /// Returns the value below which a bucket is considered sparse, once the mean of the noise is
+    /// removed from it. The threshold is `threshold_stds` standard deviations of the noise, so
+    /// that buckets with no contributions are suppressed with high probability. Suppression only
+    /// looks at noisy values, so it is post-processing and does not affect the DP guarantee.
+    ///
+    /// # Errors
+    /// If `threshold_stds` is negative, NaN or infinite.
+    pub fn suppression_threshold(
+        &self,
+        threshold_stds: f64,
+    ) -> Result<f64, SuppressionThresholdError> {
+        check_threshold_stds(threshold_stds)?;
+        Ok(threshold_stds * self.std)
+    }
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error(
+    "suppression threshold must be a finite, non-negative number of standard deviations, got {0}"
+)]
+pub struct SuppressionThresholdError(pub f64);
+
+/// Checks that `threshold_stds` can be used as a number of standard deviations of the noise.
+///
+/// # Errors
+/// If `threshold_stds` is negative, NaN or infinite.
+pub fn check_threshold_stds(threshold_stds: f64) -> Result<(), SuppressionThresholdError> {
+    if threshold_stds.is_finite() && threshold_stds >= 0.0 {
+        Ok(())
+    } else {
+        Err(SuppressionThresholdError(threshold_stds))
+    }
+}
+
+fn suppression_proof_chunk<OV: BooleanArray>() -> usize {
+    // One multiplication per bit for the comparison and one per bit for the selection.
+    (TARGET_PROOF_SIZE / (2 * usize::try_from(OV::BITS).unwrap())).next_power_of_two()
+}
+
+/// Replaces every bucket of `noisy_histogram` that is sparse with zero, so that helpers never
+/// reveal it. `noise` is the noise that [`dp_for_histogram`] added to the histogram, and the
+/// threshold is derived from it by [`HistogramNoise::suppression_threshold`]. Buckets where the
+/// noise wrapped around below zero are suppressed as well.
+///
+/// # Errors
+/// If `threshold_stds` is not valid, or if the validation of the comparisons fails.
+/// # Panics
+/// If `OV` has more than 32 bits.
+pub async fn suppress_sparse_buckets<C, OV>(
+    ctx: C,
+    noisy_histogram: Vec<Replicated<OV>>,
+    noise: &HistogramNoise,
+    threshold_stds: f64,
+) -> Result<Vec<Replicated<OV>>, Error>
+where
+    C: UpgradableContext,
+    OV: BooleanArray + U128Conversions,
+    Replicated<Boolean>: BooleanProtocols<DZKPUpgraded<C>>,
+    Replicated<OV>: BooleanArrayMul<DZKPUpgraded<C>>,
+{
+    assert!(
+        OV::BITS <= ThirtyTwoBitStep::BITS,
+        "ThirtyTwoBitStep is not large enough to accommodate this comparison"
+    );
+    let threshold = noise
+        .suppression_threshold(threshold_stds)
+        .map_err(|e| Error::InvalidQueryParameter(Box::new(e)))?;
+    if noisy_histogram.is_empty() {
+        return Ok(noisy_histogram);
+    }
+
+    // Noisy values are integers, so a bucket is kept if it is at least the ceiling of the
+    // threshold. Values in the upper half of the range are negative, so the smallest value kept
+    // is clamped to the lower half.
+    let largest_positive = 1_u128 << (OV::BITS - 1);
+    let smallest_kept = (noise.mean + threshold).ceil();
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let smallest_kept = if smallest_kept <= 0.0 {
+        0
+    } else if smallest_kept >= largest_positive as f64 {
+        largest_positive
+    } else {
+        smallest_kept as u128
+    };
+    tracing::info!("Suppressing buckets below {smallest_kept}");
+
+    let validator = ctx
+        .set_total_records(TotalRecords::specified(noisy_histogram.len())?)
+        .dzkp_validator(
+            MaliciousProtocolSteps {
+                protocol: &IpaPrfStep::SuppressSparseBuckets,
+                validate: &IpaPrfStep::SuppressSparseBucketsValidate,
+            },
+            suppression_proof_chunk::<OV>(),
+        );
+    let suppress_ctx = validator.context();
+    let smallest_kept_bits = BitDecomposed::decompose(OV::BITS, |i| {
+        Replicated::share_known_value(
+            &suppress_ctx,
+            Boolean::truncate_from((smallest_kept >> i) & 0x1),
+        )
+    });
+
+    let suppressed = noisy_histogram.into_iter().enumerate().map(|(i, value)| {
+        let record_id = RecordId::from(i);
+        let compare_ctx = suppress_ctx.narrow(&SuppressionStep::Compare);
+        let select_ctx = suppress_ctx.narrow(&SuppressionStep::Select);
+        let smallest_kept_bits = smallest_kept_bits.clone();
+        async move {
+            let bits = value.to_bits();
+            let at_least_threshold = compare_geq::<_, ThirtyTwoBitStep>(
+                compare_ctx,
+                record_id,
+                &bits,
+                &smallest_kept_bits,
+            )
+            .await?;
+            // Negative values are always at least the threshold, because it is in the lower half
+            // of the range. Flipping the comparison for them keeps only non-negative values that
+            // reach the threshold.
+            let keep = at_least_threshold + bits[bits.len() - 1].clone();
+            select(
+                select_ctx,
+                record_id,
+                &keep,
+                &value,
+                &Replicated::<OV>::ZERO,
+            )
+            .await
+        }
+    });
+
+    validated_seq_join(validator, stream::iter(suppressed))
+        .try_collect()
+        .await
+}
+
 #[cfg(all(test, unit_test))]
 mod test {
 
@@ -612,10 +1020,10 @@ mod test {
         protocol::{
             dp::{
                 apply_dp_noise, delta_constraint, dp_for_histogram, epsilon_constraint, error,
-                find_smallest_num_bernoulli, gen_binomial_noise, NoiseParams,
-                ShiftedTruncatedDiscreteLaplace,
+                find_smallest_num_bernoulli, gen_binomial_noise, suppress_sparse_buckets,
+                HistogramNoise, NoiseParams, ShiftedTruncatedDiscreteLaplace,
             },
-            ipa_prf::oprf_padding::insecure::OPRFPaddingDp,
+            ipa_prf::oprf_padding::insecure::{DiscreteGaussianDp, OPRFPaddingDp},
         },
         rand::thread_rng,
         secret_sharing::{
@@ -723,7 +1131,8 @@ mod test {
         const NUM_BREAKDOWNS: u32 = 16;
         const SS_BITS: usize = 3;
         let epsilon = 2.0;
-        let dp_params = DpMechanism::DiscreteLaplace { epsilon };
+        let delta = 1e-6;
+        let dp_params = DpMechanism::DiscreteLaplace { epsilon, delta };
         let world = TestWorld::default();
         let input_values = [0, 0, 0, 0, 1, 1, 1, 1, 100, 100, 100, 100, 10, 20, 30, 40];
 
@@ -744,7 +1153,7 @@ mod test {
             .map(|&v| u32::try_from(v.as_u128()).unwrap())
             .collect::<Vec<_>>();
         let per_user_credit_cap = 2_u32.pow(u32::try_from(SS_BITS).unwrap());
-        let truncated_discrete_laplace = OPRFPaddingDp::new(epsilon, 1e-6, per_user_credit_cap);
+        let truncated_discrete_laplace = OPRFPaddingDp::new(epsilon, delta, per_user_credit_cap);
         let (_, std) = truncated_discrete_laplace.unwrap().mean_and_std();
         let three_std = 3.0 * std;
         assert_eq!(NUM_BREAKDOWNS as usize, result_u32.len());
@@ -771,6 +1180,140 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    pub async fn test_gaussian_noise() {
+        type OV = BA8;
+        const NUM_BREAKDOWNS: u32 = 16;
+        const SS_BITS: usize = 1;
+        // large epsilon keeps the noise well within the range of `OV`
+        let epsilon = 10.0;
+        let delta = 1e-6;
+        let dp_params = DpMechanism::DiscreteGaussian { epsilon, delta };
+        let world = TestWorld::default();
+        let input_values = [0, 0, 0, 0, 1, 1, 1, 1, 100, 100, 100, 100, 10, 20, 30, 40];
+
+        let input: BitDecomposed<[Boolean; NUM_BREAKDOWNS as usize]> =
+            vectorize_input(OV::BITS as usize, &input_values);
+        let result = world
+            .semi_honest(input, |ctx, input| async move {
+                dp_for_histogram::<_, { NUM_BREAKDOWNS as usize }, OV, SS_BITS>(
+                    ctx, input, dp_params,
+                )
+                .await
+                .unwrap()
+            })
+            .await;
+        let result_reconstructed: Vec<OV> = result.reconstruct();
+        let per_user_credit_cap = 2_u32.pow(u32::try_from(SS_BITS).unwrap());
+        let discrete_gaussian =
+            DiscreteGaussianDp::new(epsilon, delta, f64::from(per_user_credit_cap)).unwrap();
+        // three pairs of helpers each add an independent sample
+        let std = 3.0_f64.sqrt() * discrete_gaussian.std();
+        let tolerance_factor = 10.0;
+        assert_eq!(NUM_BREAKDOWNS as usize, result_reconstructed.len());
+        for (result, &input) in result_reconstructed.iter().zip(input_values.iter()) {
+            let result_f64 = f64::from(u32::try_from(result.as_u128()).unwrap());
+            let result_f64_shifted = if result_f64 > 2.0_f64.powf((OV::BITS - 1).into()) {
+                result_f64 - 2.0_f64.powf(OV::BITS.into())
+            } else {
+                result_f64
+            };
+            assert!(
+                (result_f64_shifted - f64::from(input)).abs() < tolerance_factor * std,
+                "noised result {result_f64_shifted} is more than {tolerance_factor} standard \
+                deviations away from {input}. This will fail with a small chance of failure"
+            );
+        }
+    }
+
+    #[test]
+    fn histogram_noise() {
+        assert_eq!(
+            HistogramNoise::new(DpMechanism::NoDp, 8, 256).unwrap(),
+            None
+        );
+
+        for dp_params in [
+            DpMechanism::DiscreteLaplace {
+                epsilon: 1.0,
+                delta: 1e-6,
+            },
+            DpMechanism::DiscreteGaussian {
+                epsilon: 1.0,
+                delta: 1e-6,
+            },
+        ] {
+            let noise = HistogramNoise::new(dp_params, 8, 256).unwrap().unwrap();
+            assert!(
+                noise.mean.abs() < 1e-6,
+                "{dp_params:?} noise is not centered"
+            );
+            assert!(noise.std > 0.0);
+            let (lower, upper) = noise.confidence_interval_95(100.0);
+            assert!((100.0 - lower - (upper - 100.0)).abs() < 1e-6);
+        }
+
+        let binomial = DpMechanism::Binomial {
+            epsilon: 1.0,
+            delta: 1e-6,
+        };
+        let noise = HistogramNoise::new(binomial, 8, 256).unwrap().unwrap();
+        let (lower, upper) = noise.confidence_interval_95(noise.mean);
+        assert!(lower < 0.0 && upper > 0.0);
+
+        assert!(matches!(
+            HistogramNoise::new(
+                DpMechanism::Binomial {
+                    epsilon: 0.0,
+                    delta: 1e-6
+                },
+                8,
+                256
+            ),
+            Err(crate::error::Error::EpsilonOutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn suppression_threshold() {
+        let noise = HistogramNoise {
+            mean: 3.0,
+            std: 10.0,
+        };
+        assert_eq!(noise.suppression_threshold(2.0), Ok(20.0));
+        assert_eq!(noise.suppression_threshold(0.0), Ok(0.0));
+        for threshold_stds in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(noise.suppression_threshold(threshold_stds).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn suppresses_sparse_buckets() {
+        // -3 wraps around to the upper half of the range.
+        let noisy_values = [100_u128, 5, (1 << 16) - 3, 30, 23, 24].map(BA16::truncate_from);
+        let noise = HistogramNoise {
+            mean: 3.5,
+            std: 10.0,
+        };
+        let world = TestWorld::default();
+        let result: Vec<BA16> = world
+            .malicious(noisy_values.into_iter(), |ctx, input| async move {
+                suppress_sparse_buckets(ctx, input, &noise, 2.0)
+                    .await
+                    .unwrap()
+            })
+            .await
+            .reconstruct();
+
+        assert_eq!(
+            result
+                .iter()
+                .map(U128Conversions::as_u128)
+                .collect::<Vec<_>>(),
+            vec![100, 0, 0, 30, 0, 24],
+        );
+    }
+
     #[test]
     fn test_epsilon_simple_aggregation_case() {
         let noise_params = NoiseParams {
@@ -10,6 +10,12 @@ pub(crate) enum DPStep {
     LaplacePass2,
     #[step(child = ApplyDpNoise)]
     LaplacePass3,
+    #[step(child = ApplyDpNoise)]
+    GaussianPass1,
+    #[step(child = ApplyDpNoise)]
+    GaussianPass2,
+    #[step(child = ApplyDpNoise)]
+    GaussianPass3,
 }
 
 #[derive(CompactStep)]
@@ -17,3 +23,10 @@ pub(crate) enum ApplyDpNoise {
     #[step(child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
     ApplyNoise,
 }
+
+#[derive(CompactStep)]
+pub(crate) enum SuppressionStep {
+    #[step(child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
+    Compare,
+    Select,
+}
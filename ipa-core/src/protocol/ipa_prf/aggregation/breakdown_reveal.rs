@@ -1,4 +1,4 @@
-use std::{convert::Infallible, pin::pin};
+use std::{convert::Infallible, iter, pin::pin};
 
 use futures::stream;
 use futures_util::{StreamExt, TryStreamExt};
@@ -13,7 +13,7 @@ use crate::{
     },
     helpers::TotalRecords,
     protocol::{
-        basics::{reveal, Reveal},
+        basics::{reveal, BooleanArrayMul, Reveal},
         context::{
             dzkp_validator::DZKPValidator, Context, DZKPUpgraded, MaliciousProtocolSteps,
             UpgradableContext,
@@ -22,9 +22,15 @@ use crate::{
             aggregation::{
                 aggregate_values_proof_chunk, step::AggregationStep as Step, AGGREGATE_DEPTH,
             },
-            oprf_padding::{apply_dp_padding, PaddingParameters},
+            oprf_padding::{
+                apply_dp_padding, joint::apply_joint_aggregation_padding, PaddingGeneration,
+                PaddingParameters,
+            },
             prf_sharding::{AttributionOutputs, SecretSharedAttributionOutputs},
-            shuffle::{shuffle_attribution_outputs, Shuffle},
+            shuffle::{
+                sharded_shuffle_attribution_outputs, shuffle_attribution_outputs, ShardedShuffle,
+                Shuffle,
+            },
             BreakdownKey,
         },
         BooleanProtocols, RecordId,
@@ -74,7 +80,8 @@ where
     Boolean: FieldSimd<B>,
     Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
     BK: BreakdownKey<B>,
-    Replicated<BK>: Reveal<DZKPUpgraded<C>, Output = <BK as Vectorizable<1>>::Array>,
+    Replicated<BK>: BooleanArrayMul<DZKPUpgraded<C>>
+        + Reveal<DZKPUpgraded<C>, Output = <BK as Vectorizable<1>>::Array>,
     TV: BooleanArray + U128Conversions,
     HV: BooleanArray + U128Conversions,
     BitDecomposed<Replicated<Boolean, B>>:
@@ -82,14 +89,114 @@ where
 {
     // Apply DP padding for Breakdown Reveal Aggregation
     let attributed_values_padded =
-        apply_dp_padding::<_, AttributionOutputs<Replicated<BK>, Replicated<TV>>, B>(
-            ctx.narrow(&Step::PaddingDp),
-            attributed_values,
-            padding_params,
-        )
-        .await?;
+        pad_attributions::<_, BK, TV, B>(&ctx, attributed_values, padding_params).await?;
 
     let attributions = shuffle_attributions::<_, BK, TV, B>(&ctx, attributed_values_padded).await?;
+    reveal_and_aggregate::<_, BK, TV, HV, B>(ctx, attributions).await
+}
+
+/// Sharded version of [`breakdown_reveal_aggregation`].
+///
+/// Attributions are shuffled across all shards of this helper before breakdown keys are
+/// revealed, so a shard does not learn anything about the breakdowns of the users it attributed.
+/// Every shard must call this function, even if it has no attributions, because the shuffle
+/// involves all shards.
+///
+/// Each shard returns the histogram of the attributions it got after the shuffle, which is
+/// always `HV::BITS` wide. The caller is responsible for adding up the histograms of all shards.
+#[tracing::instrument(name = "sharded_breakdown_reveal_aggregation", skip_all, fields(total = attributed_values.len()))]
+pub async fn sharded_breakdown_reveal_aggregation<C, BK, TV, HV, const B: usize>(
+    ctx: C,
+    attributed_values: Vec<SecretSharedAttributionOutputs<BK, TV>>,
+    padding_params: &PaddingParameters,
+) -> Result<BitDecomposed<Replicated<Boolean, B>>, Error>
+where
+    C: UpgradableContext + ShardedShuffle,
+    Boolean: FieldSimd<B>,
+    Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
+    BK: BreakdownKey<B>,
+    Replicated<BK>: BooleanArrayMul<DZKPUpgraded<C>>
+        + Reveal<DZKPUpgraded<C>, Output = <BK as Vectorizable<1>>::Array>,
+    TV: BooleanArray + U128Conversions,
+    HV: BooleanArray + U128Conversions,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<TV>; B], Error = Infallible>,
+{
+    let attributed_values_padded =
+        pad_attributions::<_, BK, TV, B>(&ctx, attributed_values, padding_params).await?;
+
+    let attributions = sharded_shuffle_attribution_outputs::<_, BK, TV>(
+        ctx.narrow(&Step::ShardedShuffle),
+        attributed_values_padded,
+    )
+    .await?;
+
+    let hv_bits = usize::try_from(HV::BITS).unwrap();
+    if attributions.is_empty() {
+        return Ok(BitDecomposed::new(iter::repeat_n(
+            Replicated::<Boolean, B>::ZERO,
+            hv_bits,
+        )));
+    }
+
+    // A shard that got at most one value per breakdown does not aggregate anything, so its
+    // histogram is only as wide as a trigger value.
+    let mut histogram = reveal_and_aggregate::<_, BK, TV, HV, B>(ctx, attributions).await?;
+    histogram.resize(hv_bits, Replicated::ZERO);
+    Ok(histogram)
+}
+
+/// Adds the aggregation padding requested by `padding_params` to `attributed_values`, generated
+/// either by pairs of helpers or jointly by all of them.
+async fn pad_attributions<C, BK, TV, const B: usize>(
+    ctx: &C,
+    attributed_values: Vec<SecretSharedAttributionOutputs<BK, TV>>,
+    padding_params: &PaddingParameters,
+) -> Result<Vec<SecretSharedAttributionOutputs<BK, TV>>, Error>
+where
+    C: UpgradableContext,
+    BK: BreakdownKey<B>,
+    Replicated<BK>: BooleanArrayMul<DZKPUpgraded<C>>,
+    TV: BooleanArray + U128Conversions,
+{
+    let ctx = ctx.narrow(&Step::PaddingDp);
+    match padding_params.padding_generation {
+        PaddingGeneration::Pairwise => {
+            apply_dp_padding::<_, AttributionOutputs<Replicated<BK>, Replicated<TV>>, B>(
+                ctx,
+                attributed_values,
+                padding_params,
+            )
+            .await
+        }
+        PaddingGeneration::Joint => {
+            apply_joint_aggregation_padding::<_, BK, TV, B>(
+                ctx,
+                attributed_values,
+                &padding_params.aggregation_padding,
+            )
+            .await
+        }
+    }
+}
+
+/// Reveals breakdown keys of shuffled `attributions` and adds up trigger values for every
+/// breakdown.
+async fn reveal_and_aggregate<C, BK, TV, HV, const B: usize>(
+    ctx: C,
+    attributions: Vec<SecretSharedAttributionOutputs<BK, TV>>,
+) -> Result<BitDecomposed<Replicated<Boolean, B>>, Error>
+where
+    C: UpgradableContext,
+    Boolean: FieldSimd<B>,
+    Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
+    BK: BreakdownKey<B>,
+    Replicated<BK>: Reveal<DZKPUpgraded<C>, Output = <BK as Vectorizable<1>>::Array>,
+    TV: BooleanArray + U128Conversions,
+    HV: BooleanArray + U128Conversions,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<TV>; B], Error = Infallible>,
+{
     // Revealing the breakdowns doesn't do any multiplies, so won't make it as far as
     // doing a proof, but we need the validator to obtain an upgraded malicious context.
     let validator = ctx.clone().dzkp_validator(
@@ -146,8 +253,10 @@ where
 /// Shuffles attribution Breakdown key and Trigger Value secret shares. Input
 /// and output are the same type.
 ///
+/// Sharded contexts use [`sharded_shuffle_attribution_outputs`] instead, see
+/// [`sharded_breakdown_reveal_aggregation`].
+///
 /// TODO: Use a smaller BA type to contain BK and TV
-/// TODO: Sharded shuffle
 async fn shuffle_attributions<C, BK, TV, const B: usize>(
     parent_ctx: &C,
     contribs: Vec<SecretSharedAttributionOutputs<BK, TV>>,
@@ -28,6 +28,7 @@ use crate::{
 };
 
 pub(crate) mod breakdown_reveal;
+pub(crate) mod shards;
 pub(crate) mod step;
 
 type AttributionOutputsChunk<const N: usize> = AttributionOutputs<
@@ -253,7 +254,10 @@ pub mod tests {
         test_fixture::{ReconstructArr, Runner, TestWorld},
     };
 
-    fn input_row<const B: usize>(tv_bits: usize, values: &[u32]) -> BitDecomposed<[Boolean; B]> {
+    pub(super) fn input_row<const B: usize>(
+        tv_bits: usize,
+        values: &[u32],
+    ) -> BitDecomposed<[Boolean; B]> {
         let values = <&[u32; B]>::try_from(values).unwrap();
 
         BitDecomposed::decompose(tv_bits, |i| {
@@ -0,0 +1,301 @@
+use std::convert::Infallible;
+
+use futures::TryStreamExt;
+
+use crate::{
+    error::{Error, LengthError, UnwrapInfallible},
+    ff::{boolean::Boolean, boolean_array::BooleanArray, Serializable, U128Conversions},
+    helpers::TotalRecords,
+    protocol::{
+        boolean::step::ThirtyTwoBitStep,
+        context::ShardedContext,
+        ipa_prf::{
+            aggregation::step::{ShardAggregationLevelStep, ShardAggregationStep},
+            boolean_ops::addition_sequential::integer_sat_add,
+        },
+        BooleanProtocols, RecordId,
+    },
+    secret_sharing::{
+        replicated::semi_honest::AdditiveShare as Replicated, BitDecomposed, FieldSimd,
+        TransposeFrom,
+    },
+    sharding::ShardIndex,
+};
+
+/// The number of levels in the shard aggregation tree. It limits the number of shards
+/// that can be aggregated to `2^SHARD_AGGREGATE_DEPTH`.
+///
+/// The step count here is duplicated as the `ShardAggregationStep` step count.
+pub const SHARD_AGGREGATE_DEPTH: usize = 20;
+
+/// Sums histograms computed by every shard on this helper.
+///
+/// Each shard contributes a vectorized histogram with `B` buckets, where every bucket holds an
+/// `OV::BITS`-wide value. Shards are combined pairwise, level by level: at level `l`, shards with
+/// index `i` such that `i mod 2^(l+1) == 2^l` send their partial sums to shard `i - 2^l`, which
+/// adds them to its own using saturating addition. After `ceil(log2(shard_count))` levels the
+/// total ends up on the leader shard.
+///
+/// The leader shard gets `Some` total back; every other shard gets `None`. All shards must call
+/// this function, even if their histogram is all zeroes.
+///
+/// If `ctx` is a DZKP-upgraded context, the caller must validate it on every shard once this
+/// function returns, because the additions are done on all shards that receive a partial sum.
+///
+/// ## Errors
+/// If sending or receiving partial sums fails, or if a shard receives a partial sum with the
+/// wrong number of buckets.
+///
+/// ## Panics
+/// If the histogram is not `OV::BITS` wide, if `OV` is wider than 32 bits, or if there are more
+/// than `2^SHARD_AGGREGATE_DEPTH` shards.
+pub async fn aggregate_shards<C, OV, const B: usize>(
+    ctx: C,
+    histogram: BitDecomposed<Replicated<Boolean, B>>,
+) -> Result<Option<BitDecomposed<Replicated<Boolean, B>>>, Error>
+where
+    C: ShardedContext,
+    OV: BooleanArray + U128Conversions,
+    Boolean: FieldSimd<B>,
+    Replicated<Boolean, B>: BooleanProtocols<C, B>,
+    Replicated<OV>: Serializable,
+    Vec<Replicated<OV>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<OV>; B], Error = Infallible>,
+{
+    // `ThirtyTwoBitStep` limits the width of the saturating addition.
+    assert!(
+        OV::BITS <= 32,
+        "shard aggregation supports up to 32-bit histogram values, got {}",
+        OV::BITS
+    );
+    assert_eq!(
+        histogram.len(),
+        usize::try_from(OV::BITS).unwrap(),
+        "histogram must be {} bits wide",
+        OV::BITS
+    );
+    let shard_id = usize::from(ctx.shard_id());
+    let shard_count = usize::from(ctx.shard_count());
+    assert!(
+        shard_count <= 1 << SHARD_AGGREGATE_DEPTH,
+        "shard aggregation supports up to 2^{SHARD_AGGREGATE_DEPTH} shards, got {shard_count}"
+    );
+    debug_assert_eq!(ctx.leader(), ShardIndex::FIRST);
+
+    let mut partial_sum = histogram;
+    let mut stride = 1;
+    let mut level = 0;
+    while stride < shard_count {
+        let level_ctx = ctx.narrow(&ShardAggregationStep::from(level));
+        let transfer_ctx = level_ctx
+            .narrow(&ShardAggregationLevelStep::Transfer)
+            .set_total_records(TotalRecords::specified(B)?);
+
+        if shard_id % (2 * stride) == stride {
+            let dest = ShardIndex::try_from(shard_id - stride).unwrap();
+            let buckets = Vec::<Replicated<OV>>::transposed_from(&partial_sum)?;
+            let send_channel = transfer_ctx.shard_send_channel(dest);
+            for (i, bucket) in buckets.into_iter().enumerate() {
+                send_channel.send(RecordId::from(i), bucket).await?;
+            }
+
+            return Ok(None);
+        }
+
+        if shard_id + stride < shard_count {
+            let origin = ShardIndex::try_from(shard_id + stride).unwrap();
+            let buckets = transfer_ctx
+                .shard_recv_channel::<Replicated<OV>>(origin)
+                .try_collect::<Vec<_>>()
+                .await?;
+            let buckets = <[_; B]>::try_from(buckets).map_err(|buckets| LengthError {
+                expected: B,
+                actual: buckets.len(),
+            })?;
+            let peer_sum = BitDecomposed::transposed_from(&buckets).unwrap_infallible();
+
+            partial_sum = integer_sat_add::<_, ThirtyTwoBitStep, B>(
+                level_ctx
+                    .narrow(&ShardAggregationLevelStep::SaturatingAdd)
+                    .set_total_records(TotalRecords::ONE),
+                RecordId::FIRST,
+                &partial_sum,
+                &peer_sum,
+            )
+            .await?;
+        }
+
+        stride *= 2;
+        level += 1;
+    }
+
+    Ok(Some(partial_sum))
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use super::aggregate_shards;
+    use crate::{
+        error::Error,
+        ff::{
+            boolean::Boolean,
+            boolean_array::{BA32, BA8},
+            ArrayAccess,
+        },
+        protocol::{
+            context::{dzkp_validator::DZKPValidator, UpgradableContext, TEST_DZKP_STEPS},
+            ipa_prf::aggregation::tests::input_row,
+        },
+        secret_sharing::{BitDecomposed, SharedValue},
+        test_executor::run,
+        test_fixture::{ReconstructArr, Runner, TestWorld, TestWorldConfig, WithShards},
+    };
+
+    const B: usize = 32;
+
+    fn ov_bits() -> usize {
+        usize::try_from(BA8::BITS).unwrap()
+    }
+
+    /// Generates one histogram per shard. With the default round-robin distribution, shard `i`
+    /// receives histogram `i`.
+    fn shard_histograms<const SHARDS: usize>(
+        values: impl Fn(usize, usize) -> u32,
+    ) -> Vec<BitDecomposed<[Boolean; B]>> {
+        (0..SHARDS)
+            .map(|shard| {
+                let row = (0..B)
+                    .map(|bucket| values(shard, bucket))
+                    .collect::<Vec<_>>();
+                input_row(ov_bits(), &row)
+            })
+            .collect()
+    }
+
+    fn expected<const SHARDS: usize>(values: impl Fn(usize, usize) -> u32) -> Vec<u32> {
+        let max = (1 << BA8::BITS) - 1;
+        (0..B)
+            .map(|bucket| {
+                (0..SHARDS)
+                    .map(|shard| values(shard, bucket))
+                    .sum::<u32>()
+                    .min(max)
+            })
+            .collect()
+    }
+
+    fn to_totals(result: &BitDecomposed<BA32>) -> Vec<u32> {
+        (0..B)
+            .map(|bucket| {
+                result
+                    .iter()
+                    .enumerate()
+                    .map(|(i, bits)| u32::from(bool::from(bits.get(bucket).unwrap())) << i)
+                    .sum()
+            })
+            .collect()
+    }
+
+    async fn semi_honest<const SHARDS: usize>(values: impl Fn(usize, usize) -> u32) -> Vec<u32> {
+        let world = TestWorld::<WithShards<SHARDS>>::with_shards(TestWorldConfig::default());
+        let mut results = world
+            .semi_honest(
+                shard_histograms::<SHARDS>(values).into_iter(),
+                |ctx, input| async move {
+                    let [histogram] = <[_; 1]>::try_from(input).unwrap();
+                    let validator = ctx.dzkp_validator(TEST_DZKP_STEPS, 1);
+                    let total =
+                        aggregate_shards::<_, BA8, B>(validator.context(), histogram).await?;
+                    validator.validate().await?;
+                    Ok::<_, Error>(total)
+                },
+            )
+            .await
+            .into_iter();
+
+        let leader = results.next().unwrap().map(|r| r.unwrap().unwrap());
+        for shard in results {
+            assert!(shard.into_iter().all(|r| r.unwrap().is_none()));
+        }
+
+        to_totals(&leader.reconstruct_arr())
+    }
+
+    async fn malicious<const SHARDS: usize>(values: impl Fn(usize, usize) -> u32) -> Vec<u32> {
+        let world = TestWorld::<WithShards<SHARDS>>::with_shards(TestWorldConfig::default());
+        let mut results = world
+            .malicious(
+                shard_histograms::<SHARDS>(values).into_iter(),
+                |ctx, input| async move {
+                    let [histogram] = <[_; 1]>::try_from(input).unwrap();
+                    let validator = ctx.dzkp_validator(TEST_DZKP_STEPS, usize::MAX);
+                    let total =
+                        aggregate_shards::<_, BA8, B>(validator.context(), histogram).await?;
+                    validator.validate().await?;
+                    Ok::<_, Error>(total)
+                },
+            )
+            .await
+            .into_iter();
+
+        let leader = results.next().unwrap().map(|r| r.unwrap().unwrap());
+        for shard in results {
+            assert!(shard.into_iter().all(|r| r.unwrap().is_none()));
+        }
+
+        to_totals(&leader.reconstruct_arr())
+    }
+
+    fn values(shard: usize, bucket: usize) -> u32 {
+        u32::try_from((shard * 7 + bucket * 3) % 11).unwrap()
+    }
+
+    #[test]
+    fn single_shard() {
+        run(|| async {
+            assert_eq!(expected::<1>(values), semi_honest::<1>(values).await);
+        });
+    }
+
+    #[test]
+    fn two_shards() {
+        run(|| async {
+            assert_eq!(expected::<2>(values), semi_honest::<2>(values).await);
+        });
+    }
+
+    #[test]
+    fn odd_number_of_shards() {
+        run(|| async {
+            assert_eq!(expected::<3>(values), semi_honest::<3>(values).await);
+            assert_eq!(expected::<5>(values), semi_honest::<5>(values).await);
+        });
+    }
+
+    #[test]
+    fn power_of_two_shards() {
+        run(|| async {
+            assert_eq!(expected::<8>(values), semi_honest::<8>(values).await);
+        });
+    }
+
+    #[test]
+    fn saturates() {
+        run(|| async {
+            let values = |_, bucket| if bucket % 2 == 0 { 200 } else { 50 };
+            let result = semi_honest::<3>(values).await;
+            assert_eq!(expected::<3>(values), result);
+            assert_eq!([255, 150], result[..2]);
+        });
+    }
+
+    #[test]
+    fn malicious_shards() {
+        run(|| async {
+            assert_eq!(expected::<3>(values), malicious::<3>(values).await);
+            assert_eq!(expected::<4>(values), malicious::<4>(values).await);
+        });
+    }
+}
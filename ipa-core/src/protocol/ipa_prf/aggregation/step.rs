@@ -9,6 +9,8 @@ pub(crate) enum AggregationStep {
     PaddingDp,
     #[step(child = crate::protocol::ipa_prf::shuffle::step::OPRFShuffleStep)]
     Shuffle,
+    #[step(child = crate::protocol::ipa_prf::shuffle::step::ShardedShuffleStep)]
+    ShardedShuffle,
     Reveal,
     #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
     RevealValidate, // only partly used -- see code
@@ -30,3 +32,15 @@ pub(crate) enum AggregateValuesStep {
     #[step(child = crate::protocol::ipa_prf::boolean_ops::step::SaturatedAdditionStep)]
     SaturatingAdd,
 }
+
+// The step count here is duplicated as the SHARD_AGGREGATE_DEPTH constant in the code.
+#[derive(CompactStep)]
+#[step(count = 20, child = ShardAggregationLevelStep, name = "shard_level")]
+pub(crate) struct ShardAggregationStep(usize);
+
+#[derive(CompactStep)]
+pub(crate) enum ShardAggregationLevelStep {
+    Transfer,
+    #[step(child = crate::protocol::ipa_prf::boolean_ops::step::SaturatedAdditionStep)]
+    SaturatingAdd,
+}
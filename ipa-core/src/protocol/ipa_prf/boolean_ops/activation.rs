@@ -0,0 +1,497 @@
+//! Activation functions over secret-shared fixed-point values.
+//!
+//! Values are bit-decomposed and little-endian. Signed values use two's complement, so their
+//! last bit is the sign bit. A value with `f` fractional bits represents its integer value
+//! divided by `2^f`.
+
+use std::{
+    cmp::{max, min},
+    iter::zip,
+    ops::Not,
+};
+
+use futures::future::try_join;
+
+use crate::{
+    error::Error,
+    ff::{boolean::Boolean, Field},
+    protocol::{
+        basics::{mul::SecureMul, ShareKnownValue},
+        boolean::{step::ThirtyTwoBitStep, NBitStep},
+        context::Context,
+        ipa_prf::boolean_ops::step::ActivationStep as Step,
+        BooleanProtocols, RecordId,
+    },
+    secret_sharing::{replicated::semi_honest::AdditiveShare, BitDecomposed, FieldSimd},
+};
+
+/// Largest difference between the piecewise linear approximation used by [`sigmoid`] and the
+/// logistic function. The error of [`sigmoid`] also includes rounding errors.
+pub const MAX_SIGMOID_APPROXIMATION_ERROR: f64 = 0.019;
+
+/// Returns the number of integer bits of the input that [`sigmoid`] uses to select a segment.
+/// Inputs with larger magnitudes are saturated.
+fn segment_bits(output_bits: usize) -> usize {
+    // The approximation of the logistic function is below 2^-output_bits past the
+    // `output_bits`th segment. Always use at least 4 segments, to keep the saturation error
+    // smaller than the approximation error.
+    max(output_bits, 4)
+        .next_power_of_two()
+        .trailing_zeros()
+        .try_into()
+        .unwrap()
+}
+
+/// Computes `x_i * y_i` for every pair of bits under `ctx`, one `ThirtyTwoBitStep` per pair.
+async fn multiply_bits<C, const N: usize>(
+    ctx: &C,
+    record_id: RecordId,
+    x: impl IntoIterator<Item = AdditiveShare<Boolean, N>>,
+    y: impl IntoIterator<Item = AdditiveShare<Boolean, N>>,
+) -> Result<Vec<AdditiveShare<Boolean, N>>, Error>
+where
+    C: Context,
+    Boolean: FieldSimd<N>,
+    AdditiveShare<Boolean, N>: BooleanProtocols<C, N>,
+{
+    ctx.parallel_join(zip(x, y).enumerate().map(|(i, (a, b))| {
+        let ctx = ctx.narrow(&ThirtyTwoBitStep::from(i));
+        async move { a.multiply(&b, ctx, record_id).await }
+    }))
+    .await
+}
+
+/// Returns an approximation of the logistic function `1 / (1 + e^-x)`.
+///
+/// `x` is a signed value with `fractional_bits` fractional bits. The output is an unsigned value
+/// of `output_bits` bits, all of which are fractional.
+///
+/// For `|x|` in `[k, k + 1)`, the logistic function is approximated by a line with a slope of
+/// `2^-(k + 2)`, joining the points `(k, 1 - 2^-(k + 1))` and `(k + 1, 1 - 2^-(k + 2))`. These
+/// slopes are powers of two, so evaluating the approximation only requires shifting the
+/// fractional part of `x`. The approximation is at most [`MAX_SIGMOID_APPROXIMATION_ERROR`] away
+/// from the logistic function. Truncating the input and the output adds at most
+/// `2^-output_bits + 2^-(fractional_bits + 1)` to that.
+///
+/// # Errors
+/// propagates errors from multiply
+///
+/// # Panics
+/// If `x` has more than 32 bits, if all the bits of `x` except the sign bit are not enough for
+/// `fractional_bits`, or if `output_bits` is not between 1 and 32.
+pub async fn sigmoid<C, const N: usize>(
+    ctx: C,
+    record_id: RecordId,
+    x: &BitDecomposed<AdditiveShare<Boolean, N>>,
+    fractional_bits: usize,
+    output_bits: usize,
+) -> Result<BitDecomposed<AdditiveShare<Boolean, N>>, Error>
+where
+    C: Context,
+    Boolean: FieldSimd<N>,
+    AdditiveShare<Boolean, N>: BooleanProtocols<C, N>,
+{
+    let max_bits = usize::try_from(ThirtyTwoBitStep::BITS).unwrap();
+    assert!(
+        fractional_bits < x.len() && x.len() <= max_bits,
+        "Input must have a sign bit, {fractional_bits} fractional bits and up to {max_bits} bits in total, got {} bits",
+        x.len(),
+    );
+    assert!(
+        (1..=max_bits).contains(&output_bits),
+        "Output must have between 1 and {max_bits} bits, got {output_bits}",
+    );
+
+    // The approximation is symmetric: sigmoid(-x) = 1 - sigmoid(x). Flipping all the bits of a
+    // negative `x` gives `|x| - 2^-fractional_bits`, which is close enough to `|x|`.
+    let sign = x[x.len() - 1].clone();
+    let abs_x = x[..x.len() - 1]
+        .iter()
+        .map(|bit| bit.clone() + &sign)
+        .collect::<Vec<_>>();
+    let (fraction, integer) = abs_x.split_at(fractional_bits);
+    let (segment, overflow_bits) = integer.split_at(min(integer.len(), segment_bits(output_bits)));
+
+    // `1 - sigmoid(|x|)` is `(2 - fraction) * 2^-(segment + 2)`. Start with
+    // `(2 - fraction) * 2^-2`, approximating `2 - fraction` with `1 + !fraction`.
+    let one = AdditiveShare::<Boolean>::share_known_value(&ctx, Boolean::ONE).expand();
+    let complement = BitDecomposed::new((0..output_bits).map(|i| {
+        match (i + fractional_bits + 2).checked_sub(output_bits) {
+            Some(j) if j < fractional_bits => fraction[j].clone().not(),
+            Some(j) if j == fractional_bits => one.clone(),
+            _ => AdditiveShare::ZERO,
+        }
+    }));
+
+    let shift = async {
+        let mut complement = complement;
+        // Shift right by `segment`, one bit of it at a time.
+        for (i, bit) in segment.iter().enumerate() {
+            let distance = 1 << i;
+            let shifted = (0..output_bits).map(|j| {
+                complement[j].clone() + complement.get(j + distance).unwrap_or(&AdditiveShare::ZERO)
+            });
+            let selected = multiply_bits(
+                &ctx.narrow(&Step::Shift(i)),
+                record_id,
+                shifted,
+                std::iter::repeat(bit.clone()),
+            )
+            .await?;
+            for (c, s) in zip(complement.iter_mut(), selected) {
+                *c += s;
+            }
+        }
+        Ok::<_, Error>(complement)
+    };
+    let overflow = async {
+        let Some((first, rest)) = overflow_bits.split_first() else {
+            return Ok(None);
+        };
+        let ctx = ctx.narrow(&Step::Overflow);
+        let mut any = first.clone();
+        for (i, bit) in rest.iter().enumerate() {
+            let both = any
+                .multiply(bit, ctx.narrow(&ThirtyTwoBitStep::from(i)), record_id)
+                .await?;
+            any = any + bit + both;
+        }
+        Ok(Some(any))
+    };
+    let (mut complement, overflow) = try_join(shift, overflow).await?;
+
+    // `1 - sigmoid(|x|)` is zero when `|x|` is past the last segment.
+    if let Some(overflow) = overflow {
+        complement = BitDecomposed::new(
+            multiply_bits(
+                &ctx.narrow(&Step::Saturate),
+                record_id,
+                complement,
+                std::iter::repeat(overflow.not()),
+            )
+            .await?,
+        );
+    }
+
+    // Flipping all the bits gives `1 - 2^-output_bits - complement`, which is `sigmoid(|x|)`.
+    let sign_not = sign.not();
+    Ok(BitDecomposed::new(
+        complement.into_iter().map(|bit| bit + &sign_not),
+    ))
+}
+
+/// Returns an approximation of `tanh(x)`, computed as `2 * sigmoid(2 * x) - 1`.
+///
+/// `x` is a signed value with `fractional_bits` fractional bits. The output is a signed value
+/// with `output_fractional_bits` fractional bits, and a sign bit. Because of the approximation
+/// used by [`sigmoid`], the output is at most `2 * MAX_SIGMOID_APPROXIMATION_ERROR` away from
+/// `tanh(x)`, plus `2^-output_fractional_bits + 2^-(fractional_bits - 1)` for rounding.
+///
+/// # Errors
+/// propagates errors from multiply
+///
+/// # Panics
+/// If `x` has no fractional bits, and in the same cases as [`sigmoid`] otherwise.
+pub async fn tanh<C, const N: usize>(
+    ctx: C,
+    record_id: RecordId,
+    x: &BitDecomposed<AdditiveShare<Boolean, N>>,
+    fractional_bits: usize,
+    output_fractional_bits: usize,
+) -> Result<BitDecomposed<AdditiveShare<Boolean, N>>, Error>
+where
+    C: Context,
+    Boolean: FieldSimd<N>,
+    AdditiveShare<Boolean, N>: BooleanProtocols<C, N>,
+{
+    assert!(fractional_bits > 0, "Input must have a fractional bit");
+
+    // Doubling `x` is the same as reading it with one less fractional bit, and doubling the
+    // output of `sigmoid` is the same as reading it with one more integer bit. Subtracting one
+    // then flips that bit, which becomes the sign bit.
+    let mut result = sigmoid(
+        ctx,
+        record_id,
+        x,
+        fractional_bits - 1,
+        output_fractional_bits + 1,
+    )
+    .await?;
+    let sign = output_fractional_bits;
+    result[sign] = result[sign].clone().not();
+
+    Ok(result)
+}
+
+/// Returns `max(x, 0)` for a signed value `x`. The output has the same number of bits as `x`.
+///
+/// # Errors
+/// propagates errors from multiply
+///
+/// # Panics
+/// If `x` has more than 32 bits.
+pub async fn relu<C, const N: usize>(
+    ctx: C,
+    record_id: RecordId,
+    x: &BitDecomposed<AdditiveShare<Boolean, N>>,
+) -> Result<BitDecomposed<AdditiveShare<Boolean, N>>, Error>
+where
+    C: Context,
+    Boolean: FieldSimd<N>,
+    AdditiveShare<Boolean, N>: BooleanProtocols<C, N>,
+{
+    let max_bits = usize::try_from(ThirtyTwoBitStep::BITS).unwrap();
+    assert!(
+        !x.is_empty() && x.len() <= max_bits,
+        "Up to {max_bits} bit values are supported, got {} bits",
+        x.len(),
+    );
+
+    let sign_not = x[x.len() - 1].clone().not();
+    let result = multiply_bits(
+        &ctx.narrow(&Step::Relu),
+        record_id,
+        x[..x.len() - 1].iter().cloned(),
+        std::iter::repeat(sign_not),
+    )
+    .await?;
+
+    Ok(BitDecomposed::new(
+        result.into_iter().chain([AdditiveShare::ZERO]),
+    ))
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use std::iter::repeat_with;
+
+    use rand::{distributions::Standard, prelude::Distribution, thread_rng, Rng};
+
+    use super::{relu, sigmoid, tanh, MAX_SIGMOID_APPROXIMATION_ERROR};
+    use crate::{
+        ff::{
+            boolean_array::{BooleanArray, BA16, BA32},
+            U128Conversions,
+        },
+        protocol::{
+            boolean::step::ThirtyTwoBitStep, context::Context,
+            ipa_prf::boolean_ops::addition_sequential::integer_add, RecordId,
+        },
+        secret_sharing::{replicated::semi_honest::AdditiveShare, BitDecomposed, TransposeFrom},
+        test_executor::run,
+        test_fixture::{Reconstruct, Runner, TestWorld},
+    };
+
+    fn as_i128<B: BooleanArray + U128Conversions>(x: B) -> i128 {
+        let x = i128::try_from(x.as_u128()).unwrap();
+        let msb = (x >> (B::BITS - 1)) & 1;
+        x - msb * (1 << B::BITS)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn to_f64(x: i128, fractional_bits: usize) -> f64 {
+        x as f64 / 2_f64.powi(i32::try_from(fractional_bits).unwrap())
+    }
+
+    /// Random inputs, along with the smallest, the largest and the values around zero.
+    fn inputs<B: BooleanArray + U128Conversions>() -> Vec<B>
+    where
+        Standard: Distribution<B>,
+    {
+        let mut rng = thread_rng();
+        let max = (1_u128 << (B::BITS - 1)) - 1;
+        [0, 1, max, max + 1, (1 << B::BITS) - 1]
+            .into_iter()
+            .map(B::truncate_from)
+            .chain(repeat_with(|| rng.gen::<B>()))
+            .take(256)
+            .collect()
+    }
+
+    fn logistic(x: f64) -> f64 {
+        1.0 / (1.0 + f64::exp(-x))
+    }
+
+    #[test]
+    fn semi_honest_sigmoid_16_bit() {
+        const FRACTIONAL_BITS: usize = 8;
+        const OUTPUT_BITS: usize = 12;
+
+        run(|| async move {
+            let world = TestWorld::default();
+            let x_values = inputs::<BA16>();
+
+            let result: Vec<BA16> = world
+                .dzkp_semi_honest(x_values.clone().into_iter(), |ctx, x_values| async move {
+                    let x = BitDecomposed::transposed_from(
+                        <&[_; 256]>::try_from(x_values.as_slice()).unwrap(),
+                    )
+                    .unwrap();
+                    let mut result = sigmoid::<_, 256>(
+                        ctx.set_total_records(1),
+                        RecordId::FIRST,
+                        &x,
+                        FRACTIONAL_BITS,
+                        OUTPUT_BITS,
+                    )
+                    .await
+                    .unwrap();
+                    assert_eq!(result.len(), OUTPUT_BITS);
+                    result.resize(16, AdditiveShare::ZERO);
+                    Vec::transposed_from(&result).unwrap()
+                })
+                .await
+                .reconstruct();
+
+            let max_error = MAX_SIGMOID_APPROXIMATION_ERROR
+                + to_f64(1, OUTPUT_BITS)
+                + to_f64(1, FRACTIONAL_BITS + 1);
+            for (x, y) in x_values.into_iter().zip(result) {
+                let x = to_f64(as_i128(x), FRACTIONAL_BITS);
+                let y = to_f64(y.as_u128().try_into().unwrap(), OUTPUT_BITS);
+                let error = (logistic(x) - y).abs();
+                assert!(error <= max_error, "sigmoid({x}) = {y}, error {error}");
+            }
+        });
+    }
+
+    #[test]
+    fn semi_honest_sigmoid_32_bit() {
+        const FRACTIONAL_BITS: usize = 16;
+        const OUTPUT_BITS: usize = 32;
+
+        run(|| async move {
+            let world = TestWorld::default();
+            let x_values = inputs::<BA32>();
+
+            let result: Vec<BA32> = world
+                .dzkp_semi_honest(x_values.clone().into_iter(), |ctx, x_values| async move {
+                    let x = BitDecomposed::transposed_from(
+                        <&[_; 256]>::try_from(x_values.as_slice()).unwrap(),
+                    )
+                    .unwrap();
+                    let result = sigmoid::<_, 256>(
+                        ctx.set_total_records(1),
+                        RecordId::FIRST,
+                        &x,
+                        FRACTIONAL_BITS,
+                        OUTPUT_BITS,
+                    )
+                    .await
+                    .unwrap();
+                    Vec::transposed_from(&result).unwrap()
+                })
+                .await
+                .reconstruct();
+
+            let max_error = MAX_SIGMOID_APPROXIMATION_ERROR
+                + to_f64(1, OUTPUT_BITS)
+                + to_f64(1, FRACTIONAL_BITS + 1);
+            for (x, y) in x_values.into_iter().zip(result) {
+                let x = to_f64(as_i128(x), FRACTIONAL_BITS);
+                let y = to_f64(y.as_u128().try_into().unwrap(), OUTPUT_BITS);
+                let error = (logistic(x) - y).abs();
+                assert!(error <= max_error, "sigmoid({x}) = {y}, error {error}");
+            }
+        });
+    }
+
+    #[test]
+    fn semi_honest_tanh_16_bit() {
+        const FRACTIONAL_BITS: usize = 10;
+        const OUTPUT_FRACTIONAL_BITS: usize = 14;
+
+        run(|| async move {
+            let world = TestWorld::default();
+            let x_values = inputs::<BA16>();
+
+            let result: Vec<BA16> = world
+                .dzkp_semi_honest(x_values.clone().into_iter(), |ctx, x_values| async move {
+                    let x = BitDecomposed::transposed_from(
+                        <&[_; 256]>::try_from(x_values.as_slice()).unwrap(),
+                    )
+                    .unwrap();
+                    let mut result = tanh::<_, 256>(
+                        ctx.set_total_records(1),
+                        RecordId::FIRST,
+                        &x,
+                        FRACTIONAL_BITS,
+                        OUTPUT_FRACTIONAL_BITS,
+                    )
+                    .await
+                    .unwrap();
+                    assert_eq!(result.len(), OUTPUT_FRACTIONAL_BITS + 1);
+                    let sign = result[OUTPUT_FRACTIONAL_BITS].clone();
+                    result.resize(16, sign);
+                    Vec::transposed_from(&result).unwrap()
+                })
+                .await
+                .reconstruct();
+
+            let max_error = 2.0 * MAX_SIGMOID_APPROXIMATION_ERROR
+                + to_f64(1, OUTPUT_FRACTIONAL_BITS)
+                + to_f64(1, FRACTIONAL_BITS - 1);
+            for (x, y) in x_values.into_iter().zip(result) {
+                let x = to_f64(as_i128(x), FRACTIONAL_BITS);
+                let y = to_f64(as_i128(y), OUTPUT_FRACTIONAL_BITS);
+                let error = (x.tanh() - y).abs();
+                assert!(error <= max_error, "tanh({x}) = {y}, error {error}");
+            }
+        });
+    }
+
+    #[test]
+    fn semi_honest_relu_32_bit() {
+        run(|| async move {
+            let world = TestWorld::default();
+            let x_values = inputs::<BA32>();
+
+            let result: Vec<BA32> = world
+                .dzkp_semi_honest(x_values.clone().into_iter(), |ctx, x_values| async move {
+                    let x = BitDecomposed::transposed_from(
+                        <&[_; 256]>::try_from(x_values.as_slice()).unwrap(),
+                    )
+                    .unwrap();
+                    let result = relu::<_, 256>(ctx.set_total_records(1), RecordId::FIRST, &x)
+                        .await
+                        .unwrap();
+                    Vec::transposed_from(&result).unwrap()
+                })
+                .await
+                .reconstruct();
+
+            for (x, y) in x_values.into_iter().zip(result) {
+                assert_eq!(as_i128(y), as_i128(x).max(0), "relu({})", as_i128(x));
+            }
+        });
+    }
+
+    /// The activation functions narrow the context with their own steps, so the caller can use
+    /// the same context for other protocols.
+    #[test]
+    fn activations_share_context() {
+        run(|| async move {
+            let world = TestWorld::default();
+            let x_values = inputs::<BA32>();
+
+            world
+                .dzkp_semi_honest(x_values.into_iter(), |ctx, x_values| async move {
+                    let ctx = ctx.set_total_records(1);
+                    let x = BitDecomposed::transposed_from(
+                        <&[_; 256]>::try_from(x_values.as_slice()).unwrap(),
+                    )
+                    .unwrap();
+                    relu::<_, 256>(ctx.clone(), RecordId::FIRST, &x)
+                        .await
+                        .unwrap();
+                    sigmoid::<_, 256>(ctx.clone(), RecordId::FIRST, &x, 16, 16)
+                        .await
+                        .unwrap();
+                    integer_add::<_, ThirtyTwoBitStep, 256>(ctx, RecordId::FIRST, &x, &x)
+                        .await
+                        .unwrap();
+                })
+                .await;
+        });
+    }
+}
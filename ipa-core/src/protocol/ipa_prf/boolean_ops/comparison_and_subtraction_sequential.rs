@@ -25,7 +25,6 @@ use crate::{
 /// Outputs x>=y for length(x) >= log2(y).
 /// # Errors
 /// Propagates errors from multiply
-#[allow(dead_code)]
 pub async fn compare_geq<C, S>(
     ctx: C,
     record_id: RecordId,
@@ -1,6 +1,7 @@
+pub mod activation;
 pub mod addition_sequential;
 pub mod comparison_and_subtraction_sequential;
-mod multiplication;
+pub mod multiplication;
 mod share_conversion_aby;
 pub(crate) mod step;
 pub use share_conversion_aby::{
@@ -1,11 +1,12 @@
-use ipa_step::StepNarrow;
-
 use crate::{
     error::Error,
     ff::boolean::Boolean,
     protocol::{
-        basics::mul::SecureMul, boolean::NBitStep, context::Context,
-        ipa_prf::boolean_ops::addition_sequential::integer_add, BooleanProtocols, Gate, RecordId,
+        basics::mul::SecureMul,
+        boolean::{step::ThirtyTwoBitStep, NBitStep},
+        context::Context,
+        ipa_prf::boolean_ops::addition_sequential::integer_add,
+        BooleanProtocols, RecordId,
     },
     secret_sharing::{replicated::semi_honest::AdditiveShare, BitDecomposed, FieldSimd},
 };
@@ -17,8 +18,15 @@ use crate::{
 /// 3. Add up the partial products using `integer_add`
 ///    x is assumed to be a positive number
 ///    y is assumed to be in two's complement and can be either signed or unsigned
-#[allow(dead_code)]
-pub async fn integer_mul<C, S, const N: usize>(
+///
+/// The output has `x.len() + y.len()` bits. x must not be wider than y.
+///
+/// # Errors
+/// propagates errors from multiply
+///
+/// # Panics
+/// If x is wider than y, or if the output is wider than 32 bits.
+pub async fn integer_mul<C, const N: usize>(
     ctx: C,
     record_id: RecordId,
     x: &BitDecomposed<AdditiveShare<Boolean, N>>,
@@ -26,23 +34,28 @@ pub async fn integer_mul<C, S, const N: usize>(
 ) -> Result<BitDecomposed<AdditiveShare<Boolean, N>>, Error>
 where
     C: Context,
-    S: NBitStep,
     Boolean: FieldSimd<N>,
     AdditiveShare<Boolean, N>: BooleanProtocols<C, N>,
-    Gate: StepNarrow<S>,
 {
-    use super::step::MultiplicationStep as Step;
+    use super::step::{MultiplicationBitStep, MultiplicationStep as Step};
 
     let new_len = x.len() + y.len();
+    assert!(x.len() <= y.len(), "x must not be wider than y");
+    assert!(
+        new_len <= usize::try_from(ThirtyTwoBitStep::BITS).unwrap(),
+        "Up to {} bit products are supported",
+        ThirtyTwoBitStep::BITS
+    );
     let mut y = y.clone();
     y.resize(new_len, y[y.len() - 1].clone());
 
     let mut result = BitDecomposed::with_capacity(new_len);
     for (i, yb) in y.into_iter().enumerate() {
-        let ctx_for_bit_of_y = ctx.narrow(&S::from(i));
+        let ctx_for_bit_of_y = ctx.narrow(&Step::from(i));
         let product_of_x_and_yb = ctx_for_bit_of_y
             .parallel_join(x.iter().take(new_len - i).enumerate().map(|(j, xb)| {
-                let ctx_for_x_times_y_combo = ctx_for_bit_of_y.narrow(&S::from(j));
+                let ctx_for_x_times_y_combo =
+                    ctx_for_bit_of_y.narrow(&MultiplicationBitStep::Multiply(j));
                 let yb = yb.clone();
                 async move { yb.multiply(xb, ctx_for_x_times_y_combo, record_id).await }
             }))
@@ -54,8 +67,8 @@ where
         } else {
             // add up bits i.. with the product
             let add_y = BitDecomposed::new(result.clone().into_iter().skip(i));
-            let (add_result, carry) = integer_add::<_, S, N>(
-                ctx_for_bit_of_y.narrow::<Step>(&Step::Add),
+            let (add_result, carry) = integer_add::<_, ThirtyTwoBitStep, N>(
+                ctx_for_bit_of_y.narrow(&MultiplicationBitStep::Add),
                 record_id,
                 &t,
                 &add_y,
@@ -85,10 +98,7 @@ mod test {
             boolean_array::{BooleanArray, BA16, BA8},
             U128Conversions,
         },
-        protocol::{
-            boolean::step::DefaultBitStep, context::Context,
-            ipa_prf::boolean_ops::multiplication::integer_mul, RecordId,
-        },
+        protocol::{context::Context, ipa_prf::boolean_ops::multiplication::integer_mul, RecordId},
         secret_sharing::{replicated::semi_honest::AdditiveShare, BitDecomposed, TransposeFrom},
         test_executor::run,
         test_fixture::{Reconstruct, Runner, TestWorld},
@@ -134,7 +144,7 @@ mod test {
                             BitDecomposed::new(iter::empty());
                         let _ = vectorized_y_inputs.transpose_from(&y_vals);
 
-                        let result = integer_mul::<_, DefaultBitStep, 256>(
+                        let result = integer_mul::<_, 256>(
                             ctx.set_total_records(1),
                             RecordId::FIRST,
                             &vectorized_x_inputs,
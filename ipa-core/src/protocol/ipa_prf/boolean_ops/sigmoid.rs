@@ -66,6 +66,8 @@ where
 /// as a value between 0 and 1 in Little-Endian format
 /// where `0b0000_0000` is 0 and `0b1111_1111` is 255/256
 ///
+/// See [`super::activation::sigmoid`] for wider inputs and outputs.
+///
 /// # Errors
 /// propagates errors from multiply
 ///
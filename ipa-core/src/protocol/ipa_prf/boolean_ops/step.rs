@@ -30,8 +30,28 @@ pub(crate) enum Fp25519ConversionStep {
     RevealY,
 }
 
+/// Steps used by `integer_mul`, one per bit of the sign-extended multiplier.
 #[derive(CompactStep)]
-pub(crate) enum MultiplicationStep {
-    #[step(child = crate::protocol::boolean::step::SixteenBitStep)]
+#[step(count = 32, child = MultiplicationBitStep, name = "bit")]
+pub(crate) struct MultiplicationStep(usize);
+
+#[derive(CompactStep)]
+pub(crate) enum MultiplicationBitStep {
+    #[step(count = 32)]
+    Multiply(usize),
+    #[step(child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
     Add,
 }
+
+/// Steps used by the activation functions in the `activation` module.
+#[derive(CompactStep)]
+pub(crate) enum ActivationStep {
+    #[step(child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
+    Overflow,
+    #[step(count = 5, child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
+    Shift(usize),
+    #[step(child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
+    Saturate,
+    #[step(child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
+    Relu,
+}
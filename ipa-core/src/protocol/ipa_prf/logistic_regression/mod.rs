@@ -0,0 +1,980 @@
+//! Logistic regression training over secret-shared examples.
+//!
+//! Each input row is one example: a vector of `B` features and a label. Rows are expected to be
+//! attributed already, for example a source event's features labeled with whether it received
+//! attribution. The DP noise is calibrated for one row per user, so rows are grouped by the PRF
+//! of their match key, like in IPA, and only one row of each user is used for training.
+//!
+//! The model is trained with a fixed number of full-batch gradient descent iterations. Weights
+//! stay secret-shared between iterations, and each iteration adds DP noise to the secret-shared
+//! gradient before it is applied to the weights, so the weights that are eventually revealed are
+//! noisy.
+//!
+//! All values use two's complement fixed-point encodings:
+//! * Features are unsigned 8-bit values with 8 fractional bits, so they are in `[0, 1)`.
+//! * Weights are signed 16-bit values with 8 fractional bits, so they are in `[-128, 128)`.
+//!   All weights start at zero.
+//! * The input of [`sigmoid`] is the dot product of the features and the weights, saturated to a
+//!   signed 8-bit value with 4 fractional bits. The output of [`sigmoid`] is an unsigned 8-bit
+//!   value with 8 fractional bits.
+//! * The gradient contribution of every example and feature is `(sigmoid(z) - label) * feature`,
+//!   rounded down to 8 fractional bits.
+//! * Weight updates saturate at the bounds of the weights instead of wrapping around.
+//!
+//! The learning rate is a power of two, `2^-learning_rate_shift`. It is applied to the sum of
+//! the gradient contributions, so it should account for the number of rows.
+
+use std::{
+    convert::Infallible,
+    iter::{repeat, repeat_n, zip},
+    ops::Not,
+};
+
+use futures::{stream, StreamExt};
+use generic_array::GenericArray;
+use typenum::{Unsigned, U50};
+
+use crate::{
+    error::{Error, LengthError, UnwrapInfallible},
+    ff::{
+        boolean::Boolean,
+        boolean_array::{BA16, BA32, BA8},
+        curve_points::RP25519,
+        ec_prime_field::Fp25519,
+        ArrayAccess, Field, Serializable,
+    },
+    helpers::{query::DpMechanism, TotalRecords},
+    protocol::{
+        basics::{BooleanProtocols, Reveal, SecureMul, ShareKnownValue},
+        boolean::{
+            step::{SixteenBitStep, ThirtyTwoBitStep},
+            NBitStep,
+        },
+        context::{
+            dzkp_validator::DZKPValidator, Context, DZKPUpgraded, MacUpgraded,
+            MaliciousProtocolSteps, UpgradableContext,
+        },
+        dp::dp_for_histogram_with_steps,
+        ipa_prf::{
+            aggregation::aggregate_values,
+            boolean_ops::{
+                addition_sequential::integer_add, multiplication::integer_mul, sigmoid::sigmoid,
+            },
+            compute_prf_of_match_keys,
+            logistic_regression::step::{
+                LogisticRegressionIterationStep as IterationStep, LogisticRegressionStep,
+                SaturateStep, TrainStep, UpdateWeightsStep,
+            },
+            oprf_padding::{apply_dp_padding, PaddingParameters},
+            prf_eval::PrfSharing,
+            shuffle::{shuffle_logistic_regression_inputs, Shuffle},
+            step::IpaPrfStep,
+            MatchKey, CONV_CHUNK, PRF_CHUNK,
+        },
+        prss::FromPrss,
+        RecordId,
+    },
+    secret_sharing::{
+        replicated::{semi_honest::AdditiveShare as Replicated, ReplicatedSecretSharing},
+        BitDecomposed, FieldSimd, SharedValue, TransposeFrom, Vectorizable,
+    },
+    seq_join::{seq_join, SeqJoin},
+};
+
+pub(crate) mod step;
+
+/// Maximum number of gradient descent iterations.
+// This is the step count for `LogisticRegressionStep`.
+pub const MAX_ITERATIONS: usize = 32;
+
+/// Maximum number of rows. The offset-encoded sum of the gradient contributions of this many rows
+/// fits in 32 bits.
+pub const MAX_ROWS: usize = 1 << 23;
+
+/// Number of rows processed together by the vectorized forward and backward passes.
+const CHUNK: usize = 256;
+
+/// Bits of the weights.
+const WEIGHT_BITS: usize = 16;
+
+/// Fractional bits of the features and of the weights.
+const FRACTIONAL_BITS: usize = 8;
+
+/// Bits of a gradient contribution of a single example to a single feature.
+const GRADIENT_BITS: usize = 9;
+
+/// Bits of the sum of the gradient contributions.
+const GRADIENT_SUM_BITS: usize = 32;
+
+/// Input row of the logistic regression query.
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct LogisticRegressionInputRow<const B: usize> {
+    pub match_key: Replicated<MatchKey>,
+    pub features: [Replicated<BA8>; B],
+    pub label: Replicated<Boolean>,
+}
+
+impl<const B: usize> Default for LogisticRegressionInputRow<B> {
+    fn default() -> Self {
+        Self {
+            match_key: Replicated::ZERO,
+            features: [Replicated::ZERO; B],
+            label: Replicated::ZERO,
+        }
+    }
+}
+
+/// The logistic regression query reads 16 features of 8 bits each.
+impl Serializable for LogisticRegressionInputRow<16> {
+    type Size = U50;
+    type DeserializationError = Error;
+
+    fn serialize(&self, buf: &mut GenericArray<u8, Self::Size>) {
+        let mk_sz = <Replicated<MatchKey> as Serializable>::Size::USIZE;
+        let label_sz = <Replicated<Boolean> as Serializable>::Size::USIZE;
+        let fv_sz = <Replicated<BA8> as Serializable>::Size::USIZE;
+
+        self.match_key
+            .serialize(GenericArray::from_mut_slice(&mut buf[..mk_sz]));
+        self.label.serialize(GenericArray::from_mut_slice(
+            &mut buf[mk_sz..mk_sz + label_sz],
+        ));
+        for (feature, chunk) in zip(&self.features, buf[mk_sz + label_sz..].chunks_mut(fv_sz)) {
+            feature.serialize(GenericArray::from_mut_slice(chunk));
+        }
+    }
+
+    fn deserialize(buf: &GenericArray<u8, Self::Size>) -> Result<Self, Self::DeserializationError> {
+        let mk_sz = <Replicated<MatchKey> as Serializable>::Size::USIZE;
+        let label_sz = <Replicated<Boolean> as Serializable>::Size::USIZE;
+        let fv_sz = <Replicated<BA8> as Serializable>::Size::USIZE;
+
+        let match_key =
+            Replicated::<MatchKey>::deserialize(GenericArray::from_slice(&buf[..mk_sz]))
+                .unwrap_infallible();
+        let label = Replicated::<Boolean>::deserialize(GenericArray::from_slice(
+            &buf[mk_sz..mk_sz + label_sz],
+        ))
+        .map_err(|e| Error::ParseError(e.into()))?;
+        let mut features = [Replicated::<BA8>::ZERO; 16];
+        for (feature, chunk) in zip(&mut features, buf[mk_sz + label_sz..].chunks(fv_sz)) {
+            *feature =
+                Replicated::<BA8>::deserialize(GenericArray::from_slice(chunk)).unwrap_infallible();
+        }
+
+        Ok(Self {
+            match_key,
+            features,
+            label,
+        })
+    }
+}
+
+/// Up to `CHUNK` input rows, vectorized so that every lane holds one row.
+struct VectorizedRows {
+    /// Number of input rows in this chunk. The remaining lanes are zero.
+    len: usize,
+    /// Bits of each feature.
+    features: Vec<BitDecomposed<Replicated<Boolean, CHUNK>>>,
+    label: Replicated<Boolean, CHUNK>,
+}
+
+impl VectorizedRows {
+    fn new<const B: usize>(rows: &[LogisticRegressionInputRow<B>]) -> Result<Self, LengthError> {
+        assert!(rows.len() <= CHUNK);
+        let features = (0..B)
+            .map(|j| {
+                let mut feature = rows
+                    .iter()
+                    .map(|row| row.features[j].clone())
+                    .collect::<Vec<_>>();
+                feature.resize(CHUNK, Replicated::ZERO);
+                BitDecomposed::transposed_from(&feature)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let label_or_zero = |i: usize| rows.get(i).map_or(Replicated::ZERO, |r| r.label.clone());
+        let label = Replicated::from_fns(|i| label_or_zero(i).left(), |i| label_or_zero(i).right());
+
+        Ok(Self {
+            len: rows.len(),
+            features,
+            label,
+        })
+    }
+}
+
+/// Contexts used to evaluate the forward and backward passes for each chunk of rows.
+#[derive(Clone)]
+struct TrainContexts<C> {
+    multiply_features_by_weights: C,
+    /// One context for each level of the tree that sums the feature products.
+    sum_feature_products: Vec<C>,
+    saturate: C,
+    sigmoid: C,
+    multiply_features_by_error: C,
+}
+
+impl<C: Context> TrainContexts<C> {
+    fn new(ctx: &C, num_chunks: usize, num_features: usize) -> Result<Self, Error> {
+        let narrow = |step: &TrainStep, records: usize| -> Result<C, Error> {
+            Ok(ctx
+                .narrow(step)
+                .set_total_records(TotalRecords::specified(records)?))
+        };
+        Ok(Self {
+            multiply_features_by_weights: narrow(
+                &TrainStep::MultiplyFeaturesByWeights,
+                num_chunks * num_features,
+            )?,
+            sum_feature_products: sum_tree_widths(num_features)
+                .enumerate()
+                .map(|(depth, width)| {
+                    narrow(
+                        &TrainStep::SumFeatureProducts(depth),
+                        num_chunks * (width / 2),
+                    )
+                })
+                .collect::<Result<_, _>>()?,
+            saturate: narrow(&TrainStep::Saturate, num_chunks)?,
+            sigmoid: narrow(&TrainStep::Sigmoid, num_chunks)?,
+            multiply_features_by_error: narrow(
+                &TrainStep::MultiplyFeaturesByError,
+                num_chunks * num_features,
+            )?,
+        })
+    }
+}
+
+/// Trains a logistic regression model over the input rows, and returns the secret-shared weights
+/// as signed 16-bit values with 8 fractional bits. See the module documentation for the
+/// encoding of the inputs.
+///
+/// Each of the `iterations` gradient descent iterations spends an equal share of the privacy
+/// budget in `dp_params`. The sensitivity of the sum of the gradients is bounded by `2^SS_BITS`,
+/// which must be at least the L1 norm of the largest possible gradient contribution of a row.
+/// That bound holds per user because only one row of each user is kept: the input is padded with
+/// `dp_padding_params` and shuffled, the PRF of the match keys is revealed, and every user's
+/// rows beyond the first are dropped.
+///
+/// # Errors
+/// If the number of iterations, the learning rate, the number of rows or the DP mechanism are
+/// not supported. Propagates errors from the MPC protocols.
+/// # Panics
+/// If there are more than 16 features, or if `2^SS_BITS` does not bound the gradient
+/// contribution of a row.
+#[tracing::instrument(name = "logistic_regression", skip_all, fields(rows = input_rows.len()))]
+pub async fn logistic_regression<C, const SS_BITS: usize, const B: usize>(
+    ctx: C,
+    input_rows: Vec<LogisticRegressionInputRow<B>>,
+    iterations: usize,
+    learning_rate_shift: usize,
+    dp_params: DpMechanism,
+    dp_padding_params: PaddingParameters,
+) -> Result<Vec<Replicated<BA16>>, Error>
+where
+    C: UpgradableContext + Shuffle,
+    Boolean: Vectorizable<B> + FieldSimd<B> + FieldSimd<CHUNK>,
+    BitDecomposed<Replicated<Boolean, B>>: FromPrss<usize>,
+    Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
+    Replicated<Boolean, CHUNK>: BooleanProtocols<DZKPUpgraded<C>, CHUNK>,
+    Replicated<Boolean, CONV_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, CONV_CHUNK>,
+    Replicated<Fp25519, PRF_CHUNK>:
+        PrfSharing<MacUpgraded<C, Fp25519>, PRF_CHUNK, Field = Fp25519> + FromPrss,
+    Replicated<RP25519, PRF_CHUNK>:
+        Reveal<MacUpgraded<C, Fp25519>, Output = <RP25519 as Vectorizable<PRF_CHUNK>>::Array>,
+    Vec<Replicated<BA16>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
+    Vec<Replicated<BA32>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<BA32>; B], Error = Infallible>,
+{
+    // The feature products are summed in 28 bits, which is enough for 16 features, and the
+    // saturation to the input of the sigmoid supports up to 8 extra bits.
+    assert!(B <= 16, "Up to 16 features are supported, got {B}");
+    assert!(
+        B << GRADIENT_BITS <= 1 << SS_BITS,
+        "SS_BITS = {SS_BITS} does not bound the gradient contribution of {B} features",
+    );
+    if iterations == 0 || iterations > MAX_ITERATIONS {
+        return Err(Error::InvalidQueryParameter(
+            format!("number of iterations must be between 1 and {MAX_ITERATIONS}").into(),
+        ));
+    }
+    if learning_rate_shift >= GRADIENT_SUM_BITS {
+        return Err(Error::InvalidQueryParameter(
+            format!("learning rate shift must be less than {GRADIENT_SUM_BITS}").into(),
+        ));
+    }
+    if input_rows.len() > MAX_ROWS {
+        return Err(Error::InvalidQueryParameter(
+            format!("logistic regression supports up to {MAX_ROWS} rows").into(),
+        ));
+    }
+    let dp_params = match dp_params {
+        DpMechanism::NoDp => DpMechanism::NoDp,
+        // Each iteration releases a noised gradient, so they share the privacy budget.
+        DpMechanism::DiscreteLaplace { .. } | DpMechanism::DiscreteGaussian { .. } => {
+            dp_params.split_budget(u32::try_from(iterations).unwrap())
+        }
+        DpMechanism::Binomial { .. } => {
+            return Err(Error::Unsupported(
+                "binomial noise is biased, so it cannot be added to the gradients".to_string(),
+            ))
+        }
+    };
+
+    let mut weights = BitDecomposed::new(repeat_n(Replicated::ZERO, WEIGHT_BITS));
+    if input_rows.is_empty() {
+        return Ok(Vec::transposed_from(&weights)?);
+    }
+
+    let input_rows = one_row_per_user(ctx.clone(), input_rows, &dp_padding_params).await?;
+    if input_rows.len() > MAX_ROWS {
+        return Err(Error::InvalidQueryParameter(
+            format!("logistic regression supports up to {MAX_ROWS} rows after padding").into(),
+        ));
+    }
+
+    let chunks = input_rows
+        .chunks(CHUNK)
+        .map(VectorizedRows::new)
+        .collect::<Result<Vec<_>, _>>()?;
+    let ctx = ctx.narrow(&IpaPrfStep::LogisticRegression);
+    for iteration in 0..iterations {
+        weights = gradient_descent_iteration::<_, SS_BITS, B>(
+            ctx.narrow(&LogisticRegressionStep::from(iteration)),
+            &chunks,
+            input_rows.len(),
+            weights,
+            learning_rate_shift,
+            dp_params,
+        )
+        .await?;
+    }
+
+    Ok(Vec::transposed_from(&weights)?)
+}
+
+/// Pads and shuffles the input, then drops every row whose match key was already seen, so that
+/// each user contributes at most one row. Which of a user's rows is kept depends on the shuffle.
+async fn one_row_per_user<C, const B: usize>(
+    ctx: C,
+    input_rows: Vec<LogisticRegressionInputRow<B>>,
+    dp_padding_params: &PaddingParameters,
+) -> Result<Vec<LogisticRegressionInputRow<B>>, Error>
+where
+    C: UpgradableContext + Shuffle,
+    Replicated<Boolean, CONV_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, CONV_CHUNK>,
+    Replicated<Fp25519, PRF_CHUNK>:
+        PrfSharing<MacUpgraded<C, Fp25519>, PRF_CHUNK, Field = Fp25519> + FromPrss,
+    Replicated<RP25519, PRF_CHUNK>:
+        Reveal<MacUpgraded<C, Fp25519>, Output = <RP25519 as Vectorizable<PRF_CHUNK>>::Array>,
+{
+    let padded_input_rows = apply_dp_padding::<_, LogisticRegressionInputRow<B>, B>(
+        ctx.narrow(&IpaPrfStep::PaddingDp),
+        input_rows,
+        dp_padding_params,
+    )
+    .await?;
+    let shuffled =
+        shuffle_logistic_regression_inputs(ctx.narrow(&IpaPrfStep::Shuffle), padded_input_rows)
+            .await?;
+    let prf_of_match_keys = compute_prf_of_match_keys(ctx, &shuffled, |row| &row.match_key).await?;
+
+    let mut rows = zip(prf_of_match_keys, shuffled).collect::<Vec<_>>();
+    // The sort is stable, so the first row of each user in shuffled order is kept.
+    rows.sort_by_key(|(prf_of_match_key, _)| *prf_of_match_key);
+    rows.dedup_by_key(|(prf_of_match_key, _)| *prf_of_match_key);
+
+    Ok(rows.into_iter().map(|(_, row)| row).collect())
+}
+
+async fn gradient_descent_iteration<C, const SS_BITS: usize, const B: usize>(
+    ctx: C,
+    chunks: &[VectorizedRows],
+    num_rows: usize,
+    weights: BitDecomposed<Replicated<Boolean, B>>,
+    learning_rate_shift: usize,
+    dp_params: DpMechanism,
+) -> Result<BitDecomposed<Replicated<Boolean, B>>, Error>
+where
+    C: UpgradableContext,
+    Boolean: Vectorizable<B> + FieldSimd<B> + FieldSimd<CHUNK>,
+    BitDecomposed<Replicated<Boolean, B>>: FromPrss<usize>,
+    Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
+    Replicated<Boolean, CHUNK>: BooleanProtocols<DZKPUpgraded<C>, CHUNK>,
+    Vec<Replicated<BA16>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
+    Vec<Replicated<BA32>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<BA32>; B], Error = Infallible>,
+{
+    // Every lane of the forward pass needs the same weights.
+    let broadcast_weights = Vec::<Replicated<BA16>>::transposed_from(&weights)?
+        .iter()
+        .map(|weight| BitDecomposed::new(weight.to_bits().iter().map(Replicated::expand)))
+        .collect::<Vec<_>>();
+
+    let validator = ctx.clone().dzkp_validator(
+        MaliciousProtocolSteps {
+            protocol: &IterationStep::Train,
+            validate: &IterationStep::TrainValidate,
+        },
+        std::cmp::max(chunks.len() * B, num_rows),
+    );
+    let train_ctx = validator.context();
+    let contexts = TrainContexts::new(&train_ctx, chunks.len(), B)?;
+    let gradient_contributions = seq_join(
+        train_ctx.active_work(),
+        stream::iter(chunks.iter().enumerate()).map(|(i, chunk)| {
+            compute_gradient_contributions::<_, B>(
+                contexts.clone(),
+                RecordId::from(i),
+                chunk,
+                &broadcast_weights,
+            )
+        }),
+    )
+    .collect::<Vec<_>>()
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()?;
+
+    let mut gradient_sum = aggregate_values::<_, BA32, B>(
+        train_ctx.narrow(&TrainStep::AggregateGradients),
+        Box::pin(stream::iter(gradient_contributions.into_iter().flatten()).map(Ok)),
+        num_rows,
+        None,
+    )
+    .await?;
+    validator.validate().await?;
+    gradient_sum.resize(GRADIENT_SUM_BITS, Replicated::ZERO);
+
+    let noisy_gradient_sum = dp_for_histogram_with_steps::<_, _, B, BA32, SS_BITS>(
+        ctx.clone(),
+        MaliciousProtocolSteps {
+            protocol: &IterationStep::DifferentialPrivacy,
+            validate: &IterationStep::DifferentialPrivacyValidate,
+        },
+        gradient_sum,
+        dp_params,
+    )
+    .await?;
+    let noisy_gradient_sum =
+        BitDecomposed::transposed_from(&<[_; B]>::try_from(noisy_gradient_sum).map_err(|v| {
+            LengthError {
+                expected: B,
+                actual: v.len(),
+            }
+        })?)
+        .unwrap_infallible();
+
+    let validator = ctx.dzkp_validator(
+        MaliciousProtocolSteps {
+            protocol: &IterationStep::UpdateWeights,
+            validate: &IterationStep::UpdateWeightsValidate,
+        },
+        1,
+    );
+    let weights = update_weights(
+        validator.context(),
+        weights,
+        noisy_gradient_sum,
+        num_rows,
+        learning_rate_shift,
+    )
+    .await?;
+    validator.validate().await?;
+
+    Ok(weights)
+}
+
+/// Evaluates the forward and backward passes over a chunk of rows. Returns the offset-encoded
+/// gradient contribution of every row in the chunk, vectorized by feature.
+async fn compute_gradient_contributions<C, const B: usize>(
+    contexts: TrainContexts<C>,
+    record_id: RecordId,
+    chunk: &VectorizedRows,
+    broadcast_weights: &[BitDecomposed<Replicated<Boolean, CHUNK>>],
+) -> Result<Vec<BitDecomposed<Replicated<Boolean, B>>>, Error>
+where
+    C: Context,
+    Boolean: FieldSimd<B> + FieldSimd<CHUNK>,
+    Replicated<Boolean, CHUNK>: BooleanProtocols<C, CHUNK>,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<BA32>; B], Error = Infallible>,
+{
+    let chunk_index = usize::from(record_id);
+
+    // Products of the features and the weights have 16 fractional bits.
+    let feature_products = contexts
+        .multiply_features_by_weights
+        .parallel_join(zip(&chunk.features, broadcast_weights).enumerate().map(
+            |(j, (feature, weight))| {
+                integer_mul::<_, CHUNK>(
+                    contexts.multiply_features_by_weights.clone(),
+                    RecordId::from(chunk_index * B + j),
+                    feature,
+                    weight,
+                )
+            },
+        ))
+        .await?;
+    let dot_product =
+        sum_feature_products(&contexts.sum_feature_products, record_id, feature_products).await?;
+
+    // Drop 12 of the 16 fractional bits to get the input of the sigmoid.
+    let sigmoid_input = saturate(
+        contexts.saturate,
+        record_id,
+        BitDecomposed::new(dot_product.into_iter().skip(2 * FRACTIONAL_BITS - 4)),
+        8,
+    )
+    .await?;
+    let prediction = sigmoid(contexts.sigmoid, record_id, &sigmoid_input).await?;
+
+    // `prediction - label` as a signed 9-bit value. Because the prediction is less than one,
+    // the label is exactly the sign bit.
+    let mut error = prediction;
+    error.push(chunk.label.clone());
+
+    let gradients = contexts
+        .multiply_features_by_error
+        .parallel_join(chunk.features.iter().enumerate().map(|(j, feature)| {
+            integer_mul::<_, CHUNK>(
+                contexts.multiply_features_by_error.clone(),
+                RecordId::from(chunk_index * B + j),
+                feature,
+                &error,
+            )
+        }))
+        .await?;
+
+    // Round the gradients down to 8 fractional bits, and flip the sign bit to add an offset of
+    // 2^8, making them unsigned so that they can be aggregated.
+    let gradients = gradients
+        .into_iter()
+        .map(|gradient| {
+            let mut gradient = BitDecomposed::new(gradient.into_iter().skip(FRACTIONAL_BITS));
+            debug_assert_eq!(gradient.len(), GRADIENT_BITS);
+            let sign = GRADIENT_BITS - 1;
+            gradient[sign] = gradient[sign].clone().not();
+            gradient.resize(BA32::BITS as usize, Replicated::ZERO);
+            Vec::<Replicated<BA32>>::transposed_from(&gradient)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((0..chunk.len)
+        .map(|row| {
+            let row_gradients: [Replicated<BA32>; B] =
+                std::array::from_fn(|j| gradients[j][row].clone());
+            let mut row_gradients =
+                BitDecomposed::transposed_from(&row_gradients).unwrap_infallible();
+            row_gradients.truncate(GRADIENT_BITS);
+            row_gradients
+        })
+        .collect())
+}
+
+/// Number of values at each level of a tree that adds up `len` values pairwise, from the
+/// leaves up to, but excluding, the root.
+fn sum_tree_widths(len: usize) -> impl Iterator<Item = usize> {
+    std::iter::successors(Some(len), |&width| Some(width.div_ceil(2)))
+        .take_while(|&width| width > 1)
+}
+
+/// Adds up the products of the features and the weights, using one of `contexts` for each level
+/// of the tree. The sum is wide enough for up to 16 products, so it never overflows.
+async fn sum_feature_products<C>(
+    contexts: &[C],
+    record_id: RecordId,
+    mut products: Vec<BitDecomposed<Replicated<Boolean, CHUNK>>>,
+) -> Result<BitDecomposed<Replicated<Boolean, CHUNK>>, Error>
+where
+    C: Context,
+    Replicated<Boolean, CHUNK>: BooleanProtocols<C, CHUNK>,
+{
+    let sum_bits = 2 * WEIGHT_BITS - 4;
+    for product in &mut products {
+        let sign = product[product.len() - 1].clone();
+        product.resize(sum_bits, sign);
+    }
+
+    for ctx in contexts {
+        let first_record = usize::from(record_id) * (products.len() / 2);
+        let odd = if products.len() % 2 == 1 {
+            products.pop()
+        } else {
+            None
+        };
+        let sums = ctx
+            .parallel_join(products.chunks_exact(2).enumerate().map(|(i, pair)| {
+                let ctx = ctx.clone();
+                let record_id = RecordId::from(first_record + i);
+                async move {
+                    let (sum, _) = integer_add::<_, ThirtyTwoBitStep, CHUNK>(
+                        ctx, record_id, &pair[0], &pair[1],
+                    )
+                    .await?;
+                    Ok::<_, Error>(sum)
+                }
+            }))
+            .await?;
+        products = sums.into_iter().chain(odd).collect();
+    }
+
+    Ok(products.pop().unwrap())
+}
+
+/// Saturates a signed value to a signed value of `bits` bits, keeping its `bits` least
+/// significant bits.
+async fn saturate<C, const N: usize>(
+    ctx: C,
+    record_id: RecordId,
+    x: BitDecomposed<Replicated<Boolean, N>>,
+    bits: usize,
+) -> Result<BitDecomposed<Replicated<Boolean, N>>, Error>
+where
+    C: Context,
+    Boolean: FieldSimd<N>,
+    Replicated<Boolean, N>: BooleanProtocols<C, N>,
+{
+    assert!(
+        bits > 1 && x.len() > bits && x.len() <= usize::try_from(ThirtyTwoBitStep::BITS).unwrap()
+    );
+    let sign = x[x.len() - 1].clone();
+
+    // The value overflows if any of the bits that are dropped, or the sign bit of the
+    // result, differs from the sign.
+    let overflow_ctx = ctx.narrow(&SaturateStep::Overflow);
+    let mut overflow = x[bits - 1].clone() + &sign;
+    for (i, bit) in x[bits..x.len() - 1].iter().enumerate() {
+        let differs = bit.clone() + &sign;
+        let both = overflow
+            .multiply(
+                &differs,
+                overflow_ctx.narrow(&ThirtyTwoBitStep::from(i)),
+                record_id,
+            )
+            .await?;
+        overflow = overflow + differs + both;
+    }
+
+    // On overflow, the other bits are all set to the opposite of the sign.
+    let select_ctx = ctx.narrow(&SaturateStep::Select);
+    let not_sign = sign.clone().not();
+    let flips = select_ctx
+        .parallel_join(x[..bits - 1].iter().enumerate().map(|(i, bit)| {
+            let flip = bit.clone() + &not_sign;
+            let ctx = select_ctx.narrow(&ThirtyTwoBitStep::from(i));
+            let overflow = &overflow;
+            async move { flip.multiply(overflow, ctx, record_id).await }
+        }))
+        .await?;
+
+    Ok(BitDecomposed::new(
+        zip(x.iter().take(bits - 1), flips)
+            .map(|(bit, flip)| bit.clone() + flip)
+            .chain([sign]),
+    ))
+}
+
+/// Computes `weights - noisy_gradient_sum * 2^-learning_rate_shift`, where
+/// `noisy_gradient_sum` is offset by `2^8` for every row. The result saturates at the bounds of
+/// the weights.
+async fn update_weights<C, const B: usize>(
+    ctx: C,
+    weights: BitDecomposed<Replicated<Boolean, B>>,
+    noisy_gradient_sum: BitDecomposed<Replicated<Boolean, B>>,
+    num_rows: usize,
+    learning_rate_shift: usize,
+) -> Result<BitDecomposed<Replicated<Boolean, B>>, Error>
+where
+    C: Context,
+    Boolean: FieldSimd<B>,
+    Replicated<Boolean, B>: BooleanProtocols<C, B>,
+{
+    // `-(x - offset) = !x + offset + 1` in two's complement.
+    let offset = u32::try_from(num_rows << FRACTIONAL_BITS).unwrap() + 1;
+    let offset = BitDecomposed::new((0..GRADIENT_SUM_BITS).map(|i| {
+        if (offset >> i) & 1 == 1 {
+            Replicated::<Boolean>::share_known_value(&ctx, Boolean::ONE).expand()
+        } else {
+            Replicated::ZERO
+        }
+    }));
+    let (negated_gradient, _) = integer_add::<_, ThirtyTwoBitStep, B>(
+        ctx.narrow(&UpdateWeightsStep::NegateGradients)
+            .set_total_records(TotalRecords::ONE),
+        RecordId::FIRST,
+        &BitDecomposed::new(noisy_gradient_sum.into_iter().map(Not::not)),
+        &offset,
+    )
+    .await?;
+
+    // Arithmetic shift right by the learning rate shift.
+    let sign = negated_gradient[GRADIENT_SUM_BITS - 1].clone();
+    let step = BitDecomposed::new(
+        negated_gradient
+            .into_iter()
+            .skip(learning_rate_shift)
+            .chain(repeat(sign))
+            .take(GRADIENT_SUM_BITS),
+    );
+    let step = saturate(
+        ctx.narrow(&UpdateWeightsStep::SaturateUpdate)
+            .set_total_records(TotalRecords::ONE),
+        RecordId::FIRST,
+        step,
+        WEIGHT_BITS,
+    )
+    .await?;
+
+    let sign_sum = weights[WEIGHT_BITS - 1].clone() + &step[WEIGHT_BITS - 1];
+    let (mut weights, carry) = integer_add::<_, SixteenBitStep, B>(
+        ctx.narrow(&UpdateWeightsStep::AddToWeights)
+            .set_total_records(TotalRecords::ONE),
+        RecordId::FIRST,
+        &weights,
+        &step,
+    )
+    .await?;
+    // Extend the sum with the sign bit it would have with one more bit, so that it can be
+    // saturated.
+    weights.push(sign_sum + carry);
+
+    saturate(
+        ctx.narrow(&UpdateWeightsStep::SaturateWeights)
+            .set_total_records(TotalRecords::ONE),
+        RecordId::FIRST,
+        weights,
+        WEIGHT_BITS,
+    )
+    .await
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use std::iter::repeat_with;
+
+    use rand::{thread_rng, Rng};
+
+    use crate::{
+        ff::{boolean_array::BA16, U128Conversions},
+        helpers::query::DpMechanism,
+        protocol::ipa_prf::{
+            logistic_regression::logistic_regression, oprf_padding::PaddingParameters,
+        },
+        test_executor::run,
+        test_fixture::{
+            logistic_regression::{logistic_regression_in_the_clear, TestLogisticRegressionRecord},
+            Reconstruct, Runner, TestWorld,
+        },
+    };
+
+    const FEATURES: usize = 16;
+    const SS_BITS: usize = 13;
+
+    fn random_records<R: Rng>(rng: &mut R, count: usize) -> Vec<TestLogisticRegressionRecord> {
+        (0..count)
+            .map(|user_id| {
+                let features = repeat_with(|| rng.gen::<u8>())
+                    .take(FEATURES)
+                    .collect::<Vec<_>>();
+                // Make the label depend on the first two features so there is something to learn.
+                let label =
+                    u32::from(features[0]) + rng.gen_range(0..64) > u32::from(features[1]) + 32;
+                TestLogisticRegressionRecord {
+                    user_id: u64::try_from(user_id).unwrap(),
+                    features,
+                    label,
+                }
+            })
+            .collect()
+    }
+
+    fn to_i16(weights: &[BA16]) -> Vec<i16> {
+        weights
+            .iter()
+            .map(|w| i16::from_le_bytes(u16::try_from(w.as_u128()).unwrap().to_le_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn semi_honest() {
+        const ITERATIONS: usize = 2;
+        const LEARNING_RATE_SHIFT: usize = 6;
+
+        run(|| async move {
+            let world = TestWorld::default();
+            // More than one chunk, and a partial chunk.
+            let records = random_records(&mut thread_rng(), 300);
+            let expected = logistic_regression_in_the_clear(
+                &records,
+                FEATURES,
+                ITERATIONS,
+                LEARNING_RATE_SHIFT,
+            );
+
+            let result = world
+                .semi_honest(records.into_iter(), |ctx, input_rows| async move {
+                    logistic_regression::<_, SS_BITS, FEATURES>(
+                        ctx,
+                        input_rows,
+                        ITERATIONS,
+                        LEARNING_RATE_SHIFT,
+                        DpMechanism::NoDp,
+                        PaddingParameters::no_padding(),
+                    )
+                    .await
+                    .unwrap()
+                })
+                .await
+                .reconstruct();
+
+            let result = to_i16(&result);
+            assert_eq!(result, expected);
+            // The label depends on the first two features in opposite directions.
+            assert!(result[0] > result[1], "{result:?}");
+        });
+    }
+
+    #[test]
+    fn one_row_per_user() {
+        const ITERATIONS: usize = 2;
+        const LEARNING_RATE_SHIFT: usize = 4;
+
+        run(|| async move {
+            let world = TestWorld::default();
+            let mut rng = thread_rng();
+            // Some users have several copies of the same row, which must only be counted once.
+            let mut records = random_records(&mut rng, 40);
+            let duplicates = records
+                .iter()
+                .filter(|_| rng.gen_bool(0.5))
+                .cloned()
+                .collect::<Vec<_>>();
+            records.extend(duplicates.iter().cloned());
+            records.extend(duplicates);
+            let expected = logistic_regression_in_the_clear(
+                &records,
+                FEATURES,
+                ITERATIONS,
+                LEARNING_RATE_SHIFT,
+            );
+
+            let result = world
+                .semi_honest(records.into_iter(), |ctx, input_rows| async move {
+                    // Padding rows have no features, so they do not change the weights.
+                    logistic_regression::<_, SS_BITS, FEATURES>(
+                        ctx,
+                        input_rows,
+                        ITERATIONS,
+                        LEARNING_RATE_SHIFT,
+                        DpMechanism::NoDp,
+                        PaddingParameters::relaxed(),
+                    )
+                    .await
+                    .unwrap()
+                })
+                .await
+                .reconstruct();
+
+            assert_eq!(to_i16(&result), expected);
+        });
+    }
+
+    #[test]
+    fn weights_saturate() {
+        run(|| async move {
+            let world = TestWorld::default();
+            // Each row adds 128 to every weight in the first iteration, which overflows the
+            // weights, and a little more in the second one.
+            let records = (0..300)
+                .map(|user_id| TestLogisticRegressionRecord {
+                    user_id,
+                    features: vec![u8::MAX; FEATURES],
+                    label: true,
+                })
+                .collect::<Vec<_>>();
+            let expected = logistic_regression_in_the_clear(&records, FEATURES, 2, 0);
+            assert_eq!(expected, vec![i16::MAX; FEATURES]);
+
+            let result = world
+                .semi_honest(records.into_iter(), |ctx, input_rows| async move {
+                    logistic_regression::<_, SS_BITS, FEATURES>(
+                        ctx,
+                        input_rows,
+                        2,
+                        0,
+                        DpMechanism::NoDp,
+                        PaddingParameters::no_padding(),
+                    )
+                    .await
+                    .unwrap()
+                })
+                .await
+                .reconstruct();
+
+            assert_eq!(to_i16(&result), expected);
+        });
+    }
+
+    #[test]
+    fn semi_honest_with_dp() {
+        run(|| async move {
+            let world = TestWorld::default();
+            let records = random_records(&mut thread_rng(), 20);
+
+            let result = world
+                .semi_honest(records.into_iter(), |ctx, input_rows| async move {
+                    logistic_regression::<_, SS_BITS, FEATURES>(
+                        ctx,
+                        input_rows,
+                        2,
+                        4,
+                        DpMechanism::DiscreteLaplace {
+                            epsilon: 10.0,
+                            delta: DpMechanism::DEFAULT_DELTA,
+                        },
+                        PaddingParameters::relaxed(),
+                    )
+                    .await
+                    .unwrap()
+                })
+                .await
+                .reconstruct();
+
+            assert_eq!(result.len(), FEATURES);
+        });
+    }
+
+    #[test]
+    fn rejects_binomial_noise() {
+        run(|| async move {
+            let world = TestWorld::default();
+            let records = random_records(&mut thread_rng(), 4);
+
+            world
+                .semi_honest(records.into_iter(), |ctx, input_rows| async move {
+                    let result = logistic_regression::<_, SS_BITS, FEATURES>(
+                        ctx,
+                        input_rows,
+                        1,
+                        0,
+                        DpMechanism::Binomial {
+                            epsilon: 1.0,
+                            delta: DpMechanism::DEFAULT_DELTA,
+                        },
+                        PaddingParameters::no_padding(),
+                    )
+                    .await;
+                    assert!(matches!(result, Err(crate::error::Error::Unsupported(_))));
+                })
+                .await;
+        });
+    }
+}
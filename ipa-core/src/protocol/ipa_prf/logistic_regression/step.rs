@@ -0,0 +1,58 @@
+use ipa_step_derive::CompactStep;
+
+// The step count here is duplicated as the MAX_ITERATIONS constant in the code.
+#[derive(CompactStep)]
+#[step(count = 32, child = LogisticRegressionIterationStep, name = "iteration")]
+pub(crate) struct LogisticRegressionStep(usize);
+
+#[derive(CompactStep)]
+pub(crate) enum LogisticRegressionIterationStep {
+    #[step(child = TrainStep)]
+    Train,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    TrainValidate,
+    #[step(child = crate::protocol::dp::step::DPStep, name = "dp")]
+    DifferentialPrivacy,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    DifferentialPrivacyValidate,
+    #[step(child = UpdateWeightsStep)]
+    UpdateWeights,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    UpdateWeightsValidate,
+}
+
+#[derive(CompactStep)]
+pub(crate) enum TrainStep {
+    #[step(child = crate::protocol::ipa_prf::boolean_ops::step::MultiplicationStep)]
+    MultiplyFeaturesByWeights,
+    #[step(count = 4, child = crate::protocol::boolean::step::ThirtyTwoBitStep, name = "depth")]
+    SumFeatureProducts(usize),
+    #[step(child = SaturateStep)]
+    Saturate,
+    #[step(child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
+    Sigmoid,
+    #[step(child = crate::protocol::ipa_prf::boolean_ops::step::MultiplicationStep)]
+    MultiplyFeaturesByError,
+    #[step(child = crate::protocol::ipa_prf::aggregation::step::AggregateChunkStep)]
+    AggregateGradients,
+}
+
+#[derive(CompactStep)]
+pub(crate) enum SaturateStep {
+    #[step(child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
+    Overflow,
+    #[step(child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
+    Select,
+}
+
+#[derive(CompactStep)]
+pub(crate) enum UpdateWeightsStep {
+    #[step(child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
+    NegateGradients,
+    #[step(child = SaturateStep)]
+    SaturateUpdate,
+    #[step(child = crate::protocol::boolean::step::SixteenBitStep)]
+    AddToWeights,
+    #[step(child = SaturateStep)]
+    SaturateWeights,
+}
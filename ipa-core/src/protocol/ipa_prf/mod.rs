@@ -4,7 +4,13 @@ use futures::{stream, StreamExt, TryStreamExt};
 use generic_array::{ArrayLength, GenericArray};
 use typenum::{Const, Unsigned, U18};
 
-use self::{quicksort::quicksort_ranges_by_key_insecure, shuffle::shuffle_inputs};
+use self::{
+    aggregation::{
+        breakdown_reveal::sharded_breakdown_reveal_aggregation, shards::aggregate_shards,
+    },
+    quicksort::quicksort_ranges_by_key_insecure,
+    shuffle::{sharded_shuffle_inputs, shuffle_inputs, ShardedShuffle},
+};
 use crate::{
     error::{Error, LengthError, UnwrapInfallible},
     ff::{
@@ -21,15 +27,16 @@ use crate::{
     protocol::{
         basics::{BooleanArrayMul, BooleanProtocols, Reveal},
         context::{
-            dzkp_validator::DZKPValidator, DZKPUpgraded, MacUpgraded, MaliciousProtocolSteps,
-            UpgradableContext,
+            dzkp_validator::DZKPValidator, reshard_iter, DZKPUpgraded, MacUpgraded,
+            MaliciousProtocolSteps, ShardedContext, UpgradableContext,
         },
         ipa_prf::{
             boolean_ops::convert_to_fp25519,
-            oprf_padding::apply_dp_padding,
+            oprf_padding::{apply_dp_padding, joint::apply_joint_oprf_padding, PaddingGeneration},
             prf_eval::{eval_dy_prf, gen_prf_key},
             prf_sharding::{
-                attribute_cap_aggregate, histograms_ranges_sortkeys, PrfShardedIpaInputRow,
+                attribute_cap, attribute_cap_aggregate, histograms_ranges_sortkeys,
+                step::AttributionStep, PrfShardedIpaInputRow,
             },
             step::IpaPrfStep,
         },
@@ -41,10 +48,12 @@ use crate::{
         SharedValue, TransposeFrom, Vectorizable,
     },
     seq_join::seq_join,
+    sharding::ShardIndex,
 };
 
 pub(crate) mod aggregation;
 pub mod boolean_ops;
+pub mod logistic_regression;
 pub mod oprf_padding;
 pub mod prf_eval;
 pub mod prf_sharding;
@@ -53,6 +62,7 @@ mod malicious_security;
 mod quicksort;
 pub(crate) mod shuffle;
 pub(crate) mod step;
+pub mod trigger_value_cap;
 pub mod validation_protocol;
 
 pub use malicious_security::prover::{LargeProofGenerator, SmallProofGenerator};
@@ -98,8 +108,12 @@ use crate::{
     helpers::query::DpMechanism,
     protocol::{
         context::Validator,
-        dp::dp_for_histogram,
-        ipa_prf::{oprf_padding::PaddingParameters, prf_eval::PrfSharing},
+        dp::dp_for_histogram_with_sensitivity,
+        ipa_prf::{
+            oprf_padding::PaddingParameters,
+            prf_eval::PrfSharing,
+            trigger_value_cap::{cap_trigger_values, per_user_sensitivity},
+        },
     },
     secret_sharing::replicated::semi_honest::AdditiveShare,
 };
@@ -206,19 +220,21 @@ where
 ///
 /// The output of this function is a vector of secret-shared totals, one per breakdown key
 /// This protocol performs the following steps
-/// 1. Converts secret-sharings of boolean arrays to secret-sharings of elliptic curve points
-/// 2. Generates a random number of "dummy records" (needed to mask the information that will
+/// 1. Lowers every trigger value above `trigger_value_cap` to the cap, if one is given
+/// 2. Converts secret-sharings of boolean arrays to secret-sharings of elliptic curve points
+/// 3. Generates a random number of "dummy records" (needed to mask the information that will
 ///    be revealed in a later step, and thereby provide a differential privacy guarantee on that
-///    information leakage) (TBD)
-/// 3. Shuffles the input
-/// 4. Computes an OPRF of these elliptic curve points and reveals this "pseudonym"
-/// 5. Groups together rows with the same OPRF, and then obliviously sorts each group by the
+///    information leakage), either by pairs of helpers or jointly in MPC
+/// 4. Shuffles the input
+/// 5. Computes an OPRF of these elliptic curve points and reveals this "pseudonym"
+/// 6. Groups together rows with the same OPRF, and then obliviously sorts each group by the
 ///    secret-shared timestamp
-/// 6. Attributes trigger events to source events
-/// 7. Caps each user's total contribution to the final result
-/// 8. Aggregates the contributions of all users
-/// 9. Adds random noise to the total for each breakdown key (to provide a differential
-///    privacy guarantee)
+/// 7. Attributes trigger events to source events. If `unattributed_breakdown_key` is set, the
+///    trigger events that are not attributed are credited to that breakdown
+/// 8. Caps each user's total contribution to the final result
+/// 9. Aggregates the contributions of all users
+/// 10. Adds random noise to the total for each breakdown key (to provide a differential
+///     privacy guarantee), calibrated to the largest total a single user can contribute
 /// # Errors
 /// Propagates errors from config issues or while running the protocol
 /// # Panics
@@ -227,6 +243,8 @@ pub async fn oprf_ipa<'ctx, C, BK, TV, HV, TS, const SS_BITS: usize, const B: us
     ctx: C,
     input_rows: Vec<OPRFIPAInputRow<BK, TV, TS>>,
     attribution_window_seconds: Option<NonZeroU32>,
+    trigger_value_cap: Option<u32>,
+    unattributed_breakdown_key: Option<BK>,
     dp_params: DpMechanism,
     dp_padding_params: PaddingParameters,
 ) -> Result<Vec<Replicated<HV>>, Error>
@@ -250,6 +268,7 @@ where
         + Reveal<DZKPUpgraded<C>, Output = <BK as Vectorizable<1>>::Array>,
     Replicated<TS>: BooleanArrayMul<DZKPUpgraded<C>>,
     Replicated<TV>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<MatchKey>: BooleanArrayMul<DZKPUpgraded<C>>,
     BitDecomposed<Replicated<Boolean, AGG_CHUNK>>:
         for<'a> TransposeFrom<&'a Vec<Replicated<BK>>, Error = LengthError>,
     BitDecomposed<Replicated<Boolean, AGG_CHUNK>>:
@@ -269,10 +288,10 @@ where
         return Ok(vec![Replicated::ZERO; B]);
     }
 
-    // Apply DP padding for OPRF
-    let padded_input_rows = apply_dp_padding::<_, OPRFIPAInputRow<BK, TV, TS>, B>(
-        ctx.narrow(&Step::PaddingDp),
+    let padded_input_rows = cap_and_pad_inputs::<_, _, _, _, B>(
+        ctx.clone(),
         input_rows,
+        trigger_value_cap,
         &dp_padding_params,
     )
     .await?;
@@ -283,8 +302,8 @@ where
     prfd_inputs.sort_by(|a, b| a.prf_of_match_key.cmp(&b.prf_of_match_key));
 
     let (row_count_histogram, ranges) = histograms_ranges_sortkeys(&mut prfd_inputs);
-    if row_count_histogram.len() == 1 {
-        // No user has more than one record.
+    if row_count_histogram.len() == 1 && unattributed_breakdown_key.is_none() {
+        // No user has more than one record, so nothing can be attributed.
         return Ok(vec![Replicated::ZERO; B]);
     }
     quicksort_ranges_by_key_insecure(
@@ -300,16 +319,274 @@ where
         ctx.narrow(&Step::Attribution),
         prfd_inputs,
         attribution_window_seconds,
+        unattributed_breakdown_key,
         &row_count_histogram,
         &dp_padding_params,
     )
     .await?;
 
-    let noisy_output_histogram =
-        dp_for_histogram::<_, B, HV, SS_BITS>(ctx, output_histogram, dp_params).await?;
+    let per_user_credit_cap = 2_u32.pow(u32::try_from(SS_BITS).unwrap());
+    let noisy_output_histogram = dp_for_histogram_with_sensitivity::<_, _, B, HV>(
+        ctx,
+        MaliciousProtocolSteps {
+            protocol: &Step::DifferentialPrivacy,
+            validate: &Step::DifferentialPrivacyValidate,
+        },
+        output_histogram,
+        dp_params,
+        per_user_sensitivity(per_user_credit_cap, trigger_value_cap),
+    )
+    .await?;
     Ok(noisy_output_histogram)
 }
 
+/// Sharded IPA OPRF Protocol
+///
+/// Runs the same protocol as [`oprf_ipa`] on a helper that is split into several shards, each of
+/// which holds a part of the input. The differences are:
+/// * The input is shuffled across all shards, not just within each shard.
+/// * The PRF key is generated by the leader shard and shared with the other shards on the same
+///   helper, so that a match key gets the same pseudonym on every shard.
+/// * After the PRF is evaluated, rows are resharded by their pseudonym, so all rows that belong to
+///   one user end up on one shard. Each shard then sorts, attributes and caps its rows locally.
+/// * Attributed values are shuffled across all shards before their breakdown keys are revealed.
+///   Each shard aggregates the values it got after the shuffle.
+/// * The histograms computed by the shards are summed on the leader shard, which then adds DP
+///   noise to the total. Noise is added once per query, not once per shard.
+/// * Only the leader shard generates OPRF and aggregation padding. The sharded shuffles that
+///   follow spread the fake rows across all shards, so the query gets as much padding as an
+///   unsharded one rather than one set of padding per shard.
+///
+/// The output histogram is only returned by the leader shard. Other shards return an empty
+/// vector.
+/// # Errors
+/// Propagates errors from config issues or while running the protocol
+/// # Panics
+/// Propagates errors from config issues or while running the protocol
+#[allow(clippy::too_many_lines)]
+pub async fn sharded_oprf_ipa<'ctx, C, BK, TV, HV, TS, const SS_BITS: usize, const B: usize>(
+    ctx: C,
+    input_rows: Vec<OPRFIPAInputRow<BK, TV, TS>>,
+    attribution_window_seconds: Option<NonZeroU32>,
+    trigger_value_cap: Option<u32>,
+    unattributed_breakdown_key: Option<BK>,
+    dp_params: DpMechanism,
+    dp_padding_params: PaddingParameters,
+) -> Result<Vec<Replicated<HV>>, Error>
+where
+    C: UpgradableContext + ShardedContext + ShardedShuffle + 'ctx,
+    DZKPUpgraded<C>: ShardedContext,
+    BK: BreakdownKey<B>,
+    TV: BooleanArray + U128Conversions,
+    HV: BooleanArray + U128Conversions,
+    TS: BooleanArray + U128Conversions,
+    Boolean: FieldSimd<B>,
+    Replicated<Boolean>: BooleanProtocols<DZKPUpgraded<C>>,
+    Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
+    Replicated<Boolean, AGG_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, AGG_CHUNK>,
+    Replicated<Boolean, CONV_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, CONV_CHUNK>,
+    Replicated<Boolean, SORT_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, SORT_CHUNK>,
+    Replicated<Fp25519, PRF_CHUNK>:
+        PrfSharing<MacUpgraded<C, Fp25519>, PRF_CHUNK, Field = Fp25519> + FromPrss,
+    Replicated<RP25519, PRF_CHUNK>:
+        Reveal<MacUpgraded<C, Fp25519>, Output = <RP25519 as Vectorizable<PRF_CHUNK>>::Array>,
+    Replicated<BK>: BooleanArrayMul<DZKPUpgraded<C>>
+        + Reveal<DZKPUpgraded<C>, Output = <BK as Vectorizable<1>>::Array>,
+    Replicated<TS>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<TV>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<MatchKey>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<HV>: Serializable,
+    PrfShardedIpaInputRow<BK, TV, TS>: Serializable,
+    BitDecomposed<Replicated<Boolean, AGG_CHUNK>>:
+        for<'a> TransposeFrom<&'a Vec<Replicated<BK>>, Error = LengthError>,
+    BitDecomposed<Replicated<Boolean, AGG_CHUNK>>:
+        for<'a> TransposeFrom<&'a Vec<Replicated<TV>>, Error = LengthError>,
+    Vec<BitDecomposed<Replicated<Boolean, B>>>: for<'a> TransposeFrom<
+        &'a [BitDecomposed<Replicated<Boolean, AGG_CHUNK>>],
+        Error = Infallible,
+    >,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<TV>; B], Error = Infallible>,
+    Vec<Replicated<HV>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
+    BitDecomposed<AdditiveShare<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [AdditiveShare<HV>; B], Error = Infallible>,
+{
+    // A helper reveals the pseudonyms and breakdown keys of all its shards, so it needs one set
+    // of padding in total, which the leader shard generates.
+    let dp_padding_params = if ctx.is_leader() {
+        dp_padding_params
+    } else {
+        PaddingParameters::no_padding()
+    };
+
+    // Unlike `oprf_ipa`, a shard with no input can't return early, because it still needs to take
+    // part in the steps that involve all shards.
+    let padded_input_rows = cap_and_pad_inputs::<_, _, _, _, B>(
+        ctx.clone(),
+        input_rows,
+        trigger_value_cap,
+        &dp_padding_params,
+    )
+    .await?;
+
+    let shuffled =
+        sharded_shuffle_inputs(ctx.narrow(&Step::ShardedShuffle), padded_input_rows).await?;
+    let prf_key = sharded_prf_key(ctx.narrow(&Step::PrfKeyGen)).await?;
+    let prf_of_match_keys =
+        evaluate_prf_of_match_keys(ctx.clone(), &shuffled, |row| &row.match_key, &prf_key).await?;
+    let prfd_inputs = with_prf_of_match_keys(&shuffled, prf_of_match_keys);
+
+    // Pseudonyms are uniformly distributed, so this spreads users evenly across shards.
+    let mut prfd_inputs = reshard_iter(
+        ctx.narrow(&Step::ReshardByPrf),
+        prfd_inputs,
+        |ctx, _, row| {
+            let shard_count = u64::from(ctx.shard_count());
+            ShardIndex::from(u32::try_from(row.prf_of_match_key % shard_count).unwrap())
+        },
+    )
+    .await?;
+
+    prfd_inputs.sort_by_key(|row| row.prf_of_match_key);
+
+    let (row_count_histogram, ranges) = histograms_ranges_sortkeys(&mut prfd_inputs);
+    let attribution_ctx = ctx.narrow(&Step::Attribution);
+    let user_contributions = if prfd_inputs.is_empty()
+        || (row_count_histogram.len() == 1 && unattributed_breakdown_key.is_none())
+    {
+        // Nothing can be attributed on this shard, but it still has to take part in the
+        // aggregation, which shuffles attributions across all shards.
+        Vec::new()
+    } else {
+        quicksort_ranges_by_key_insecure(
+            ctx.narrow(&Step::SortByTimestamp),
+            &mut prfd_inputs,
+            false,
+            |x| &x.sort_key,
+            ranges,
+        )
+        .await?;
+
+        attribute_cap::<_, _, _, _, SS_BITS, B>(
+            attribution_ctx.clone(),
+            prfd_inputs,
+            attribution_window_seconds,
+            unattributed_breakdown_key,
+            &row_count_histogram,
+        )
+        .await?
+    };
+    let output_histogram = sharded_breakdown_reveal_aggregation::<_, BK, TV, HV, B>(
+        attribution_ctx.narrow(&AttributionStep::Aggregate),
+        user_contributions,
+        &dp_padding_params,
+    )
+    .await?;
+
+    // The total is only known to the leader shard, so only the leader applies DP noise.
+    let validator = ctx.clone().dzkp_validator(
+        MaliciousProtocolSteps {
+            protocol: &Step::AggregateShardHistograms,
+            validate: &Step::AggregateShardHistogramsValidate,
+        },
+        1,
+    );
+    let total = aggregate_shards::<_, HV, B>(validator.context(), output_histogram).await?;
+    validator.validate().await?;
+    let Some(output_histogram) = total else {
+        return Ok(Vec::new());
+    };
+
+    let per_user_credit_cap = 2_u32.pow(u32::try_from(SS_BITS).unwrap());
+    dp_for_histogram_with_sensitivity::<_, _, B, HV>(
+        ctx,
+        MaliciousProtocolSteps {
+            protocol: &Step::DifferentialPrivacy,
+            validate: &Step::DifferentialPrivacyValidate,
+        },
+        output_histogram,
+        dp_params,
+        per_user_sensitivity(per_user_credit_cap, trigger_value_cap),
+    )
+    .await
+}
+
+/// Lowers trigger values above `trigger_value_cap` to the cap, if one is given, and then adds
+/// the OPRF padding rows requested by `dp_padding_params`.
+async fn cap_and_pad_inputs<C, BK, TV, TS, const B: usize>(
+    ctx: C,
+    input_rows: Vec<OPRFIPAInputRow<BK, TV, TS>>,
+    trigger_value_cap: Option<u32>,
+    dp_padding_params: &PaddingParameters,
+) -> Result<Vec<OPRFIPAInputRow<BK, TV, TS>>, Error>
+where
+    C: UpgradableContext,
+    BK: BreakdownKey<B>,
+    TV: BooleanArray + U128Conversions,
+    TS: BooleanArray,
+    Replicated<Boolean>: BooleanProtocols<DZKPUpgraded<C>>,
+    Replicated<TV>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<MatchKey>: BooleanArrayMul<DZKPUpgraded<C>>,
+{
+    let input_rows = match trigger_value_cap {
+        Some(cap) => cap_trigger_values(ctx.clone(), input_rows, cap).await?,
+        None => input_rows,
+    };
+
+    // Apply DP padding for OPRF
+    match dp_padding_params.padding_generation {
+        PaddingGeneration::Pairwise => {
+            apply_dp_padding::<_, OPRFIPAInputRow<BK, TV, TS>, B>(
+                ctx.narrow(&Step::PaddingDp),
+                input_rows,
+                dp_padding_params,
+            )
+            .await
+        }
+        PaddingGeneration::Joint => {
+            apply_joint_oprf_padding(
+                ctx.narrow(&Step::PaddingDp),
+                input_rows,
+                &dp_padding_params.oprf_padding,
+            )
+            .await
+        }
+    }
+}
+
+/// Generates the PRF key on the leader shard and sends it to every other shard on this helper.
+/// All shards must use the same key, otherwise rows with the same match key would get different
+/// pseudonyms on different shards.
+async fn sharded_prf_key<C>(ctx: C) -> Result<Replicated<Fp25519>, Error>
+where
+    C: UpgradableContext + ShardedContext,
+{
+    let ctx = ctx.set_total_records(TotalRecords::ONE);
+    if ctx.is_leader() {
+        let prf_key = gen_prf_key(&ctx);
+        ctx.parallel_join(ctx.peer_shards().map(|shard| {
+            let send_channel = ctx.shard_send_channel(shard);
+            let prf_key = prf_key.clone();
+            async move { send_channel.send(RecordId::FIRST, prf_key).await }
+        }))
+        .await?;
+
+        Ok(prf_key)
+    } else {
+        let prf_keys = ctx
+            .shard_recv_channel::<Replicated<Fp25519>>(ctx.leader())
+            .try_collect::<Vec<_>>()
+            .await?;
+        let [prf_key] = <[_; 1]>::try_from(prf_keys).map_err(|keys| LengthError {
+            expected: 1,
+            actual: keys.len(),
+        })?;
+
+        Ok(prf_key)
+    }
+}
+
 // We expect 2*256 = 512 gates in total for two additions per conversion. The vectorization factor
 // is CONV_CHUNK. Let `len` equal the number of converted shares. The total amount of
 // multiplications is CONV_CHUNK*512*len. We want CONV_CHUNK*512*len ≈ 50M, or len ≈ 381, for a
@@ -334,6 +611,88 @@ where
     Replicated<RP25519, PRF_CHUNK>:
         Reveal<MacUpgraded<C, Fp25519>, Output = <RP25519 as Vectorizable<PRF_CHUNK>>::Array>,
 {
+    let prf_of_match_keys =
+        compute_prf_of_match_keys(ctx, input_rows, |row| &row.match_key).await?;
+
+    Ok(with_prf_of_match_keys(input_rows, prf_of_match_keys))
+}
+
+/// Replaces the match key of every input row with its PRF value.
+fn with_prf_of_match_keys<BK, TV, TS>(
+    input_rows: &[OPRFIPAInputRow<BK, TV, TS>],
+    prf_of_match_keys: Vec<u64>,
+) -> Vec<PrfShardedIpaInputRow<BK, TV, TS>>
+where
+    BK: BooleanArray,
+    TV: BooleanArray,
+    TS: BooleanArray,
+{
+    zip(input_rows, prf_of_match_keys)
+        .map(|(input, prf_of_match_key)| {
+            let OPRFIPAInputRow {
+                match_key: _,
+                is_trigger,
+                breakdown_key,
+                trigger_value,
+                timestamp,
+            } = &input;
+
+            PrfShardedIpaInputRow {
+                prf_of_match_key,
+                is_trigger_bit: is_trigger.clone(),
+                breakdown_key: breakdown_key.clone(),
+                trigger_value: trigger_value.clone(),
+                timestamp: timestamp.clone(),
+                sort_key: Replicated::ZERO,
+            }
+        })
+        .collect()
+}
+
+/// Converts the match key of every input row into an elliptic curve point and evaluates the
+/// PRF on it. The PRF values are revealed and returned in the same order as `input_rows`.
+///
+/// `match_key` selects the match key from an input row, so that this can be shared by every
+/// query that groups its input by user.
+async fn compute_prf_of_match_keys<C, R>(
+    ctx: C,
+    input_rows: &[R],
+    match_key: fn(&R) -> &Replicated<MatchKey>,
+) -> Result<Vec<u64>, Error>
+where
+    C: UpgradableContext,
+    R: Clone + Default + Sync,
+    Replicated<Boolean, CONV_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, CONV_CHUNK>,
+    Replicated<Fp25519, PRF_CHUNK>:
+        PrfSharing<MacUpgraded<C, Fp25519>, PRF_CHUNK, Field = Fp25519> + FromPrss,
+    Replicated<RP25519, PRF_CHUNK>:
+        Reveal<MacUpgraded<C, Fp25519>, Output = <RP25519 as Vectorizable<PRF_CHUNK>>::Array>,
+{
+    let prf_key = gen_prf_key(&ctx.narrow(&IpaPrfStep::PrfKeyGen));
+    evaluate_prf_of_match_keys(ctx, input_rows, match_key, &prf_key).await
+}
+
+/// Same as [`compute_prf_of_match_keys`], but evaluates the PRF with the given key instead of
+/// generating a new one.
+async fn evaluate_prf_of_match_keys<C, R>(
+    ctx: C,
+    input_rows: &[R],
+    match_key: fn(&R) -> &Replicated<MatchKey>,
+    prf_key: &Replicated<Fp25519>,
+) -> Result<Vec<u64>, Error>
+where
+    C: UpgradableContext,
+    R: Clone + Default + Sync,
+    Replicated<Boolean, CONV_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, CONV_CHUNK>,
+    Replicated<Fp25519, PRF_CHUNK>:
+        PrfSharing<MacUpgraded<C, Fp25519>, PRF_CHUNK, Field = Fp25519> + FromPrss,
+    Replicated<RP25519, PRF_CHUNK>:
+        Reveal<MacUpgraded<C, Fp25519>, Output = <RP25519 as Vectorizable<PRF_CHUNK>>::Array>,
+{
+    if input_rows.is_empty() {
+        return Ok(Vec::new());
+    }
+
     let conv_records =
         TotalRecords::specified(div_round_up(input_rows.len(), Const::<CONV_CHUNK>))?;
     let eval_records = TotalRecords::specified(div_round_up(input_rows.len(), Const::<PRF_CHUNK>))?;
@@ -353,7 +712,7 @@ where
         process_slice_by_chunks(input_rows, move |idx, records: ChunkData<_, CONV_CHUNK>| {
             let record_id = RecordId::from(idx);
             let input_match_keys: &dyn Fn(usize) -> Replicated<MatchKey> =
-                &|i| records[i].match_key.clone();
+                &|i| match_key(&records[i]).clone();
             let match_keys =
                 BitDecomposed::<Replicated<Boolean, 256>>::transposed_from(input_match_keys)
                     .unwrap_infallible();
@@ -365,7 +724,6 @@ where
     .try_collect::<Vec<_>>()
     .await?;
 
-    let prf_key = gen_prf_key(&ctx.narrow(&IpaPrfStep::PrfKeyGen));
     let validator = ctx
         .narrow(&Step::EvalPrf)
         .set_total_records(eval_records)
@@ -377,7 +735,6 @@ where
         stream::iter(curve_pts).enumerate().map(|(i, curve_pts)| {
             let record_id = RecordId::from(i);
             let eval_ctx = eval_ctx.clone();
-            let prf_key = &prf_key;
             curve_pts
                 .then(move |pts| eval_dy_prf::<_, PRF_CHUNK>(eval_ctx, record_id, prf_key, pts))
         }),
@@ -385,25 +742,10 @@ where
     .try_collect::<Vec<_>>()
     .await?;
 
-    Ok(zip(input_rows, prf_of_match_keys.into_iter().flatten())
-        .map(|(input, prf_of_match_key)| {
-            let OPRFIPAInputRow {
-                match_key: _,
-                is_trigger,
-                breakdown_key,
-                trigger_value,
-                timestamp,
-            } = &input;
-
-            PrfShardedIpaInputRow {
-                prf_of_match_key,
-                is_trigger_bit: is_trigger.clone(),
-                breakdown_key: breakdown_key.clone(),
-                trigger_value: trigger_value.clone(),
-                timestamp: timestamp.clone(),
-                sort_key: Replicated::ZERO,
-            }
-        })
+    Ok(prf_of_match_keys
+        .into_iter()
+        .flatten()
+        .take(input_rows.len())
         .collect())
 }
 
@@ -418,10 +760,12 @@ pub mod tests {
         helpers::query::DpMechanism,
         protocol::{
             dp::NoiseParams,
-            ipa_prf::{oprf_ipa, oprf_padding::PaddingParameters},
+            ipa_prf::{oprf_ipa, oprf_padding::PaddingParameters, sharded_oprf_ipa},
         },
         test_executor::run,
-        test_fixture::{ipa::TestRawDataRecord, Reconstruct, Runner, TestWorld},
+        test_fixture::{
+            ipa::TestRawDataRecord, Reconstruct, Runner, TestWorld, TestWorldConfig, WithShards,
+        },
     };
 
     fn test_input(
@@ -471,6 +815,8 @@ pub mod tests {
                         ctx,
                         input_rows,
                         None,
+                        None,
+                        None,
                         dp_params,
                         padding_params,
                     )
@@ -487,6 +833,150 @@ pub mod tests {
         });
     }
 
+    #[test]
+    fn sharded_semi_honest() {
+        const SHARDS: usize = 3;
+        const EXPECTED: &[u128] = &[0, 2, 5, 5, 3, 0, 0, 0];
+
+        run(|| async {
+            let world = TestWorld::<WithShards<SHARDS>>::with_shards(TestWorldConfig::default());
+
+            // Rows of the same user are spread across shards by the input distribution, so this
+            // only works if they are brought back together after the PRF is evaluated.
+            let records: Vec<TestRawDataRecord> = vec![
+                test_input(0, 12345, false, 1, 0),
+                test_input(5, 12345, false, 2, 0),
+                test_input(10, 12345, true, 0, 5),
+                test_input(0, 68362, false, 1, 0),
+                test_input(20, 68362, true, 0, 2),
+                test_input(0, 1, false, 3, 0),
+                test_input(1, 1, true, 0, 1),
+                test_input(0, 2, false, 3, 0),
+                test_input(1, 2, true, 0, 4),
+                test_input(0, 3, false, 4, 0),
+                test_input(3, 3, true, 0, 3),
+                test_input(0, 4, true, 0, 7),
+                test_input(0, 5, false, 5, 0),
+            ];
+            let padding_params = if cfg!(feature = "shuttle") {
+                PaddingParameters::no_padding()
+            } else {
+                PaddingParameters::relaxed()
+            };
+
+            let results = world
+                .semi_honest(records.into_iter(), |ctx, input_rows| async move {
+                    sharded_oprf_ipa::<_, BA5, BA3, BA16, BA20, 5, 32>(
+                        ctx,
+                        input_rows,
+                        None,
+                        None,
+                        None,
+                        DpMechanism::NoDp,
+                        padding_params,
+                    )
+                    .await
+                    .unwrap()
+                })
+                .await;
+
+            // Only the leader shard gets the histogram back.
+            let mut results = results.into_iter().map(|r| r.reconstruct());
+            let mut result = results.next().unwrap();
+            assert!(results.all(|r| r.is_empty()));
+            result.truncate(EXPECTED.len());
+            assert_eq!(
+                result.iter().map(|&v| v.as_u128()).collect::<Vec<_>>(),
+                EXPECTED,
+            );
+        });
+    }
+
+    #[test]
+    fn sharded_malicious() {
+        const SHARDS: usize = 2;
+        const EXPECTED: &[u128] = &[0, 2, 5, 4, 0, 0, 0, 0];
+
+        run(|| async {
+            let world = TestWorld::<WithShards<SHARDS>>::with_shards(TestWorldConfig::default());
+
+            let records: Vec<TestRawDataRecord> = vec![
+                test_input(0, 12345, false, 1, 0),
+                test_input(5, 12345, false, 2, 0),
+                test_input(10, 12345, true, 0, 5),
+                test_input(0, 68362, false, 1, 0),
+                test_input(20, 68362, true, 0, 2),
+                test_input(0, 1, false, 3, 0),
+                test_input(1, 1, true, 0, 4),
+                test_input(0, 2, true, 0, 7),
+            ];
+
+            let results = world
+                .malicious(records.into_iter(), |ctx, input_rows| async move {
+                    sharded_oprf_ipa::<_, BA5, BA3, BA16, BA20, 5, 32>(
+                        ctx,
+                        input_rows,
+                        None,
+                        None,
+                        None,
+                        DpMechanism::NoDp,
+                        PaddingParameters::no_padding(),
+                    )
+                    .await
+                    .unwrap()
+                })
+                .await;
+
+            let mut results = results.into_iter().map(|r| r.reconstruct());
+            let mut result = results.next().unwrap();
+            assert!(results.all(|r| r.is_empty()));
+            result.truncate(EXPECTED.len());
+            assert_eq!(
+                result.iter().map(|&v| v.as_u128()).collect::<Vec<_>>(),
+                EXPECTED,
+            );
+        });
+    }
+
+    #[test]
+    fn trigger_value_cap() {
+        const EXPECTED: &[u128] = &[0, 2, 3, 0, 0, 0, 0, 0];
+
+        run(|| async {
+            let world = TestWorld::default();
+
+            let records: Vec<TestRawDataRecord> = vec![
+                test_input(0, 12345, false, 1, 0),
+                test_input(5, 12345, false, 2, 0),
+                test_input(10, 12345, true, 0, 5),
+                test_input(0, 68362, false, 1, 0),
+                test_input(20, 68362, true, 0, 2),
+            ]; // trigger value of 5 is capped at 3 before it is attributed to breakdown 2.
+
+            let mut result: Vec<_> = world
+                .malicious(records.into_iter(), |ctx, input_rows| async move {
+                    oprf_ipa::<_, BA5, BA3, BA16, BA20, 5, 32>(
+                        ctx,
+                        input_rows,
+                        None,
+                        Some(3),
+                        None,
+                        DpMechanism::NoDp,
+                        PaddingParameters::no_padding(),
+                    )
+                    .await
+                    .unwrap()
+                })
+                .await
+                .reconstruct();
+            result.truncate(EXPECTED.len());
+            assert_eq!(
+                result.iter().map(|&v| v.as_u128()).collect::<Vec<_>>(),
+                EXPECTED,
+            );
+        });
+    }
+
     #[test]
     fn malicious() {
         const EXPECTED: &[u128] = &[0, 2, 5, 0, 0, 0, 0, 0];
@@ -511,6 +1001,8 @@ pub mod tests {
                         ctx,
                         input_rows,
                         None,
+                        None,
+                        None,
                         dp_params,
                         padding_params,
                     )
@@ -532,7 +1024,10 @@ pub mod tests {
         const SS_BITS: usize = 1;
         // setting SS_BITS this small will cause clipping in capping
         // since per_user_credit_cap == 2^SS_BITS
-        semi_honest_with_dp_internal::<SS_BITS>(DpMechanism::DiscreteLaplace { epsilon: 5.0 });
+        semi_honest_with_dp_internal::<SS_BITS>(DpMechanism::DiscreteLaplace {
+            epsilon: 5.0,
+            delta: DpMechanism::DEFAULT_DELTA,
+        });
     }
     #[test]
     fn semi_honest_with_dp_slow() {
@@ -540,7 +1035,10 @@ pub mod tests {
         if std::env::var("EXEC_SLOW_TESTS").is_err() {
             return;
         }
-        semi_honest_with_dp_internal::<SS_BITS>(DpMechanism::Binomial { epsilon: 10.0 });
+        semi_honest_with_dp_internal::<SS_BITS>(DpMechanism::Binomial {
+            epsilon: 10.0,
+            delta: DpMechanism::DEFAULT_DELTA,
+        });
     }
 
     fn semi_honest_with_dp_internal<const SS_BITS: usize>(_dp_mechanism: DpMechanism) {
@@ -550,7 +1048,10 @@ pub mod tests {
             const B: usize = 32; // number of histogram bins
             let expected: Vec<u32> = vec![0, 2, 5, 0, 0, 0, 0, 0];
             let epsilon = 10.0;
-            let dp_params = DpMechanism::Binomial { epsilon };
+            let dp_params = DpMechanism::Binomial {
+                epsilon,
+                delta: DpMechanism::DEFAULT_DELTA,
+            };
             let per_user_credit_cap = 2_f64.powi(i32::try_from(SS_BITS).unwrap());
             let padding_params = PaddingParameters::relaxed();
             let world = TestWorld::default();
@@ -568,6 +1069,8 @@ pub mod tests {
                         ctx,
                         input_rows,
                         None,
+                        None,
+                        None,
                         dp_params,
                         padding_params,
                     )
@@ -629,6 +1132,8 @@ pub mod tests {
                         ctx,
                         input_rows,
                         None,
+                        None,
+                        None,
                         dp_params,
                         padding_params,
                     )
@@ -665,6 +1170,8 @@ pub mod tests {
                         ctx,
                         input_rows,
                         None,
+                        None,
+                        None,
                         dp_params,
                         padding_params,
                     )
@@ -719,6 +1226,8 @@ pub mod tests {
                         ctx,
                         input_rows,
                         None,
+                        None,
+                        None,
                         dp_params,
                         padding_params,
                     )
@@ -836,6 +1345,8 @@ mod compact_gate_tests {
                         ctx,
                         input_rows,
                         None,
+                        None,
+                        None,
                         dp_params,
                         padding_params,
                     )
@@ -173,6 +173,54 @@ impl Distribution<u32> for TruncatedDoubleGeometric {
     }
 }
 
+/// Discrete Gaussian distribution centered at zero, as defined in [`CKS20`]. Samples are
+/// drawn by rejection from a Double Geometric distribution with scale `floor(sigma) + 1`
+/// (Algorithm 3 of the paper). Unlike the reference implementation, acceptance probabilities
+/// are computed with floating point arithmetic.
+///
+/// [`CKS20`]: https://arxiv.org/abs/2004.00010
+#[derive(Debug, PartialEq)]
+pub struct DiscreteGaussian {
+    sigma: f64,
+    scale: f64,
+    double_geometric: DoubleGeometric,
+}
+
+impl DiscreteGaussian {
+    /// Creates a new `DiscreteGaussian` distribution with the given scale parameter.
+    pub fn new(sigma: f64) -> Result<Self, Error> {
+        if !(f64::MIN_POSITIVE..=1_000_000.0).contains(&sigma) {
+            return Err(Error::BadSigma(sigma));
+        }
+        let scale = sigma.floor() + 1.0;
+        Ok(Self {
+            sigma,
+            scale,
+            double_geometric: DoubleGeometric::new(scale, 0)?,
+        })
+    }
+
+    #[must_use]
+    pub fn sigma(&self) -> f64 {
+        self.sigma
+    }
+}
+
+impl Distribution<i32> for DiscreteGaussian {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> i32 {
+        let variance = self.sigma.powi(2);
+        loop {
+            let candidate = self.double_geometric.sample(rng);
+            let distance = f64::from(candidate.unsigned_abs()) - variance / self.scale;
+            let accept = Bernoulli::new((-distance.powi(2) / (2.0 * variance)).exp())
+                .expect("acceptance probability is within [0, 1]");
+            if accept.sample(rng) {
+                return candidate;
+            }
+        }
+    }
+}
+
 #[cfg(all(test, unit_test))]
 mod tests {
     use std::{collections::HashMap, f64::consts::E, iter::repeat_with};
@@ -182,7 +230,8 @@ mod tests {
 
     use crate::protocol::ipa_prf::oprf_padding::{
         distributions::{
-            is_close, BoxMuller, DoubleGeometric, Geometric, TruncatedDoubleGeometric,
+            is_close, BoxMuller, DiscreteGaussian, DoubleGeometric, Geometric,
+            TruncatedDoubleGeometric,
         },
         insecure::Error,
     };
@@ -410,4 +459,40 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_discrete_gaussian_constructor() {
+        for sigma in [0.0, -1.0, f64::NAN, 2_000_000.0] {
+            assert!(matches!(
+                DiscreteGaussian::new(sigma),
+                Err(Error::BadSigma(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_discrete_gaussian_sample_dist() {
+        let mut rng = rand::thread_rng();
+        let sigma = 3.0;
+        let distribution =
+            DiscreteGaussian::new(sigma).expect("failed to construct DiscreteGaussian");
+        let num_samples = 100_000;
+        let mut histogram = HashMap::new();
+        for _ in 0..num_samples {
+            *histogram.entry(distribution.sample(&mut rng)).or_insert(0) += 1;
+        }
+
+        let density = |x: i32| E.powf(-f64::from(x).powi(2) / (2.0 * sigma * sigma));
+        let normalizing_factor = (-100..=100).map(density).sum::<f64>();
+        for x in -10..=10 {
+            let observed_probability = histogram
+                .get(&x)
+                .map_or(0.0, |count| f64::from(*count) / f64::from(num_samples));
+            let expected_probability = density(x) / normalizing_factor;
+            assert!(
+                (observed_probability - expected_probability).abs() <= 0.01,
+                "Observed probability of {x} is {observed_probability}, expected {expected_probability}"
+            );
+        }
+    }
 }
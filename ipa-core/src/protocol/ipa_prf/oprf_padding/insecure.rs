@@ -4,7 +4,7 @@ use rand::distributions::{BernoulliError, Distribution};
 use rand_core::{CryptoRng, RngCore};
 
 use crate::protocol::ipa_prf::oprf_padding::distributions::{
-    BoxMuller, RoundedBoxMuller, TruncatedDoubleGeometric,
+    BoxMuller, DiscreteGaussian, RoundedBoxMuller, TruncatedDoubleGeometric,
 };
 
 pub type DpError = Error;
@@ -35,6 +35,8 @@ pub enum Error {
         in Double Geometric sample",
     )]
     BadSensitivity(u32),
+    #[error("Valid values for the scale of DiscreteGaussian are within (0, 1M], got: {0}")]
+    BadSigma(f64),
 }
 impl From<BernoulliError> for Error {
     fn from(_: BernoulliError) -> Self {
@@ -314,6 +316,55 @@ impl OPRFPaddingDp {
     }
 }
 
+/// Discrete Gaussian noise calibrated to provide `(epsilon, delta)`-DP for queries with the given
+/// L2 sensitivity. Unlike [`OPRFPaddingDp`], samples are centered at zero and not truncated.
+#[derive(Debug)]
+pub struct DiscreteGaussianDp {
+    discrete_gaussian: DiscreteGaussian,
+}
+
+impl DiscreteGaussianDp {
+    /// The discrete Gaussian with scale `sigma` satisfies `rho`-zCDP with
+    /// `rho = sensitivity^2 / (2 * sigma^2)` (Theorem 14 in <https://arxiv.org/abs/2004.00010>),
+    /// which implies `(rho + 2 * sqrt(rho * ln(1/delta)), delta)`-DP. This picks the largest
+    /// `rho` that meets the requested epsilon and derives `sigma` from it.
+    ///
+    /// # Errors
+    /// will return errors if invalid DP parameters are provided.
+    pub fn new(epsilon: f64, delta: f64, sensitivity: f64) -> Result<Self, Error> {
+        if epsilon < f64::MIN_POSITIVE {
+            return Err(Error::BadEpsilon(epsilon));
+        }
+        if !(f64::MIN_POSITIVE..=1.0 - f64::MIN_POSITIVE).contains(&delta) {
+            return Err(Error::BadDelta(delta));
+        }
+        if !(1.0..=1_000_000.0).contains(&sensitivity) {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            return Err(Error::BadSensitivity(sensitivity as u32));
+        }
+
+        let log_inv_delta = (1.0 / delta).ln();
+        let rho = ((log_inv_delta + epsilon).sqrt() - log_inv_delta.sqrt()).powi(2);
+        let sigma = sensitivity / (2.0 * rho).sqrt();
+
+        Ok(Self {
+            discrete_gaussian: DiscreteGaussian::new(sigma)?,
+        })
+    }
+
+    /// Generates a sample from the `DiscreteGaussianDp` struct.
+    pub fn sample<R: RngCore + CryptoRng>(&self, rng: &mut R) -> i32 {
+        self.discrete_gaussian.sample(rng)
+    }
+
+    /// Returns the scale of the discrete Gaussian. It is within a tiny fraction of its standard
+    /// deviation for scales larger than 1.
+    #[must_use]
+    pub fn std(&self) -> f64 {
+        self.discrete_gaussian.sigma()
+    }
+}
+
 #[cfg(all(test, unit_test))]
 mod test {
     use std::collections::BTreeMap;
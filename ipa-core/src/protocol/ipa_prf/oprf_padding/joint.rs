@@ -0,0 +1,431 @@
+use std::iter::repeat_n;
+
+use futures_util::{stream, TryStreamExt};
+
+use crate::{
+    error::Error,
+    ff::{
+        boolean::Boolean,
+        boolean_array::{BooleanArray, BA64},
+        U128Conversions,
+    },
+    helpers::TotalRecords,
+    protocol::{
+        basics::{select, BooleanArrayMul, ShareKnownValue},
+        context::{
+            dzkp_validator::{validated_seq_join, DZKPValidator, TARGET_PROOF_SIZE},
+            Context, DZKPUpgraded, MaliciousProtocolSteps, UpgradableContext,
+        },
+        dp::{find_smallest_num_bernoulli, NoiseParams},
+        ipa_prf::{
+            oprf_padding::{
+                step::{JointAggregationPaddingStep, JointPaddingStep, PaddingDpStep},
+                AggregationPadding, OPRFPadding,
+            },
+            prf_sharding::AttributionOutputs,
+            BreakdownKey, OPRFIPAInputRow,
+        },
+        prss::SharedRandomness,
+        RecordId,
+    },
+    secret_sharing::{
+        replicated::{semi_honest::AdditiveShare, ReplicatedSecretSharing},
+        SharedValue,
+    },
+};
+
+/// Number of candidate dummy groups generated for each match key cardinality. Every candidate
+/// is kept with probability 1/2, so the number of dummy groups follows a binomial distribution,
+/// calibrated the same way as the binomial noise in [`crate::protocol::dp`].
+#[must_use]
+pub fn candidates_per_cardinality(
+    epsilon: f64,
+    delta: f64,
+    sensitivity: u32,
+    matchkey_cardinality_cap: u32,
+) -> u32 {
+    // Cardinality 1 needs no candidates, see [`apply_joint_oprf_padding`].
+    if matchkey_cardinality_cap < 2 {
+        return 0;
+    }
+    binomial_candidates(epsilon, delta, sensitivity, matchkey_cardinality_cap - 1)
+}
+
+/// Number of candidate dummy rows generated for each breakdown, see
+/// [`apply_joint_aggregation_padding`]. Like for [`candidates_per_cardinality`], every candidate is
+/// kept with probability 1/2.
+#[must_use]
+pub fn candidates_per_breakdown(
+    epsilon: f64,
+    delta: f64,
+    sensitivity: u32,
+    breakdowns: u32,
+) -> u32 {
+    if breakdowns == 0 {
+        return 0;
+    }
+    binomial_candidates(epsilon, delta, sensitivity, breakdowns)
+}
+
+fn binomial_candidates(epsilon: f64, delta: f64, sensitivity: u32, dimensions: u32) -> u32 {
+    let noise_params = NoiseParams {
+        epsilon,
+        delta,
+        ell_1_sensitivity: f64::from(sensitivity),
+        ell_2_sensitivity: f64::from(sensitivity),
+        ell_infty_sensitivity: f64::from(sensitivity),
+        dimensions: f64::from(dimensions),
+        ..Default::default()
+    };
+    find_smallest_num_bernoulli(&noise_params)
+}
+
+fn joint_padding_proof_chunk() -> usize {
+    (TARGET_PROOF_SIZE / usize::try_from(BA64::BITS).unwrap()).next_power_of_two()
+}
+
+/// Adds OPRF padding whose noise is generated jointly by all three helpers, unlike
+/// [`super::apply_dp_padding`], where each pair of helpers knows the noise it adds.
+///
+/// For every cardinality `k` in `2..=matchkey_cardinality_cap`, helpers add the same public number
+/// of candidate groups of `k` rows. All match keys and a secret "keep" bit per group are drawn
+/// from PRSS, so no helper knows them. The rows of a kept group share the match key of the group,
+/// while every row of a dropped group gets its own match key. The number of groups of each
+/// cardinality above one is thus binomial noise that nobody knows, and dropped groups only add to
+/// the number of match keys seen once, which is implied by the other cardinalities and the total
+/// number of rows.
+///
+/// The match key of every row is picked in MPC, so this is slower than pairwise padding, but it
+/// is secure against a malicious helper.
+///
+/// # Errors
+/// If the validation of the match key selection fails.
+/// # Panics
+/// If the number of padding rows does not fit into `usize`.
+pub async fn apply_joint_oprf_padding<C, BK, TV, TS>(
+    ctx: C,
+    mut input: Vec<OPRFIPAInputRow<BK, TV, TS>>,
+    oprf_padding: &OPRFPadding,
+) -> Result<Vec<OPRFIPAInputRow<BK, TV, TS>>, Error>
+where
+    C: UpgradableContext,
+    BK: BooleanArray + U128Conversions,
+    TV: BooleanArray,
+    TS: BooleanArray,
+    AdditiveShare<BA64>: BooleanArrayMul<DZKPUpgraded<C>>,
+{
+    let OPRFPadding::Parameters {
+        oprf_epsilon,
+        oprf_delta,
+        matchkey_cardinality_cap,
+        oprf_padding_sensitivity,
+    } = *oprf_padding
+    else {
+        return Ok(input);
+    };
+    let candidates = candidates_per_cardinality(
+        oprf_epsilon,
+        oprf_delta,
+        oprf_padding_sensitivity,
+        matchkey_cardinality_cap,
+    );
+    // Cardinality of the group that every candidate row belongs to.
+    let group_cardinalities = (2..=matchkey_cardinality_cap)
+        .flat_map(|cardinality| repeat_n(cardinality, candidates as usize))
+        .collect::<Vec<_>>();
+    let total_rows = group_cardinalities
+        .iter()
+        .map(|&cardinality| cardinality as usize)
+        .sum::<usize>();
+    if total_rows == 0 {
+        return Ok(input);
+    }
+
+    let validator = ctx
+        .set_total_records(TotalRecords::specified(total_rows)?)
+        .dzkp_validator(
+            MaliciousProtocolSteps {
+                protocol: &PaddingDpStep::JointPadding,
+                validate: &PaddingDpStep::JointPaddingValidate,
+            },
+            joint_padding_proof_chunk(),
+        );
+    let padding_ctx = validator.context();
+    let group_key_ctx = padding_ctx.narrow(&JointPaddingStep::GroupMatchKey);
+    let keep_ctx = padding_ctx.narrow(&JointPaddingStep::KeepGroup);
+    let row_key_ctx = padding_ctx.narrow(&JointPaddingStep::RowMatchKey);
+    let select_ctx = padding_ctx.narrow(&JointPaddingStep::Select);
+
+    // Match key and keep bit of every candidate group.
+    let groups = (0..group_cardinalities.len())
+        .map(|group| {
+            let group_key: AdditiveShare<BA64> =
+                group_key_ctx.prss().generate(RecordId::from(group));
+            let keep: AdditiveShare<Boolean> = keep_ctx.prss().generate(RecordId::from(group));
+            (group_key, keep)
+        })
+        .collect::<Vec<_>>();
+
+    let rows = group_cardinalities
+        .iter()
+        .zip(groups)
+        .flat_map(|(&cardinality, group)| repeat_n(group, cardinality as usize))
+        .enumerate()
+        .map(|(row, (group_key, keep))| {
+            let row_key: AdditiveShare<BA64> = row_key_ctx.prss().generate(RecordId::from(row));
+            let select_ctx = select_ctx.clone();
+            async move {
+                let match_key =
+                    select(select_ctx, RecordId::from(row), &keep, &group_key, &row_key).await?;
+                Ok::<_, Error>(OPRFIPAInputRow {
+                    match_key,
+                    is_trigger: AdditiveShare::new(Boolean::FALSE, Boolean::FALSE),
+                    breakdown_key: AdditiveShare::new(BK::ZERO, BK::ZERO),
+                    trigger_value: AdditiveShare::new(TV::ZERO, TV::ZERO),
+                    timestamp: AdditiveShare::new(TS::ZERO, TS::ZERO),
+                })
+            }
+        });
+    let padding_rows = validated_seq_join(validator, stream::iter(rows))
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    tracing::info!(
+        "Added {total_rows} jointly generated padding rows, {candidates} candidate groups for \
+        each cardinality up to {matchkey_cardinality_cap}"
+    );
+    input.extend(padding_rows);
+    Ok(input)
+}
+
+fn joint_aggregation_padding_proof_chunk<BK: BooleanArray>() -> usize {
+    (TARGET_PROOF_SIZE / usize::try_from(BK::BITS).unwrap()).next_power_of_two()
+}
+
+/// Adds aggregation padding whose noise is generated jointly by all three helpers, unlike
+/// [`super::apply_dp_padding`], where each pair of helpers knows how many dummy rows it added for
+/// every breakdown.
+///
+/// For every breakdown, helpers add the same public number of candidate rows with a zero trigger
+/// value. A secret "keep" bit and a random breakdown key are drawn from PRSS for every candidate.
+/// Kept candidates get the breakdown they were generated for, so the number of kept candidates of
+/// each breakdown is binomial noise that nobody knows. Breakdown keys are revealed after the
+/// shuffle, so dropped candidates can't be removed. They get the random breakdown key instead,
+/// which only adds noise that does not depend on the input.
+///
+/// # Errors
+/// If the validation of the breakdown key selection fails.
+/// # Panics
+/// If the number of padding rows does not fit into `usize`.
+pub async fn apply_joint_aggregation_padding<C, BK, TV, const B: usize>(
+    ctx: C,
+    mut input: Vec<AttributionOutputs<AdditiveShare<BK>, AdditiveShare<TV>>>,
+    aggregation_padding: &AggregationPadding,
+) -> Result<Vec<AttributionOutputs<AdditiveShare<BK>, AdditiveShare<TV>>>, Error>
+where
+    C: UpgradableContext,
+    BK: BreakdownKey<B>,
+    TV: BooleanArray,
+    AdditiveShare<BK>: BooleanArrayMul<DZKPUpgraded<C>>,
+{
+    let AggregationPadding::Parameters {
+        aggregation_epsilon,
+        aggregation_delta,
+        aggregation_padding_sensitivity,
+    } = *aggregation_padding
+    else {
+        return Ok(input);
+    };
+    let breakdowns = u32::try_from(B).unwrap();
+    let candidates = candidates_per_breakdown(
+        aggregation_epsilon,
+        aggregation_delta,
+        aggregation_padding_sensitivity,
+        breakdowns,
+    );
+    let total_rows = B * candidates as usize;
+    if total_rows == 0 {
+        return Ok(input);
+    }
+
+    let validator = ctx
+        .set_total_records(TotalRecords::specified(total_rows)?)
+        .dzkp_validator(
+            MaliciousProtocolSteps {
+                protocol: &PaddingDpStep::JointAggregationPadding,
+                validate: &PaddingDpStep::JointAggregationPaddingValidate,
+            },
+            joint_aggregation_padding_proof_chunk::<BK>(),
+        );
+    let padding_ctx = validator.context();
+    let keep_ctx = padding_ctx.narrow(&JointAggregationPaddingStep::KeepRow);
+    let random_key_ctx = padding_ctx.narrow(&JointAggregationPaddingStep::RandomBreakdownKey);
+    let select_ctx = padding_ctx.narrow(&JointAggregationPaddingStep::Select);
+
+    let rows = (0..breakdowns)
+        .flat_map(|breakdown| repeat_n(breakdown, candidates as usize))
+        .enumerate()
+        .map(|(row, breakdown)| {
+            let record_id = RecordId::from(row);
+            let keep: AdditiveShare<Boolean> = keep_ctx.prss().generate(record_id);
+            let random_key: AdditiveShare<BK> = random_key_ctx.prss().generate(record_id);
+            let breakdown_key =
+                AdditiveShare::<BK>::share_known_value(&select_ctx, BK::truncate_from(breakdown));
+            let select_ctx = select_ctx.clone();
+            async move {
+                let breakdown_key =
+                    select(select_ctx, record_id, &keep, &breakdown_key, &random_key).await?;
+                Ok::<_, Error>(AttributionOutputs {
+                    attributed_breakdown_key_bits: breakdown_key,
+                    capped_attributed_trigger_value: AdditiveShare::new(TV::ZERO, TV::ZERO),
+                })
+            }
+        });
+    let padding_rows = validated_seq_join(validator, stream::iter(rows))
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    tracing::info!(
+        "Added {total_rows} jointly generated aggregation padding rows, {candidates} candidates \
+        for each of {breakdowns} breakdowns"
+    );
+    input.extend(padding_rows);
+    Ok(input)
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        ff::{
+            boolean_array::{BA20, BA3, BA5, BA64, BA8},
+            U128Conversions,
+        },
+        protocol::ipa_prf::{
+            oprf_padding::{
+                joint::{
+                    apply_joint_aggregation_padding, apply_joint_oprf_padding,
+                    candidates_per_breakdown, candidates_per_cardinality,
+                },
+                AggregationPadding, OPRFPadding,
+            },
+            OPRFIPAInputRow,
+        },
+        secret_sharing::replicated::semi_honest::AdditiveShare,
+        test_executor::run,
+        test_fixture::{Reconstruct, Runner, TestWorld},
+    };
+
+    #[test]
+    fn joint_padding() {
+        run(|| async {
+            let oprf_padding = OPRFPadding::Parameters {
+                oprf_epsilon: 10.0,
+                oprf_delta: 1e-4,
+                matchkey_cardinality_cap: 3,
+                oprf_padding_sensitivity: OPRFPadding::SENSITIVITY,
+            };
+            let candidates = candidates_per_cardinality(10.0, 1e-4, OPRFPadding::SENSITIVITY, 3);
+            let world = TestWorld::default();
+            let result = world
+                .malicious((), |ctx, ()| async move {
+                    apply_joint_oprf_padding::<_, BA8, BA3, BA20>(
+                        ctx,
+                        Vec::<OPRFIPAInputRow<BA8, BA3, BA20>>::new(),
+                        &oprf_padding,
+                    )
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|row| row.match_key)
+                    .collect::<Vec<AdditiveShare<BA64>>>()
+                })
+                .await
+                .reconstruct();
+
+            assert_eq!(result.len(), 5 * candidates as usize);
+            let mut cardinalities = HashMap::<u128, u32>::new();
+            for match_key in result {
+                *cardinalities.entry(match_key.as_u128()).or_default() += 1;
+            }
+            let mut groups = [0_u32; 4];
+            for cardinality in cardinalities.into_values() {
+                groups[cardinality as usize] += 1;
+            }
+            // Each candidate group is kept with probability 1/2.
+            for kept in &groups[2..] {
+                let kept = f64::from(*kept);
+                let mean = f64::from(candidates) / 2.0;
+                let std = f64::from(candidates).sqrt() / 2.0;
+                assert!(
+                    (kept - mean).abs() < 6.0 * std,
+                    "{kept} groups kept, expected {mean}"
+                );
+            }
+            assert_eq!(
+                groups[1] + 2 * groups[2] + 3 * groups[3],
+                5 * candidates,
+                "dropped groups must turn into match keys seen once"
+            );
+        });
+    }
+
+    #[test]
+    fn joint_aggregation_padding() {
+        run(|| async {
+            let aggregation_padding = AggregationPadding::Parameters {
+                aggregation_epsilon: 10.0,
+                aggregation_delta: 1e-4,
+                aggregation_padding_sensitivity: 3,
+            };
+            let candidates = candidates_per_breakdown(10.0, 1e-4, 3, 32);
+            let world = TestWorld::default();
+            let result = world
+                .malicious((), |ctx, ()| async move {
+                    apply_joint_aggregation_padding::<_, BA5, BA3, 32>(
+                        ctx,
+                        Vec::new(),
+                        &aggregation_padding,
+                    )
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|row| {
+                        (
+                            row.attributed_breakdown_key_bits,
+                            row.capped_attributed_trigger_value,
+                        )
+                    })
+                    .unzip::<_, _, Vec<AdditiveShare<BA5>>, Vec<AdditiveShare<BA3>>>()
+                })
+                .await;
+            let breakdown_keys = result.clone().map(|(bk, _)| bk).reconstruct();
+            let trigger_values = result.map(|(_, tv)| tv).reconstruct();
+
+            assert_eq!(breakdown_keys.len(), 32 * candidates as usize);
+            assert!(trigger_values.iter().all(|tv| tv.as_u128() == 0));
+            let mut counts = [0_u32; 32];
+            for bk in breakdown_keys {
+                counts[usize::try_from(bk.as_u128()).unwrap()] += 1;
+            }
+            // Half of the candidates are kept, the other half is spread over all breakdowns.
+            let mean = f64::from(candidates);
+            let std = f64::from(candidates).sqrt();
+            for count in counts {
+                assert!(
+                    (f64::from(count) - mean).abs() < 6.0 * std,
+                    "{count} padding rows for a breakdown, expected {mean}"
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn no_candidates_without_cardinalities_above_one() {
+        assert_eq!(
+            candidates_per_cardinality(1.0, 1e-6, OPRFPadding::SENSITIVITY, 1),
+            0
+        );
+    }
+}
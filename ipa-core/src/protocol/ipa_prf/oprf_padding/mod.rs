@@ -1,5 +1,6 @@
 pub(crate) mod distributions;
 pub mod insecure;
+pub mod joint;
 pub mod step;
 
 use std::iter::{repeat, repeat_with};
@@ -21,11 +22,15 @@ use crate::{
     protocol::{
         context::{prss::InstrumentedSequentialSharedRandomness, Context},
         ipa_prf::{
+            logistic_regression::LogisticRegressionInputRow,
             oprf_padding::{
                 insecure::OPRFPaddingDp,
                 step::{PaddingDpStep, SendTotalRows},
             },
-            prf_sharding::AttributionOutputs,
+            prf_sharding::{
+                feature_label_dot_product::FeatureLabelInputRow, reach_frequency::ReachPaddingRow,
+                AttributionOutputs,
+            },
             OPRFIPAInputRow,
         },
         RecordId,
@@ -42,6 +47,19 @@ use crate::{
 pub struct PaddingParameters {
     pub aggregation_padding: AggregationPadding,
     pub oprf_padding: OPRFPadding,
+    pub padding_generation: PaddingGeneration,
+}
+
+/// How the noise that determines the number of OPRF and aggregation padding rows is generated.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PaddingGeneration {
+    /// Each pair of helpers samples noise from shared randomness, see [`apply_dp_padding`].
+    /// This is fast, but each pair knows the noise it added.
+    #[default]
+    Pairwise,
+    /// All three helpers generate the noise in MPC, see [`joint::apply_joint_oprf_padding`] and
+    /// [`joint::apply_joint_aggregation_padding`].
+    Joint,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -76,13 +94,19 @@ impl Default for AggregationPadding {
     }
 }
 
+impl OPRFPadding {
+    /// Sensitivity of the match key cardinality histogram that OPRF padding protects. It should
+    /// remain 2.
+    pub const SENSITIVITY: u32 = 2;
+}
+
 impl Default for OPRFPadding {
     fn default() -> Self {
         OPRFPadding::Parameters {
             oprf_epsilon: 5.0,
             oprf_delta: 1e-6,
             matchkey_cardinality_cap: 10,
-            oprf_padding_sensitivity: 2, // should remain 2
+            oprf_padding_sensitivity: Self::SENSITIVITY,
         }
     }
 }
@@ -100,8 +124,9 @@ impl PaddingParameters {
                 oprf_epsilon: 10.0,
                 oprf_delta: 1e-4,
                 matchkey_cardinality_cap: 3,
-                oprf_padding_sensitivity: 2,
+                oprf_padding_sensitivity: OPRFPadding::SENSITIVITY,
             },
+            padding_generation: PaddingGeneration::Pairwise,
         }
     }
 
@@ -110,7 +135,37 @@ impl PaddingParameters {
         PaddingParameters {
             aggregation_padding: AggregationPadding::NoAggPadding,
             oprf_padding: OPRFPadding::NoOPRFPadding,
+            padding_generation: PaddingGeneration::Pairwise,
+        }
+    }
+
+    /// Checks that the padding distributions can be set up with these parameters.
+    ///
+    /// # Errors
+    /// If any of the epsilons, deltas or sensitivities is rejected by [`OPRFPaddingDp`].
+    pub fn validate(&self) -> Result<(), insecure::Error> {
+        if let OPRFPadding::Parameters {
+            oprf_epsilon,
+            oprf_delta,
+            oprf_padding_sensitivity,
+            ..
+        } = self.oprf_padding
+        {
+            OPRFPaddingDp::new(oprf_epsilon, oprf_delta, oprf_padding_sensitivity)?;
         }
+        if let AggregationPadding::Parameters {
+            aggregation_epsilon,
+            aggregation_delta,
+            aggregation_padding_sensitivity,
+        } = self.aggregation_padding
+        {
+            OPRFPaddingDp::new(
+                aggregation_epsilon,
+                aggregation_delta,
+                aggregation_padding_sensitivity,
+            )?;
+        }
+        Ok(())
     }
 }
 
@@ -269,6 +324,165 @@ where
     }
 }
 
+impl<FV, TS, const FEATURES: usize> Paddable for FeatureLabelInputRow<FV, TS, FEATURES>
+where
+    FV: BooleanArray,
+    TS: BooleanArray,
+{
+    /// Dummy rows are source events with a random `match_key` and an all-zero feature vector,
+    /// so they never contribute to the dot product.
+    fn add_padding_items<V: Extend<Self>, const B: usize>(
+        direction_to_excluded_helper: Direction,
+        padding_input_rows: &mut V,
+        padding_params: &PaddingParameters,
+        rng: &mut InstrumentedSequentialSharedRandomness,
+    ) -> Result<u32, Error> {
+        let mut total_number_of_fake_rows = 0;
+        match padding_params.oprf_padding {
+            OPRFPadding::NoOPRFPadding => {}
+            OPRFPadding::Parameters {
+                oprf_epsilon,
+                oprf_delta,
+                matchkey_cardinality_cap,
+                oprf_padding_sensitivity,
+            } => {
+                let oprf_padding =
+                    OPRFPaddingDp::new(oprf_epsilon, oprf_delta, oprf_padding_sensitivity)?;
+                for cardinality in 1..=matchkey_cardinality_cap {
+                    let sample = oprf_padding.sample(rng);
+                    total_number_of_fake_rows += sample * cardinality;
+
+                    for _ in 0..sample {
+                        let dummy_mk: BA64 = rng.gen();
+                        for _ in 0..cardinality {
+                            let match_key = match direction_to_excluded_helper {
+                                Direction::Left => AdditiveShare::new(BA64::ZERO, dummy_mk),
+                                Direction::Right => AdditiveShare::new(dummy_mk, BA64::ZERO),
+                            };
+                            let row = FeatureLabelInputRow {
+                                match_key,
+                                ..Default::default()
+                            };
+                            padding_input_rows.extend(std::iter::once(row));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(total_number_of_fake_rows)
+    }
+
+    fn add_zero_shares<V: Extend<Self>>(
+        padding_input_rows: &mut V,
+        total_number_of_fake_rows: u32,
+    ) {
+        padding_input_rows.extend(
+            repeat_with(FeatureLabelInputRow::default).take(total_number_of_fake_rows as usize),
+        );
+    }
+}
+
+impl<const FEATURES: usize> Paddable for LogisticRegressionInputRow<FEATURES> {
+    /// Dummy rows have a random `match_key`, an all-zero feature vector and a zero label, so
+    /// their gradient contribution is always zero.
+    fn add_padding_items<V: Extend<Self>, const B: usize>(
+        direction_to_excluded_helper: Direction,
+        padding_input_rows: &mut V,
+        padding_params: &PaddingParameters,
+        rng: &mut InstrumentedSequentialSharedRandomness,
+    ) -> Result<u32, Error> {
+        let mut total_number_of_fake_rows = 0;
+        match padding_params.oprf_padding {
+            OPRFPadding::NoOPRFPadding => {}
+            OPRFPadding::Parameters {
+                oprf_epsilon,
+                oprf_delta,
+                matchkey_cardinality_cap,
+                oprf_padding_sensitivity,
+            } => {
+                let oprf_padding =
+                    OPRFPaddingDp::new(oprf_epsilon, oprf_delta, oprf_padding_sensitivity)?;
+                for cardinality in 1..=matchkey_cardinality_cap {
+                    let sample = oprf_padding.sample(rng);
+                    total_number_of_fake_rows += sample * cardinality;
+
+                    for _ in 0..sample {
+                        let dummy_mk: BA64 = rng.gen();
+                        for _ in 0..cardinality {
+                            let match_key = match direction_to_excluded_helper {
+                                Direction::Left => AdditiveShare::new(BA64::ZERO, dummy_mk),
+                                Direction::Right => AdditiveShare::new(dummy_mk, BA64::ZERO),
+                            };
+                            let row = LogisticRegressionInputRow {
+                                match_key,
+                                ..Default::default()
+                            };
+                            padding_input_rows.extend(std::iter::once(row));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(total_number_of_fake_rows)
+    }
+
+    fn add_zero_shares<V: Extend<Self>>(
+        padding_input_rows: &mut V,
+        total_number_of_fake_rows: u32,
+    ) {
+        padding_input_rows.extend(
+            repeat_with(LogisticRegressionInputRow::default)
+                .take(total_number_of_fake_rows as usize),
+        );
+    }
+}
+
+impl<BK, TV, TS> Paddable for ReachPaddingRow<BK, TV, TS>
+where
+    BK: BooleanArray + U128Conversions,
+    TV: BooleanArray,
+    TS: BooleanArray,
+{
+    /// Dummy rows are the dummy rows of `OPRFIPAInputRow`, turned into trigger events so that
+    /// they are never counted as impressions.
+    fn add_padding_items<V: Extend<Self>, const B: usize>(
+        direction_to_excluded_helper: Direction,
+        padding_input_rows: &mut V,
+        padding_params: &PaddingParameters,
+        rng: &mut InstrumentedSequentialSharedRandomness,
+    ) -> Result<u32, Error> {
+        let mut rows = Vec::new();
+        let total_number_of_fake_rows = OPRFIPAInputRow::<BK, TV, TS>::add_padding_items::<_, B>(
+            direction_to_excluded_helper,
+            &mut rows,
+            padding_params,
+            rng,
+        )?;
+
+        let is_trigger = match direction_to_excluded_helper {
+            Direction::Left => AdditiveShare::new(Boolean::FALSE, Boolean::TRUE),
+            Direction::Right => AdditiveShare::new(Boolean::TRUE, Boolean::FALSE),
+        };
+        padding_input_rows.extend(rows.into_iter().map(|row| {
+            ReachPaddingRow(OPRFIPAInputRow {
+                is_trigger: is_trigger.clone(),
+                ..row
+            })
+        }));
+
+        Ok(total_number_of_fake_rows)
+    }
+
+    fn add_zero_shares<V: Extend<Self>>(
+        padding_input_rows: &mut V,
+        total_number_of_fake_rows: u32,
+    ) {
+        let mut rows = Vec::new();
+        OPRFIPAInputRow::<BK, TV, TS>::add_zero_shares(&mut rows, total_number_of_fake_rows);
+        padding_input_rows.extend(rows.into_iter().map(ReachPaddingRow));
+    }
+}
+
 impl<BK, TV> Paddable for AttributionOutputs<AdditiveShare<BK>, AdditiveShare<TV>>
 where
     BK: BooleanArray + U128Conversions,
@@ -488,7 +702,7 @@ mod tests {
             ipa_prf::{
                 oprf_padding::{
                     apply_dp_padding_pass, insecure, insecure::OPRFPaddingDp, AggregationPadding,
-                    OPRFPadding, PaddingParameters,
+                    OPRFPadding, PaddingGeneration, PaddingParameters,
                 },
                 prf_sharding::{tests::PreAggregationTestOutputInDecimal, AttributionOutputs},
                 OPRFIPAInputRow,
@@ -568,6 +782,7 @@ mod tests {
                         oprf_padding_sensitivity,
                     },
                     aggregation_padding: AggregationPadding::NoAggPadding,
+                    padding_generation: PaddingGeneration::Pairwise,
                 };
                 set_up_apply_dp_padding_pass_for_oprf::<_, BK, TV, TS, B>(ctx, padding_params).await
             })
@@ -644,6 +859,7 @@ mod tests {
                         oprf_padding_sensitivity,
                     },
                     aggregation_padding: AggregationPadding::NoAggPadding,
+                    padding_generation: PaddingGeneration::Pairwise,
                 };
                 set_up_apply_dp_padding_pass_for_indistinguishable_reports::<_, BK, V, B>(
                     ctx,
@@ -735,6 +951,7 @@ mod tests {
                         aggregation_delta,
                         aggregation_padding_sensitivity,
                     },
+                    padding_generation: PaddingGeneration::Pairwise,
                 };
                 set_up_apply_dp_padding_pass_for_agg::<_, BK, TV, B>(ctx, padding_params).await
             })
@@ -868,6 +1085,7 @@ mod tests {
                                 matchkey_cardinality_cap,
                                 oprf_padding_sensitivity: 2,
                             },
+                            padding_generation: PaddingGeneration::Pairwise,
                         };
                         // Call the function to get expected number of fake rows
                         let (expected_oprf_total_rows, expected_agg_total_rows) =
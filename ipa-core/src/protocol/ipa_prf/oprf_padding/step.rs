@@ -9,6 +9,29 @@ pub(crate) enum PaddingDpStep {
     PaddingDpPass2,
     #[step(child = crate::protocol::ipa_prf::oprf_padding::step::SendTotalRows)]
     PaddingDpPass3,
+    #[step(child = crate::protocol::ipa_prf::oprf_padding::step::JointPaddingStep)]
+    JointPadding,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    JointPaddingValidate,
+    #[step(child = crate::protocol::ipa_prf::oprf_padding::step::JointAggregationPaddingStep)]
+    JointAggregationPadding,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    JointAggregationPaddingValidate,
+}
+
+#[derive(CompactStep)]
+pub(crate) enum JointPaddingStep {
+    GroupMatchKey,
+    KeepGroup,
+    RowMatchKey,
+    Select,
+}
+
+#[derive(CompactStep)]
+pub(crate) enum JointAggregationPaddingStep {
+    KeepRow,
+    RandomBreakdownKey,
+    Select,
 }
 
 #[derive(CompactStep)]
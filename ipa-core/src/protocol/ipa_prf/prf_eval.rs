@@ -19,7 +19,7 @@ use crate::{
         replicated::{malicious, semi_honest::AdditiveShare},
         FieldSimd, Vectorizable,
     },
-    sharding::NotSharded,
+    sharding::ShardBinding,
 };
 
 /// This trait defines the requirements to the sharing types and the underlying fields
@@ -36,26 +36,26 @@ pub trait PrfSharing<C: UpgradedContext, const N: usize>:
 }
 
 /// Allow semi-honest shares to be used for PRF generation
-impl<'a, const N: usize> PrfSharing<UpgradedSemiHonestContext<'a, NotSharded, Fp25519>, N>
+impl<'a, B: ShardBinding, const N: usize> PrfSharing<UpgradedSemiHonestContext<'a, B, Fp25519>, N>
     for AdditiveShare<Fp25519, N>
 where
     Fp25519: FieldSimd<N>,
     RP25519: Vectorizable<N>,
     AdditiveShare<Fp25519, N>:
-        BasicProtocols<UpgradedSemiHonestContext<'a, NotSharded, Fp25519>, Fp25519, N> + FromPrss,
+        BasicProtocols<UpgradedSemiHonestContext<'a, B, Fp25519>, Fp25519, N> + FromPrss,
 {
     type Field = Fp25519;
     type UpgradedSharing = AdditiveShare<Fp25519, N>;
 }
 
 /// Allow MAC-malicious shares to be used for PRF generation
-impl<'a, const N: usize> PrfSharing<UpgradedMaliciousContext<'a, Fp25519>, N>
+impl<'a, B: ShardBinding, const N: usize> PrfSharing<UpgradedMaliciousContext<'a, Fp25519, B>, N>
     for AdditiveShare<Fp25519, N>
 where
     Fp25519: FieldSimd<N>,
     RP25519: Vectorizable<N>,
     malicious::AdditiveShare<Fp25519, N>:
-        BasicProtocols<UpgradedMaliciousContext<'a, Fp25519>, Fp25519, N>,
+        BasicProtocols<UpgradedMaliciousContext<'a, Fp25519, B>, Fp25519, N>,
     AdditiveShare<Fp25519, N>: FromPrss,
 {
     type Field = Fp25519;
@@ -149,7 +149,7 @@ mod test {
     use crate::{
         error::Error,
         ff::{curve_points::RP25519, ec_prime_field::Fp25519},
-        helpers::{in_memory_config::MaliciousHelper, Role},
+        helpers::{self, in_memory_config::MaliciousHelper, Role},
         protocol::{
             basics::Reveal,
             context::{Context, MacUpgraded, UpgradableContext, Validator},
@@ -305,14 +305,12 @@ mod test {
 
     #[test]
     fn malicious_attack_resistant() {
-        const STEPS: [&PrfStep; 3] = [
+        const STEPS: [&PrfStep; 5] = [
             &PrfStep::UpgradeY,
             &PrfStep::UpgradeMask,
             &PrfStep::MultMaskWithPRFInput,
-            // TODO errors during malicious reveal does not terminate the execution, some helpers
-            // make progress and get stuck waiting for messages.
-            // &PrfStep::RevealR,
-            // &PrfStep::Revealz,
+            &PrfStep::RevealR,
+            &PrfStep::Revealz,
         ];
         run(|| async move {
             for attacker_role in Role::all() {
@@ -337,7 +335,9 @@ mod test {
 
                                 match compute_match_key_pseudonym(ctx, prf_key, match_key_shares).await {
                                     Ok(_) if my_role == *attacker_role => {}
-                                    Err(Error::MaliciousSecurityCheckFailed | Error::MaliciousRevealFailed) => {}
+                                    Err(Error::MaliciousSecurityCheckFailed(_) | Error::MaliciousRevealFailed) => {}
+                                    // helper that detected the failed reveal aborts the query
+                                    Err(Error::MpcInfraError(helpers::Error::Aborted { .. })) => {}
                                     Ok(_) | Err(_) => {
                                         panic!(
                                             "Malicious validation check passed when it shouldn't have"
@@ -4,35 +4,182 @@ use std::{
 };
 
 use futures::stream;
-use futures_util::{future::try_join, stream::unfold, Stream, StreamExt};
+use futures_util::{future::try_join, stream::unfold, Stream, StreamExt, TryStreamExt};
+use generic_array::GenericArray;
+use typenum::{Unsigned, U56};
 
 use crate::{
     error::{Error, LengthError, UnwrapInfallible},
-    ff::{boolean::Boolean, boolean_array::BooleanArray, Field, U128Conversions},
-    helpers::{stream::TryFlattenItersExt, TotalRecords},
+    ff::{
+        boolean::Boolean,
+        boolean_array::{BooleanArray, BA20, BA32, BA8},
+        curve_points::RP25519,
+        ec_prime_field::Fp25519,
+        Field, Serializable, U128Conversions,
+    },
+    helpers::{query::DpMechanism, stream::TryFlattenItersExt, TotalRecords},
     protocol::{
-        basics::{SecureMul, ShareKnownValue},
+        basics::{Reveal, SecureMul, ShareKnownValue},
         boolean::{and::bool_and_8_bit, or::or},
-        context::Context,
+        context::{
+            dzkp_validator::DZKPValidator, Context, DZKPUpgraded, MacUpgraded,
+            MaliciousProtocolSteps, UpgradableContext,
+        },
+        dp::dp_for_histogram,
         ipa_prf::{
             aggregation::aggregate_values,
-            prf_sharding::step::{FeatureLabelDotProductStep as Step, UserNthRowStep},
+            compute_prf_of_match_keys,
+            oprf_padding::{apply_dp_padding, PaddingParameters},
+            prf_eval::PrfSharing,
+            prf_sharding::{
+                histograms_ranges_sortkeys,
+                step::{
+                    FeatureLabelDotProductStep as Step, FeatureLabelPerRowStep as PerRowStep,
+                    FeatureLabelUserNthRowStep,
+                },
+                timestamp_sort_key, GroupingKey, SortKey,
+            },
+            quicksort::quicksort_ranges_by_key_insecure,
+            shuffle::{shuffle_feature_label_inputs, Shuffle},
+            step::IpaPrfStep,
+            MatchKey, CONV_CHUNK, PRF_CHUNK, SORT_CHUNK,
         },
+        prss::FromPrss,
         BooleanProtocols, RecordId,
     },
     secret_sharing::{
         replicated::semi_honest::{AdditiveShare as Replicated, AdditiveShare},
-        BitDecomposed, FieldSimd, SharedValue, TransposeFrom,
+        BitDecomposed, FieldSimd, SharedValue, TransposeFrom, Vectorizable,
     },
     seq_join::seq_join,
 };
 
+/// Input row of the feature-label dot product query.
+///
+/// Source events carry a vector of `B` features. Trigger events carry the label, which is
+/// implied by their presence, so their feature vector is ignored.
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct FeatureLabelInputRow<FV: SharedValue, TS: SharedValue, const B: usize> {
+    pub match_key: Replicated<MatchKey>,
+    pub is_trigger: Replicated<Boolean>,
+    pub timestamp: Replicated<TS>,
+    pub feature_vector: [Replicated<FV>; B],
+}
+
+impl<FV: SharedValue, TS: SharedValue, const B: usize> Default for FeatureLabelInputRow<FV, TS, B> {
+    fn default() -> Self {
+        Self {
+            match_key: Replicated::ZERO,
+            is_trigger: Replicated::ZERO,
+            timestamp: Replicated::ZERO,
+            feature_vector: [Replicated::ZERO; B],
+        }
+    }
+}
+
+/// The feature-label dot product query reads 16 features of 8 bits each, and 20 bit timestamps.
+impl Serializable for FeatureLabelInputRow<BA8, BA20, 16> {
+    type Size = U56;
+    type DeserializationError = Error;
+
+    fn serialize(&self, buf: &mut GenericArray<u8, Self::Size>) {
+        let mk_sz = <Replicated<MatchKey> as Serializable>::Size::USIZE;
+        let ts_sz = <Replicated<BA20> as Serializable>::Size::USIZE;
+        let it_sz = <Replicated<Boolean> as Serializable>::Size::USIZE;
+        let fv_sz = <Replicated<BA8> as Serializable>::Size::USIZE;
+
+        self.match_key
+            .serialize(GenericArray::from_mut_slice(&mut buf[..mk_sz]));
+        self.timestamp
+            .serialize(GenericArray::from_mut_slice(&mut buf[mk_sz..mk_sz + ts_sz]));
+        self.is_trigger.serialize(GenericArray::from_mut_slice(
+            &mut buf[mk_sz + ts_sz..mk_sz + ts_sz + it_sz],
+        ));
+
+        let features = &mut buf[mk_sz + ts_sz + it_sz..];
+        for (feature, chunk) in zip(&self.feature_vector, features.chunks_mut(fv_sz)) {
+            feature.serialize(GenericArray::from_mut_slice(chunk));
+        }
+    }
+
+    fn deserialize(buf: &GenericArray<u8, Self::Size>) -> Result<Self, Self::DeserializationError> {
+        let mk_sz = <Replicated<MatchKey> as Serializable>::Size::USIZE;
+        let ts_sz = <Replicated<BA20> as Serializable>::Size::USIZE;
+        let it_sz = <Replicated<Boolean> as Serializable>::Size::USIZE;
+        let fv_sz = <Replicated<BA8> as Serializable>::Size::USIZE;
+
+        let match_key =
+            Replicated::<MatchKey>::deserialize(GenericArray::from_slice(&buf[..mk_sz]))
+                .unwrap_infallible();
+        let timestamp =
+            Replicated::<BA20>::deserialize(GenericArray::from_slice(&buf[mk_sz..mk_sz + ts_sz]))
+                .map_err(|e| Error::ParseError(e.into()))?;
+        let is_trigger = Replicated::<Boolean>::deserialize(GenericArray::from_slice(
+            &buf[mk_sz + ts_sz..mk_sz + ts_sz + it_sz],
+        ))
+        .map_err(|e| Error::ParseError(e.into()))?;
+
+        let mut feature_vector = [Replicated::<BA8>::ZERO; 16];
+        let features = &buf[mk_sz + ts_sz + it_sz..];
+        for (feature, chunk) in zip(&mut feature_vector, features.chunks(fv_sz)) {
+            *feature =
+                Replicated::<BA8>::deserialize(GenericArray::from_slice(chunk)).unwrap_infallible();
+        }
+
+        Ok(Self {
+            match_key,
+            is_trigger,
+            timestamp,
+            feature_vector,
+        })
+    }
+}
+
 pub struct PrfShardedIpaInputRow<FV: SharedValue, const B: usize> {
     prf_of_match_key: u64,
     is_trigger_bit: Replicated<Boolean>,
     feature_vector: [Replicated<FV>; B],
 }
 
+/// Row of the feature-label dot product query after the PRF of the match key is revealed,
+/// while it still has to be sorted by timestamp.
+struct PrfdFeatureLabelInputRow<FV: SharedValue, TS: SharedValue, const B: usize> {
+    prf_of_match_key: u64,
+    is_trigger_bit: Replicated<Boolean>,
+    timestamp: Replicated<TS>,
+    feature_vector: [Replicated<FV>; B],
+    sort_key: Replicated<BA32>,
+}
+
+impl<FV: SharedValue, TS: SharedValue, const B: usize> GroupingKey
+    for PrfdFeatureLabelInputRow<FV, TS, B>
+{
+    fn get_grouping_key(&self) -> u64 {
+        self.prf_of_match_key
+    }
+}
+
+impl<FV: SharedValue, TS: BooleanArray, const B: usize> SortKey
+    for PrfdFeatureLabelInputRow<FV, TS, B>
+{
+    fn compute_sort_key(&mut self, counter: u64) {
+        self.sort_key = timestamp_sort_key(&self.is_trigger_bit, &self.timestamp, counter);
+    }
+}
+
+impl<FV: SharedValue, TS: SharedValue, const B: usize> From<PrfdFeatureLabelInputRow<FV, TS, B>>
+    for PrfShardedIpaInputRow<FV, B>
+{
+    fn from(row: PrfdFeatureLabelInputRow<FV, TS, B>) -> Self {
+        Self {
+            prf_of_match_key: row.prf_of_match_key,
+            is_trigger_bit: row.is_trigger_bit,
+            feature_vector: row.feature_vector,
+        }
+    }
+}
+
 struct InputsRequiredFromPrevRow {
     ever_encountered_a_trigger_event: Replicated<Boolean>,
     is_saturated: Replicated<Boolean>,
@@ -75,14 +222,14 @@ impl InputsRequiredFromPrevRow {
 
         let (ever_encountered_a_trigger_event, did_source_get_attributed) = try_join(
             or(
-                ctx.narrow(&Step::EverEncounteredTriggerEvent),
+                ctx.narrow(&PerRowStep::EverEncounteredTriggerEvent),
                 record_id,
                 &input_row.is_trigger_bit,
                 &self.ever_encountered_a_trigger_event,
             ),
             is_source_event.multiply(
                 &self.ever_encountered_a_trigger_event,
-                ctx.narrow(&Step::DidSourceReceiveAttribution),
+                ctx.narrow(&PerRowStep::DidSourceReceiveAttribution),
                 record_id,
             ),
         )
@@ -90,14 +237,14 @@ impl InputsRequiredFromPrevRow {
 
         let (updated_is_saturated, capped_label) = try_join(
             or(
-                ctx.narrow(&Step::ComputeSaturatingSum),
+                ctx.narrow(&PerRowStep::ComputeSaturatingSum),
                 record_id,
                 &self.is_saturated,
                 &did_source_get_attributed,
             ),
             did_source_get_attributed.multiply(
                 &(share_of_one - &self.is_saturated),
-                ctx.narrow(&Step::IsAttributedSourceAndPrevRowNotSaturated),
+                ctx.narrow(&PerRowStep::IsAttributedSourceAndPrevRowNotSaturated),
                 record_id,
             ),
         )
@@ -107,7 +254,7 @@ impl InputsRequiredFromPrevRow {
         let bit_decomposed_output =
             BitDecomposed::transposed_from(&input_row.feature_vector).unwrap_infallible();
         let capped_attributed_feature_vector = bool_and_8_bit(
-            ctx,
+            ctx.narrow(&PerRowStep::ComputedCappedFeatureVector),
             record_id,
             &bit_decomposed_output,
             repeat_n(&condition, FV::BITS.try_into().unwrap()),
@@ -133,7 +280,7 @@ where
         } else {
             let total_records = TotalRecords::specified(*num_users_having_that_row_number)?;
             let ctx_for_row_number = root_ctx
-                .narrow(&UserNthRowStep::from(row_number))
+                .narrow(&FeatureLabelUserNthRowStep::from(row_number))
                 .set_total_records(total_records);
             context_per_row_depth.push(ctx_for_row_number);
         }
@@ -220,15 +367,13 @@ where
     Vec<Replicated<HV>>:
         for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
 {
-    // Get the validator and context to use for Boolean multiplication operations
-    let binary_m_ctx = sh_ctx.narrow(&Step::BinaryValidator);
-
     // Tricky hacks to work around the limitations of our current infrastructure
     // There will be 0 outputs for users with just one row.
     // There will be 1 output for users with at least 2 rows.
     // So we just use the number of users having at least 2 rows.
     let num_outputs = users_having_n_records[1];
-    let ctx_for_row_number = set_up_contexts(&binary_m_ctx, users_having_n_records)?;
+    let ctx_for_row_number =
+        set_up_contexts(&sh_ctx.narrow(&Step::Attribute), users_having_n_records)?;
 
     // Chunk the incoming stream of records into stream of vectors of records with the same PRF
     let mut input_stream = stream::iter(input_rows);
@@ -257,11 +402,20 @@ where
 
     // Execute all of the async futures (sequentially), and flatten the result
     // The call to `try_flatten_iters` only serves to eliminate the "Option" wrapping, and filter out `None` elements
-    let flattened_stream = Box::pin(
-        seq_join(sh_ctx.active_work(), stream::iter(chunked_user_results)).try_flatten_iters(),
-    );
-    let aggregated_result: BitDecomposed<AdditiveShare<Boolean, B>> =
-        aggregate_values::<_, HV, B>(binary_m_ctx, flattened_stream, num_outputs, None).await?;
+    // The attribution outputs are collected before aggregating them, because aggregation does not
+    // poll the attribution stream while it waits on its own multiplications, which can stall
+    // helpers that are further ahead in the attribution.
+    let user_contributions = seq_join(sh_ctx.active_work(), stream::iter(chunked_user_results))
+        .try_flatten_iters()
+        .try_collect::<Vec<_>>()
+        .await?;
+    let aggregated_result: BitDecomposed<AdditiveShare<Boolean, B>> = aggregate_values::<_, HV, B>(
+        sh_ctx.narrow(&Step::Aggregate),
+        Box::pin(stream::iter(user_contributions).map(Ok)),
+        num_outputs,
+        None,
+    )
+    .await?;
 
     let transposed_aggregated_result: Vec<Replicated<HV>> =
         Vec::transposed_from(&aggregated_result)?;
@@ -325,6 +479,128 @@ where
     }
 }
 
+/// Feature-label dot product protocol
+///
+/// The output of this function is a vector of secret-shared sums, one per feature. Each sum
+/// adds up the corresponding feature of every source event that received attribution.
+///
+/// This protocol performs the following steps
+/// 1. Adds dummy records for the OPRF padding (see [`apply_dp_padding`])
+/// 2. Shuffles the input
+/// 3. Computes an OPRF of the match keys and reveals this "pseudonym"
+/// 4. Groups together rows with the same OPRF, and then obliviously sorts each group by the
+///    secret-shared timestamp, most recent event first
+/// 5. Attributes trigger events to source events and caps each user's contribution to a
+///    single feature vector (see [`compute_feature_label_dot_product`])
+/// 6. Adds random noise to the sum of each feature (to provide a differential privacy
+///    guarantee)
+///
+/// Every user contributes at most one feature vector, so `2^SS_BITS` must be large enough to hold
+/// the sum of the largest possible feature vector, which bounds the sensitivity of the output.
+///
+/// # Errors
+/// Propagates errors from config issues or while running the protocol
+/// # Panics
+/// If `2^SS_BITS` is smaller than the sum of a feature vector with all features saturated.
+#[tracing::instrument(name = "feature_label_dot_product", skip_all, fields(rows = input_rows.len()))]
+pub async fn feature_label_dot_product<'ctx, C, FV, TS, HV, const SS_BITS: usize, const B: usize>(
+    ctx: C,
+    input_rows: Vec<FeatureLabelInputRow<FV, TS, B>>,
+    dp_params: DpMechanism,
+    dp_padding_params: PaddingParameters,
+) -> Result<Vec<Replicated<HV>>, Error>
+where
+    C: UpgradableContext + Shuffle + 'ctx,
+    FV: BooleanArray,
+    TS: BooleanArray,
+    HV: BooleanArray + U128Conversions,
+    Boolean: FieldSimd<B>,
+    Replicated<Boolean>: BooleanProtocols<DZKPUpgraded<C>>,
+    Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
+    Replicated<Boolean, CONV_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, CONV_CHUNK>,
+    Replicated<Boolean, SORT_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, SORT_CHUNK>,
+    Replicated<Fp25519, PRF_CHUNK>:
+        PrfSharing<MacUpgraded<C, Fp25519>, PRF_CHUNK, Field = Fp25519> + FromPrss,
+    Replicated<RP25519, PRF_CHUNK>:
+        Reveal<MacUpgraded<C, Fp25519>, Output = <RP25519 as Vectorizable<PRF_CHUNK>>::Array>,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<FV>; B], Error = Infallible>,
+    Vec<Replicated<HV>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<HV>; B], Error = Infallible>,
+{
+    let max_feature_vector_sum = u128::try_from(B).unwrap() * ((1 << FV::BITS) - 1);
+    assert!(
+        max_feature_vector_sum <= 1 << SS_BITS,
+        "SS_BITS = {SS_BITS} does not bound the sum of {B} features of {} bits",
+        FV::BITS,
+    );
+
+    if input_rows.is_empty() {
+        return Ok(vec![Replicated::ZERO; B]);
+    }
+
+    let padded_input_rows = apply_dp_padding::<_, FeatureLabelInputRow<FV, TS, B>, B>(
+        ctx.narrow(&IpaPrfStep::PaddingDp),
+        input_rows,
+        &dp_padding_params,
+    )
+    .await?;
+
+    let shuffled =
+        shuffle_feature_label_inputs(ctx.narrow(&IpaPrfStep::Shuffle), padded_input_rows).await?;
+    let prf_of_match_keys =
+        compute_prf_of_match_keys(ctx.clone(), &shuffled, |row| &row.match_key).await?;
+
+    let mut prfd_inputs = zip(shuffled, prf_of_match_keys)
+        .map(|(row, prf_of_match_key)| PrfdFeatureLabelInputRow {
+            prf_of_match_key,
+            is_trigger_bit: row.is_trigger,
+            timestamp: row.timestamp,
+            feature_vector: row.feature_vector,
+            sort_key: Replicated::ZERO,
+        })
+        .collect::<Vec<_>>();
+    prfd_inputs.sort_by_key(|r| r.prf_of_match_key);
+
+    let (users_having_n_records, ranges) = histograms_ranges_sortkeys(&mut prfd_inputs);
+    if users_having_n_records.len() == 1 {
+        // No user has more than one record.
+        return Ok(vec![Replicated::ZERO; B]);
+    }
+    quicksort_ranges_by_key_insecure(
+        ctx.narrow(&IpaPrfStep::SortByTimestamp),
+        &mut prfd_inputs,
+        true,
+        |x| &x.sort_key,
+        ranges,
+    )
+    .await?;
+
+    let validator = ctx.clone().dzkp_validator(
+        MaliciousProtocolSteps {
+            protocol: &IpaPrfStep::FeatureLabelDotProduct,
+            validate: &IpaPrfStep::FeatureLabelDotProductValidate,
+        },
+        users_having_n_records[1],
+    );
+    let dot_product = compute_feature_label_dot_product::<_, FV, HV, B>(
+        validator.context(),
+        prfd_inputs.into_iter().map(Into::into).collect(),
+        &users_having_n_records,
+    )
+    .await?;
+    validator.validate().await?;
+
+    dp_for_histogram::<_, B, HV, SS_BITS>(
+        ctx,
+        BitDecomposed::transposed_from(&dot_product).unwrap_infallible(),
+        dp_params,
+    )
+    .await
+}
+
 #[cfg(all(test, unit_test))]
 pub mod tests {
     use std::iter::zip;
@@ -334,18 +610,26 @@ pub mod tests {
     use crate::{
         ff::{
             boolean::Boolean,
-            boolean_array::{BA16, BA8},
+            boolean_array::{BA16, BA20, BA32, BA8},
             Field, U128Conversions,
         },
-        protocol::ipa_prf::prf_sharding::feature_label_dot_product::{
-            compute_feature_label_dot_product, PrfShardedIpaInputRow,
+        helpers::query::DpMechanism,
+        protocol::ipa_prf::{
+            oprf_padding::PaddingParameters,
+            prf_sharding::feature_label_dot_product::{
+                compute_feature_label_dot_product, feature_label_dot_product, FeatureLabelInputRow,
+                PrfShardedIpaInputRow,
+            },
         },
         rand::Rng,
         secret_sharing::{
             replicated::semi_honest::AdditiveShare as Replicated, IntoShares, SharedValue,
         },
         test_executor::run,
-        test_fixture::{Reconstruct, Runner, TestWorld},
+        test_fixture::{
+            feature_label::{feature_label_dot_product_in_the_clear, TestFeatureLabelRecord},
+            Reconstruct, Runner, TestWorld,
+        },
     };
 
     struct PreShardedAndSortedOPRFTestInput<FV, const B: usize> {
@@ -470,4 +754,63 @@ pub mod tests {
             assert_eq!(&result, &expected);
         });
     }
+
+    fn test_record(
+        timestamp: u64,
+        user_id: u64,
+        is_trigger_report: bool,
+        features: [u32; 16],
+    ) -> TestFeatureLabelRecord {
+        TestFeatureLabelRecord {
+            timestamp,
+            user_id,
+            is_trigger_report,
+            features: features.to_vec(),
+        }
+    }
+
+    fn random_features<R: Rng>(rng: &mut R) -> [u32; 16] {
+        std::array::from_fn(|_| rng.gen_range(0..256))
+    }
+
+    #[test]
+    fn semi_honest_end_to_end() {
+        run(|| async move {
+            let world = TestWorld::default();
+
+            let mut rng = thread_rng();
+            let records = vec![
+                test_record(0, 12345, false, random_features(&mut rng)),
+                test_record(5, 12345, false, random_features(&mut rng)),
+                test_record(10, 12345, true, [0; 16]),
+                test_record(0, 68362, false, random_features(&mut rng)),
+                test_record(20, 68362, true, [0; 16]),
+                test_record(30, 68362, false, random_features(&mut rng)),
+                test_record(3, 77777, false, random_features(&mut rng)),
+            ];
+            let expected = feature_label_dot_product_in_the_clear(&records, 16);
+
+            let result = world
+                .semi_honest(
+                    records.into_iter(),
+                    |ctx, input_rows: Vec<FeatureLabelInputRow<BA8, BA20, 16>>| async move {
+                        feature_label_dot_product::<_, BA8, BA20, BA32, 12, 16>(
+                            ctx,
+                            input_rows,
+                            DpMechanism::NoDp,
+                            PaddingParameters::relaxed(),
+                        )
+                        .await
+                        .unwrap()
+                    },
+                )
+                .await
+                .reconstruct()
+                .iter()
+                .map(|v| u32::try_from(v.as_u128()).unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(result, expected);
+        });
+    }
 }
@@ -0,0 +1,472 @@
+use std::{convert::Infallible, num::NonZeroU32};
+
+use futures::stream;
+use futures_util::TryStreamExt;
+
+use crate::{
+    error::{Error, LengthError},
+    ff::{
+        boolean::Boolean, boolean_array::BooleanArray, curve_points::RP25519,
+        ec_prime_field::Fp25519, ArrayAccess, Field, U128Conversions,
+    },
+    helpers::{query::DpMechanism, TotalRecords},
+    protocol::{
+        basics::{BooleanArrayMul, BooleanProtocols, Reveal, ShareKnownValue},
+        boolean::{or::or, step::EightBitStep, NBitStep},
+        context::{
+            dzkp_validator::{validated_seq_join, DZKPValidator},
+            Context, DZKPUpgraded, MacUpgraded, MaliciousProtocolSteps, UpgradableContext,
+        },
+        dp::dp_for_histogram,
+        ipa_prf::{
+            aggregation::breakdown_reveal::breakdown_reveal_aggregation,
+            compute_prf_for_inputs,
+            oprf_padding::{apply_dp_padding, PaddingParameters},
+            prf_eval::PrfSharing,
+            prf_sharding::{
+                attribute_cap, histograms_ranges_sortkeys, step::LiftStep as Step,
+                AttributionOutputs, SecretSharedAttributionOutputs,
+            },
+            quicksort::quicksort_ranges_by_key_insecure,
+            shuffle::{shuffle_inputs, Shuffle},
+            step::IpaPrfStep,
+            BreakdownKey, OPRFIPAInputRow, AGG_CHUNK, CONV_CHUNK, PRF_CHUNK, SORT_CHUNK,
+        },
+        prss::FromPrss,
+        RecordId,
+    },
+    secret_sharing::{
+        replicated::semi_honest::AdditiveShare as Replicated, BitDecomposed, FieldSimd,
+        TransposeFrom, Vectorizable,
+    },
+};
+
+/// Number of values in the output of the lift query:
+/// `[control_total, control_count, test_total, test_count]`.
+pub const LIFT_OUTPUT_LEN: usize = 4;
+
+/// The totals and the counts are released separately, so each gets half of the privacy budget.
+pub const DP_RELEASES: u32 = 2;
+
+/// Computes the indicator of a conversion that was attributed and kept a non-zero value after
+/// capping, that is the OR of all bits of its capped trigger value.
+async fn conversion_indicator<C, TV>(
+    ctx: C,
+    record_id: RecordId,
+    capped_trigger_value: &Replicated<TV>,
+) -> Result<Replicated<Boolean>, Error>
+where
+    C: Context,
+    TV: BooleanArray,
+    Replicated<Boolean>: BooleanProtocols<C>,
+{
+    let mut indicator = capped_trigger_value.get(0).unwrap();
+    for i in 1..usize::try_from(TV::BITS).unwrap() {
+        indicator = or(
+            ctx.narrow(&EightBitStep::from(i)),
+            record_id,
+            &indicator,
+            &capped_trigger_value.get(i).unwrap(),
+        )
+        .await?;
+    }
+    Ok(indicator)
+}
+
+/// Splits an attributed conversion into a row that adds its capped value to the total of its arm,
+/// and a row that adds the conversion indicator to the count of its arm.
+///
+/// The arm is bit 0 of the attributed breakdown key. The new breakdown key of a row is
+/// `2 * arm + is_count`, which is the position of its value in the output of [`lift`].
+async fn split_by_arm<C, BK, TV>(
+    ctx: C,
+    record_id: RecordId,
+    attributed: SecretSharedAttributionOutputs<BK, TV>,
+) -> Result<[SecretSharedAttributionOutputs<BK, TV>; 2], Error>
+where
+    C: Context,
+    BK: BooleanArray,
+    TV: BooleanArray,
+    Replicated<Boolean>: BooleanProtocols<C>,
+{
+    let arm = attributed.attributed_breakdown_key_bits.get(0).unwrap();
+    let is_converted = conversion_indicator(
+        ctx.clone(),
+        record_id,
+        &attributed.capped_attributed_trigger_value,
+    )
+    .await?;
+
+    let bucket = |is_count: Replicated<Boolean>| {
+        let mut breakdown_key = Replicated::<BK>::ZERO;
+        breakdown_key.set(0, is_count);
+        breakdown_key.set(1, arm.clone());
+        breakdown_key
+    };
+    let mut count = Replicated::<TV>::ZERO;
+    count.set(0, is_converted);
+
+    Ok([
+        AttributionOutputs {
+            attributed_breakdown_key_bits: bucket(Replicated::ZERO),
+            capped_attributed_trigger_value: attributed.capped_attributed_trigger_value,
+        },
+        AttributionOutputs {
+            attributed_breakdown_key_bits: bucket(Replicated::share_known_value(
+                &ctx,
+                Boolean::ONE,
+            )),
+            capped_attributed_trigger_value: count,
+        },
+    ])
+}
+
+/// Aggregates the attributed conversions of each arm into the totals and counts of the lift
+/// query. Only the first [`LIFT_OUTPUT_LEN`] values of the output histogram are used.
+async fn aggregate_by_arm<C, BK, TV, HV, const B: usize>(
+    sh_ctx: C,
+    attributed_values: Vec<SecretSharedAttributionOutputs<BK, TV>>,
+    padding_params: &PaddingParameters,
+) -> Result<BitDecomposed<Replicated<Boolean, B>>, Error>
+where
+    C: UpgradableContext + Shuffle,
+    BK: BreakdownKey<B>,
+    TV: BooleanArray + U128Conversions,
+    HV: BooleanArray + U128Conversions,
+    Boolean: FieldSimd<B>,
+    Replicated<Boolean>: BooleanProtocols<DZKPUpgraded<C>>,
+    Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
+    Replicated<BK>: BooleanArrayMul<DZKPUpgraded<C>>
+        + Reveal<DZKPUpgraded<C>, Output = <BK as Vectorizable<1>>::Array>,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<TV>; B], Error = Infallible>,
+{
+    let total_records = TotalRecords::specified(attributed_values.len())?;
+    let mut validator = sh_ctx.clone().dzkp_validator(
+        MaliciousProtocolSteps {
+            protocol: &Step::ConversionIndicator,
+            validate: &Step::ConversionIndicatorValidate,
+        },
+        sh_ctx.active_work().get(),
+    );
+    validator.set_total_records(total_records);
+    let ctx = validator.context().set_total_records(total_records);
+
+    let rows =
+        validated_seq_join(
+            validator,
+            stream::iter(attributed_values.into_iter().enumerate().map(
+                |(record_id, attributed)| {
+                    split_by_arm(ctx.clone(), RecordId::from(record_id), attributed)
+                },
+            )),
+        )
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    breakdown_reveal_aggregation::<_, BK, TV, HV, B>(
+        sh_ctx.narrow(&Step::Aggregate),
+        rows.into_iter().flatten().collect(),
+        padding_params,
+    )
+    .await
+}
+
+/// Lift measurement protocol
+///
+/// Lift studies compare the conversions of users in a test group, that were shown an ad, to the
+/// conversions of users in a control group, that were not. The treatment arm of every source
+/// event is carried in bit 0 of its breakdown key (0 for control, 1 for test), and the remaining
+/// bits are ignored.
+///
+/// Attribution and per-user capping are the same as in [`oprf_ipa`]. The attributed conversions
+/// are then aggregated separately for each arm, and the output is
+/// `[control_total, control_count, test_total, test_count]`, where the total is the sum of the
+/// capped trigger values and the count is the number of conversions that kept a non-zero value
+/// after capping.
+///
+/// A user contributes at most `2^SS_BITS` to the total and to the count of the arms, so each of
+/// them uses half of the privacy budget.
+///
+/// [`oprf_ipa`]: crate::protocol::ipa_prf::oprf_ipa
+///
+/// # Errors
+/// Propagates errors from config issues or while running the protocol
+/// # Panics
+/// If `B` is smaller than [`LIFT_OUTPUT_LEN`], or if `TV` has more bits than supported by the
+/// conversion count.
+#[tracing::instrument(name = "lift", skip_all, fields(rows = input_rows.len()))]
+pub async fn lift<'ctx, C, BK, TV, HV, TS, const SS_BITS: usize, const B: usize>(
+    ctx: C,
+    input_rows: Vec<OPRFIPAInputRow<BK, TV, TS>>,
+    attribution_window_seconds: Option<NonZeroU32>,
+    dp_params: DpMechanism,
+    dp_padding_params: PaddingParameters,
+) -> Result<Vec<Replicated<HV>>, Error>
+where
+    C: UpgradableContext + Shuffle + 'ctx,
+    BK: BreakdownKey<B>,
+    TV: BooleanArray + U128Conversions,
+    HV: BooleanArray + U128Conversions,
+    TS: BooleanArray + U128Conversions,
+    Boolean: FieldSimd<B>,
+    Replicated<Boolean>: BooleanProtocols<DZKPUpgraded<C>>,
+    Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
+    Replicated<Boolean, AGG_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, AGG_CHUNK>,
+    Replicated<Boolean, CONV_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, CONV_CHUNK>,
+    Replicated<Boolean, SORT_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, SORT_CHUNK>,
+    Replicated<Fp25519, PRF_CHUNK>:
+        PrfSharing<MacUpgraded<C, Fp25519>, PRF_CHUNK, Field = Fp25519> + FromPrss,
+    Replicated<RP25519, PRF_CHUNK>:
+        Reveal<MacUpgraded<C, Fp25519>, Output = <RP25519 as Vectorizable<PRF_CHUNK>>::Array>,
+    Replicated<BK>: BooleanArrayMul<DZKPUpgraded<C>>
+        + Reveal<DZKPUpgraded<C>, Output = <BK as Vectorizable<1>>::Array>,
+    Replicated<TS>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<TV>: BooleanArrayMul<DZKPUpgraded<C>>,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<TV>; B], Error = Infallible>,
+    Vec<Replicated<HV>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<HV>; B], Error = Infallible>,
+{
+    assert!(
+        B >= LIFT_OUTPUT_LEN,
+        "lift needs at least {LIFT_OUTPUT_LEN} breakdowns, but B = {B}"
+    );
+    assert!(
+        TV::BITS <= EightBitStep::BITS,
+        "Up to {max_bits} bit trigger values are supported, but TV has {bits} bits",
+        max_bits = EightBitStep::BITS,
+        bits = TV::BITS,
+    );
+
+    if input_rows.is_empty() {
+        return Ok(vec![Replicated::ZERO; LIFT_OUTPUT_LEN]);
+    }
+
+    let padded_input_rows = apply_dp_padding::<_, OPRFIPAInputRow<BK, TV, TS>, B>(
+        ctx.narrow(&IpaPrfStep::PaddingDp),
+        input_rows,
+        &dp_padding_params,
+    )
+    .await?;
+
+    let shuffled = shuffle_inputs(ctx.narrow(&IpaPrfStep::Shuffle), padded_input_rows).await?;
+    let mut prfd_inputs = compute_prf_for_inputs(ctx.clone(), &shuffled).await?;
+    prfd_inputs.sort_by_key(|row| row.prf_of_match_key);
+
+    let (row_count_histogram, ranges) = histograms_ranges_sortkeys(&mut prfd_inputs);
+    if row_count_histogram.len() == 1 {
+        // No user has more than one record.
+        return Ok(vec![Replicated::ZERO; LIFT_OUTPUT_LEN]);
+    }
+    quicksort_ranges_by_key_insecure(
+        ctx.narrow(&IpaPrfStep::SortByTimestamp),
+        &mut prfd_inputs,
+        false,
+        |x| &x.sort_key,
+        ranges,
+    )
+    .await?;
+
+    let attributed_values = attribute_cap::<_, BK, TV, TS, SS_BITS, B>(
+        ctx.narrow(&IpaPrfStep::Attribution),
+        prfd_inputs,
+        attribution_window_seconds,
+        None,
+        &row_count_histogram,
+    )
+    .await?;
+    let histogram = aggregate_by_arm::<_, BK, TV, HV, B>(
+        ctx.narrow(&IpaPrfStep::Lift),
+        attributed_values,
+        &dp_padding_params,
+    )
+    .await?;
+
+    let mut noisy_histogram =
+        dp_for_histogram::<_, B, HV, SS_BITS>(ctx, histogram, dp_params.split_budget(DP_RELEASES))
+            .await?;
+    noisy_histogram.truncate(LIFT_OUTPUT_LEN);
+
+    Ok(noisy_histogram)
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use crate::{
+        ff::{
+            boolean_array::{BA16, BA20, BA3, BA5},
+            U128Conversions,
+        },
+        helpers::query::DpMechanism,
+        protocol::ipa_prf::{
+            oprf_padding::PaddingParameters, prf_sharding::lift::lift, OPRFIPAInputRow,
+        },
+        test_executor::run,
+        test_fixture::{
+            ipa::TestRawDataRecord, lift::lift_in_the_clear, Reconstruct, Runner, TestWorld,
+        },
+    };
+
+    /// Per-user cap of the tests, `2^SS_BITS`.
+    const PER_USER_CAP: u32 = 8;
+
+    fn test_input(
+        timestamp: u64,
+        user_id: u64,
+        is_trigger_report: bool,
+        breakdown_key: u32,
+        trigger_value: u32,
+    ) -> TestRawDataRecord {
+        TestRawDataRecord {
+            timestamp,
+            user_id,
+            is_trigger_report,
+            breakdown_key,
+            trigger_value,
+        }
+    }
+
+    fn records() -> Vec<TestRawDataRecord> {
+        vec![
+            /* Control user, two conversions */
+            test_input(0, 12345, false, 0, 0),
+            test_input(10, 12345, true, 0, 5),
+            test_input(20, 12345, true, 0, 2),
+            /* Test user, the second conversion is capped to 1 and the third one to 0 */
+            test_input(0, 68362, false, 1, 0),
+            test_input(10, 68362, true, 0, 7),
+            test_input(20, 68362, true, 0, 4),
+            test_input(30, 68362, true, 0, 3),
+            /* Test user, the first conversion is not attributed */
+            test_input(0, 77777, true, 0, 6),
+            test_input(10, 77777, false, 1, 0),
+            test_input(20, 77777, true, 0, 1),
+            /* Control user without conversions */
+            test_input(0, 88888, false, 0, 0),
+        ]
+    }
+
+    #[test]
+    fn semi_honest() {
+        const EXPECTED: &[u32] = &[7, 2, 9, 3];
+
+        run(|| async {
+            let world = TestWorld::default();
+
+            let records = records();
+            assert_eq!(lift_in_the_clear(&records, PER_USER_CAP, None), EXPECTED);
+
+            let result: Vec<_> = world
+                .semi_honest(
+                    records.into_iter(),
+                    |ctx, input_rows: Vec<OPRFIPAInputRow<BA5, BA3, BA20>>| async move {
+                        lift::<_, BA5, BA3, BA16, BA20, 3, 32>(
+                            ctx,
+                            input_rows,
+                            None,
+                            DpMechanism::NoDp,
+                            PaddingParameters::no_padding(),
+                        )
+                        .await
+                        .unwrap()
+                    },
+                )
+                .await
+                .reconstruct();
+            assert_eq!(
+                result
+                    .iter()
+                    .map(|v| u32::try_from(v.as_u128()).unwrap())
+                    .collect::<Vec<_>>(),
+                EXPECTED,
+            );
+        });
+    }
+
+    #[test]
+    fn malicious() {
+        run(|| async {
+            let world = TestWorld::default();
+
+            let records = records();
+            let expected = lift_in_the_clear(&records, PER_USER_CAP, None);
+
+            let result: Vec<_> = world
+                .malicious(
+                    records.into_iter(),
+                    |ctx, input_rows: Vec<OPRFIPAInputRow<BA5, BA3, BA20>>| async move {
+                        lift::<_, BA5, BA3, BA16, BA20, 3, 32>(
+                            ctx,
+                            input_rows,
+                            None,
+                            DpMechanism::NoDp,
+                            PaddingParameters::no_padding(),
+                        )
+                        .await
+                        .unwrap()
+                    },
+                )
+                .await
+                .reconstruct();
+            assert_eq!(
+                result
+                    .iter()
+                    .map(|v| u32::try_from(v.as_u128()).unwrap())
+                    .collect::<Vec<_>>(),
+                expected,
+            );
+        });
+    }
+
+    /// The dummy rows added by the padding must not change the output.
+    #[test]
+    fn semi_honest_with_padding() {
+        run(|| async {
+            let world = TestWorld::default();
+
+            let mut rng = thread_rng();
+            let records = (0..40)
+                .map(|i| {
+                    test_input(
+                        i,
+                        rng.gen_range(0..10),
+                        rng.gen_bool(0.5),
+                        rng.gen_range(0..2),
+                        rng.gen_range(0..8),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let expected = lift_in_the_clear(&records, PER_USER_CAP, None);
+
+            let result: Vec<_> = world
+                .semi_honest(
+                    records.into_iter(),
+                    |ctx, input_rows: Vec<OPRFIPAInputRow<BA5, BA3, BA20>>| async move {
+                        lift::<_, BA5, BA3, BA16, BA20, 3, 32>(
+                            ctx,
+                            input_rows,
+                            None,
+                            DpMechanism::NoDp,
+                            PaddingParameters::relaxed(),
+                        )
+                        .await
+                        .unwrap()
+                    },
+                )
+                .await
+                .reconstruct();
+            assert_eq!(
+                result
+                    .iter()
+                    .map(|v| u32::try_from(v.as_u128()).unwrap())
+                    .collect::<Vec<_>>(),
+                expected,
+            );
+        });
+    }
+}
@@ -1,8 +1,9 @@
 use std::{
     convert::Infallible,
     iter::{self, repeat_n, zip},
+    mem::size_of,
     num::NonZeroU32,
-    ops::{Not, Range},
+    ops::{Add, Not, Range},
 };
 
 use futures::{
@@ -10,6 +11,8 @@ use futures::{
     stream::{self, unfold},
     FutureExt, Stream, StreamExt, TryStreamExt,
 };
+use generic_array::{ArrayLength, GenericArray};
+use typenum::{Unsigned, U10};
 
 use super::aggregation::breakdown_reveal::breakdown_reveal_aggregation;
 use crate::{
@@ -17,7 +20,7 @@ use crate::{
     ff::{
         boolean::Boolean,
         boolean_array::{BooleanArray, BA32, BA7},
-        ArrayAccess, Field, U128Conversions,
+        ArrayAccess, Field, Serializable, U128Conversions,
     },
     helpers::{stream::TryFlattenItersExt, TotalRecords},
     protocol::{
@@ -55,9 +58,11 @@ use crate::{
 };
 
 pub mod feature_label_dot_product;
+pub mod lift;
+pub mod reach_frequency;
 pub(crate) mod step;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PrfShardedIpaInputRow<BK: SharedValue, TV: SharedValue, TS: SharedValue> {
     pub prf_of_match_key: u64,
     pub is_trigger_bit: Replicated<Boolean>,
@@ -67,31 +72,102 @@ pub struct PrfShardedIpaInputRow<BK: SharedValue, TV: SharedValue, TS: SharedVal
     pub sort_key: Replicated<BA32>,
 }
 
-impl<BK: SharedValue, TS, TV: SharedValue> PrfShardedIpaInputRow<BK, TV, TS>
+/// Rows are serialized when they are resharded by their PRF value. The sort key is computed
+/// after resharding, so it is not serialized and deserializes as zero.
+impl<BK: SharedValue, TV: SharedValue, TS: SharedValue> Serializable
+    for PrfShardedIpaInputRow<BK, TV, TS>
 where
-    TS: BooleanArray,
+    Replicated<BK>: Serializable,
+    Replicated<TV>: Serializable,
+    Replicated<TS>: Serializable,
+    <Replicated<BK> as Serializable>::Size: Add<U10>,
+    <Replicated<TS> as Serializable>::Size:
+        Add<<<Replicated<BK> as Serializable>::Size as Add<U10>>::Output>,
+    <Replicated<TV> as Serializable>::Size: Add<
+        <<Replicated<TS> as Serializable>::Size as Add<
+            <<Replicated<BK> as Serializable>::Size as Add<U10>>::Output,
+        >>::Output,
+    >,
+    <<Replicated<TV> as Serializable>::Size as Add<
+        <<Replicated<TS> as Serializable>::Size as Add<
+            <<Replicated<BK> as Serializable>::Size as Add<U10>>::Output,
+        >>::Output,
+    >>::Output: ArrayLength,
 {
-    /// This function defines the sort key.
-    /// The order of sorting is `timestamp`, `is_trigger_bit`, `counter`.
-    /// We sort by `is_trigger_bit` to ensure source events come before trigger in case there
-    /// is a tie in timestamp
-    /// Counter is added to ensure each sorting key is unique to avoid privacy leakage
-    /// NOTE: the sort key will be interpreted in Little endian format, so the order in
-    /// which things are appended is important.
-    /// We still need to add epoch which will be added later
-    pub fn compute_sort_key(&mut self, counter: u64) {
-        expand_shared_array_in_place(
-            &mut self.sort_key,
-            &Replicated::new(BA7::truncate_from(counter), BA7::truncate_from(counter)),
-            0,
-        );
-        let mut offset = BA7::BITS as usize;
+    type Size = <<Replicated<TV> as Serializable>::Size as Add<
+        <<Replicated<TS> as Serializable>::Size as Add<
+            <<Replicated<BK> as Serializable>::Size as Add<U10>>::Output,
+        >>::Output,
+    >>::Output;
+    type DeserializationError = Error;
+
+    fn serialize(&self, buf: &mut GenericArray<u8, Self::Size>) {
+        let prf_sz = size_of::<u64>();
+        let ts_sz = <Replicated<TS> as Serializable>::Size::USIZE;
+        let bk_sz = <Replicated<BK> as Serializable>::Size::USIZE;
+        let tv_sz = <Replicated<TV> as Serializable>::Size::USIZE;
+        let it_sz = <Replicated<Boolean> as Serializable>::Size::USIZE;
+
+        buf[..prf_sz].copy_from_slice(&self.prf_of_match_key.to_le_bytes());
+
+        self.timestamp.serialize(GenericArray::from_mut_slice(
+            &mut buf[prf_sz..prf_sz + ts_sz],
+        ));
 
-        self.sort_key.set(offset, self.is_trigger_bit.clone());
+        self.breakdown_key.serialize(GenericArray::from_mut_slice(
+            &mut buf[prf_sz + ts_sz..prf_sz + ts_sz + bk_sz],
+        ));
+
+        self.trigger_value.serialize(GenericArray::from_mut_slice(
+            &mut buf[prf_sz + ts_sz + bk_sz..prf_sz + ts_sz + bk_sz + tv_sz],
+        ));
+
+        self.is_trigger_bit.serialize(GenericArray::from_mut_slice(
+            &mut buf[prf_sz + ts_sz + bk_sz + tv_sz..prf_sz + ts_sz + bk_sz + tv_sz + it_sz],
+        ));
+    }
+
+    fn deserialize(buf: &GenericArray<u8, Self::Size>) -> Result<Self, Self::DeserializationError> {
+        let prf_sz = size_of::<u64>();
+        let ts_sz = <Replicated<TS> as Serializable>::Size::USIZE;
+        let bk_sz = <Replicated<BK> as Serializable>::Size::USIZE;
+        let tv_sz = <Replicated<TV> as Serializable>::Size::USIZE;
+        let it_sz = <Replicated<Boolean> as Serializable>::Size::USIZE;
+
+        let prf_of_match_key = u64::from_le_bytes(buf[..prf_sz].try_into().unwrap());
+        let timestamp =
+            Replicated::<TS>::deserialize(GenericArray::from_slice(&buf[prf_sz..prf_sz + ts_sz]))
+                .map_err(|e| Error::ParseError(e.into()))?;
+        let breakdown_key = Replicated::<BK>::deserialize(GenericArray::from_slice(
+            &buf[prf_sz + ts_sz..prf_sz + ts_sz + bk_sz],
+        ))
+        .map_err(|e| Error::ParseError(e.into()))?;
+        let trigger_value = Replicated::<TV>::deserialize(GenericArray::from_slice(
+            &buf[prf_sz + ts_sz + bk_sz..prf_sz + ts_sz + bk_sz + tv_sz],
+        ))
+        .map_err(|e| Error::ParseError(e.into()))?;
+        let is_trigger_bit = Replicated::<Boolean>::deserialize(GenericArray::from_slice(
+            &buf[prf_sz + ts_sz + bk_sz + tv_sz..prf_sz + ts_sz + bk_sz + tv_sz + it_sz],
+        ))
+        .map_err(|e| Error::ParseError(e.into()))?;
+
+        Ok(Self {
+            prf_of_match_key,
+            is_trigger_bit,
+            breakdown_key,
+            trigger_value,
+            timestamp,
+            sort_key: Replicated::ZERO,
+        })
+    }
+}
 
-        offset += 1;
-        expand_shared_array_in_place(&mut self.sort_key, &self.timestamp, offset);
-        // TODO(richaj): add epoch to sort key computation
+impl<BK: SharedValue, TS, TV: SharedValue> SortKey for PrfShardedIpaInputRow<BK, TV, TS>
+where
+    TS: BooleanArray,
+{
+    fn compute_sort_key(&mut self, counter: u64) {
+        self.sort_key = timestamp_sort_key(&self.is_trigger_bit, &self.timestamp, counter);
     }
 }
 
@@ -103,6 +179,35 @@ impl<BK: SharedValue, TS: SharedValue, TV: SharedValue> GroupingKey
     }
 }
 
+/// This function defines the sort key.
+/// The order of sorting is `timestamp`, `is_trigger_bit`, `counter`.
+/// We sort by `is_trigger_bit` to ensure source events come before trigger in case there
+/// is a tie in timestamp
+/// Counter is added to ensure each sorting key is unique to avoid privacy leakage
+/// NOTE: the sort key will be interpreted in Little endian format, so the order in
+/// which things are appended is important.
+/// We still need to add epoch which will be added later
+pub(crate) fn timestamp_sort_key<TS: BooleanArray>(
+    is_trigger_bit: &Replicated<Boolean>,
+    timestamp: &Replicated<TS>,
+    counter: u64,
+) -> Replicated<BA32> {
+    let mut sort_key = Replicated::ZERO;
+    expand_shared_array_in_place(
+        &mut sort_key,
+        &Replicated::new(BA7::truncate_from(counter), BA7::truncate_from(counter)),
+        0,
+    );
+    let mut offset = BA7::BITS as usize;
+
+    sort_key.set(offset, is_trigger_bit.clone());
+
+    offset += 1;
+    expand_shared_array_in_place(&mut sort_key, timestamp, offset);
+    // TODO(richaj): add epoch to sort key computation
+    sort_key
+}
+
 struct InputsRequiredFromPrevRow<BK: SharedValue, TV: SharedValue, TS: SharedValue> {
     ever_encountered_a_source_event: Replicated<Boolean>,
     attributed_breakdown_key_bits: Replicated<BK>,
@@ -117,6 +222,7 @@ struct InputsRequiredFromPrevRow<BK: SharedValue, TV: SharedValue, TS: SharedVal
 /// functions it calls.
 fn multiplications_per_record<BK: SharedValue, TV: SharedValue, TS: SharedValue>(
     attribution_window: Option<NonZeroU32>,
+    unattributed_bucket: bool,
 ) -> usize {
     let mut count =
         // breakdown_key_of_most_recent_source_event
@@ -141,6 +247,11 @@ fn multiplications_per_record<BK: SharedValue, TV: SharedValue, TS: SharedValue>
             1;
     }
 
+    if unattributed_bucket {
+        // breakdown key of the output row
+        count += BK::BITS;
+    }
+
     usize::try_from(count).unwrap()
 }
 
@@ -174,12 +285,17 @@ where
     ///         - `did_trigger_get_attributed` - a secret-shared bit indicating if this row corresponds to a trigger event
     ///           which was attributed. Might be able to reveal this (after a shuffle and the addition of dummies) to minimize
     ///           the amount of processing work that must be done in the Aggregation stage.
+    /// - Unattributed bucket
+    ///     - If `unattributed_breakdown_key` is set, trigger events that were not attributed are credited to that
+    ///       breakdown instead of being zeroed out. They count towards the per user cap like any other trigger event.
+    #[allow(clippy::too_many_lines)]
     pub async fn compute_row_with_previous<C>(
         &mut self,
         ctx: C,
         record_id: RecordId,
         input_row: &PrfShardedIpaInputRow<BK, TV, TS>,
         attribution_window_seconds: Option<NonZeroU32>,
+        unattributed_breakdown_key: Option<&Replicated<BK>>,
     ) -> Result<AttributionOutputs<Replicated<BK>, Replicated<TV>>, Error>
     where
         C: Context,
@@ -219,18 +335,54 @@ where
         )
         .await?;
 
-        let attributed_trigger_value = zero_out_trigger_value_unless_attributed(
-            ctx.narrow(&PerRowStep::AttributedTriggerValue),
+        let attributed_ctx = ctx.narrow(&PerRowStep::AttributedTriggerValue);
+        let did_trigger_get_attributed = did_trigger_get_attributed(
+            attributed_ctx.clone(),
             record_id,
             &input_row.is_trigger_bit,
             &ever_encountered_a_source_event,
-            &input_row.trigger_value,
             attribution_window_seconds,
             &input_row.timestamp,
             &source_event_timestamp,
         )
         .await?;
 
+        let (attributed_trigger_value, output_breakdown_key_bits) =
+            if let Some(unattributed_breakdown_key) = unattributed_breakdown_key {
+                // Every trigger value is credited, either to the breakdown of the source event
+                // or to the unattributed bucket.
+                try_join(
+                    select(
+                        attributed_ctx,
+                        record_id,
+                        &input_row.is_trigger_bit,
+                        &input_row.trigger_value,
+                        &Replicated::<TV>::ZERO,
+                    ),
+                    select(
+                        ctx.narrow(&PerRowStep::UnattributedBreakdownKey),
+                        record_id,
+                        &did_trigger_get_attributed,
+                        &attributed_breakdown_key_bits,
+                        unattributed_breakdown_key,
+                    ),
+                )
+                .await?
+            } else {
+                let attributed_trigger_value = select(
+                    attributed_ctx,
+                    record_id,
+                    &did_trigger_get_attributed,
+                    &input_row.trigger_value,
+                    &Replicated::<TV>::ZERO,
+                )
+                .await?;
+                (
+                    attributed_trigger_value,
+                    attributed_breakdown_key_bits.clone(),
+                )
+            };
+
         assert!(
             TV::BITS <= EightBitStep::BITS,
             "EightBitStep not large enough to accomodate this sum"
@@ -287,14 +439,14 @@ where
         .await?;
 
         self.ever_encountered_a_source_event = ever_encountered_a_source_event;
-        self.attributed_breakdown_key_bits = attributed_breakdown_key_bits.clone();
+        self.attributed_breakdown_key_bits = attributed_breakdown_key_bits;
         self.saturating_sum = updated_sum;
         self.is_saturated = is_saturated;
         self.difference_to_cap = difference_to_cap;
         self.source_event_timestamp = source_event_timestamp;
 
         let outputs_for_aggregation = AttributionOutputs {
-            attributed_breakdown_key_bits,
+            attributed_breakdown_key_bits: output_breakdown_key_bits,
             capped_attributed_trigger_value,
         };
         Ok(outputs_for_aggregation)
@@ -345,20 +497,21 @@ pub trait GroupingKey {
     fn get_grouping_key(&self) -> u64;
 }
 
+/// Rows that are grouped by user and then sorted within each group, see
+/// [`histograms_ranges_sortkeys`].
+pub trait SortKey: GroupingKey {
+    /// Computes the key used to sort this row among the other rows of the same user.
+    /// `counter` is the position of the row within its group.
+    fn compute_sort_key(&mut self, counter: u64);
+}
+
 #[tracing::instrument(name = "histograms_ranges_sortkeys", skip_all)]
 /// This function does following computations per user
 /// 1. Compute histogram of users with row counts. `histogram[row number]` contains the count of
 ///    users having that row number (i.e. the count of users with at least row_number+1 records)
 /// 2. Compute range of rows for each user in the input vector
 /// 3. Compute the sort key for the input rows which is used later for sorting
-pub fn histograms_ranges_sortkeys<BK, TV, TS>(
-    input: &mut [PrfShardedIpaInputRow<BK, TV, TS>],
-) -> (Vec<usize>, Vec<Range<usize>>)
-where
-    BK: BooleanArray,
-    TV: BooleanArray,
-    TS: BooleanArray,
-{
+pub fn histograms_ranges_sortkeys<R: SortKey>(input: &mut [R]) -> (Vec<usize>, Vec<Range<usize>>) {
     let mut histogram = vec![];
     let mut last_prf = 0;
     let mut cur_count = 0;
@@ -386,13 +539,17 @@ where
     (histogram, ranges)
 }
 
-fn set_up_contexts<C>(ctx: &C, histogram: &[usize]) -> Result<Vec<C>, Error>
+fn set_up_contexts<C>(
+    ctx: &C,
+    histogram: &[usize],
+    include_first_row: bool,
+) -> Result<Vec<C>, Error>
 where
     C: Context,
 {
     let mut context_per_row_depth = Vec::with_capacity(histogram.len());
     for (row_number, num_users_having_that_row_number) in histogram.iter().enumerate() {
-        if row_number == 0 {
+        if row_number == 0 && !include_first_row {
             // no multiplications needed for each user's row 0. No context needed
         } else {
             let total_records = TotalRecords::specified(*num_users_having_that_row_number)?;
@@ -409,11 +566,13 @@ where
 /// Takes an input stream of `PrfShardedIpaInputRecordRow` which is assumed to have all records with a given PRF adjacent
 /// and converts it into a stream of vectors of `PrfShardedIpaInputRecordRow` having the same PRF.
 ///
-/// Filters out any users that only have a single row, since they will produce no attributed conversions.
+/// Unless `include_single_row_users` is set, filters out any users that only have a single row, since they
+/// will produce no attributed conversions.
 ///
 fn chunk_rows_by_user<IS, BK, TV, TS>(
     input_stream: IS,
     first_row: PrfShardedIpaInputRow<BK, TV, TS>,
+    include_single_row_users: bool,
 ) -> impl Stream<Item = Vec<PrfShardedIpaInputRow<BK, TV, TS>>>
 where
     BK: SharedValue,
@@ -421,21 +580,22 @@ where
     TS: SharedValue,
     IS: Stream<Item = PrfShardedIpaInputRow<BK, TV, TS>> + Unpin,
 {
-    unfold(Some((input_stream, first_row)), |state| async move {
+    let min_rows = if include_single_row_users { 1 } else { 2 };
+    unfold(Some((input_stream, first_row)), move |state| async move {
         let (mut s, last_row) = state?;
         let mut last_row_prf = last_row.prf_of_match_key;
         let mut current_chunk = vec![last_row];
         while let Some(row) = s.next().await {
             if row.prf_of_match_key == last_row_prf {
                 current_chunk.push(row);
-            } else if current_chunk.len() > 1 {
+            } else if current_chunk.len() >= min_rows {
                 return Some((current_chunk, Some((s, row))));
             } else {
                 last_row_prf = row.prf_of_match_key;
                 current_chunk = vec![row];
             }
         }
-        (current_chunk.len() > 1).then_some((current_chunk, None))
+        (current_chunk.len() >= min_rows).then_some((current_chunk, None))
     })
 }
 
@@ -468,6 +628,7 @@ pub async fn attribute_cap_aggregate<
     sh_ctx: C,
     input_rows: Vec<PrfShardedIpaInputRow<BK, TV, TS>>,
     attribution_window_seconds: Option<NonZeroU32>,
+    unattributed_breakdown_key: Option<BK>,
     histogram: &[usize],
     padding_parameters: &PaddingParameters,
 ) -> Result<BitDecomposed<Replicated<Boolean, B>>, Error>
@@ -498,13 +659,77 @@ where
     Vec<Replicated<HV>>:
         for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
 {
+    if input_rows.is_empty() {
+        return Ok(BitDecomposed::new(
+            iter::repeat(Replicated::<Boolean, B>::ZERO).take(B),
+        ));
+    }
+
+    let user_contributions = attribute_cap::<_, BK, TV, TS, SS_BITS, B>(
+        sh_ctx.clone(),
+        input_rows,
+        attribution_window_seconds,
+        unattributed_breakdown_key,
+        histogram,
+    )
+    .await?;
+    breakdown_reveal_aggregation::<_, BK, TV, HV, B>(
+        sh_ctx.narrow(&Step::Aggregate),
+        user_contributions,
+        padding_parameters,
+    )
+    .await
+}
+
+/// Computes attribution and per-user capping, without aggregating the results.
+///
+/// The input has the same requirements as for [`attribute_cap_aggregate`]. The output has one
+/// entry for every row except the first row of each user, holding the breakdown key of the source
+/// event the row was attributed to and the capped trigger value. Rows that were not attributed
+/// have a capped trigger value of zero.
+///
+/// If `unattributed_breakdown_key` is set, the value of every trigger event that is not attributed
+/// is credited to that breakdown instead. Because the first row of a user can be such a trigger
+/// event, the output then has one entry for every row, including users with a single row.
+///
+/// # Errors
+/// Propagates errors from multiplications
+/// # Panics
+/// If no user has more than one row, according to `histogram`.
+pub async fn attribute_cap<'ctx, C, BK, TV, TS, const SS_BITS: usize, const B: usize>(
+    sh_ctx: C,
+    input_rows: Vec<PrfShardedIpaInputRow<BK, TV, TS>>,
+    attribution_window_seconds: Option<NonZeroU32>,
+    unattributed_breakdown_key: Option<BK>,
+    histogram: &[usize],
+) -> Result<Vec<SecretSharedAttributionOutputs<BK, TV>>, Error>
+where
+    C: UpgradableContext + 'ctx,
+    BK: BreakdownKey<B>,
+    TV: BooleanArray + U128Conversions,
+    TS: BooleanArray + U128Conversions,
+    Replicated<Boolean>: BooleanProtocols<DZKPUpgraded<C>>,
+    Replicated<BK>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<TS>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<TV>: BooleanArrayMul<DZKPUpgraded<C>>,
+{
+    let include_first_row = unattributed_breakdown_key.is_some();
     // Get the validator and context to use for Boolean multiplication operations.
     // Record IDs count users. The maximum number of multiplications per record (user) is:
     // (max_events - 1) * multiplictions_per_record, because the attribution circuit is
-    // only evaluated for the second and subsequent records.
+    // only evaluated for the second and subsequent records, unless there is an unattributed
+    // bucket.
+    let evaluated_rows = if include_first_row {
+        histogram.len()
+    } else {
+        histogram.len() - 1
+    };
     let chunk_size = TARGET_PROOF_SIZE
-        / ((histogram.len() - 1)
-            * multiplications_per_record::<BK, TV, TS>(attribution_window_seconds));
+        / (evaluated_rows
+            * multiplications_per_record::<BK, TV, TS>(
+                attribution_window_seconds,
+                include_first_row,
+            ));
 
     // Tricky hacks to work around the limitations of our current infrastructure
     let mut dzkp_validator = sh_ctx.clone().dzkp_validator(
@@ -517,17 +742,23 @@ where
         // is still observed to help performance (see #1376), so has been retained.
         std::cmp::min(sh_ctx.active_work().get(), chunk_size.next_power_of_two()),
     );
-    dzkp_validator.set_total_records(TotalRecords::specified(histogram[1]).unwrap());
-    let ctx_for_row_number = set_up_contexts(&dzkp_validator.context(), histogram)?;
+    let users = if include_first_row {
+        histogram[0]
+    } else {
+        histogram[1]
+    };
+    dzkp_validator.set_total_records(TotalRecords::specified(users).unwrap());
+    let ctx_for_row_number =
+        set_up_contexts(&dzkp_validator.context(), histogram, include_first_row)?;
+    let unattributed_breakdown_key = unattributed_breakdown_key
+        .map(|bk| Replicated::share_known_value(&dzkp_validator.context(), bk));
 
     // Chunk the incoming stream of records into stream of vectors of records with the same PRF
     let mut input_stream = stream::iter(input_rows);
     let Some(first_row) = input_stream.next().await else {
-        return Ok(BitDecomposed::new(
-            iter::repeat(Replicated::<Boolean, B>::ZERO).take(B),
-        ));
+        return Ok(Vec::new());
     };
-    let rows_chunked_by_user = chunk_rows_by_user(input_stream, first_row);
+    let rows_chunked_by_user = chunk_rows_by_user(input_stream, first_row, include_first_row);
 
     let mut collected = rows_chunked_by_user.collect::<Vec<_>>().await;
     collected.sort_by(|a, b| std::cmp::Ord::cmp(&b.len(), &a.len()));
@@ -537,15 +768,10 @@ where
         ctx_for_row_number,
         collected,
         attribution_window_seconds,
+        unattributed_breakdown_key,
     );
 
-    let user_contributions = flattened_user_results.try_collect::<Vec<_>>().await?;
-    breakdown_reveal_aggregation::<_, BK, TV, HV, B>(
-        sh_ctx.narrow(&Step::Aggregate),
-        user_contributions,
-        padding_parameters,
-    )
-    .await
+    flattened_user_results.try_collect::<Vec<_>>().await
 }
 
 #[tracing::instrument(name = "attribute_cap", skip_all, fields(unique_match_keys = input.len()))]
@@ -554,6 +780,7 @@ fn attribute<'ctx, V, BK, TV, TS, const SS_BITS: usize, const B: usize>(
     contexts: Vec<V::Context>,
     input: Vec<Vec<PrfShardedIpaInputRow<BK, TV, TS>>>,
     attribution_window_seconds: Option<NonZeroU32>,
+    unattributed_breakdown_key: Option<Replicated<BK>>,
 ) -> impl Stream<Item = Result<SecretSharedAttributionOutputs<BK, TV>, Error>> + Send + 'ctx
 where
     V: DZKPValidator + 'ctx,
@@ -571,13 +798,19 @@ where
             .enumerate()
             .map(move |(record_id, rows_for_user)| {
                 let num_user_rows = rows_for_user.len();
-                let contexts = contexts[..num_user_rows - 1].to_owned();
+                let evaluated_rows = if unattributed_breakdown_key.is_some() {
+                    num_user_rows
+                } else {
+                    num_user_rows - 1
+                };
+                let contexts = contexts[..evaluated_rows].to_owned();
 
                 evaluate_per_user_attribution_circuit::<_, BK, TV, TS, SS_BITS>(
                     contexts,
                     RecordId::from(record_id),
                     rows_for_user,
                     attribution_window_seconds,
+                    unattributed_breakdown_key.clone(),
                 )
             });
 
@@ -594,6 +827,7 @@ async fn evaluate_per_user_attribution_circuit<C, BK, TV, TS, const SS_BITS: usi
     record_id: RecordId,
     rows_for_user: Vec<PrfShardedIpaInputRow<BK, TV, TS>>,
     attribution_window_seconds: Option<NonZeroU32>,
+    unattributed_breakdown_key: Option<Replicated<BK>>,
 ) -> Result<Vec<SecretSharedAttributionOutputs<BK, TV>>, Error>
 where
     C: DZKPContext,
@@ -606,17 +840,32 @@ where
     Replicated<TV>: BooleanArrayMul<C>,
 {
     assert!(!rows_for_user.is_empty());
-    if rows_for_user.len() == 1 {
-        return Ok(Vec::new());
-    }
-    let first_row = &rows_for_user[0];
-    let mut prev_row_inputs =
-        initialize_new_device_attribution_variables::<BK, TV, TS, SS_BITS>(first_row);
+    let (mut prev_row_inputs, evaluated_rows) = if unattributed_breakdown_key.is_some() {
+        // The first row may be an unattributed trigger event, so it goes through the circuit too.
+        (
+            initialize_empty_device_attribution_variables::<BK, TV, TS, SS_BITS>(),
+            &rows_for_user[..],
+        )
+    } else {
+        if rows_for_user.len() == 1 {
+            return Ok(Vec::new());
+        }
+        (
+            initialize_new_device_attribution_variables::<BK, TV, TS, SS_BITS>(&rows_for_user[0]),
+            &rows_for_user[1..],
+        )
+    };
 
-    let mut output = Vec::with_capacity(rows_for_user.len() - 1);
-    for (row, ctx) in zip(rows_for_user.iter().skip(1), ctx_for_row_number.into_iter()) {
+    let mut output = Vec::with_capacity(evaluated_rows.len());
+    for (row, ctx) in zip(evaluated_rows, ctx_for_row_number.into_iter()) {
         let capped_attribution_outputs = prev_row_inputs
-            .compute_row_with_previous(ctx, record_id, row, attribution_window_seconds)
+            .compute_row_with_previous(
+                ctx,
+                record_id,
+                row,
+                attribution_window_seconds,
+                unattributed_breakdown_key.as_ref(),
+            )
             .await?;
 
         output.push(capped_attribution_outputs);
@@ -648,6 +897,27 @@ where
     }
 }
 
+///
+/// The state before the first row of a user, i.e. no source event encountered and nothing
+/// contributed yet.
+///
+fn initialize_empty_device_attribution_variables<BK, TV, TS, const SS_BITS: usize>(
+) -> InputsRequiredFromPrevRow<BK, TV, TS>
+where
+    BK: SharedValue,
+    TV: SharedValue,
+    TS: SharedValue,
+{
+    InputsRequiredFromPrevRow {
+        ever_encountered_a_source_event: Replicated::<Boolean>::ZERO,
+        attributed_breakdown_key_bits: Replicated::<BK>::ZERO,
+        saturating_sum: BitDecomposed::new(repeat_n(Replicated::ZERO, SS_BITS)),
+        is_saturated: Replicated::<Boolean>::ZERO,
+        difference_to_cap: Replicated::<TV>::ZERO,
+        source_event_timestamp: Replicated::<TS>::ZERO,
+    }
+}
+
 ///
 /// To support "Last Touch Attribution" we move the `breakdown_key` of the most recent source event
 /// down to all of trigger events that follow it.
@@ -710,29 +980,25 @@ where
 ///
 /// In this simple "Last Touch Attribution" model, the `trigger_value` of a trigger event is either
 /// (a) Attributed to a single `breakdown_key`
-/// (b) Not attributed, and thus zeroed out
+/// (b) Not attributed, and thus zeroed out (or credited to the unattributed bucket)
 ///
 /// The logic here is extremely simple. There is a secret-shared bit indicating if a given row is an "attributed trigger event" and
-/// another secret-shared bit indicating if a given row is within the attribution window. We multiply these two bits together and
-/// multiply it with the bits of the `trigger_value` in order to zero out contributions from unattributed trigger events.
+/// another secret-shared bit indicating if a given row is within the attribution window. We multiply these two bits together
+/// to get a secret-shared bit indicating if the `trigger_value` of this row should be attributed.
 ///
-#[allow(clippy::too_many_arguments)]
-async fn zero_out_trigger_value_unless_attributed<C, TV, TS>(
+async fn did_trigger_get_attributed<C, TS>(
     ctx: C,
     record_id: RecordId,
     is_trigger_bit: &Replicated<Boolean>,
     ever_encountered_a_source_event: &Replicated<Boolean>,
-    trigger_value: &Replicated<TV>,
     attribution_window_seconds: Option<NonZeroU32>,
     trigger_event_timestamp: &Replicated<TS>,
     source_event_timestamp: &Replicated<TS>,
-) -> Result<Replicated<TV>, Error>
+) -> Result<Replicated<Boolean>, Error>
 where
     C: Context,
-    TV: BooleanArray + U128Conversions,
     TS: BooleanArray + U128Conversions,
     Replicated<Boolean>: BooleanProtocols<C>,
-    Replicated<TV>: BooleanArrayMul<C>,
 {
     let (did_trigger_get_attributed, is_trigger_within_window) = try_join(
         is_trigger_bit.multiply(
@@ -751,23 +1017,14 @@ where
     .await?;
 
     // save 1 multiplication if there is no attribution window
-    let zero_out_flag = if attribution_window_seconds.is_some() {
+    if attribution_window_seconds.is_some() {
         let c = ctx.narrow(&ZeroOutTriggerStep::AttributedEventCheckFlag);
         did_trigger_get_attributed
             .multiply(&is_trigger_within_window, c, record_id)
-            .await?
+            .await
     } else {
-        did_trigger_get_attributed.clone()
-    };
-
-    select(
-        ctx,
-        record_id,
-        &zero_out_flag,
-        trigger_value,
-        &Replicated::<TV>::ZERO,
-    )
-    .await
+        Ok(did_trigger_get_attributed)
+    }
 }
 
 /// If the `attribution_window_seconds` is not `None`, we calculate the time
@@ -1078,6 +1335,67 @@ pub mod tests {
                             ctx,
                             input_rows,
                             None,
+                            None,
+                            &histogram,
+                            &PaddingParameters::relaxed(),
+                        )
+                        .await
+                        .unwrap(),
+                    )
+                })
+                .await
+                .map(Result::unwrap);
+            let result_reconstructed: Vec<BA16> = result.reconstruct();
+            assert_eq!(
+                result_reconstructed
+                    .iter()
+                    .map(U128Conversions::as_u128)
+                    .collect::<Vec<_>>(),
+                &expected
+            );
+        });
+    }
+
+    #[test]
+    fn unattributed_bucket() {
+        run(|| async move {
+            let world = TestWorld::default();
+
+            let records: Vec<PreShardedAndSortedOPRFTestInput<BA5, BA3, BA20>> = vec![
+                /* First User: trigger before any source event */
+                oprf_test_input(123, true, 0, 7),
+                oprf_test_input(123, false, 17, 0),
+                oprf_test_input(123, true, 0, 3),
+                /* Second User: single trigger event */
+                oprf_test_input(234, true, 0, 5),
+                /* Third User */
+                oprf_test_input(345, false, 12, 0),
+                oprf_test_input(345, true, 0, 6),
+                /* Fourth User: single source event */
+                oprf_test_input(456, false, 20, 0),
+                /* Fifth User: unattributed trigger events are capped too */
+                oprf_test_input(567, true, 0, 7),
+                oprf_test_input(567, true, 0, 7),
+                oprf_test_input(567, true, 0, 7),
+                oprf_test_input(567, true, 0, 7),
+                oprf_test_input(567, true, 0, 7),
+            ];
+
+            let mut expected = [0_u128; 32];
+            expected[12] = 6;
+            expected[17] = 3;
+            expected[31] = 7 + 5 + 32;
+
+            let histogram = [5, 3, 2, 1, 1];
+
+            let result: [Vec<Replicated<BA16>>; 3] = world
+                .malicious(records.into_iter(), |ctx, input_rows| async move {
+                    Vec::transposed_from(
+                        &attribute_cap_aggregate::<_, BA5, BA3, BA16, BA20, 5, 32>(
+                            ctx,
+                            input_rows,
+                            None,
+                            Some(BA5::truncate_from(31_u128)),
                             &histogram,
                             &PaddingParameters::relaxed(),
                         )
@@ -1139,6 +1457,7 @@ pub mod tests {
                             ctx,
                             input_rows,
                             NonZeroU32::new(ATTRIBUTION_WINDOW_SECONDS),
+                            None,
                             &histogram,
                             &PaddingParameters::relaxed(),
                         )
@@ -1177,6 +1496,7 @@ pub mod tests {
                         ctx,
                         input_rows,
                         None,
+                        None,
                         histogram_ref,
                         &PaddingParameters::relaxed(),
                     )
@@ -1269,6 +1589,7 @@ pub mod tests {
                             ctx,
                             input_rows,
                             None,
+                            None,
                             &HISTOGRAM,
                             &PaddingParameters::relaxed(),
                         )
@@ -0,0 +1,734 @@
+use std::{
+    cmp::{min, Reverse},
+    iter::{once, repeat_n, zip},
+};
+
+use futures::stream;
+use futures_util::{future::try_join, StreamExt, TryStreamExt};
+
+use crate::{
+    error::{Error, LengthError},
+    ff::{
+        boolean::Boolean, boolean_array::BooleanArray, curve_points::RP25519,
+        ec_prime_field::Fp25519, ArrayAccess, Field, U128Conversions,
+    },
+    helpers::{query::DpMechanism, TotalRecords},
+    protocol::{
+        basics::{Reveal, SecureMul, ShareKnownValue},
+        boolean::{and::bool_and_8_bit, or::or},
+        context::{
+            dzkp_validator::DZKPValidator, Context, DZKPUpgraded, MacUpgraded,
+            MaliciousProtocolSteps, UpgradableContext,
+        },
+        dp::{dp_for_histogram, dp_for_histogram_with_steps},
+        ipa_prf::{
+            aggregation::aggregate_values,
+            compute_prf_for_inputs,
+            oprf_padding::{apply_dp_padding, PaddingParameters},
+            prf_eval::PrfSharing,
+            prf_sharding::{
+                histograms_ranges_sortkeys,
+                step::{
+                    ReachFrequencyPerRowStep as PerRowStep, ReachFrequencyStep as Step,
+                    ReachFrequencyUserNthRowStep,
+                },
+                PrfShardedIpaInputRow,
+            },
+            shuffle::{shuffle_inputs, Shuffle},
+            step::IpaPrfStep,
+            BreakdownKey, OPRFIPAInputRow, CONV_CHUNK, PRF_CHUNK,
+        },
+        prss::FromPrss,
+        BooleanProtocols, RecordId,
+    },
+    secret_sharing::{
+        replicated::{semi_honest::AdditiveShare as Replicated, ReplicatedSecretSharing},
+        BitDecomposed, FieldSimd, SharedValue, TransposeFrom, Vectorizable,
+    },
+    seq_join::seq_join,
+};
+
+/// Largest supported frequency cap. Users are counted towards the frequency distribution with a
+/// per-user counter of this many bits.
+pub const MAX_FREQUENCY_CAP: usize = 8;
+
+/// The reach and the frequency distribution are released separately, so each gets half of the
+/// privacy budget.
+pub const DP_RELEASES: u32 = 2;
+
+/// Input row of the reach and frequency query while it goes through the OPRF padding.
+///
+/// The query reads the same rows as IPA, but only impressions (source events) are counted. Unlike
+/// the dummy rows of [`OPRFIPAInputRow`], the dummy rows of this wrapper are trigger events, so the
+/// padding does not change the output of the query.
+pub struct ReachPaddingRow<BK: SharedValue, TV: SharedValue, TS: SharedValue>(
+    pub OPRFIPAInputRow<BK, TV, TS>,
+);
+
+/// Per-user state of the reach and frequency circuit.
+struct ImpressionCounts<const B: usize>
+where
+    Boolean: FieldSimd<B>,
+{
+    /// Lane `b` is set if the user saw an impression with breakdown key `b`.
+    seen_breakdowns: Replicated<Boolean, B>,
+    /// Bit `k` is set if the user saw more than `k` impressions.
+    saw_more_than: BitDecomposed<Replicated<Boolean>>,
+}
+
+impl<const B: usize> ImpressionCounts<B>
+where
+    Boolean: FieldSimd<B>,
+{
+    fn new(frequency_cap: usize) -> Self {
+        Self {
+            seen_breakdowns: Replicated::ZERO,
+            saw_more_than: BitDecomposed::new(repeat_n(Replicated::ZERO, frequency_cap)),
+        }
+    }
+
+    /// Adds one row of the user to the counts. Trigger events leave the counts unchanged.
+    ///
+    /// The first row of a user does not need to be combined with the previous rows, which saves
+    /// a multiplication.
+    async fn count_row<C, BK, TV, TS>(
+        &mut self,
+        ctx: C,
+        record_id: RecordId,
+        row: &PrfShardedIpaInputRow<BK, TV, TS>,
+        is_first_row: bool,
+    ) -> Result<(), Error>
+    where
+        C: Context,
+        BK: BreakdownKey<B>,
+        TV: SharedValue,
+        TS: SharedValue,
+        Replicated<Boolean>: SecureMul<C>,
+        Replicated<Boolean, B>: BooleanProtocols<C, B>,
+    {
+        let share_of_one = Replicated::share_known_value(&ctx, Boolean::ONE);
+        let is_impression = &share_of_one - &row.is_trigger_bit;
+
+        // The bits of `saw_more_than` are monotone, so an impression only sets the first bit that
+        // is not set yet, that is the bit that differs from its predecessor.
+        let can_increment = BitDecomposed::new(
+            zip(
+                once(&share_of_one).chain(self.saw_more_than.iter()),
+                self.saw_more_than.iter(),
+            )
+            .map(|(prev, cur)| prev - cur),
+        );
+
+        let (impression_breakdown, increment) = try_join(
+            breakdown_indicator::<_, BK, B>(
+                ctx.clone(),
+                record_id,
+                &row.breakdown_key,
+                &is_impression,
+            ),
+            bool_and_8_bit::<_, _, 1>(
+                ctx.narrow(&PerRowStep::CountImpression),
+                record_id,
+                &can_increment,
+                repeat_n(&is_impression, can_increment.len()),
+            ),
+        )
+        .await?;
+
+        self.seen_breakdowns = if is_first_row {
+            impression_breakdown
+        } else {
+            or(
+                ctx.narrow(&PerRowStep::UpdateSeenBreakdowns),
+                record_id,
+                &self.seen_breakdowns,
+                &impression_breakdown,
+            )
+            .await?
+        };
+        zip(self.saw_more_than.iter_mut(), increment).for_each(|(x, y)| *x += y);
+
+        Ok(())
+    }
+
+    /// Returns the contributions of the user to the reach and to the frequency distribution.
+    ///
+    /// Lane `k` of the frequency contribution is set if the user saw exactly `k + 1` impressions.
+    /// The last lane used by the frequency distribution counts the users that saw `frequency_cap`
+    /// impressions or more. The frequency distribution has `F` lanes, which must be at least
+    /// `frequency_cap`.
+    fn into_contributions<const F: usize>(
+        self,
+    ) -> (
+        BitDecomposed<Replicated<Boolean, B>>,
+        BitDecomposed<Replicated<Boolean, F>>,
+    )
+    where
+        Boolean: FieldSimd<F>,
+    {
+        let Self {
+            seen_breakdowns,
+            saw_more_than,
+        } = self;
+        debug_assert!(saw_more_than.len() <= F);
+        let saw_exactly = (0..F)
+            .map(|k| match (saw_more_than.get(k), saw_more_than.get(k + 1)) {
+                (Some(more_than_k), Some(more_than_next)) => more_than_k - more_than_next,
+                (Some(more_than_k), None) => more_than_k.clone(),
+                (None, _) => Replicated::ZERO,
+            })
+            .collect::<Vec<_>>();
+        let frequency = Replicated::from_fns(|k| saw_exactly[k].left(), |k| saw_exactly[k].right());
+
+        (
+            BitDecomposed::new(once(seen_breakdowns)),
+            BitDecomposed::new(once(frequency)),
+        )
+    }
+}
+
+/// Computes a vector with a lane for every breakdown. The lane of `breakdown_key` is set to
+/// `is_impression`, and the other lanes are zero.
+async fn breakdown_indicator<C, BK, const B: usize>(
+    ctx: C,
+    record_id: RecordId,
+    breakdown_key: &Replicated<BK>,
+    is_impression: &Replicated<Boolean>,
+) -> Result<Replicated<Boolean, B>, Error>
+where
+    C: Context,
+    BK: BreakdownKey<B>,
+    Boolean: FieldSimd<B>,
+    Replicated<Boolean, B>: BooleanProtocols<C, B>,
+{
+    let mut indicator = Replicated::from_fns(|_| is_impression.left(), |_| is_impression.right());
+    for i in 0..usize::try_from(BK::BITS).unwrap() {
+        let bit = breakdown_key.get(i).unwrap();
+        // Lane `b` is set if bit `i` of the breakdown key matches bit `i` of `b`. Flipping both
+        // shares of every helper flips the shared bit.
+        let matches_bit = |share: Boolean| {
+            move |b: usize| {
+                if (b >> i) & 1 == 1 {
+                    share
+                } else {
+                    !share
+                }
+            }
+        };
+        let bit_matches =
+            Replicated::<Boolean, B>::from_fns(matches_bit(bit.left()), matches_bit(bit.right()));
+        indicator = indicator
+            .multiply(
+                &bit_matches,
+                ctx.narrow(&PerRowStep::MatchBreakdownKeyBit(i)),
+                record_id,
+            )
+            .await?;
+    }
+
+    Ok(indicator)
+}
+
+fn set_up_contexts<C>(root_ctx: &C, users_having_n_records: &[usize]) -> Result<Vec<C>, Error>
+where
+    C: Context,
+{
+    users_having_n_records
+        .iter()
+        .enumerate()
+        .map(|(row_number, num_users_having_that_row_number)| {
+            Ok(root_ctx
+                .narrow(&ReachFrequencyUserNthRowStep::from(row_number))
+                .set_total_records(TotalRecords::specified(*num_users_having_that_row_number)?))
+        })
+        .collect()
+}
+
+async fn evaluate_per_user_reach_frequency_circuit<C, BK, TV, TS, const B: usize, const F: usize>(
+    ctx_for_row_number: Vec<C>,
+    record_id: RecordId,
+    rows_for_user: &[PrfShardedIpaInputRow<BK, TV, TS>],
+    frequency_cap: usize,
+) -> Result<
+    (
+        BitDecomposed<Replicated<Boolean, B>>,
+        BitDecomposed<Replicated<Boolean, F>>,
+    ),
+    Error,
+>
+where
+    C: Context,
+    BK: BreakdownKey<B>,
+    TV: SharedValue,
+    TS: SharedValue,
+    Boolean: FieldSimd<B> + FieldSimd<F>,
+    Replicated<Boolean>: SecureMul<C>,
+    Replicated<Boolean, B>: BooleanProtocols<C, B>,
+{
+    let mut counts = ImpressionCounts::<B>::new(frequency_cap);
+    for (row_number, (row, ctx)) in zip(rows_for_user, ctx_for_row_number).enumerate() {
+        counts
+            .count_row::<_, BK, TV, TS>(ctx, record_id, row, row_number == 0)
+            .await?;
+    }
+
+    Ok(counts.into_contributions())
+}
+
+/// Sub-protocol of the reach and frequency query.
+///
+/// This circuit expects to receive records from multiple users, with all of the records from a
+/// given user adjacent to one another. The order of the records of a user does not matter.
+///
+/// Every user contributes one to the reach of each breakdown for which they saw at least one
+/// impression, and one to the bucket of the frequency distribution that matches their number of
+/// impressions. Users that only have trigger events contribute nothing.
+///
+/// The first output is the reach of each of the `B` breakdowns. The second output has `F` lanes,
+/// independent of the number of breakdowns. Its first `frequency_cap` lanes are the number of
+/// users that saw `1, 2, ..., frequency_cap` impressions, where the last bucket also counts the
+/// users that saw more impressions. The other lanes are zero.
+///
+/// The count at index `n` of `users_having_n_records` is the number of users having more than `n`
+/// records, see [`compute_feature_label_dot_product`].
+///
+/// # Errors
+/// Propagates errors from multiplications
+/// # Panics
+/// If `frequency_cap` is larger than `F` or [`MAX_FREQUENCY_CAP`], or if a user has more than 64
+/// records.
+///
+/// [`compute_feature_label_dot_product`]: super::feature_label_dot_product::compute_feature_label_dot_product
+pub async fn compute_reach_frequency<C, BK, TV, TS, HV, const B: usize, const F: usize>(
+    sh_ctx: C,
+    input_rows: Vec<PrfShardedIpaInputRow<BK, TV, TS>>,
+    users_having_n_records: &[usize],
+    frequency_cap: usize,
+) -> Result<
+    (
+        BitDecomposed<Replicated<Boolean, B>>,
+        BitDecomposed<Replicated<Boolean, F>>,
+    ),
+    Error,
+>
+where
+    C: Context,
+    BK: BreakdownKey<B>,
+    TV: SharedValue,
+    TS: SharedValue,
+    HV: BooleanArray + U128Conversions,
+    Boolean: FieldSimd<B> + FieldSimd<F>,
+    Replicated<Boolean>: SecureMul<C>,
+    Replicated<Boolean, B>: BooleanProtocols<C, B>,
+    Replicated<Boolean, F>: BooleanProtocols<C, F>,
+{
+    assert!(
+        frequency_cap <= min(F, MAX_FREQUENCY_CAP),
+        "frequency cap {frequency_cap} is larger than {}",
+        min(F, MAX_FREQUENCY_CAP),
+    );
+    let Some(&num_users) = users_having_n_records.first() else {
+        return Ok((
+            BitDecomposed::new(repeat_n(Replicated::ZERO, HV::BITS as usize)),
+            BitDecomposed::new(repeat_n(Replicated::ZERO, HV::BITS as usize)),
+        ));
+    };
+    let ctx_for_row_number = set_up_contexts(
+        &sh_ctx.narrow(&Step::CountImpressions),
+        users_having_n_records,
+    )?;
+
+    // Users with more records come first, so that the record IDs of every row depth stay below
+    // the number of users having that many records.
+    let mut rows_by_user = input_rows
+        .chunk_by(|a, b| a.prf_of_match_key == b.prf_of_match_key)
+        .collect::<Vec<_>>();
+    rows_by_user.sort_by_key(|rows_for_user| Reverse(rows_for_user.len()));
+
+    let per_user_results =
+        rows_by_user
+            .into_iter()
+            .enumerate()
+            .map(|(record_id, rows_for_user)| {
+                evaluate_per_user_reach_frequency_circuit::<_, BK, TV, TS, B, F>(
+                    ctx_for_row_number[..rows_for_user.len()].to_owned(),
+                    RecordId::from(record_id),
+                    rows_for_user,
+                    frequency_cap,
+                )
+            });
+
+    // The contributions are collected before aggregating them, for the same reason as in the
+    // feature-label dot product.
+    let (reach_contributions, frequency_contributions): (Vec<_>, Vec<_>) =
+        seq_join(sh_ctx.active_work(), stream::iter(per_user_results))
+            .try_collect::<Vec<_>>()
+            .await?
+            .into_iter()
+            .unzip();
+
+    let reach = aggregate_values::<_, HV, B>(
+        sh_ctx.narrow(&Step::AggregateReach),
+        Box::pin(stream::iter(reach_contributions).map(Ok)),
+        num_users,
+        None,
+    )
+    .await?;
+    let frequency = aggregate_values::<_, HV, F>(
+        sh_ctx.narrow(&Step::AggregateFrequency),
+        Box::pin(stream::iter(frequency_contributions).map(Ok)),
+        num_users,
+        None,
+    )
+    .await?;
+
+    Ok((reach, frequency))
+}
+
+/// Reach and frequency protocol
+///
+/// Reach is the number of distinct users that saw at least one impression (source event) of a
+/// breakdown. The frequency distribution is the number of users that saw `1, 2, ...,
+/// frequency_cap` impressions, where the last bucket also counts the users that saw more. Trigger
+/// events are ignored.
+///
+/// The output has `B` reach values, one per breakdown, followed by `frequency_cap` frequency
+/// buckets. The frequency distribution is computed over `F` lanes, so `frequency_cap` can be up
+/// to `F`, independently of the number of breakdowns.
+///
+/// This protocol performs the following steps
+/// 1. Adds dummy trigger events for the OPRF padding (see [`ReachPaddingRow`])
+/// 2. Shuffles the input
+/// 3. Computes an OPRF of the match keys and reveals this "pseudonym"
+/// 4. Groups together rows with the same OPRF
+/// 5. Counts the impressions of every user (see [`compute_reach_frequency`])
+/// 6. Adds random noise to the reach and to the frequency distribution (to provide a
+///    differential privacy guarantee). Each of them uses half of the privacy budget.
+///
+/// A user contributes to the reach of up to `B` breakdowns, so `2^SS_BITS` must be at least `B`.
+/// A user contributes to a single frequency bucket.
+///
+/// # Errors
+/// If `frequency_cap` is zero or larger than `F` or [`MAX_FREQUENCY_CAP`], and propagates errors
+/// from config issues or while running the protocol.
+/// # Panics
+/// If `2^SS_BITS` is smaller than `B`.
+#[tracing::instrument(name = "reach_frequency", skip_all, fields(rows = input_rows.len()))]
+pub async fn reach_frequency<
+    'ctx,
+    C,
+    BK,
+    TV,
+    HV,
+    TS,
+    const SS_BITS: usize,
+    const B: usize,
+    const F: usize,
+>(
+    ctx: C,
+    input_rows: Vec<OPRFIPAInputRow<BK, TV, TS>>,
+    frequency_cap: usize,
+    dp_params: DpMechanism,
+    dp_padding_params: PaddingParameters,
+) -> Result<Vec<Replicated<HV>>, Error>
+where
+    C: UpgradableContext + Shuffle + 'ctx,
+    BK: BreakdownKey<B>,
+    TV: BooleanArray,
+    TS: BooleanArray,
+    HV: BooleanArray + U128Conversions,
+    Boolean: FieldSimd<B> + FieldSimd<F>,
+    Replicated<Boolean>: BooleanProtocols<DZKPUpgraded<C>>,
+    Replicated<Boolean, B>: BooleanProtocols<DZKPUpgraded<C>, B>,
+    Replicated<Boolean, F>: BooleanProtocols<DZKPUpgraded<C>, F>,
+    Replicated<Boolean, CONV_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, CONV_CHUNK>,
+    Replicated<Fp25519, PRF_CHUNK>:
+        PrfSharing<MacUpgraded<C, Fp25519>, PRF_CHUNK, Field = Fp25519> + FromPrss,
+    Replicated<RP25519, PRF_CHUNK>:
+        Reveal<MacUpgraded<C, Fp25519>, Output = <RP25519 as Vectorizable<PRF_CHUNK>>::Array>,
+    Vec<Replicated<HV>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, B>>, Error = LengthError>,
+    BitDecomposed<Replicated<Boolean, B>>:
+        for<'a> TransposeFrom<&'a [Replicated<HV>; B], Error = std::convert::Infallible>,
+    Vec<Replicated<HV>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, F>>, Error = LengthError>,
+    BitDecomposed<Replicated<Boolean, F>>:
+        for<'a> TransposeFrom<&'a [Replicated<HV>; F], Error = std::convert::Infallible>,
+{
+    assert!(
+        B <= 1 << SS_BITS,
+        "SS_BITS = {SS_BITS} does not bound the contribution of a user to {B} breakdowns",
+    );
+    let max_frequency_cap = min(F, MAX_FREQUENCY_CAP);
+    if frequency_cap == 0 || frequency_cap > max_frequency_cap {
+        return Err(Error::InvalidQueryParameter(
+            format!("frequency cap must be between 1 and {max_frequency_cap}").into(),
+        ));
+    }
+
+    if input_rows.is_empty() {
+        return Ok(vec![Replicated::ZERO; B + frequency_cap]);
+    }
+
+    let padded_input_rows = apply_dp_padding::<_, ReachPaddingRow<BK, TV, TS>, B>(
+        ctx.narrow(&IpaPrfStep::PaddingDp),
+        input_rows.into_iter().map(ReachPaddingRow).collect(),
+        &dp_padding_params,
+    )
+    .await?;
+
+    let shuffled = shuffle_inputs(
+        ctx.narrow(&IpaPrfStep::Shuffle),
+        padded_input_rows.into_iter().map(|row| row.0).collect(),
+    )
+    .await?;
+    let mut prfd_inputs = compute_prf_for_inputs(ctx.clone(), &shuffled).await?;
+    prfd_inputs.sort_by_key(|row| row.prf_of_match_key);
+
+    // The order of the rows of a user does not matter, so unlike attribution, they are not sorted
+    // by timestamp.
+    let (users_having_n_records, _) = histograms_ranges_sortkeys(&mut prfd_inputs);
+
+    let validator = ctx.clone().dzkp_validator(
+        MaliciousProtocolSteps {
+            protocol: &IpaPrfStep::ReachFrequency,
+            validate: &IpaPrfStep::ReachFrequencyValidate,
+        },
+        users_having_n_records[0],
+    );
+    let (reach, frequency) = compute_reach_frequency::<_, BK, TV, TS, HV, B, F>(
+        validator.context(),
+        prfd_inputs,
+        &users_having_n_records,
+        frequency_cap,
+    )
+    .await?;
+    validator.validate().await?;
+
+    let dp_params = dp_params.split_budget(DP_RELEASES);
+    let mut noisy_output =
+        dp_for_histogram::<_, B, HV, SS_BITS>(ctx.clone(), reach, dp_params).await?;
+    let noisy_frequency = dp_for_histogram_with_steps::<_, _, F, HV, 0>(
+        ctx,
+        MaliciousProtocolSteps {
+            protocol: &IpaPrfStep::FrequencyDifferentialPrivacy,
+            validate: &IpaPrfStep::FrequencyDifferentialPrivacyValidate,
+        },
+        frequency,
+        dp_params,
+    )
+    .await?;
+    noisy_output.extend(noisy_frequency.into_iter().take(frequency_cap));
+
+    Ok(noisy_output)
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use crate::{
+        error::Error,
+        ff::{
+            boolean_array::{BA16, BA20, BA3, BA5},
+            U128Conversions,
+        },
+        helpers::query::DpMechanism,
+        protocol::ipa_prf::{
+            oprf_padding::PaddingParameters,
+            prf_sharding::reach_frequency::{reach_frequency, MAX_FREQUENCY_CAP},
+            OPRFIPAInputRow,
+        },
+        test_executor::run,
+        test_fixture::{
+            ipa::TestRawDataRecord, reach_frequency::reach_frequency_in_the_clear, Reconstruct,
+            Runner, TestWorld,
+        },
+    };
+
+    const FREQUENCY_CAP: usize = 3;
+
+    fn test_record(user_id: u64, is_trigger_report: bool, breakdown_key: u32) -> TestRawDataRecord {
+        TestRawDataRecord {
+            timestamp: 0,
+            user_id,
+            is_trigger_report,
+            breakdown_key,
+            trigger_value: 0,
+        }
+    }
+
+    #[test]
+    fn semi_honest() {
+        run(|| async move {
+            let world = TestWorld::default();
+
+            let records = vec![
+                /* First user: two impressions of the same breakdown */
+                test_record(123, false, 3),
+                test_record(123, false, 3),
+                test_record(123, true, 0),
+                /* Second user: three impressions of two breakdowns */
+                test_record(234, false, 3),
+                test_record(234, false, 31),
+                test_record(234, false, 3),
+                /* Third user: only a trigger event */
+                test_record(345, true, 7),
+                /* Fourth user: five impressions, counted in the last frequency bucket */
+                test_record(456, false, 0),
+                test_record(456, false, 1),
+                test_record(456, true, 2),
+                test_record(456, false, 2),
+                test_record(456, false, 3),
+                test_record(456, false, 4),
+                /* Fifth user: a single impression */
+                test_record(567, false, 31),
+            ];
+
+            let mut expected = vec![0_u32; 32 + FREQUENCY_CAP];
+            expected[..5].copy_from_slice(&[1, 1, 1, 3, 1]);
+            expected[31] = 2;
+            expected[32..].copy_from_slice(&[1, 1, 2]);
+            assert_eq!(
+                reach_frequency_in_the_clear(&records, 32, FREQUENCY_CAP),
+                expected
+            );
+
+            let result = world
+                .semi_honest(
+                    records.into_iter(),
+                    |ctx, input_rows: Vec<OPRFIPAInputRow<BA5, BA3, BA20>>| async move {
+                        reach_frequency::<_, BA5, BA3, BA16, BA20, 5, 32, MAX_FREQUENCY_CAP>(
+                            ctx,
+                            input_rows,
+                            FREQUENCY_CAP,
+                            DpMechanism::NoDp,
+                            PaddingParameters::no_padding(),
+                        )
+                        .await
+                        .unwrap()
+                    },
+                )
+                .await
+                .reconstruct()
+                .iter()
+                .map(|v| u32::try_from(v.as_u128()).unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(result, expected);
+        });
+    }
+
+    /// The dummy rows added by the padding must not count towards reach or frequency.
+    #[test]
+    fn semi_honest_with_padding() {
+        run(|| async move {
+            let world = TestWorld::default();
+
+            let mut rng = thread_rng();
+            let records = (0..40)
+                .map(|_| {
+                    test_record(
+                        rng.gen_range(0..10),
+                        rng.gen_bool(0.3),
+                        rng.gen_range(0..32),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let expected = reach_frequency_in_the_clear(&records, 32, FREQUENCY_CAP);
+
+            let result = world
+                .semi_honest(
+                    records.into_iter(),
+                    |ctx, input_rows: Vec<OPRFIPAInputRow<BA5, BA3, BA20>>| async move {
+                        reach_frequency::<_, BA5, BA3, BA16, BA20, 5, 32, MAX_FREQUENCY_CAP>(
+                            ctx,
+                            input_rows,
+                            FREQUENCY_CAP,
+                            DpMechanism::NoDp,
+                            PaddingParameters::relaxed(),
+                        )
+                        .await
+                        .unwrap()
+                    },
+                )
+                .await
+                .reconstruct()
+                .iter()
+                .map(|v| u32::try_from(v.as_u128()).unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(result, expected);
+        });
+    }
+
+    /// The frequency distribution has its own number of lanes, and can use all of them.
+    #[test]
+    fn full_frequency_distribution() {
+        run(|| async move {
+            let world = TestWorld::default();
+
+            // One user for every frequency up to one more than the cap.
+            let records = (1..=MAX_FREQUENCY_CAP + 1)
+                .flat_map(|frequency| {
+                    let user_id = u64::try_from(frequency).unwrap();
+                    (0..frequency)
+                        .map(move |i| test_record(user_id, false, u32::try_from(i % 2).unwrap()))
+                })
+                .collect::<Vec<_>>();
+            let expected = reach_frequency_in_the_clear(&records, 32, MAX_FREQUENCY_CAP);
+            assert_eq!(expected[32..], [1, 1, 1, 1, 1, 1, 1, 2]);
+
+            let result = world
+                .semi_honest(
+                    records.into_iter(),
+                    |ctx, input_rows: Vec<OPRFIPAInputRow<BA5, BA3, BA20>>| async move {
+                        reach_frequency::<_, BA5, BA3, BA16, BA20, 5, 32, MAX_FREQUENCY_CAP>(
+                            ctx,
+                            input_rows,
+                            MAX_FREQUENCY_CAP,
+                            DpMechanism::NoDp,
+                            PaddingParameters::no_padding(),
+                        )
+                        .await
+                        .unwrap()
+                    },
+                )
+                .await
+                .reconstruct()
+                .iter()
+                .map(|v| u32::try_from(v.as_u128()).unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(result, expected);
+        });
+    }
+
+    #[test]
+    fn rejects_invalid_frequency_cap() {
+        run(|| async move {
+            let world = TestWorld::default();
+
+            for frequency_cap in [0, MAX_FREQUENCY_CAP + 1] {
+                let results = world
+                    .semi_honest(
+                        vec![test_record(123, false, 0)].into_iter(),
+                        |ctx, input_rows: Vec<OPRFIPAInputRow<BA5, BA3, BA20>>| async move {
+                            reach_frequency::<_, BA5, BA3, BA16, BA20, 5, 32, MAX_FREQUENCY_CAP>(
+                                ctx,
+                                input_rows,
+                                frequency_cap,
+                                DpMechanism::NoDp,
+                                PaddingParameters::no_padding(),
+                            )
+                            .await
+                        },
+                    )
+                    .await;
+                for result in results {
+                    assert!(matches!(result, Err(Error::InvalidQueryParameter(_))));
+                }
+            }
+        });
+    }
+}
@@ -20,6 +20,7 @@ pub(crate) enum AttributionPerRowStep {
     AttributedBreakdownKey,
     #[step(child = AttributionZeroOutTriggerStep)]
     AttributedTriggerValue,
+    UnattributedBreakdownKey,
     SourceEventTimestamp,
     #[step(child = crate::protocol::boolean::step::EightBitStep)]
     ComputeSaturatingSum,
@@ -48,10 +49,55 @@ pub(crate) enum AttributionWindowStep {
 
 #[derive(CompactStep)]
 pub(crate) enum FeatureLabelDotProductStep {
-    BinaryValidator,
-    PrimeFieldValidator,
+    #[step(child = FeatureLabelUserNthRowStep)]
+    Attribute,
+    #[step(child = crate::protocol::ipa_prf::aggregation::step::AggregateChunkStep)]
+    Aggregate,
+}
+
+#[derive(CompactStep)]
+#[step(count = 64, child = FeatureLabelPerRowStep, name = "row")]
+pub struct FeatureLabelUserNthRowStep(usize);
+
+#[derive(CompactStep)]
+pub(crate) enum FeatureLabelPerRowStep {
     EverEncounteredTriggerEvent,
     DidSourceReceiveAttribution,
     ComputeSaturatingSum,
     IsAttributedSourceAndPrevRowNotSaturated,
+    #[step(child = crate::protocol::boolean::step::EightBitStep)]
+    ComputedCappedFeatureVector,
+}
+
+#[derive(CompactStep)]
+pub(crate) enum ReachFrequencyStep {
+    #[step(child = ReachFrequencyUserNthRowStep)]
+    CountImpressions,
+    #[step(child = crate::protocol::ipa_prf::aggregation::step::AggregateChunkStep)]
+    AggregateReach,
+    #[step(child = crate::protocol::ipa_prf::aggregation::step::AggregateChunkStep)]
+    AggregateFrequency,
+}
+
+#[derive(CompactStep)]
+#[step(count = 64, child = ReachFrequencyPerRowStep, name = "row")]
+pub struct ReachFrequencyUserNthRowStep(usize);
+
+#[derive(CompactStep)]
+pub(crate) enum ReachFrequencyPerRowStep {
+    #[step(count = 8)]
+    MatchBreakdownKeyBit(usize),
+    UpdateSeenBreakdowns,
+    #[step(child = crate::protocol::boolean::step::EightBitStep)]
+    CountImpression,
+}
+
+#[derive(CompactStep)]
+pub(crate) enum LiftStep {
+    #[step(child = crate::protocol::boolean::step::EightBitStep)]
+    ConversionIndicator,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    ConversionIndicatorValidate,
+    #[step(child = crate::protocol::ipa_prf::aggregation::step::AggregationStep)]
+    Aggregate,
 }
@@ -21,7 +21,7 @@ use crate::{
         ipa_prf::shuffle::{
             base::shuffle_protocol,
             sharded::{h1_shuffle_for_shard, h2_shuffle_for_shard, h3_shuffle_for_shard},
-            step::{OPRFShuffleStep, VerifyShuffleStep},
+            step::{OPRFShuffleStep, ShardedShuffleStep, VerifyShuffleStep},
             IntermediateShuffleMessages,
         },
         prss::SharedRandomness,
@@ -128,7 +128,6 @@ where
 /// Failure to communicate over the network, either to other MPC helpers, and/or to other shards
 /// will generate a shuffle error, as will detection of data inconsistencies that could indicate
 /// a malicious helper.
-#[allow(dead_code)]
 pub async fn malicious_sharded_shuffle<I, S, B, C>(
     ctx: C,
     shares: I,
@@ -146,11 +145,11 @@ where
 
     // prepare keys
     let amount_of_keys: usize = (usize::try_from(S::BITS).unwrap() + 31) / 32;
-    let keys = setup_keys(ctx.narrow(&OPRFShuffleStep::SetupKeys), amount_of_keys).await?;
+    let keys = setup_keys(ctx.narrow(&ShardedShuffleStep::SetupKeys), amount_of_keys).await?;
 
     // compute and append tags to rows
     let shares_and_tags: Vec<AdditiveShare<B>> =
-        compute_and_add_tags(ctx.narrow(&OPRFShuffleStep::GenerateTags), &keys, shares).await?;
+        compute_and_add_tags(ctx.narrow(&ShardedShuffleStep::GenerateTags), &keys, shares).await?;
 
     let (shuffled_shares, messages) = match ctx.role() {
         Role::H1 => h1_shuffle_for_shard(ctx.clone(), shares_and_tags).await,
@@ -160,7 +159,7 @@ where
 
     // verify the shuffle
     verify_shuffle::<_, S, B>(
-        ctx.narrow(&OPRFShuffleStep::VerifyShuffle),
+        ctx.narrow(&ShardedShuffleStep::VerifyShuffle),
         &keys,
         &shuffled_shares,
         messages,
@@ -11,13 +11,15 @@ use crate::{
     error::Error,
     ff::{
         boolean::Boolean,
-        boolean_array::{BooleanArray, BA112, BA144, BA64, BA96},
+        boolean_array::{BooleanArray, BA112, BA144, BA256, BA64, BA8, BA96},
         ArrayAccess,
     },
     helpers::Role,
     protocol::{
         context::{Context, MaliciousContext, SemiHonestContext},
         ipa_prf::{
+            logistic_regression::LogisticRegressionInputRow,
+            prf_sharding::feature_label_dot_product::FeatureLabelInputRow,
             shuffle::sharded::{MaliciousShuffleable, ShuffleContext},
             OPRFIPAInputRow,
         },
@@ -127,15 +129,12 @@ impl<'b, T: ShardBinding> Shuffle for MaliciousContext<'b, T> {
 
 /// Trait used by protocols to invoke either semi-honest or malicious sharded shuffle,
 /// depending on the type of context being used.
-#[allow(dead_code)]
 pub trait ShardedShuffle: ShuffleContext {
     fn sharded_shuffle<S, I>(self, shares: I) -> impl Future<Output = Result<Vec<S>, Error>> + Send
     where
         S: MaliciousShuffleable,
         I: IntoIterator<Item = AdditiveShare<S::Share>> + Send,
-        I::IntoIter: ExactSizeIterator + Send,
-        for<'a> &'a S: Add<S, Output = S> + Add<&'a S, Output = S>,
-        Standard: Distribution<S>;
+        I::IntoIter: ExactSizeIterator + Send;
 }
 
 impl<'b> ShardedShuffle for SemiHonestContext<'b, Sharded> {
@@ -144,8 +143,6 @@ impl<'b> ShardedShuffle for SemiHonestContext<'b, Sharded> {
         S: MaliciousShuffleable,
         I: IntoIterator<Item = AdditiveShare<S::Share>> + Send,
         I::IntoIter: ExactSizeIterator + Send,
-        for<'a> &'a S: Add<S, Output = S> + Add<&'a S, Output = S>,
-        Standard: Distribution<S>,
     {
         let fut = sharded_shuffle::<_, S, _>(self, shares.into_iter().map(S::from));
         fut.map(|res| res.map(|(output, _intermediates)| output))
@@ -158,8 +155,6 @@ impl<'b> ShardedShuffle for MaliciousContext<'b, Sharded> {
         S: MaliciousShuffleable,
         I: IntoIterator<Item = AdditiveShare<S::Share>> + Send,
         I::IntoIter: ExactSizeIterator + Send,
-        for<'a> &'a S: Add<S, Output = S> + Add<&'a S, Output = S>,
-        Standard: Distribution<S>,
     {
         let fut = malicious_sharded_shuffle::<_, S::Share, S::ShareAndTag, _>(self, shares);
         fut.map(|res| res.map(|vec| vec.into_iter().map(S::from).collect()))
@@ -190,6 +185,86 @@ where
         .collect::<Vec<_>>())
 }
 
+/// Sharded version of [`shuffle_inputs`]. Rows are shuffled across all shards of this helper,
+/// so the number of rows each shard gets back may differ from the number of rows it had.
+#[tracing::instrument(name = "sharded_shuffle_inputs", skip_all)]
+pub async fn sharded_shuffle_inputs<C, BK, TV, TS>(
+    ctx: C,
+    input: Vec<OPRFIPAInputRow<BK, TV, TS>>,
+) -> Result<Vec<OPRFIPAInputRow<BK, TV, TS>>, Error>
+where
+    C: ShardedShuffle,
+    BK: BooleanArray,
+    TV: BooleanArray,
+    TS: BooleanArray,
+{
+    let shuffle_input: Vec<AdditiveShare<BA112>> = input
+        .into_iter()
+        .map(|item| oprfreport_to_shuffle_input::<BA112, BK, TV, TS>(&item))
+        .collect::<Vec<_>>();
+
+    let shuffled = ctx
+        .sharded_shuffle::<AdditiveShare<BA112>, _>(shuffle_input)
+        .await?;
+
+    Ok(shuffled
+        .into_iter()
+        .map(|item| shuffled_to_oprfreport(&item))
+        .collect::<Vec<_>>())
+}
+
+/// Shuffles the input of the feature-label dot product query.
+///
+/// Rows are packed into a `BA256`, which does not leave room for the tag used by the malicious
+/// shuffle, so this is only supported with semi-honest contexts.
+#[tracing::instrument(name = "shuffle_feature_label_inputs", skip_all)]
+pub async fn shuffle_feature_label_inputs<C, FV, TS, const B: usize>(
+    ctx: C,
+    input: Vec<FeatureLabelInputRow<FV, TS, B>>,
+) -> Result<Vec<FeatureLabelInputRow<FV, TS, B>>, Error>
+where
+    C: Context + Shuffle,
+    FV: BooleanArray,
+    TS: BooleanArray,
+{
+    let shuffle_input: Vec<AdditiveShare<BA256>> = input
+        .into_iter()
+        .map(|item| feature_label_row_to_shuffle_input::<BA256, FV, TS, B>(&item))
+        .collect::<Vec<_>>();
+
+    let shuffled = ctx.shuffle::<BA256, BA256, _>(shuffle_input).await?;
+
+    Ok(shuffled
+        .into_iter()
+        .map(|item| shuffled_to_feature_label_row(&item))
+        .collect::<Vec<_>>())
+}
+
+/// Shuffles the input of the logistic regression query.
+///
+/// Like [`shuffle_feature_label_inputs`], rows are packed into a `BA256`, so this is only
+/// supported with semi-honest contexts.
+#[tracing::instrument(name = "shuffle_logistic_regression_inputs", skip_all)]
+pub async fn shuffle_logistic_regression_inputs<C, const B: usize>(
+    ctx: C,
+    input: Vec<LogisticRegressionInputRow<B>>,
+) -> Result<Vec<LogisticRegressionInputRow<B>>, Error>
+where
+    C: Context + Shuffle,
+{
+    let shuffle_input: Vec<AdditiveShare<BA256>> = input
+        .into_iter()
+        .map(|item| logistic_regression_row_to_shuffle_input::<BA256, B>(&item))
+        .collect::<Vec<_>>();
+
+    let shuffled = ctx.shuffle::<BA256, BA256, _>(shuffle_input).await?;
+
+    Ok(shuffled
+        .into_iter()
+        .map(|item| shuffled_to_logistic_regression_row(&item))
+        .collect::<Vec<_>>())
+}
+
 #[tracing::instrument(name = "shuffle_attribution_outputs", skip_all)]
 pub async fn shuffle_attribution_outputs<C, BK, TV, R>(
     ctx: C,
@@ -217,6 +292,37 @@ where
         .collect::<Vec<_>>())
 }
 
+/// Sharded version of [`shuffle_attribution_outputs`]. Attribution outputs are shuffled across
+/// all shards of this helper, so the number of outputs each shard gets back may differ from the
+/// number of outputs it had.
+///
+/// Breakdown keys and trigger values are packed into a `BA64`, which also determines the size of
+/// the MAC tag used by the malicious shuffle.
+#[tracing::instrument(name = "sharded_shuffle_attribution_outputs", skip_all)]
+pub async fn sharded_shuffle_attribution_outputs<C, BK, TV>(
+    ctx: C,
+    input: Vec<SecretSharedAttributionOutputs<BK, TV>>,
+) -> Result<Vec<SecretSharedAttributionOutputs<BK, TV>>, Error>
+where
+    C: ShardedShuffle,
+    BK: BooleanArray,
+    TV: BooleanArray,
+{
+    let shuffle_input: Vec<AdditiveShare<BA64>> = input
+        .into_iter()
+        .map(|item| attribution_outputs_to_shuffle_input::<BK, TV, BA64>(&item))
+        .collect::<Vec<_>>();
+
+    let shuffled = ctx
+        .sharded_shuffle::<AdditiveShare<BA64>, _>(shuffle_input)
+        .await?;
+
+    Ok(shuffled
+        .into_iter()
+        .map(|item| shuffled_to_attribution_outputs(&item))
+        .collect::<Vec<_>>())
+}
+
 // This function converts OprfReport to an AdditiveShare needed for shuffle protocol
 pub fn oprfreport_to_shuffle_input<YS, BK, TV, TS>(
     input: &OPRFIPAInputRow<BK, TV, TS>,
@@ -285,6 +391,131 @@ where
     }
 }
 
+// This function converts a feature-label input row to an AdditiveShare needed for shuffle protocol
+pub fn feature_label_row_to_shuffle_input<YS, FV, TS, const B: usize>(
+    input: &FeatureLabelInputRow<FV, TS, B>,
+) -> AdditiveShare<YS>
+where
+    YS: BooleanArray,
+    FV: BooleanArray,
+    TS: BooleanArray,
+{
+    assert!(
+        BA64::BITS as usize + 1 + TS::BITS as usize + B * FV::BITS as usize <= YS::BITS as usize,
+        "feature-label input row does not fit into {} bits",
+        YS::BITS,
+    );
+
+    let mut y = ReplicatedSecretSharing::new(YS::ZERO, YS::ZERO);
+    expand_shared_array_in_place(&mut y, &input.match_key, 0);
+
+    let mut offset = BA64::BITS as usize;
+
+    y.set(offset, input.is_trigger.clone());
+
+    offset += 1;
+    expand_shared_array_in_place(&mut y, &input.timestamp, offset);
+
+    offset += TS::BITS as usize;
+    for feature in &input.feature_vector {
+        expand_shared_array_in_place(&mut y, feature, offset);
+        offset += FV::BITS as usize;
+    }
+
+    y
+}
+
+// This function converts AdditiveShare obtained from shuffle protocol to a feature-label input row
+pub fn shuffled_to_feature_label_row<YS, FV, TS, const B: usize>(
+    input: &AdditiveShare<YS>,
+) -> FeatureLabelInputRow<FV, TS, B>
+where
+    YS: BooleanArray,
+    FV: BooleanArray,
+    TS: BooleanArray,
+{
+    let match_key = extract_from_shared_array::<YS, BA64>(input, 0);
+
+    let mut offset = BA64::BITS as usize;
+
+    let is_trigger = ReplicatedSecretSharing::new(
+        input.left().get(offset).unwrap_or(Boolean::ZERO),
+        input.right().get(offset).unwrap_or(Boolean::ZERO),
+    );
+
+    offset += 1;
+    let timestamp = extract_from_shared_array::<YS, TS>(input, offset);
+
+    offset += TS::BITS as usize;
+    let feature_vector = std::array::from_fn(|i| {
+        extract_from_shared_array::<YS, FV>(input, offset + i * FV::BITS as usize)
+    });
+
+    FeatureLabelInputRow {
+        match_key,
+        is_trigger,
+        timestamp,
+        feature_vector,
+    }
+}
+
+// This function converts a logistic regression input row to an AdditiveShare needed for shuffle protocol
+pub fn logistic_regression_row_to_shuffle_input<YS, const B: usize>(
+    input: &LogisticRegressionInputRow<B>,
+) -> AdditiveShare<YS>
+where
+    YS: BooleanArray,
+{
+    assert!(
+        BA64::BITS as usize + 1 + B * BA8::BITS as usize <= YS::BITS as usize,
+        "logistic regression input row does not fit into {} bits",
+        YS::BITS,
+    );
+
+    let mut y = ReplicatedSecretSharing::new(YS::ZERO, YS::ZERO);
+    expand_shared_array_in_place(&mut y, &input.match_key, 0);
+
+    let mut offset = BA64::BITS as usize;
+
+    y.set(offset, input.label.clone());
+
+    offset += 1;
+    for feature in &input.features {
+        expand_shared_array_in_place(&mut y, feature, offset);
+        offset += BA8::BITS as usize;
+    }
+
+    y
+}
+
+// This function converts AdditiveShare obtained from shuffle protocol to a logistic regression input row
+pub fn shuffled_to_logistic_regression_row<YS, const B: usize>(
+    input: &AdditiveShare<YS>,
+) -> LogisticRegressionInputRow<B>
+where
+    YS: BooleanArray,
+{
+    let match_key = extract_from_shared_array::<YS, BA64>(input, 0);
+
+    let offset = BA64::BITS as usize;
+
+    let label = ReplicatedSecretSharing::new(
+        input.left().get(offset).unwrap_or(Boolean::ZERO),
+        input.right().get(offset).unwrap_or(Boolean::ZERO),
+    );
+
+    let offset = offset + 1;
+    let features = std::array::from_fn(|i| {
+        extract_from_shared_array::<YS, BA8>(input, offset + i * BA8::BITS as usize)
+    });
+
+    LogisticRegressionInputRow {
+        match_key,
+        features,
+        label,
+    }
+}
+
 // This function converts Attribution Outputs to an AdditiveShare needed for shuffle protocol
 pub fn attribution_outputs_to_shuffle_input<BK, TV, YS>(
     input: &SecretSharedAttributionOutputs<BK, TV>,
@@ -1,4 +1,3 @@
-#![allow(dead_code)] // until sharded shuffle is used in OPRF
 //! This implements the 3-way shuffle protocol from paper
 //! "Secure Graph Analysis at Scale" by
 //! Toshinori Araki, Jun Furukawa, Benny Pinkas, Kazuma Ohara, Hanan Rosemarin, and Hikaru Tsuchida.
@@ -15,13 +14,16 @@ use rand::seq::SliceRandom;
 
 use crate::{
     ff::{
-        boolean_array::{BooleanArray, BA32, BA64},
+        boolean_array::{BooleanArray, BA112, BA144, BA32, BA64, BA96},
         Serializable, U128Conversions,
     },
     helpers::{Direction, Error, Role, TotalRecords},
     protocol::{
         context::{reshard_iter, ShardedContext},
-        ipa_prf::shuffle::{step::ShardedShuffleStep as ShuffleStep, IntermediateShuffleMessages},
+        ipa_prf::shuffle::{
+            step::{ShardedPermuteStep, ShardedShuffleStep as ShuffleStep},
+            IntermediateShuffleMessages,
+        },
         prss::{FromRandom, SharedRandomness},
         RecordId,
     },
@@ -93,7 +95,7 @@ pub trait ShuffleContext: ShardedContext {
     {
         let data = data.into_iter();
         async move {
-            let masking_ctx = self.narrow(&ShuffleStep::Mask);
+            let masking_ctx = self.narrow(&ShardedPermuteStep::Mask);
             let mut resharded = assert_send(reshard_iter(
                 self.clone(),
                 data.enumerate().map(|(i, item)| {
@@ -110,7 +112,7 @@ pub trait ShuffleContext: ShardedContext {
             ))
             .await?;
 
-            let ctx = self.narrow(&ShuffleStep::LocalShuffle);
+            let ctx = self.narrow(&ShardedPermuteStep::LocalShuffle);
             resharded.shuffle(&mut match direction {
                 Direction::Left => ctx.prss_rng().0,
                 Direction::Right => ctx.prss_rng().1,
@@ -242,6 +244,14 @@ impl MaliciousShuffleable for AdditiveShare<BA32> {
     type ShareAndTag = BA64;
 }
 
+impl MaliciousShuffleable for AdditiveShare<BA64> {
+    type ShareAndTag = BA96;
+}
+
+impl MaliciousShuffleable for AdditiveShare<BA112> {
+    type ShareAndTag = BA144;
+}
+
 /// Sharded shuffle as performed by shards on H1.
 pub(super) async fn h1_shuffle_for_shard<I, S, C>(
     ctx: C,
@@ -6,7 +6,6 @@ use ipa_step_derive::CompactStep;
 
 #[derive(CompactStep)]
 pub(crate) enum OPRFShuffleStep {
-    SetupKeys,
     ApplyPermutations,
     GenerateAHat,
     GenerateBHat,
@@ -31,10 +30,13 @@ pub(crate) enum ShardedShuffleStep {
     /// Depending on the helper position inside the MPC ring, generate Ã, B̃ or both.
     PseudoRandomTable,
     /// Permute the input according to the PRSS shared between H1 and H2.
+    #[step(child = ShardedPermuteStep)]
     Permute12,
     /// Permute the input according to the PRSS shared between H2 and H3.
+    #[step(child = ShardedPermuteStep)]
     Permute23,
     /// Permute the input according to the PRSS shared between H3 and H1.
+    #[step(child = ShardedPermuteStep)]
     Permute31,
     /// Specific to H1 and H2 interaction - H2 informs H1 about |C|.
     Cardinality,
@@ -42,6 +44,20 @@ pub(crate) enum ShardedShuffleStep {
     TransferXY,
     /// H2 and H3 interaction - Exchange `C_1` and `C_2`.
     TransferC,
+    /// Malicious shuffle only. The leader shard generates MAC keys and sends them to all other
+    /// shards.
+    SetupKeys,
+    /// Malicious shuffle only. Compute MAC tags for every row before shuffling it.
+    GenerateTags,
+    /// Malicious shuffle only. Check the shuffled rows and the intermediate messages against
+    /// their MAC tags.
+    #[step(child = VerifyShuffleStep)]
+    VerifyShuffle,
+}
+
+/// Steps used by every permutation of the sharded shuffle.
+#[derive(CompactStep)]
+pub(crate) enum ShardedPermuteStep {
     /// Apply a mask to the given set of shares. Masking values come from PRSS.
     Mask,
     /// Local per-shard shuffle, where each shard redistributes shares locally according to samples
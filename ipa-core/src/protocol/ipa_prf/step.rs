@@ -4,8 +4,18 @@ use ipa_step_derive::CompactStep;
 pub(crate) enum IpaPrfStep {
     #[step(child = crate::protocol::ipa_prf::oprf_padding::step::PaddingDpStep, name="padding_dp")]
     PaddingDp,
+    #[step(child = TriggerValueCapStep)]
+    CapTriggerValues,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    CapTriggerValuesValidate,
+    #[step(child = crate::protocol::dp::step::SuppressionStep)]
+    SuppressSparseBuckets,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    SuppressSparseBucketsValidate,
     #[step(child = crate::protocol::ipa_prf::shuffle::step::OPRFShuffleStep)]
     Shuffle,
+    #[step(child = crate::protocol::ipa_prf::shuffle::step::ShardedShuffleStep)]
+    ShardedShuffle,
     #[step(child = crate::protocol::ipa_prf::boolean_ops::step::Fp25519ConversionStep)]
     ConvertFp25519,
     #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
@@ -13,14 +23,42 @@ pub(crate) enum IpaPrfStep {
     PrfKeyGen,
     #[step(child = crate::protocol::context::step::MaliciousProtocolStep)]
     EvalPrf,
+    ReshardByPrf,
     #[step(child = QuicksortStep)]
     SortByTimestamp,
     #[step(child = crate::protocol::ipa_prf::prf_sharding::step::AttributionStep)]
     Attribution,
+    #[step(child = crate::protocol::ipa_prf::aggregation::step::ShardAggregationStep)]
+    AggregateShardHistograms,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    AggregateShardHistogramsValidate,
+    #[step(child = crate::protocol::ipa_prf::prf_sharding::step::FeatureLabelDotProductStep)]
+    FeatureLabelDotProduct,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    FeatureLabelDotProductValidate,
+    #[step(child = crate::protocol::ipa_prf::logistic_regression::step::LogisticRegressionStep)]
+    LogisticRegression,
+    #[step(child = crate::protocol::ipa_prf::prf_sharding::step::ReachFrequencyStep)]
+    ReachFrequency,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    ReachFrequencyValidate,
+    #[step(child = crate::protocol::ipa_prf::prf_sharding::step::LiftStep)]
+    Lift,
     #[step(child = crate::protocol::dp::step::DPStep, name = "dp")]
     DifferentialPrivacy,
     #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
     DifferentialPrivacyValidate,
+    #[step(child = crate::protocol::dp::step::DPStep, name = "frequency_dp")]
+    FrequencyDifferentialPrivacy,
+    #[step(child = crate::protocol::context::step::DzkpValidationProtocolStep)]
+    FrequencyDifferentialPrivacyValidate,
+}
+
+#[derive(CompactStep)]
+pub(crate) enum TriggerValueCapStep {
+    #[step(child = crate::protocol::boolean::step::ThirtyTwoBitStep)]
+    Compare,
+    Select,
 }
 
 #[derive(CompactStep)]
@@ -0,0 +1,196 @@
+use futures::{stream, TryStreamExt};
+use ipa_step::CompactStep;
+
+use crate::{
+    error::Error,
+    ff::{boolean::Boolean, boolean_array::BooleanArray, ArrayAccess, U128Conversions},
+    helpers::TotalRecords,
+    protocol::{
+        basics::{select, BooleanArrayMul, BooleanProtocols, ShareKnownValue},
+        boolean::{step::ThirtyTwoBitStep, NBitStep},
+        context::{
+            dzkp_validator::{validated_seq_join, DZKPValidator, TARGET_PROOF_SIZE},
+            Context, DZKPUpgraded, MaliciousProtocolSteps, UpgradableContext,
+        },
+        ipa_prf::{
+            boolean_ops::comparison_and_subtraction_sequential::compare_gt,
+            prf_sharding::step::{AttributionPerRowStep, UserNthRowStep},
+            step::{IpaPrfStep as Step, TriggerValueCapStep},
+            OPRFIPAInputRow,
+        },
+        RecordId,
+    },
+    secret_sharing::{replicated::semi_honest::AdditiveShare as Replicated, BitDecomposed},
+};
+
+/// Largest number of trigger events of a single user that can be attributed. Attribution handles
+/// at most 64 rows per user (see `UserNthRowStep`), and the first row is never attributed.
+pub const MAX_ATTRIBUTED_EVENTS_PER_USER: u32 = 63;
+
+// `UserNthRowStep` takes its count as a literal, so make sure the two stay in sync.
+const _: () = assert!(
+    <UserNthRowStep as CompactStep>::STEP_COUNT
+        == (<AttributionPerRowStep as CompactStep>::STEP_COUNT + 1)
+            * (MAX_ATTRIBUTED_EVENTS_PER_USER + 1),
+    "MAX_ATTRIBUTED_EVENTS_PER_USER must be one less than the count of UserNthRowStep"
+);
+
+/// Returns the largest total that a single user can contribute to the IPA histogram.
+///
+/// Per-user capping limits the sum of attributed trigger values to `per_user_credit_cap`. If every
+/// trigger value is also capped at `trigger_value_cap`, a user cannot contribute more than
+/// `trigger_value_cap` for each event that can be attributed, which is a tighter bound when the
+/// cap is small.
+#[must_use]
+pub fn per_user_sensitivity(per_user_credit_cap: u32, trigger_value_cap: Option<u32>) -> u32 {
+    match trigger_value_cap {
+        Some(cap) => per_user_credit_cap.min(cap.saturating_mul(MAX_ATTRIBUTED_EVENTS_PER_USER)),
+        None => per_user_credit_cap,
+    }
+}
+
+fn trigger_value_cap_proof_chunk<TV: BooleanArray>() -> usize {
+    // One multiplication per bit for the comparison and one per bit for the selection.
+    (TARGET_PROOF_SIZE / (2 * usize::try_from(TV::BITS).unwrap())).next_power_of_two()
+}
+
+/// Replaces every trigger value larger than `trigger_value_cap` with `trigger_value_cap`.
+///
+/// Capping individual trigger values before attribution keeps a single large conversion from
+/// dominating its breakdown, see [`per_user_sensitivity`].
+///
+/// # Errors
+/// If the validation of the comparisons fails.
+/// # Panics
+/// If `TV` has more than 32 bits.
+pub async fn cap_trigger_values<C, BK, TV, TS>(
+    ctx: C,
+    input_rows: Vec<OPRFIPAInputRow<BK, TV, TS>>,
+    trigger_value_cap: u32,
+) -> Result<Vec<OPRFIPAInputRow<BK, TV, TS>>, Error>
+where
+    C: UpgradableContext,
+    BK: BooleanArray,
+    TV: BooleanArray + U128Conversions,
+    TS: BooleanArray,
+    Replicated<Boolean>: BooleanProtocols<DZKPUpgraded<C>>,
+    Replicated<TV>: BooleanArrayMul<DZKPUpgraded<C>>,
+{
+    assert!(
+        TV::BITS <= ThirtyTwoBitStep::BITS,
+        "ThirtyTwoBitStep is not large enough to accommodate this comparison"
+    );
+    if input_rows.is_empty() || u128::from(trigger_value_cap) >= (1 << TV::BITS) - 1 {
+        // No trigger value can exceed the cap.
+        return Ok(input_rows);
+    }
+
+    let validator = ctx
+        .set_total_records(TotalRecords::specified(input_rows.len())?)
+        .dzkp_validator(
+            MaliciousProtocolSteps {
+                protocol: &Step::CapTriggerValues,
+                validate: &Step::CapTriggerValuesValidate,
+            },
+            trigger_value_cap_proof_chunk::<TV>(),
+        );
+    let cap_ctx = validator.context();
+    let cap = Replicated::<TV>::share_known_value(&cap_ctx, TV::truncate_from(trigger_value_cap));
+    let cap_bits = BitDecomposed::decompose(TV::BITS, |i| {
+        Replicated::share_known_value(
+            &cap_ctx,
+            Boolean::truncate_from((trigger_value_cap >> i) & 0x1),
+        )
+    });
+
+    let capped_rows = input_rows.into_iter().enumerate().map(|(i, row)| {
+        let record_id = RecordId::from(i);
+        let compare_ctx = cap_ctx.narrow(&TriggerValueCapStep::Compare);
+        let select_ctx = cap_ctx.narrow(&TriggerValueCapStep::Select);
+        let cap = cap.clone();
+        let cap_bits = cap_bits.clone();
+        async move {
+            let exceeds_cap = compare_gt::<_, ThirtyTwoBitStep, 1>(
+                compare_ctx,
+                record_id,
+                &row.trigger_value.to_bits(),
+                &cap_bits,
+            )
+            .await?;
+            let trigger_value = select(
+                select_ctx,
+                record_id,
+                &exceeds_cap,
+                &cap,
+                &row.trigger_value,
+            )
+            .await?;
+            Ok(OPRFIPAInputRow {
+                trigger_value,
+                ..row
+            })
+        }
+    });
+
+    validated_seq_join(validator, stream::iter(capped_rows))
+        .try_collect()
+        .await
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use crate::{
+        ff::{
+            boolean_array::{BA20, BA3, BA5},
+            U128Conversions,
+        },
+        protocol::ipa_prf::{
+            trigger_value_cap::{cap_trigger_values, per_user_sensitivity},
+            OPRFIPAInputRow,
+        },
+        test_executor::run,
+        test_fixture::{Reconstruct, Runner, TestWorld},
+    };
+
+    #[test]
+    fn caps_trigger_values() {
+        run(|| async {
+            let records = [0_u128, 1, 3, 4, 5, 7].map(BA3::truncate_from);
+            let world = TestWorld::default();
+            let result = world
+                .malicious(records.into_iter(), |ctx, input| async move {
+                    let rows = input
+                        .into_iter()
+                        .map(|trigger_value| OPRFIPAInputRow::<BA5, BA3, BA20> {
+                            trigger_value,
+                            ..Default::default()
+                        })
+                        .collect::<Vec<_>>();
+                    cap_trigger_values(ctx, rows, 4)
+                        .await
+                        .unwrap()
+                        .into_iter()
+                        .map(|row| row.trigger_value)
+                        .collect::<Vec<_>>()
+                })
+                .await
+                .reconstruct();
+
+            assert_eq!(
+                result
+                    .iter()
+                    .map(U128Conversions::as_u128)
+                    .collect::<Vec<_>>(),
+                vec![0, 1, 3, 4, 4, 4],
+            );
+        });
+    }
+
+    #[test]
+    fn sensitivity() {
+        assert_eq!(per_user_sensitivity(128, None), 128);
+        assert_eq!(per_user_sensitivity(128, Some(1)), 63);
+        assert_eq!(per_user_sensitivity(8, Some(1)), 8);
+        assert_eq!(per_user_sensitivity(8, Some(u32::MAX)), 8);
+    }
+}
@@ -3,12 +3,13 @@ use std::{
     iter::{once, repeat, zip},
 };
 
+use futures::try_join;
 use futures_util::future::{try_join, try_join4};
 use typenum::{Unsigned, U288, U80};
 
 use crate::{
     const_assert_eq,
-    error::{Error, UnwrapInfallible},
+    error::{Error, UnwrapInfallible, ValidationCheck, ValidationFailure},
     ff::{Fp61BitPrime, Serializable},
     helpers::{
         hashing::{compute_hash, hash_to_field, Hash},
@@ -16,7 +17,9 @@ use crate::{
     },
     protocol::{
         context::{
-            dzkp_validator::MAX_PROOF_RECURSION, step::DzkpProofVerifyStep as Step, Context,
+            dzkp_validator::MAX_PROOF_RECURSION,
+            step::{DzkpProofHashesStep as HashesStep, DzkpProofVerifyStep as Step},
+            Context,
         },
         ipa_prf::{
             malicious_security::{
@@ -90,6 +93,80 @@ impl BatchToVerify {
         }
     }
 
+    /// Non-interactive counterpart of [`Self::generate_batch_to_verify`] followed by
+    /// [`Self::generate_challenges`]. Together with the proof share sent to the left, each helper
+    /// sends to the right the hashes of that share, as well as the hashes of its share of the
+    /// proof from the left prover. After this single round, both verifiers of every proof know
+    /// the hashes of both proof shares and derive the challenges from them (Fiat-Shamir).
+    ///
+    /// The prover may send hashes that do not match the proof share it sent to the other
+    /// verifier, so challenges obtained this way must be checked with [`Self::check_challenges`].
+    ///
+    /// It outputs (`BatchToVerify`, `challenges_for_left_prover`, `challenges_for_right_prover`).
+    ///
+    /// ## Errors
+    /// Propagates network errors.
+    pub async fn generate_batch_and_challenges<C>(
+        proof_ctx: C,
+        hashes_ctx: C,
+        record_id: RecordId,
+        my_batch_left_shares: ProofBatch,
+        shares_of_batch_from_left_prover: ProofBatch,
+        p_mask_from_right_prover: Fp61BitPrime,
+        q_mask_from_left_prover: Fp61BitPrime,
+    ) -> Result<(Self, Vec<Fp61BitPrime>, Vec<Fp61BitPrime>), Error>
+    where
+        C: Context,
+    {
+        let length = my_batch_left_shares.len();
+        let hashes_length = my_batch_left_shares.proofs.len() + 1;
+        let prover_ctx = hashes_ctx.narrow(&HashesStep::Prover);
+        let verifier_ctx = hashes_ctx.narrow(&HashesStep::Verifier);
+
+        // Both hashes go to the right: the helper on the right verifies this helper's proof
+        // together with the helper on the left, which is the other verifier of the left prover.
+        let my_hashes = ProofHashes::from_batch(&my_batch_left_shares);
+        let my_hashes_prover_left = ProofHashes::from_batch(&shares_of_batch_from_left_prover);
+        let (
+            (),
+            (),
+            (),
+            shares_of_batch_from_right_prover,
+            other_hashes_prover_left,
+            other_hashes_prover_right,
+        ) = try_join!(
+            my_batch_left_shares.send_to_left(&proof_ctx, record_id),
+            my_hashes.send_to(&prover_ctx, record_id, Direction::Right),
+            my_hashes_prover_left.send_to(&verifier_ctx, record_id, Direction::Right),
+            ProofBatch::receive_from_right(&proof_ctx, record_id, length),
+            ProofHashes::receive_from(&prover_ctx, record_id, hashes_length, Direction::Left),
+            ProofHashes::receive_from(&verifier_ctx, record_id, hashes_length, Direction::Left),
+        )?;
+
+        let batch = BatchToVerify {
+            first_proof_from_left_prover: shares_of_batch_from_left_prover.first_proof,
+            first_proof_from_right_prover: shares_of_batch_from_right_prover.first_proof,
+            proofs_from_left_prover: shares_of_batch_from_left_prover.proofs,
+            proofs_from_right_prover: shares_of_batch_from_right_prover.proofs,
+            p_mask_from_right_prover,
+            q_mask_from_left_prover,
+        };
+        let my_hashes_prover_right = ProofHashes::generate_hashes(&batch, Direction::Right);
+
+        // The left prover sent the hashes of the share that its left verifier received, and the
+        // left verifier of the right prover sent the hashes of the share derived from PRSS.
+        let challenges_for_left_prover =
+            ProofHashes::challenges(&other_hashes_prover_left, &my_hashes_prover_left);
+        let challenges_for_right_prover =
+            ProofHashes::challenges(&my_hashes_prover_right, &other_hashes_prover_right);
+
+        Ok((
+            batch,
+            challenges_for_left_prover,
+            challenges_for_right_prover,
+        ))
+    }
+
     /// This function computes a tuple of vector of challenges from a `BatchToVerify`
     /// It outputs (`challenges_for_left_prover`, `challenges_for_right_prover`)
     ///
@@ -104,14 +181,6 @@ impl BatchToVerify {
     where
         C: Context,
     {
-        const LRF: usize = LargeProofGenerator::RECURSION_FACTOR;
-        const SRF: usize = SmallProofGenerator::RECURSION_FACTOR;
-
-        // exclude for first proof
-        let exclude_large = u128::try_from(LRF).unwrap();
-        // exclude for other proofs
-        let exclude_small = u128::try_from(SRF).unwrap();
-
         // generate hashes
         let my_hashes_prover_left = ProofHashes::generate_hashes(self, Direction::Left);
         let my_hashes_prover_right = ProofHashes::generate_hashes(self, Direction::Right);
@@ -137,29 +206,52 @@ impl BatchToVerify {
         .unwrap();
 
         // From the perspective of the *prover_left*, _left_ is the other helper and _right_ is this verifier
-        let challenges_for_prover_left = other_hashes_prover_left
-            .hashes
-            .iter()
-            .zip(my_hashes_prover_left.hashes.iter())
-            .zip(once(exclude_large).chain(repeat(exclude_small)))
-            .map(|((hash_left, hash_right), exclude)| {
-                hash_to_field(hash_left, hash_right, exclude)
-            });
+        let challenges_for_prover_left =
+            ProofHashes::challenges(&other_hashes_prover_left, &my_hashes_prover_left);
 
         // From the perspective of the *prover_right*, _left_ is this helper and _right_ is the other verifier
-        let challenges_for_prover_right = my_hashes_prover_right
-            .hashes
-            .iter()
-            .zip(other_hashes_prover_right.hashes.iter())
-            .zip(once(exclude_large).chain(repeat(exclude_small)))
-            .map(|((hash_left, hash_right), exclude)| {
-                hash_to_field(hash_left, hash_right, exclude)
-            });
+        let challenges_for_prover_right =
+            ProofHashes::challenges(&my_hashes_prover_right, &other_hashes_prover_right);
 
-        (
-            challenges_for_prover_left.collect(),
-            challenges_for_prover_right.collect(),
+        (challenges_for_prover_left, challenges_for_prover_right)
+    }
+
+    /// Checks that the other verifier of the right prover derived the same challenges as this
+    /// helper. This is required when challenges come from [`Self::generate_batch_and_challenges`],
+    /// because they depend on hashes provided by the prover. The check runs in the same round as
+    /// the last step of [`Self::verify`].
+    ///
+    /// ## Errors
+    /// Propagates network errors or when the challenges do not match.
+    pub async fn check_challenges<C>(
+        ctx: C,
+        record_id: RecordId,
+        challenges_for_left_prover: &[Fp61BitPrime],
+        challenges_for_right_prover: &[Fp61BitPrime],
+    ) -> Result<(), Error>
+    where
+        C: Context,
+    {
+        let communication_ctx = ctx
+            .narrow(&Step::ChallengeCheck)
+            .set_total_records(TotalRecords::Indeterminate);
+
+        // the other verifier of the left prover is on the right
+        let ((), other_challenges_for_right_prover) = try_join(
+            communication_ctx
+                .send_channel::<Hash>(ctx.role().peer(Direction::Right))
+                .send(record_id, compute_hash(challenges_for_left_prover)),
+            communication_ctx
+                .recv_channel::<Hash>(ctx.role().peer(Direction::Left))
+                .receive(record_id),
         )
+        .await?;
+
+        if other_challenges_for_right_prover == compute_hash(challenges_for_right_prover) {
+            Ok(())
+        } else {
+            Err(right_prover_failed(&ctx, record_id))
+        }
     }
 
     /// This function computes and outputs `p_r_right_prover`, `q_r_left_prover`.
@@ -296,7 +388,9 @@ impl BatchToVerify {
         // compare recombined dif to zero
         for i in 0..length {
             if diff_right[i] + diff_right_from_other_verifier[i] != Fp61BitPrime::ZERO {
-                return Err(Error::DZKPValidationFailed);
+                // The right prover's proof did not verify together with the difference sent
+                // by the left helper, which verifies the same proof.
+                return Err(right_prover_failed(&ctx, record_id));
             }
         }
 
@@ -304,6 +398,21 @@ impl BatchToVerify {
     }
 }
 
+/// Reports that the proof of the right prover did not verify, together with the values sent by the
+/// left helper, which is the other verifier of the same proof.
+fn right_prover_failed<C: Context>(ctx: &C, record_id: RecordId) -> Error {
+    Error::DZKPValidationFailed(Box::new(ValidationFailure {
+        check: ValidationCheck::Dzkp,
+        gate: ctx.gate().clone(),
+        batch: usize::from(record_id),
+        reported_by: ctx.role(),
+        involved: [
+            ctx.role().peer(Direction::Right),
+            ctx.role().peer(Direction::Left),
+        ],
+    }))
+}
+
 struct ProofHashes {
     hashes: Vec<Hash>,
 }
@@ -322,6 +431,18 @@ impl ProofHashes {
             ),
         };
 
+        Self::from_proofs(first_proof, other_proofs)
+    }
+
+    // Generates hashes for the proofs in a batch that this helper is going to send
+    fn from_batch(batch: &ProofBatch) -> Self {
+        Self::from_proofs(&batch.first_proof, &batch.proofs)
+    }
+
+    fn from_proofs(
+        first_proof: &[Fp61BitPrime; LargeProofGenerator::PROOF_LENGTH],
+        other_proofs: &[[Fp61BitPrime; SmallProofGenerator::PROOF_LENGTH]],
+    ) -> Self {
         Self {
             hashes: once(compute_hash(first_proof))
                 .chain(other_proofs.iter().map(|proof| compute_hash(proof.iter())))
@@ -329,6 +450,30 @@ impl ProofHashes {
         }
     }
 
+    /// Computes the challenges for a prover from the hashes of its left and right proof shares.
+    /// This must produce the same values as the prover computes in
+    /// `ProofGenerator::gen_challenge_and_recurse`.
+    ///
+    /// ## Panics
+    /// Panics when recursion factor constant cannot be converted to `u128`.
+    fn challenges(left_share: &Self, right_share: &Self) -> Vec<Fp61BitPrime> {
+        const LRF: usize = LargeProofGenerator::RECURSION_FACTOR;
+        const SRF: usize = SmallProofGenerator::RECURSION_FACTOR;
+
+        // exclude for first proof
+        let exclude_large = u128::try_from(LRF).unwrap();
+        // exclude for other proofs
+        let exclude_small = u128::try_from(SRF).unwrap();
+
+        left_share
+            .hashes
+            .iter()
+            .zip(right_share.hashes.iter())
+            .zip(once(exclude_large).chain(repeat(exclude_small)))
+            .map(|((hash_left, hash_right), exclude)| hash_to_field(hash_left, hash_right, exclude))
+            .collect()
+    }
+
     /// Sends the one verifier's hashes to the other verifier
     /// `direction` indicates the direction of the prover.
     async fn send_hashes<C: Context>(
@@ -336,32 +481,50 @@ impl ProofHashes {
         ctx: &C,
         record_id: RecordId,
         direction: Direction,
+    ) -> Result<(), Error> {
+        self.send_to(ctx, record_id, !direction).await
+    }
+
+    /// This function receives hashes from the other verifier
+    /// `direction` indicates the direction of the prover.
+    async fn receive_hashes<C: Context>(
+        ctx: &C,
+        record_id: RecordId,
+        length: usize,
+        direction: Direction,
+    ) -> Result<Self, Error> {
+        Self::receive_from(ctx, record_id, length, !direction).await
+    }
+
+    /// Sends hashes to the helper in the given direction.
+    async fn send_to<C: Context>(
+        &self,
+        ctx: &C,
+        record_id: RecordId,
+        direction: Direction,
     ) -> Result<(), Error> {
         assert!(self.hashes.len() <= MAX_PROOF_RECURSION);
         let hashes_send =
             array::from_fn(|i| self.hashes.get(i).unwrap_or(&Hash::default()).clone());
-        let verifier_direction = !direction;
         ctx.set_total_records(TotalRecords::Indeterminate)
-            .send_channel::<[Hash; MAX_PROOF_RECURSION]>(ctx.role().peer(verifier_direction))
+            .send_channel::<[Hash; MAX_PROOF_RECURSION]>(ctx.role().peer(direction))
             .send(record_id, hashes_send)
             .await?;
 
         Ok(())
     }
 
-    /// This function receives hashes from the other verifier
-    /// `direction` indicates the direction of the prover.
-    async fn receive_hashes<C: Context>(
+    /// Receives hashes from the helper in the given direction.
+    async fn receive_from<C: Context>(
         ctx: &C,
         record_id: RecordId,
         length: usize,
         direction: Direction,
     ) -> Result<Self, Error> {
         assert!(length <= MAX_PROOF_RECURSION);
-        let verifier_direction = !direction;
         let hashes_received = ctx
             .set_total_records(TotalRecords::Indeterminate)
-            .recv_channel::<[Hash; MAX_PROOF_RECURSION]>(ctx.role().peer(verifier_direction))
+            .recv_channel::<[Hash; MAX_PROOF_RECURSION]>(ctx.role().peer(direction))
             .receive(record_id)
             .await?;
         Ok(Self {
@@ -773,6 +936,87 @@ pub mod test {
         });
     }
 
+    /// This test checks that challenges derived from the hashes sent along with the proofs are the
+    /// ones that the interactive protocol would generate for the same batch
+    #[test]
+    fn non_interactive_challenges() {
+        const LEN: usize = 100;
+
+        run(|| async move {
+            let world = TestWorld::default();
+
+            let [(h1_c_left, h1_c_right, h1_batch), (h2_c_left, h2_c_right, h2_batch), (h3_c_left, h3_c_right, h3_batch)] =
+                world
+                    .semi_honest((), |ctx, ()| async move {
+                        let (vec_my_u_and_v, _, _, _) = generate_u_v(&ctx, LEN);
+
+                        let (
+                            my_batch_left_shares,
+                            shares_of_batch_from_left_prover,
+                            p_mask_from_right_prover,
+                            q_mask_from_left_prover,
+                        ) = ProofBatch::generate(
+                            &ctx.narrow("generate_batch"),
+                            RecordIdRange::ALL,
+                            vec_my_u_and_v.into_iter(),
+                        );
+
+                        let (
+                            batch_to_verify,
+                            challenges_for_left_prover,
+                            challenges_for_right_prover,
+                        ) = BatchToVerify::generate_batch_and_challenges(
+                            ctx.narrow("generate_batch"),
+                            ctx.narrow("proof_hashes"),
+                            RecordId::FIRST,
+                            my_batch_left_shares,
+                            shares_of_batch_from_left_prover,
+                            p_mask_from_right_prover,
+                            q_mask_from_left_prover,
+                        )
+                        .await
+                        .unwrap();
+
+                        assert_eq!(
+                            (
+                                challenges_for_left_prover.clone(),
+                                challenges_for_right_prover.clone()
+                            ),
+                            batch_to_verify
+                                .generate_challenges(ctx.narrow("generate_hash"), RecordId::FIRST)
+                                .await
+                        );
+
+                        BatchToVerify::check_challenges(
+                            ctx.narrow("check_challenges"),
+                            RecordId::FIRST,
+                            &challenges_for_left_prover,
+                            &challenges_for_right_prover,
+                        )
+                        .await
+                        .unwrap();
+
+                        (
+                            challenges_for_left_prover,
+                            challenges_for_right_prover,
+                            batch_to_verify,
+                        )
+                    })
+                    .await;
+
+            // h1 prover
+            assert_eq!(h2_c_left, h3_c_right);
+            // h2 prover
+            assert_eq!(h3_c_left, h1_c_right);
+            // h3 prover
+            assert_eq!(h1_c_left, h2_c_right);
+
+            assert_batch(&h2_batch, &h3_batch, &h3_c_right);
+            assert_batch(&h3_batch, &h1_batch, &h1_c_right);
+            assert_batch(&h1_batch, &h2_batch, &h2_c_right);
+        });
+    }
+
     fn assert_batch(left: &BatchToVerify, right: &BatchToVerify, challenges: &[Fp61BitPrime]) {
         const SRF: usize = SmallProofGenerator::RECURSION_FACTOR;
         const SPL: usize = SmallProofGenerator::PROOF_LENGTH;
@@ -11,6 +11,13 @@ pub enum ProtocolStep {
     Hybrid,
     Multiply,
     PrimeFieldAddition,
+    #[step(child = crate::protocol::ipa_prf::shuffle::step::ShardedShuffleStep)]
+    ShardedShuffle,
+    /// Used by the query executor to detect failed shards in sharded deployments.
+    #[step(child = LivenessStep)]
+    Liveness,
+    /// Used to abort the query on all helpers when one of them cannot continue running it.
+    Abort,
     /// Steps used in unit tests are grouped under this one. Ideally it should be
     /// gated behind test configuration, but it does not work with build.rs that
     /// does not enable any features when creating protocol gate file
@@ -35,12 +42,16 @@ impl<'de> serde::Deserialize<'de> for ProtocolGate {
 pub enum DeadCodeStep {
     #[step(child = crate::protocol::ipa_prf::boolean_ops::step::SaturatedSubtractionStep)]
     SaturatedSubtraction,
-    #[step(child = crate::protocol::ipa_prf::prf_sharding::step::FeatureLabelDotProductStep)]
-    FeatureLabelDotProduct,
-    #[step(child = crate::protocol::ipa_prf::boolean_ops::step::MultiplicationStep)]
-    Multiplication,
-    #[step(child = crate::protocol::ipa_prf::shuffle::step::ShardedShuffleStep)]
-    ShardedShuffle,
+    #[step(child = crate::protocol::ipa_prf::boolean_ops::step::ActivationStep)]
+    Activation,
+}
+
+#[derive(CompactStep)]
+pub enum LivenessStep {
+    /// Shards of the same helper tell each other that they are still running the query.
+    Heartbeat,
+    /// A shard failed and the query must be aborted everywhere.
+    Abort,
 }
 
 /// Provides a unique per-iteration context in tests.
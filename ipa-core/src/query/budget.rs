@@ -0,0 +1,624 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    helpers::query::{
+        DpConfigError, DpMechanism, HybridQueryParams, IpaQueryConfig, QueryConfig, QueryType,
+    },
+    protocol::ipa_prf::oprf_padding::{AggregationPadding, OPRFPadding, PaddingParameters},
+    report::{hybrid_info::HybridConversionInfo, ipa::Epoch},
+    sync::Mutex,
+};
+
+/// Privacy budget is tracked separately for every conversion site and epoch.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BudgetKey {
+    pub conversion_site: String,
+    pub epoch: u32,
+}
+
+/// The amount of privacy budget that a query spends.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BudgetCharge {
+    pub key: BudgetKey,
+    pub epsilon: f64,
+}
+
+impl BudgetCharge {
+    /// Computes the charge for the given query. It covers the noise added to the output together
+    /// with the OPRF and aggregation padding, as all of them spend the privacy budget of the
+    /// conversion site.
+    ///
+    /// ## Errors
+    /// If the query does not say which budget it should be charged against, does not apply DP
+    /// to its output, requests invalid DP parameters or is not a query that can be charged.
+    pub fn for_query(config: &QueryConfig) -> Result<Self, BudgetError> {
+        let (conversion_site, epoch, dp_mechanism, padding) = match &config.query_type {
+            QueryType::SemiHonestOprfIpa(config)
+            | QueryType::MaliciousOprfIpa(config)
+            | QueryType::SemiHonestShardedOprfIpa(config)
+            | QueryType::MaliciousShardedOprfIpa(config) => (
+                config.conversion_site.as_ref(),
+                config.epoch,
+                config.dp_mechanism()?,
+                config.padding_params(),
+            ),
+            QueryType::SemiHonestHybrid(config) => (
+                config.conversion_site.as_ref(),
+                config.epoch,
+                config.dp_mechanism()?,
+                config.padding_params(),
+            ),
+            QueryType::SemiHonestFeatureLabelDotProduct(config) => (
+                config.conversion_site.as_ref(),
+                config.epoch,
+                config.dp_mechanism()?,
+                config.padding_params(),
+            ),
+            QueryType::SemiHonestLogisticRegression(config) => (
+                config.conversion_site.as_ref(),
+                config.epoch,
+                config.dp_mechanism()?,
+                config.padding_params(),
+            ),
+            QueryType::SemiHonestReachFrequency(config) => (
+                config.conversion_site.as_ref(),
+                config.epoch,
+                config.dp_mechanism()?,
+                config.padding_params(),
+            ),
+            QueryType::SemiHonestLift(config) => (
+                config.conversion_site.as_ref(),
+                config.epoch,
+                config.dp_mechanism()?,
+                config.padding_params(),
+            ),
+            #[cfg(any(test, feature = "test-fixture", feature = "cli"))]
+            QueryType::TestMultiply
+            | QueryType::TestAddInPrimeField
+            | QueryType::TestShardedShuffle => {
+                return Err(BudgetError::Unpriced(
+                    config.query_type.as_ref().to_string(),
+                ))
+            }
+        };
+
+        let (Some(conversion_site), Some(epoch)) = (conversion_site, epoch) else {
+            return Err(BudgetError::MissingKey);
+        };
+        let output_epsilon = match dp_mechanism {
+            DpMechanism::NoDp => return Err(BudgetError::NoDp),
+            DpMechanism::Binomial { epsilon, .. }
+            | DpMechanism::DiscreteLaplace { epsilon, .. }
+            | DpMechanism::DiscreteGaussian { epsilon, .. } => epsilon,
+        };
+
+        Ok(Self {
+            key: BudgetKey {
+                conversion_site: conversion_site.clone(),
+                epoch,
+            },
+            epsilon: output_epsilon + padding_epsilon(&padding),
+        })
+    }
+}
+
+/// Epsilon spent by the dummy events that padding adds to the input and to the aggregation.
+fn padding_epsilon(padding: &PaddingParameters) -> f64 {
+    let oprf = match padding.oprf_padding {
+        OPRFPadding::NoOPRFPadding => 0.0,
+        OPRFPadding::Parameters { oprf_epsilon, .. } => oprf_epsilon,
+    };
+    let aggregation = match padding.aggregation_padding {
+        AggregationPadding::NoAggPadding => 0.0,
+        AggregationPadding::Parameters {
+            aggregation_epsilon,
+            ..
+        } => aggregation_epsilon,
+    };
+
+    oprf + aggregation
+}
+
+/// Conversion reports are issued for a particular conversion site and carry the epsilon that
+/// the site is willing to spend on them. Queries over these reports must not be charged to
+/// another site and must not spend more than that.
+///
+/// ## Errors
+/// If the query does not match the information bound to conversion reports.
+pub fn check_conversion_info(
+    config: &HybridQueryParams,
+    info: &HybridConversionInfo,
+) -> Result<(), BudgetError> {
+    check_site(
+        config.conversion_site.as_deref(),
+        info.conversion_site_domain,
+    )?;
+    if let DpMechanism::Binomial { epsilon, .. }
+    | DpMechanism::DiscreteLaplace { epsilon, .. }
+    | DpMechanism::DiscreteGaussian { epsilon, .. } = config.dp_mechanism()?
+    {
+        if epsilon > info.epsilon {
+            return Err(BudgetError::ReportEpsilonExceeded {
+                requested: epsilon,
+                allowed: info.epsilon,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Trigger reports in IPA queries are bound to the conversion site and the epoch they were
+/// issued in. Queries must only read reports that belong to the budget they are charged against.
+///
+/// ## Errors
+/// If the report was issued for another conversion site or epoch than the query is charged to.
+pub fn check_trigger_report(
+    config: &IpaQueryConfig,
+    site_domain: &str,
+    epoch: Epoch,
+) -> Result<(), BudgetError> {
+    check_site(config.conversion_site.as_deref(), site_domain)?;
+    match config.epoch {
+        Some(query) if query != u32::from(epoch) => Err(BudgetError::EpochMismatch {
+            query,
+            reports: epoch,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn check_site(query: Option<&str>, reports: &str) -> Result<(), BudgetError> {
+    match query {
+        Some(query) if query != reports => Err(BudgetError::SiteMismatch {
+            query: query.to_string(),
+            reports: reports.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum BudgetError {
+    #[error(
+        "privacy budget for {key:?} is exhausted: requested {requested}, remaining {remaining}"
+    )]
+    Exhausted {
+        key: BudgetKey,
+        requested: f64,
+        remaining: f64,
+    },
+    #[error("query must set conversion_site and epoch to be charged against a privacy budget")]
+    MissingKey,
+    #[error("queries without DP cannot be charged against a privacy budget")]
+    NoDp,
+    #[error("{0} queries cannot be charged against a privacy budget")]
+    Unpriced(String),
+    #[error(
+        "query epsilon {requested} exceeds the epsilon {allowed} that conversion reports were \
+        issued with"
+    )]
+    ReportEpsilonExceeded { requested: f64, allowed: f64 },
+    #[error("query is charged to {query:?}, but conversion reports belong to {reports:?}")]
+    SiteMismatch { query: String, reports: String },
+    #[error("query is charged to epoch {query}, but conversion reports belong to epoch {reports}")]
+    EpochMismatch { query: u32, reports: Epoch },
+    #[error(transparent)]
+    DpParameters(#[from] DpConfigError),
+    #[error("failed to access the privacy budget ledger: {0}")]
+    Io(#[from] io::Error),
+    #[error("privacy budget ledger is malformed: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Balance {
+    spent: f64,
+    reserved: f64,
+}
+
+/// The state of a single budget, as reported to helper operators.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BudgetEntry {
+    pub conversion_site: String,
+    pub epoch: u32,
+    pub spent: f64,
+    pub reserved: f64,
+    pub remaining: f64,
+}
+
+/// What is written to disk: only the budget that has been spent. Reservations belong to
+/// queries in flight and do not survive a restart.
+#[derive(Serialize, Deserialize)]
+struct PersistedEntry {
+    conversion_site: String,
+    epoch: u32,
+    spent: f64,
+}
+
+/// Keeps track of the privacy budget spent by queries on this helper.
+///
+/// Each (conversion site, epoch) pair gets the same budget. Queries reserve their epsilon when
+/// they are accepted, and the reservation is turned into spent budget once they complete
+/// successfully. Queries that fail or are killed release their reservation. If the ledger is
+/// backed by a file, it is rewritten every time budget is spent.
+pub struct BudgetLedger {
+    epoch_budget: f64,
+    path: Option<PathBuf>,
+    balances: Mutex<HashMap<BudgetKey, Balance>>,
+}
+
+impl BudgetLedger {
+    /// Creates a ledger that is not persisted.
+    #[must_use]
+    pub fn new(epoch_budget: f64) -> Self {
+        Self {
+            epoch_budget,
+            path: None,
+            balances: Mutex::default(),
+        }
+    }
+
+    /// Opens the ledger stored at `path`, or creates a new one if the file does not exist yet.
+    ///
+    /// ## Errors
+    /// If the file exists, but cannot be read or parsed.
+    pub fn open<P: AsRef<Path>>(path: P, epoch_budget: f64) -> Result<Self, BudgetError> {
+        let path = path.as_ref().to_path_buf();
+        let balances = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<Vec<PersistedEntry>>(&bytes)?
+                .into_iter()
+                .map(|entry| {
+                    (
+                        BudgetKey {
+                            conversion_site: entry.conversion_site,
+                            epoch: entry.epoch,
+                        },
+                        Balance {
+                            spent: entry.spent,
+                            reserved: 0.0,
+                        },
+                    )
+                })
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            epoch_budget,
+            path: Some(path),
+            balances: Mutex::new(balances),
+        })
+    }
+
+    #[must_use]
+    pub fn epoch_budget(&self) -> f64 {
+        self.epoch_budget
+    }
+
+    /// Sets aside budget for a query that is about to run.
+    ///
+    /// ## Errors
+    /// If the remaining budget is not enough to cover this charge.
+    ///
+    /// ## Panics
+    /// If the ledger mutex is poisoned.
+    pub fn reserve(&self, charge: &BudgetCharge) -> Result<(), BudgetError> {
+        let mut balances = self.balances.lock().unwrap();
+        let balance = balances.entry(charge.key.clone()).or_default();
+        let remaining = self.epoch_budget - balance.spent - balance.reserved;
+        if charge.epsilon > remaining {
+            return Err(BudgetError::Exhausted {
+                key: charge.key.clone(),
+                requested: charge.epsilon,
+                remaining: remaining.max(0.0),
+            });
+        }
+        balance.reserved += charge.epsilon;
+
+        Ok(())
+    }
+
+    /// Returns the budget reserved by a query that did not release any results.
+    ///
+    /// ## Panics
+    /// If the ledger mutex is poisoned.
+    pub fn release(&self, charge: &BudgetCharge) {
+        let mut balances = self.balances.lock().unwrap();
+        if let Some(balance) = balances.get_mut(&charge.key) {
+            balance.reserved = (balance.reserved - charge.epsilon).max(0.0);
+        }
+    }
+
+    /// Marks the budget reserved by a completed query as spent.
+    ///
+    /// ## Errors
+    /// If the ledger cannot be written to disk. The budget is considered spent regardless.
+    ///
+    /// ## Panics
+    /// If the ledger mutex is poisoned.
+    pub fn debit(&self, charge: &BudgetCharge) -> Result<(), BudgetError> {
+        let mut balances = self.balances.lock().unwrap();
+        let balance = balances.entry(charge.key.clone()).or_default();
+        balance.reserved = (balance.reserved - charge.epsilon).max(0.0);
+        balance.spent += charge.epsilon;
+
+        // keep the lock while writing to make sure older state never overwrites newer
+        self.persist(&balances)
+    }
+
+    /// Returns the state of all budgets known to this ledger.
+    ///
+    /// ## Panics
+    /// If the ledger mutex is poisoned.
+    #[must_use]
+    pub fn entries(&self) -> Vec<BudgetEntry> {
+        let balances = self.balances.lock().unwrap();
+        let mut entries = balances
+            .iter()
+            .map(|(key, balance)| BudgetEntry {
+                conversion_site: key.conversion_site.clone(),
+                epoch: key.epoch,
+                spent: balance.spent,
+                reserved: balance.reserved,
+                remaining: (self.epoch_budget - balance.spent - balance.reserved).max(0.0),
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| (&a.conversion_site, a.epoch).cmp(&(&b.conversion_site, b.epoch)));
+
+        entries
+    }
+
+    fn persist(&self, balances: &HashMap<BudgetKey, Balance>) -> Result<(), BudgetError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let entries = balances
+            .iter()
+            .filter(|(_, balance)| balance.spent > 0.0)
+            .map(|(key, balance)| PersistedEntry {
+                conversion_site: key.conversion_site.clone(),
+                epoch: key.epoch,
+                spent: balance.spent,
+            })
+            .collect::<Vec<_>>();
+
+        // write the new state next to the ledger first, so a crash never leaves it truncated
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(&entries)?)?;
+        fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+/// Snapshot of the ledger returned to helper operators by the inspection endpoint.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BudgetLedgerStatus {
+    /// Budget that every conversion site gets for each epoch. `None` if this helper does not
+    /// track privacy budget.
+    pub epoch_budget: Option<f64>,
+    pub entries: Vec<BudgetEntry>,
+}
+
+impl From<&BudgetLedger> for BudgetLedgerStatus {
+    fn from(ledger: &BudgetLedger) -> Self {
+        Self {
+            epoch_budget: Some(ledger.epoch_budget),
+            entries: ledger.entries(),
+        }
+    }
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use super::{
+        check_conversion_info, check_trigger_report, BudgetCharge, BudgetEntry, BudgetError,
+        BudgetKey, BudgetLedger,
+    };
+    use crate::{
+        ff::FieldType,
+        helpers::query::{
+            HybridQueryParams, IpaQueryConfig, LiftQueryParams, QueryConfig, QueryType,
+        },
+        report::hybrid_info::HybridConversionInfo,
+    };
+
+    fn charge(conversion_site: &str, epoch: u32, epsilon: f64) -> BudgetCharge {
+        BudgetCharge {
+            key: BudgetKey {
+                conversion_site: conversion_site.to_string(),
+                epoch,
+            },
+            epsilon,
+        }
+    }
+
+    fn entry(
+        conversion_site: &str,
+        epoch: u32,
+        spent: f64,
+        reserved: f64,
+        remaining: f64,
+    ) -> BudgetEntry {
+        BudgetEntry {
+            conversion_site: conversion_site.to_string(),
+            epoch,
+            spent,
+            reserved,
+            remaining,
+        }
+    }
+
+    #[test]
+    fn charge_for_query() {
+        let query = |config: IpaQueryConfig| {
+            QueryConfig::new(
+                QueryType::MaliciousOprfIpa(config),
+                FieldType::Fp32BitPrime,
+                1,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            charge("shop.example", 3, 0.75),
+            BudgetCharge::for_query(&query(IpaQueryConfig {
+                epsilon: 0.5,
+                oprf_padding_epsilon: 0.125,
+                aggregation_padding_epsilon: 0.125,
+                conversion_site: Some("shop.example".to_string()),
+                epoch: Some(3),
+                ..Default::default()
+            }))
+            .unwrap()
+        );
+        // queries that can't configure padding are charged for the default one
+        assert_eq!(
+            charge("shop.example", 3, 11.0),
+            BudgetCharge::for_query(
+                &QueryConfig::new(
+                    QueryType::SemiHonestLift(LiftQueryParams {
+                        epsilon: 1.0,
+                        conversion_site: Some("shop.example".to_string()),
+                        epoch: Some(3),
+                        ..Default::default()
+                    }),
+                    FieldType::Fp32BitPrime,
+                    1,
+                )
+                .unwrap()
+            )
+            .unwrap()
+        );
+        assert!(matches!(
+            BudgetCharge::for_query(&query(IpaQueryConfig::default())),
+            Err(BudgetError::MissingKey)
+        ));
+        assert!(matches!(
+            BudgetCharge::for_query(&query(IpaQueryConfig {
+                with_dp: 0,
+                conversion_site: Some("shop.example".to_string()),
+                epoch: Some(3),
+                ..Default::default()
+            })),
+            Err(BudgetError::NoDp)
+        ));
+        assert!(matches!(
+            BudgetCharge::for_query(
+                &QueryConfig::new(QueryType::TestMultiply, FieldType::Fp31, 1).unwrap()
+            ),
+            Err(BudgetError::Unpriced(_))
+        ));
+    }
+
+    #[test]
+    fn trigger_report() {
+        let config = |conversion_site: Option<&str>, epoch: Option<u32>| IpaQueryConfig {
+            conversion_site: conversion_site.map(ToString::to_string),
+            epoch,
+            ..Default::default()
+        };
+
+        check_trigger_report(&config(None, None), "shop.example", 1).unwrap();
+        check_trigger_report(&config(Some("shop.example"), Some(1)), "shop.example", 1).unwrap();
+        assert!(matches!(
+            check_trigger_report(&config(Some("shop.example"), None), "other.example", 1),
+            Err(BudgetError::SiteMismatch { .. })
+        ));
+        assert!(matches!(
+            check_trigger_report(&config(None, Some(2)), "shop.example", 1),
+            Err(BudgetError::EpochMismatch {
+                query: 2,
+                reports: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn conversion_info() {
+        let info =
+            HybridConversionInfo::new(0, "HELPER_ORIGIN", "shop.example", 1, 1.0, 1.0).unwrap();
+        let params = |epsilon: f64, conversion_site: Option<&str>| HybridQueryParams {
+            epsilon,
+            conversion_site: conversion_site.map(ToString::to_string),
+            ..Default::default()
+        };
+
+        check_conversion_info(&params(1.0, None), &info).unwrap();
+        check_conversion_info(&params(0.5, Some("shop.example")), &info).unwrap();
+        assert!(matches!(
+            check_conversion_info(&params(1.5, Some("shop.example")), &info),
+            Err(BudgetError::ReportEpsilonExceeded { .. })
+        ));
+        assert!(matches!(
+            check_conversion_info(&params(0.5, Some("other.example")), &info),
+            Err(BudgetError::SiteMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn reserve_and_debit() {
+        let ledger = BudgetLedger::new(1.0);
+        let first = charge("shop.example", 1, 0.6);
+        let second = charge("shop.example", 1, 0.5);
+
+        ledger.reserve(&first).unwrap();
+        // reserved budget can't be used by other queries
+        assert!(matches!(
+            ledger.reserve(&second),
+            Err(BudgetError::Exhausted { .. })
+        ));
+        // other epochs and sites have their own budget
+        ledger.reserve(&charge("shop.example", 2, 0.5)).unwrap();
+        ledger.reserve(&charge("other.example", 1, 0.5)).unwrap();
+
+        ledger.release(&first);
+        ledger.reserve(&second).unwrap();
+        ledger.debit(&second).unwrap();
+        assert!(matches!(
+            ledger.reserve(&first),
+            Err(BudgetError::Exhausted { .. })
+        ));
+
+        assert_eq!(
+            vec![
+                entry("other.example", 1, 0.0, 0.5, 0.5),
+                entry("shop.example", 1, 0.5, 0.0, 0.5),
+                entry("shop.example", 2, 0.0, 0.5, 0.5),
+            ],
+            ledger.entries()
+        );
+    }
+
+    #[test]
+    fn persists_spent_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger.json");
+
+        let ledger = BudgetLedger::open(&path, 1.0).unwrap();
+        let spent = charge("shop.example", 1, 0.75);
+        ledger.reserve(&spent).unwrap();
+        ledger.debit(&spent).unwrap();
+        // reservations are not persisted
+        ledger.reserve(&charge("shop.example", 2, 0.5)).unwrap();
+        drop(ledger);
+
+        let ledger = BudgetLedger::open(&path, 1.0).unwrap();
+        assert_eq!(
+            vec![entry("shop.example", 1, 0.75, 0.0, 0.25)],
+            ledger.entries()
+        );
+        assert!(matches!(
+            ledger.reserve(&charge("shop.example", 1, 0.5)),
+            Err(BudgetError::Exhausted { .. })
+        ));
+    }
+}
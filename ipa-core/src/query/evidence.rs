@@ -0,0 +1,461 @@
+use std::{
+    fmt::{Debug, Formatter},
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    error::{ValidationCheck, ValidationFailure},
+    helpers::Role,
+    sync::Mutex,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum EvidenceLogError {
+    #[error("failed to access the evidence log: {0}")]
+    Io(#[from] io::Error),
+    #[error("evidence log is malformed: {0}")]
+    Format(#[from] serde_json::Error),
+    #[error("evidence key must be 32 bytes encoded as hex")]
+    BadKey,
+    #[error("evidence log has been tampered with: entry {0} does not match the MAC chain")]
+    Tampered(u64),
+    #[error(
+        "evidence log has been truncated: expected at least {expected} entries, found {found}"
+    )]
+    Truncated { expected: u64, found: u64 },
+    #[error("evidence log has entries, but its head file is missing")]
+    MissingHead,
+}
+
+/// Secret key of this helper that authenticates entries of its evidence log. Without it, nobody
+/// can add, modify or remove entries without that being detected by [`verify_chain`].
+#[derive(Clone)]
+pub struct EvidenceKey([u8; 32]);
+
+impl EvidenceKey {
+    /// Reads the key from its hex encoding, which is how it is stored on disk.
+    ///
+    /// ## Errors
+    /// If `hex` does not encode exactly 32 bytes.
+    pub fn from_hex(hex: &str) -> Result<Self, EvidenceLogError> {
+        let mut key = [0_u8; 32];
+        hex::decode_to_slice(hex.trim(), &mut key).map_err(|_| EvidenceLogError::BadKey)?;
+        Ok(Self(key))
+    }
+
+    pub fn random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut key = [0_u8; 32];
+        rng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    fn mac(&self, data: &[u8]) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts keys of any length");
+        mac.update(data);
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+impl Debug for EvidenceKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EvidenceKey(..)")
+    }
+}
+
+/// A single failure recorded in the evidence log.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Evidence {
+    /// Position of this entry in the log, starting from 0.
+    pub sequence: u64,
+    /// Seconds since Unix epoch.
+    pub recorded_at: u64,
+    pub check: ValidationCheck,
+    pub gate: String,
+    pub batch: usize,
+    pub reported_by: Role,
+    pub involved: [Role; 2],
+    /// MAC of the previous entry, or an empty string for the first one.
+    pub prev_mac: String,
+}
+
+impl Evidence {
+    fn mac(&self, key: &EvidenceKey) -> String {
+        let body = serde_json::to_vec(self).expect("evidence can always be serialized");
+        key.mac(&body)
+    }
+}
+
+/// Evidence as it is stored in the log, along with the MAC that links it to the next entry.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvidenceEntry {
+    #[serde(flatten)]
+    pub evidence: Evidence,
+    pub mac: String,
+}
+
+/// The number of entries in the log and the MAC of the last one. It is kept next to the log, so
+/// entries removed from the end of the log can be detected.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct EvidenceHead {
+    entries: u64,
+    last_mac: String,
+    mac: String,
+}
+
+impl EvidenceHead {
+    fn new(key: &EvidenceKey, entries: &[EvidenceEntry]) -> Self {
+        let entries_count = entries.len() as u64;
+        let last_mac = entries
+            .last()
+            .map_or_else(String::new, |entry| entry.mac.clone());
+        Self {
+            mac: key.mac(format!("{entries_count}:{last_mac}").as_bytes()),
+            entries: entries_count,
+            last_mac,
+        }
+    }
+}
+
+/// Checks that every entry was written with `key` and points to the entry before it.
+///
+/// ## Errors
+/// If any entry was modified, removed or reordered after it had been written.
+pub fn verify_chain(entries: &[EvidenceEntry], key: &EvidenceKey) -> Result<(), EvidenceLogError> {
+    let mut prev_mac = "";
+    for (sequence, entry) in (0_u64..).zip(entries) {
+        if entry.evidence.sequence != sequence
+            || entry.evidence.prev_mac != prev_mac
+            || entry.evidence.mac(key) != entry.mac
+        {
+            return Err(EvidenceLogError::Tampered(sequence));
+        }
+        prev_mac = &entry.mac;
+    }
+
+    Ok(())
+}
+
+/// Append-only record of malicious security checks that failed on this helper.
+///
+/// Each entry is authenticated with a key only this helper holds and carries the MAC of the
+/// previous one, so [`verify_chain`] detects entries that were forged, modified or removed after
+/// the fact. If the log is backed by a file, entries are appended to it as JSON lines and flushed
+/// to disk before [`record`] returns. The number of entries and the MAC of the last one are
+/// kept in a separate head file, which catches entries removed from the end of the log. Rolling
+/// back the log and its head file together is only detected by comparing the head with the one
+/// reported in the helper logs each time an entry is recorded.
+///
+/// [`record`]: Self::record
+pub struct EvidenceLog {
+    key: EvidenceKey,
+    path: Option<PathBuf>,
+    entries: Mutex<Vec<EvidenceEntry>>,
+}
+
+impl EvidenceLog {
+    /// Creates a log that is not persisted.
+    #[must_use]
+    pub fn new(key: EvidenceKey) -> Self {
+        Self {
+            key,
+            path: None,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Opens the log stored at `path`, or creates a new one if the file does not exist yet.
+    ///
+    /// ## Errors
+    /// If the file exists, but cannot be read or parsed, its MAC chain is broken, its head file
+    /// is missing or it has fewer entries than the head file says.
+    pub fn open<P: AsRef<Path>>(path: P, key: EvidenceKey) -> Result<Self, EvidenceLogError> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read_to_string(&path) {
+            Ok(content) => content
+                .lines()
+                .map(serde_json::from_str)
+                .collect::<Result<Vec<EvidenceEntry>, _>>()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        verify_chain(&entries, &key)?;
+        let head_path = head_path(&path);
+        match fs::read_to_string(&head_path) {
+            Ok(content) => Self::verify_head(&serde_json::from_str(&content)?, &entries, &key)?,
+            // The head is written as soon as the log is created, so only an empty log may
+            // lack it.
+            Err(e) if e.kind() == io::ErrorKind::NotFound && entries.is_empty() => {
+                write_head(&head_path, &EvidenceHead::new(&key, &entries))?;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(EvidenceLogError::MissingHead)
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(Self {
+            key,
+            path: Some(path),
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Checks that the log has all the entries the head file recorded. A log may have more
+    /// entries than that if the helper stopped before it updated the head file.
+    fn verify_head(
+        head: &EvidenceHead,
+        entries: &[EvidenceEntry],
+        key: &EvidenceKey,
+    ) -> Result<(), EvidenceLogError> {
+        let found = entries.len() as u64;
+        let expected = head.entries;
+        if key.mac(format!("{expected}:{}", head.last_mac).as_bytes()) != head.mac {
+            return Err(EvidenceLogError::Tampered(expected));
+        }
+        if found < expected {
+            return Err(EvidenceLogError::Truncated { expected, found });
+        }
+        let last_mac = match usize::try_from(expected).unwrap() {
+            0 => "",
+            n => entries[n - 1].mac.as_str(),
+        };
+        if last_mac != head.last_mac {
+            return Err(EvidenceLogError::Tampered(expected.saturating_sub(1)));
+        }
+
+        Ok(())
+    }
+
+    /// Appends the given failure to the log.
+    ///
+    /// ## Errors
+    /// If the entry cannot be written to disk. It is kept in memory regardless.
+    ///
+    /// ## Panics
+    /// If the log mutex is poisoned.
+    pub fn record(&self, failure: &ValidationFailure) -> Result<EvidenceEntry, EvidenceLogError> {
+        let mut entries = self.entries.lock().unwrap();
+        let evidence = Evidence {
+            sequence: entries.len() as u64,
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            check: failure.check,
+            gate: failure.gate.to_string(),
+            batch: failure.batch,
+            reported_by: failure.reported_by,
+            involved: failure.involved,
+            prev_mac: entries
+                .last()
+                .map_or_else(String::new, |entry| entry.mac.clone()),
+        };
+        let entry = EvidenceEntry {
+            mac: evidence.mac(&self.key),
+            evidence,
+        };
+        entries.push(entry.clone());
+        let head = EvidenceHead::new(&self.key, &entries);
+        tracing::warn!(
+            "evidence log now has {} entries, last MAC {}",
+            head.entries,
+            head.last_mac
+        );
+
+        // keep the lock while writing to make sure entries are appended in order
+        self.persist(&entry, &head)?;
+
+        Ok(entry)
+    }
+
+    /// Returns all entries recorded in this log.
+    ///
+    /// ## Panics
+    /// If the log mutex is poisoned.
+    #[must_use]
+    pub fn entries(&self) -> Vec<EvidenceEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    fn persist(&self, entry: &EvidenceEntry, head: &EvidenceHead) -> Result<(), EvidenceLogError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(&line)?;
+        file.sync_data()?;
+
+        write_head(&head_path(path), head)
+    }
+}
+
+/// Replaces the head file atomically, so it never describes a partially written log.
+fn write_head(path: &Path, head: &EvidenceHead) -> Result<(), EvidenceLogError> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut tmp = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&tmp_path)?;
+    tmp.write_all(&serde_json::to_vec(head)?)?;
+    tmp.sync_data()?;
+    fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+/// The head of the log at `path` is stored next to it, with `.head` appended to its name.
+fn head_path(path: &Path) -> PathBuf {
+    let mut head = path.as_os_str().to_owned();
+    head.push(".head");
+    PathBuf::from(head)
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use rand::thread_rng;
+
+    use super::{head_path, verify_chain, EvidenceKey, EvidenceLog, EvidenceLogError};
+    use crate::{
+        error::{ValidationCheck, ValidationFailure},
+        helpers::Role,
+        protocol::Gate,
+    };
+
+    fn failure(batch: usize) -> ValidationFailure {
+        ValidationFailure {
+            check: ValidationCheck::Dzkp,
+            gate: Gate::default(),
+            batch,
+            reported_by: Role::H1,
+            involved: [Role::H2, Role::H3],
+        }
+    }
+
+    fn key() -> EvidenceKey {
+        EvidenceKey::random(&mut thread_rng())
+    }
+
+    #[test]
+    fn entries_are_chained() {
+        let key = key();
+        let log = EvidenceLog::new(key.clone());
+        let first = log.record(&failure(0)).unwrap();
+        let second = log.record(&failure(1)).unwrap();
+
+        assert_eq!("", first.evidence.prev_mac);
+        assert_eq!(first.mac, second.evidence.prev_mac);
+        assert_eq!(1, second.evidence.batch);
+        verify_chain(&log.entries(), &key).unwrap();
+    }
+
+    #[test]
+    fn detects_tampering() {
+        let key = key();
+        let log = EvidenceLog::new(key.clone());
+        for batch in 0..3 {
+            log.record(&failure(batch)).unwrap();
+        }
+
+        let mut modified = log.entries();
+        modified[1].evidence.reported_by = Role::H2;
+        assert!(matches!(
+            verify_chain(&modified, &key),
+            Err(EvidenceLogError::Tampered(1))
+        ));
+
+        let mut removed = log.entries();
+        removed.remove(1);
+        assert!(matches!(
+            verify_chain(&removed, &key),
+            Err(EvidenceLogError::Tampered(1))
+        ));
+    }
+
+    #[test]
+    fn detects_forged_chain() {
+        let key = key();
+        let log = EvidenceLog::new(key.clone());
+        log.record(&failure(0)).unwrap();
+
+        // Someone without the key can build a chain that links correctly, but it does not
+        // verify with the key of the helper.
+        let forged = EvidenceLog::new(self::key());
+        forged.record(&failure(0)).unwrap();
+        verify_chain(&forged.entries(), &key).unwrap_err();
+    }
+
+    #[test]
+    fn key_from_hex() {
+        let key = EvidenceKey::from_hex(&format!("{}\n", "ab".repeat(32))).unwrap();
+        assert_eq!([0xab; 32], key.0);
+        assert!(matches!(
+            EvidenceKey::from_hex("abcd"),
+            Err(EvidenceLogError::BadKey)
+        ));
+    }
+
+    #[test]
+    fn persists_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("evidence.log");
+        let key = key();
+
+        let log = EvidenceLog::open(&path, key.clone()).unwrap();
+        log.record(&failure(0)).unwrap();
+        log.record(&failure(1)).unwrap();
+
+        let reopened = EvidenceLog::open(&path, key.clone()).unwrap();
+        assert_eq!(log.entries(), reopened.entries());
+        reopened.record(&failure(2)).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, content.replacen("\"batch\":1", "\"batch\":7", 1)).unwrap();
+        assert!(matches!(
+            EvidenceLog::open(&path, key),
+            Err(EvidenceLogError::Tampered(1))
+        ));
+    }
+
+    #[test]
+    fn detects_truncation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("evidence.log");
+        let key = key();
+
+        let log = EvidenceLog::open(&path, key.clone()).unwrap();
+        for batch in 0..3 {
+            log.record(&failure(batch)).unwrap();
+        }
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let truncated = content.lines().take(2).collect::<Vec<_>>().join("\n");
+        std::fs::write(&path, truncated).unwrap();
+        assert!(matches!(
+            EvidenceLog::open(&path, key.clone()),
+            Err(EvidenceLogError::Truncated {
+                expected: 3,
+                found: 2
+            })
+        ));
+
+        // Removing the head does not help either.
+        std::fs::remove_file(head_path(&path)).unwrap();
+        assert!(matches!(
+            EvidenceLog::open(&path, key),
+            Err(EvidenceLogError::MissingHead)
+        ));
+    }
+}
@@ -29,28 +29,41 @@ use crate::{
     ff::{boolean_array::BA32, Serializable},
     helpers::{
         negotiate_prss,
-        query::{QueryConfig, QueryType},
+        query::{OutputDp, QueryConfig, QueryType},
         BodyStream, Gateway,
     },
     hpke::PrivateKeyRegistry,
     protocol::{
-        context::{MaliciousContext, SemiHonestContext},
+        context::{
+            MaliciousContext, SemiHonestContext, ShardedMaliciousContext, ShardedSemiHonestContext,
+        },
         prss::Endpoint as PrssEndpoint,
         Gate,
     },
     query::{
-        runner::{OprfIpaQuery, QueryResult},
+        liveness::run_with_liveness,
+        runner::{
+            FeatureLabelDotProductQuery, LiftQuery, LogisticRegressionQuery, OprfIpaQuery,
+            QueryResult, ReachFrequencyQuery, ShardedOprfIpaQuery,
+        },
         state::RunningQuery,
     },
     sync::Arc,
 };
 #[cfg(any(test, feature = "cli", feature = "test-fixture"))]
 use crate::{
-    ff::Fp32BitPrime, query::runner::execute_test_multiply, query::runner::test_add_in_prime_field,
+    ff::Fp32BitPrime,
+    query::runner::{execute_sharded_shuffle, execute_test_multiply, test_add_in_prime_field},
 };
 
 pub trait Result: Send + Debug {
     fn to_bytes(&self) -> Vec<u8>;
+
+    /// Differential privacy applied by this helper to the output. Not set for queries that are not
+    /// differentially private.
+    fn output_dp(&self) -> Option<&OutputDp> {
+        None
+    }
 }
 
 impl<T> Result for Vec<T>
@@ -70,6 +83,27 @@ where
     }
 }
 
+/// Output of a differentially private query, together with the noise applied to it.
+#[derive(Debug)]
+pub struct DpQueryOutput<T> {
+    pub values: Vec<T>,
+    pub dp: OutputDp,
+}
+
+impl<T> Result for DpQueryOutput<T>
+where
+    Vec<T>: Result,
+    T: Send + Debug,
+{
+    fn to_bytes(&self) -> Vec<u8> {
+        self.values.to_bytes()
+    }
+
+    fn output_dp(&self) -> Option<&OutputDp> {
+        Some(&self.dp)
+    }
+}
+
 /// Needless pass by value because IPA v3 does not make use of key registry yet.
 #[allow(clippy::too_many_lines, clippy::needless_pass_by_value)]
 pub fn execute<R: PrivateKeyRegistry>(
@@ -79,7 +113,7 @@ pub fn execute<R: PrivateKeyRegistry>(
     gateway: Gateway,
     input: BodyStream,
 ) -> RunningQuery {
-    match (config.query_type, config.field_type) {
+    match (config.query_type.clone(), config.field_type) {
         #[cfg(any(test, feature = "weak-field"))]
         (QueryType::TestMultiply, FieldType::Fp31) => do_query(
             runtime,
@@ -108,7 +142,7 @@ pub fn execute<R: PrivateKeyRegistry>(
             config,
             gateway,
             input,
-            |_prss, _gateway, _config, _input| unimplemented!(),
+            |prss, gateway, _config, input| Box::pin(execute_sharded_shuffle(prss, gateway, input)),
         ),
         #[cfg(any(test, feature = "weak-field"))]
         (QueryType::TestAddInPrimeField, FieldType::Fp31) => do_query(
@@ -156,7 +190,7 @@ pub fn execute<R: PrivateKeyRegistry>(
             gateway,
             input,
             move |prss, gateway, config, input| {
-                let ctx = MaliciousContext::new(prss, gateway);
+                let ctx = MaliciousContext::new(prss, gateway).set_dzkp_mode(ipa_config.dzkp_mode);
                 Box::pin(
                     OprfIpaQuery::<_, BA32, R>::new(ipa_config, key_registry)
                         .execute(ctx, config.size, input)
@@ -164,7 +198,98 @@ pub fn execute<R: PrivateKeyRegistry>(
                 )
             },
         ),
+        (QueryType::SemiHonestShardedOprfIpa(ipa_config), _) => do_query(
+            runtime,
+            config,
+            gateway,
+            input,
+            move |prss, gateway, config, input| {
+                let ctx =
+                    ShardedSemiHonestContext::new_sharded(prss, gateway, gateway.shard_config());
+                Box::pin(
+                    ShardedOprfIpaQuery::<_, BA32, R>::new(ipa_config, key_registry)
+                        .execute(ctx, config.size, input)
+                        .then(|res| ready(res.map(|out| Box::new(out) as Box<dyn Result>))),
+                )
+            },
+        ),
+        (QueryType::MaliciousShardedOprfIpa(ipa_config), _) => do_query(
+            runtime,
+            config,
+            gateway,
+            input,
+            move |prss, gateway, config, input| {
+                let ctx = ShardedMaliciousContext::new_with_gate(
+                    prss,
+                    gateway,
+                    Gate::default(),
+                    gateway.shard_config(),
+                )
+                .set_dzkp_mode(ipa_config.dzkp_mode);
+                Box::pin(
+                    ShardedOprfIpaQuery::<_, BA32, R>::new(ipa_config, key_registry)
+                        .execute(ctx, config.size, input)
+                        .then(|res| ready(res.map(|out| Box::new(out) as Box<dyn Result>))),
+                )
+            },
+        ),
         (QueryType::SemiHonestHybrid(_), _) => todo!(),
+        (QueryType::SemiHonestFeatureLabelDotProduct(feature_label_config), _) => do_query(
+            runtime,
+            config,
+            gateway,
+            input,
+            move |prss, gateway, config, input| {
+                let ctx = SemiHonestContext::new(prss, gateway);
+                Box::pin(
+                    FeatureLabelDotProductQuery::new(feature_label_config)
+                        .execute(ctx, config.size, input)
+                        .then(|res| ready(res.map(|out| Box::new(out) as Box<dyn Result>))),
+                )
+            },
+        ),
+        (QueryType::SemiHonestLogisticRegression(logistic_regression_config), _) => do_query(
+            runtime,
+            config,
+            gateway,
+            input,
+            move |prss, gateway, config, input| {
+                let ctx = SemiHonestContext::new(prss, gateway);
+                Box::pin(
+                    LogisticRegressionQuery::new(logistic_regression_config)
+                        .execute(ctx, config.size, input)
+                        .then(|res| ready(res.map(|out| Box::new(out) as Box<dyn Result>))),
+                )
+            },
+        ),
+        (QueryType::SemiHonestReachFrequency(reach_frequency_config), _) => do_query(
+            runtime,
+            config,
+            gateway,
+            input,
+            move |prss, gateway, config, input| {
+                let ctx = SemiHonestContext::new(prss, gateway);
+                Box::pin(
+                    ReachFrequencyQuery::new(reach_frequency_config)
+                        .execute(ctx, config.size, input)
+                        .then(|res| ready(res.map(|out| Box::new(out) as Box<dyn Result>))),
+                )
+            },
+        ),
+        (QueryType::SemiHonestLift(lift_config), _) => do_query(
+            runtime,
+            config,
+            gateway,
+            input,
+            move |prss, gateway, config, input| {
+                let ctx = SemiHonestContext::new(prss, gateway);
+                Box::pin(
+                    LiftQuery::new(lift_config)
+                        .execute(ctx, config.size, input)
+                        .then(|res| ready(res.map(|out| Box::new(out) as Box<dyn Result>))),
+                )
+            },
+        ),
     }
 }
 
@@ -204,11 +329,13 @@ where
             block_in_place(|| {
                 // block_on runs on the current thread, so if it is also responsible for IO
                 // it's been handed off already by block_in_place.
-                Handle::current()
-                    .block_on(async { query_impl(&prss, gateway, &config, input_stream).await })
+                Handle::current().block_on(run_with_liveness(
+                    gateway,
+                    query_impl(&prss, gateway, &config, input_stream),
+                ))
             })
         } else {
-            query_impl(&prss, gateway, &config, input_stream).await
+            run_with_liveness(gateway, query_impl(&prss, gateway, &config, input_stream)).await
         };
 
         tx.send(v).unwrap();
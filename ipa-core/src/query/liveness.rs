@@ -0,0 +1,568 @@
+//! Failure detection for queries that run on sharded helpers.
+//!
+//! A sharded query runs on every shard of all three helpers, and a shard that crashed leaves the
+//! other shards waiting for data that is never going to arrive. To avoid that, shards of the same
+//! helper send heartbeats to each other for as long as they run the query. A shard that stops
+//! sending heartbeats before it announced that it is done is considered to have failed.
+//!
+//! A shard that fails to run the query, for example because a malicious security check did not
+//! pass, counts as failed as well. It stops sending heartbeats and tells everyone to abort the
+//! query right away, instead of letting them wait for the failure timeout.
+//!
+//! Heartbeats are sent from their own task, so that a shard busy computing its part of the query
+//! keeps sending them.
+//!
+//! The shard that detected a failure tells the other shards of its helper and its MPC peers to
+//! abort the query. Shards forward abort signals to the parties the sender could not reach
+//! directly: signals received from other shards go to the MPC peers, and signals received from
+//! MPC peers go to the other shards. That way, the query is aborted on every shard of all three
+//! helpers, and all of them report the same failed shard.
+//!
+//! Shards start the query as soon as they receive their input, which may happen at different
+//! times. For that reason, the failure timeout starts only after the first heartbeat is received
+//! from a shard. Stall detection covers shards that never start the query.
+use std::{
+    convert::Infallible,
+    future::{pending, Future},
+    pin::pin,
+    time::Duration,
+};
+
+use futures::{
+    channel::oneshot,
+    future::{select, BoxFuture, Either},
+    stream::FuturesUnordered,
+    FutureExt, StreamExt,
+};
+use generic_array::GenericArray;
+use ipa_step::StepNarrow;
+use typenum::{U1, U5};
+
+use crate::{
+    error::Error,
+    executor::{IpaJoinHandle, IpaRuntime},
+    ff::Serializable,
+    helpers::{
+        ChannelId, Direction, Gateway, HelperIdentity, MpcMessage, ShardReceivingEnd, TotalRecords,
+    },
+    protocol::{
+        step::{LivenessStep, ProtocolStep},
+        Gate, RecordId,
+    },
+    query::runner::QueryResult,
+    sharding::{ShardConfiguration, ShardIndex, ShardedHelperIdentity},
+};
+
+/// Runs `query` on this shard while watching all other shards of this helper. If any shard of any
+/// helper fails before `query` completes, `query` is dropped and [`Error::ShardFailed`] is
+/// returned. If `query` itself fails, its error is returned and the query is aborted on all
+/// other shards.
+///
+/// Helpers that are not sharded just run `query`.
+pub(super) async fn run_with_liveness<F>(gateway: &Gateway, query: F) -> QueryResult
+where
+    F: Future<Output = QueryResult>,
+{
+    // Shuttle does not support timers.
+    if cfg!(feature = "shuttle") || gateway.shard_config().shard_count() <= ShardIndex::from(1) {
+        return query.await;
+    }
+
+    let liveness = Liveness::new(gateway);
+    let heartbeats = liveness.spawn_heartbeats();
+
+    let failure = match select(pin!(query), pin!(liveness.detect_failure())).await {
+        Either::Left((Ok(result), _)) => {
+            heartbeats.finish().await;
+            return Ok(result);
+        }
+        Either::Left((Err(e), _)) => {
+            heartbeats.cancel();
+            liveness
+                .abort(Failure {
+                    shard: liveness.identity,
+                    origin: Origin::Local,
+                })
+                .await;
+            return Err(e);
+        }
+        Either::Right((failure, _)) => failure,
+    };
+
+    heartbeats.cancel();
+    liveness.abort(failure).await;
+    Err(Error::ShardFailed(failure.shard))
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid liveness message: {0}")]
+pub struct InvalidLivenessMessage(String);
+
+/// Sent periodically by every shard to all other shards of the same helper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Heartbeat {
+    /// The sending shard is still running the query.
+    Alive,
+    /// The sending shard finished the query and is not going to send more heartbeats.
+    Done,
+}
+
+impl Serializable for Heartbeat {
+    type Size = U1;
+    type DeserializationError = InvalidLivenessMessage;
+
+    fn serialize(&self, buf: &mut GenericArray<u8, Self::Size>) {
+        buf[0] = match self {
+            Self::Alive => 0,
+            Self::Done => 1,
+        };
+    }
+
+    fn deserialize(buf: &GenericArray<u8, Self::Size>) -> Result<Self, Self::DeserializationError> {
+        match buf[0] {
+            0 => Ok(Self::Alive),
+            1 => Ok(Self::Done),
+            v => Err(InvalidLivenessMessage(format!("unknown heartbeat {v}"))),
+        }
+    }
+}
+
+/// Tells the receiver to abort the query because the given shard failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AbortSignal {
+    failed: ShardedHelperIdentity,
+}
+
+impl Serializable for AbortSignal {
+    type Size = U5;
+    type DeserializationError = InvalidLivenessMessage;
+
+    fn serialize(&self, buf: &mut GenericArray<u8, Self::Size>) {
+        buf[0] = u8::from(self.failed.helper_identity);
+        buf[1..].copy_from_slice(&self.failed.shard_index.0.to_le_bytes());
+    }
+
+    fn deserialize(buf: &GenericArray<u8, Self::Size>) -> Result<Self, Self::DeserializationError> {
+        let helper_identity =
+            HelperIdentity::try_from(usize::from(buf[0])).map_err(InvalidLivenessMessage)?;
+        let shard_index = ShardIndex(u32::from_le_bytes(buf[1..].try_into().unwrap()));
+
+        Ok(Self {
+            failed: ShardedHelperIdentity::new(helper_identity, shard_index),
+        })
+    }
+}
+
+/// The identity of a failed shard is not secret, so it can be sent to other helpers.
+impl MpcMessage for AbortSignal {}
+
+/// Who told this shard about the failure.
+#[derive(Debug, Clone, Copy)]
+enum Origin {
+    /// This shard failed to run the query.
+    Local,
+    /// This shard stopped receiving heartbeats from the failed one.
+    Detected,
+    /// Another shard of this helper.
+    Shard,
+    /// A peer helper.
+    Helper,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Failure {
+    shard: ShardedHelperIdentity,
+    origin: Origin,
+}
+
+struct Liveness<'a> {
+    gateway: &'a Gateway,
+    identity: ShardedHelperIdentity,
+    heartbeat_gate: Gate,
+    abort_gate: Gate,
+}
+
+impl<'a> Liveness<'a> {
+    fn new(gateway: &'a Gateway) -> Self {
+        let gate = Gate::default().narrow(&ProtocolStep::Liveness);
+        Self {
+            gateway,
+            identity: gateway.sharded_identity(),
+            heartbeat_gate: gate.narrow(&LivenessStep::Heartbeat),
+            abort_gate: gate.narrow(&LivenessStep::Abort),
+        }
+    }
+
+    /// Starts sending heartbeats to all other shards on a separate task. Heartbeats keep going
+    /// until the returned handle says whether this shard finished the query.
+    fn spawn_heartbeats(&self) -> Heartbeats {
+        let (stop_tx, mut stop) = oneshot::channel();
+        let interval = self.gateway.config().heartbeat_interval;
+        let senders = self
+            .gateway
+            .shard_config()
+            .peer_shards()
+            .map(|peer| {
+                self.gateway.get_shard_sender::<Heartbeat>(
+                    &ChannelId::new(peer, self.heartbeat_gate.clone()),
+                    TotalRecords::Indeterminate,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let task = IpaRuntime::current().spawn(async move {
+            let send_all = |record_id: RecordId, heartbeat: Heartbeat| {
+                // Send errors are ignored. If a shard cannot be reached, other shards stop
+                // receiving heartbeats from it as well.
+                senders
+                    .iter()
+                    .map(|sender| sender.send(record_id, heartbeat))
+                    .collect::<FuturesUnordered<_>>()
+                    .for_each(|_| async {})
+            };
+
+            let mut record_id = RecordId::FIRST;
+            loop {
+                send_all(record_id, Heartbeat::Alive).await;
+                record_id += 1;
+                let sleep = pin!(tokio::time::sleep(interval));
+                match select(sleep, &mut stop).await {
+                    Either::Left(_) => {}
+                    Either::Right((Ok(()), _)) => break,
+                    // This shard failed, so it must not tell others that it is done.
+                    Either::Right((Err(_), _)) => return,
+                }
+            }
+
+            send_all(record_id, Heartbeat::Done).await;
+            for sender in &senders {
+                sender.close(record_id + 1).await;
+            }
+        });
+
+        Heartbeats {
+            stop: stop_tx,
+            task,
+        }
+    }
+
+    /// Resolves when this shard finds out that a shard failed, either because it stopped
+    /// receiving heartbeats from it, or because another shard or helper said so.
+    async fn detect_failure(&self) -> Failure {
+        let timeout = self.gateway.config().shard_failure_timeout;
+        let mut watchers = FuturesUnordered::<BoxFuture<'_, Failure>>::new();
+
+        for peer in self.gateway.shard_config().peer_shards() {
+            let shard = ShardedHelperIdentity::new(self.identity.helper_identity, peer);
+            let heartbeats = self
+                .gateway
+                .get_shard_receiver(&ChannelId::new(peer, self.heartbeat_gate.clone()));
+            watchers.push(
+                watch_heartbeats(heartbeats, timeout)
+                    .map(move |_| Failure {
+                        shard,
+                        origin: Origin::Detected,
+                    })
+                    .boxed(),
+            );
+
+            let mut aborts = self
+                .gateway
+                .get_shard_receiver::<AbortSignal>(&ChannelId::new(peer, self.abort_gate.clone()));
+            watchers.push(
+                async move {
+                    match aborts.next().await {
+                        Some(Ok(signal)) => Failure {
+                            shard: signal.failed,
+                            origin: Origin::Shard,
+                        },
+                        // A shard that went away without sending an abort signal is caught by
+                        // its heartbeats.
+                        _ => pending().await,
+                    }
+                }
+                .boxed(),
+            );
+        }
+
+        for direction in [Direction::Left, Direction::Right] {
+            let aborts = self
+                .gateway
+                .get_mpc_receiver::<AbortSignal>(&ChannelId::new(
+                    self.gateway.role().peer(direction),
+                    self.abort_gate.clone(),
+                ));
+            watchers.push(
+                async move {
+                    match aborts.receive(RecordId::FIRST).await {
+                        Ok(signal) => Failure {
+                            shard: signal.failed,
+                            origin: Origin::Helper,
+                        },
+                        Err(_) => pending().await,
+                    }
+                }
+                .boxed(),
+            );
+        }
+
+        watchers.next().await.unwrap()
+    }
+
+    /// Tells everyone who may not know about `failure` yet to abort the query.
+    async fn abort(&self, failure: Failure) {
+        tracing::error!(
+            "{failed} failed ({origin:?}), aborting the query on {this}",
+            failed = failure.shard,
+            origin = failure.origin,
+            this = self.identity,
+        );
+        let signal = AbortSignal {
+            failed: failure.shard,
+        };
+
+        if !matches!(failure.origin, Origin::Shard) {
+            self.gateway
+                .shard_config()
+                .peer_shards()
+                .filter(|&peer| {
+                    ShardedHelperIdentity::new(self.identity.helper_identity, peer) != failure.shard
+                })
+                .map(|peer| async move {
+                    self.gateway
+                        .get_shard_sender::<AbortSignal>(
+                            &ChannelId::new(peer, self.abort_gate.clone()),
+                            TotalRecords::ONE,
+                        )
+                        .send(RecordId::FIRST, signal)
+                        .await
+                })
+                .collect::<FuturesUnordered<_>>()
+                .for_each(|_| async {})
+                .await;
+        }
+
+        if !matches!(failure.origin, Origin::Helper) {
+            [Direction::Left, Direction::Right]
+                .map(|direction| async move {
+                    self.gateway
+                        .get_mpc_sender::<AbortSignal>(
+                            &ChannelId::new(
+                                self.gateway.role().peer(direction),
+                                self.abort_gate.clone(),
+                            ),
+                            TotalRecords::ONE,
+                            self.gateway.config().active_work_as_power_of_two(),
+                        )
+                        .send(RecordId::FIRST, signal)
+                        .await
+                })
+                .into_iter()
+                .collect::<FuturesUnordered<_>>()
+                .for_each(|_| async {})
+                .await;
+        }
+    }
+}
+
+/// Handle to the task that sends heartbeats of this shard.
+struct Heartbeats {
+    stop: oneshot::Sender<()>,
+    task: IpaJoinHandle<()>,
+}
+
+impl Heartbeats {
+    /// Tells other shards that this one is done, so they stop expecting heartbeats from it.
+    async fn finish(self) {
+        // The task only goes away after it receives the stop signal.
+        self.stop.send(()).unwrap();
+        self.task.await;
+    }
+
+    /// Stops sending heartbeats without telling other shards that this one is done. If they
+    /// do not get an abort signal, they find out about the failure from missing heartbeats.
+    fn cancel(self) {
+        self.task.abort();
+    }
+}
+
+/// Resolves if the shard on the other end of `heartbeats` stopped sending them before it said
+/// it is done.
+async fn watch_heartbeats(
+    mut heartbeats: ShardReceivingEnd<Heartbeat>,
+    timeout: Duration,
+) -> Result<Infallible, ()> {
+    // The first heartbeat arrives when the other shard starts the query.
+    let mut next = heartbeats.next().await;
+    loop {
+        match next {
+            Some(Ok(Heartbeat::Alive)) => {}
+            Some(Ok(Heartbeat::Done)) => return pending().await,
+            Some(Err(e)) => {
+                tracing::warn!("failed to receive a heartbeat: {e}");
+                return Err(());
+            }
+            None => return Err(()),
+        }
+        next = tokio::time::timeout(timeout, heartbeats.next())
+            .await
+            .map_err(|_| ())?;
+    }
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use std::{future::pending, time::Duration};
+
+    use futures::future::join_all;
+    use tokio::task::block_in_place;
+
+    use crate::{
+        error::Error,
+        ff::Fp31,
+        helpers::{Gateway, GatewayConfig, Role},
+        query::{liveness::run_with_liveness, runner::QueryResult, ProtocolResult},
+        sharding::ShardIndex,
+        test_fixture::{TestWorld, TestWorldConfig, WithShards},
+    };
+
+    fn world<const SHARDS: usize>() -> TestWorld<WithShards<SHARDS>> {
+        TestWorld::<WithShards<SHARDS>>::with_shards(TestWorldConfig {
+            gateway_config: GatewayConfig {
+                heartbeat_interval: Duration::from_millis(10),
+                shard_failure_timeout: Duration::from_millis(200),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    fn gateways<const SHARDS: usize>(world: &TestWorld<WithShards<SHARDS>>) -> Vec<&Gateway> {
+        ShardIndex::try_from(SHARDS)
+            .unwrap()
+            .iter()
+            .flat_map(|shard| {
+                Role::all()
+                    .iter()
+                    .map(move |&role| world.gateway(shard, role))
+            })
+            .collect()
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn ok() -> QueryResult {
+        Ok(Box::<Vec<Fp31>>::default() as Box<dyn ProtocolResult>)
+    }
+
+    #[tokio::test]
+    async fn shards_finishing_at_different_times() {
+        let world = world::<3>();
+
+        // Shards that finished earlier must not be reported as failed by the ones that still
+        // run the query, even though they stopped sending heartbeats.
+        let results = join_all(gateways(&world).into_iter().map(|gateway| {
+            let shard_index = gateway.sharded_identity().shard_index;
+            run_with_liveness(gateway, async move {
+                tokio::time::sleep(Duration::from_millis(300) * shard_index.0).await;
+                ok()
+            })
+        }))
+        .await;
+
+        assert!(results.iter().all(Result::is_ok), "{results:?}");
+    }
+
+    #[tokio::test]
+    async fn failed_shard_aborts_query_everywhere() {
+        let world = world::<3>();
+        let failed = world
+            .gateway(ShardIndex::from(1), Role::H2)
+            .sharded_identity();
+
+        let results = join_all(gateways(&world).into_iter().map(|gateway| async move {
+            // Every shard waits for the failed one, so no shard can finish the query.
+            let query = run_with_liveness(gateway, pending());
+            if gateway.sharded_identity() == failed {
+                // The failed shard stops sending heartbeats without saying it is done.
+                tokio::time::timeout(Duration::from_millis(50), query)
+                    .await
+                    .unwrap_err();
+                None
+            } else {
+                Some(query.await)
+            }
+        }))
+        .await;
+
+        assert_eq!(8, results.iter().flatten().count());
+        for result in results.into_iter().flatten() {
+            assert!(
+                matches!(result, Err(Error::ShardFailed(id)) if id == failed),
+                "{result:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn query_error_aborts_query_everywhere() {
+        let world = world::<2>();
+        let failed = world
+            .gateway(ShardIndex::from(1), Role::H3)
+            .sharded_identity();
+
+        let results = join_all(gateways(&world).into_iter().map(|gateway| {
+            run_with_liveness(gateway, async move {
+                if gateway.sharded_identity() == failed {
+                    Err(Error::InvalidQueryParameter(
+                        "malicious check failed".into(),
+                    ))
+                } else {
+                    pending().await
+                }
+            })
+        }))
+        .await;
+
+        for (gateway, result) in gateways(&world).into_iter().zip(results) {
+            if gateway.sharded_identity() == failed {
+                assert!(
+                    matches!(result, Err(Error::InvalidQueryParameter(_))),
+                    "{result:?}"
+                );
+            } else {
+                assert!(
+                    matches!(result, Err(Error::ShardFailed(id)) if id == failed),
+                    "{result:?}"
+                );
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn busy_shard_keeps_sending_heartbeats() {
+        let world = Box::leak(Box::new(world::<2>()));
+        let busy = world
+            .gateway(ShardIndex::FIRST, Role::H1)
+            .sharded_identity();
+
+        // The busy shard blocks its thread for longer than the failure timeout, like a long
+        // computation does, while the other shards are waiting for it. Every shard runs on its
+        // own task, as it would on a real helper.
+        let results = join_all(gateways(world).into_iter().map(|gateway| {
+            tokio::spawn(run_with_liveness(gateway, async move {
+                if gateway.sharded_identity() == busy {
+                    block_in_place(|| std::thread::sleep(Duration::from_millis(500)));
+                } else {
+                    tokio::time::sleep(Duration::from_millis(700)).await;
+                }
+                ok()
+            }))
+        }))
+        .await;
+
+        assert!(
+            results.iter().all(|r| matches!(r, Ok(Ok(_)))),
+            "{results:?}"
+        );
+    }
+}
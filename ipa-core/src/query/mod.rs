@@ -1,11 +1,23 @@
+mod budget;
 mod completion;
+mod evidence;
 mod executor;
+mod liveness;
+mod policy;
 mod processor;
 mod runner;
 mod state;
 
+pub use budget::{
+    check_conversion_info, check_trigger_report, BudgetCharge, BudgetEntry, BudgetError, BudgetKey,
+    BudgetLedger, BudgetLedgerStatus,
+};
 use completion::Handle as CompletionHandle;
-pub use executor::Result as ProtocolResult;
+pub use evidence::{
+    verify_chain, Evidence, EvidenceEntry, EvidenceKey, EvidenceLog, EvidenceLogError,
+};
+pub use executor::{DpQueryOutput, Result as ProtocolResult};
+pub use policy::{PolicyViolation, PrivacyPolicy};
 pub use processor::{
     NewQueryError, PrepareQueryError, Processor as QueryProcessor, QueryCompletionError,
     QueryInputError, QueryKillStatus, QueryKilled, QueryStatusError,
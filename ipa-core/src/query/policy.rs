@@ -0,0 +1,298 @@
+use serde::Deserialize;
+
+use crate::{
+    helpers::query::{QueryConfig, QueryType},
+    protocol::ipa_prf::oprf_padding::{AggregationPadding, OPRFPadding, PaddingParameters},
+};
+
+/// Minimum privacy guarantees that a helper accepts. Helper operators provide it in a TOML file,
+/// and queries that request weaker guarantees are rejected before they start.
+///
+/// Bounds that are not set are not enforced, so the default policy accepts any query.
+#[derive(Debug, Default, Copy, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrivacyPolicy {
+    pub max_oprf_padding_epsilon: Option<f64>,
+    pub max_oprf_padding_delta: Option<f64>,
+    pub min_matchkey_cardinality_cap: Option<u32>,
+    pub max_aggregation_padding_epsilon: Option<f64>,
+    pub max_aggregation_padding_delta: Option<f64>,
+    pub min_aggregation_padding_sensitivity: Option<u32>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PolicyViolation {
+    #[error("{parameter}={requested} is above the maximum of {limit} accepted by this helper")]
+    AboveMaximum {
+        parameter: &'static str,
+        requested: f64,
+        limit: f64,
+    },
+    #[error("{parameter}={requested} is below the minimum of {limit} accepted by this helper")]
+    BelowMinimum {
+        parameter: &'static str,
+        requested: u32,
+        limit: u32,
+    },
+    #[error("This helper does not accept queries without {0} padding")]
+    PaddingRequired(&'static str),
+}
+
+impl PrivacyPolicy {
+    /// Checks that the query does not request weaker guarantees than this policy allows.
+    ///
+    /// ## Errors
+    /// If any of the query parameters is outside of the bounds set by this policy.
+    pub fn check(&self, config: &QueryConfig) -> Result<(), PolicyViolation> {
+        match &config.query_type {
+            QueryType::SemiHonestOprfIpa(config)
+            | QueryType::MaliciousOprfIpa(config)
+            | QueryType::SemiHonestShardedOprfIpa(config)
+            | QueryType::MaliciousShardedOprfIpa(config) => {
+                self.check_padding(&config.padding_params())
+            }
+            QueryType::SemiHonestHybrid(config) => self.check_padding(&config.padding_params()),
+            QueryType::SemiHonestFeatureLabelDotProduct(config) => {
+                self.check_padding(&config.padding_params())
+            }
+            QueryType::SemiHonestLogisticRegression(config) => {
+                self.check_padding(&config.padding_params())
+            }
+            QueryType::SemiHonestReachFrequency(config) => {
+                self.check_padding(&config.padding_params())
+            }
+            QueryType::SemiHonestLift(config) => self.check_padding(&config.padding_params()),
+            #[cfg(any(test, feature = "test-fixture", feature = "cli"))]
+            QueryType::TestMultiply
+            | QueryType::TestAddInPrimeField
+            | QueryType::TestShardedShuffle => Ok(()),
+        }
+    }
+
+    fn check_padding(&self, padding: &PaddingParameters) -> Result<(), PolicyViolation> {
+        match padding.oprf_padding {
+            OPRFPadding::NoOPRFPadding => {
+                if self.max_oprf_padding_epsilon.is_some()
+                    || self.max_oprf_padding_delta.is_some()
+                    || self.min_matchkey_cardinality_cap.is_some()
+                {
+                    return Err(PolicyViolation::PaddingRequired("OPRF"));
+                }
+            }
+            OPRFPadding::Parameters {
+                oprf_epsilon,
+                oprf_delta,
+                matchkey_cardinality_cap,
+                ..
+            } => {
+                check_max(
+                    "oprf_padding_epsilon",
+                    oprf_epsilon,
+                    self.max_oprf_padding_epsilon,
+                )?;
+                check_max(
+                    "oprf_padding_delta",
+                    oprf_delta,
+                    self.max_oprf_padding_delta,
+                )?;
+                check_min(
+                    "matchkey_cardinality_cap",
+                    matchkey_cardinality_cap,
+                    self.min_matchkey_cardinality_cap,
+                )?;
+            }
+        }
+
+        match padding.aggregation_padding {
+            AggregationPadding::NoAggPadding => {
+                if self.max_aggregation_padding_epsilon.is_some()
+                    || self.max_aggregation_padding_delta.is_some()
+                    || self.min_aggregation_padding_sensitivity.is_some()
+                {
+                    return Err(PolicyViolation::PaddingRequired("aggregation"));
+                }
+            }
+            AggregationPadding::Parameters {
+                aggregation_epsilon,
+                aggregation_delta,
+                aggregation_padding_sensitivity,
+            } => {
+                check_max(
+                    "aggregation_padding_epsilon",
+                    aggregation_epsilon,
+                    self.max_aggregation_padding_epsilon,
+                )?;
+                check_max(
+                    "aggregation_padding_delta",
+                    aggregation_delta,
+                    self.max_aggregation_padding_delta,
+                )?;
+                check_min(
+                    "aggregation_padding_sensitivity",
+                    aggregation_padding_sensitivity,
+                    self.min_aggregation_padding_sensitivity,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn check_max(
+    parameter: &'static str,
+    requested: f64,
+    limit: Option<f64>,
+) -> Result<(), PolicyViolation> {
+    match limit {
+        Some(limit) if requested.is_nan() || requested > limit => {
+            Err(PolicyViolation::AboveMaximum {
+                parameter,
+                requested,
+                limit,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_min(
+    parameter: &'static str,
+    requested: u32,
+    limit: Option<u32>,
+) -> Result<(), PolicyViolation> {
+    match limit {
+        Some(limit) if requested < limit => Err(PolicyViolation::BelowMinimum {
+            parameter,
+            requested,
+            limit,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use super::{PolicyViolation, PrivacyPolicy};
+    use crate::{
+        ff::FieldType,
+        helpers::query::{
+            HybridQueryParams, IpaQueryConfig, LiftQueryParams, QueryConfig, QueryType,
+        },
+    };
+
+    fn ipa_query(config: IpaQueryConfig) -> QueryConfig {
+        QueryConfig::new(
+            QueryType::MaliciousOprfIpa(config),
+            FieldType::Fp32BitPrime,
+            1,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn default_accepts_everything() {
+        let policy = PrivacyPolicy::default();
+        policy
+            .check(&ipa_query(IpaQueryConfig {
+                oprf_padding_epsilon: 100.0,
+                matchkey_cardinality_cap: 1,
+                ..Default::default()
+            }))
+            .unwrap();
+        policy
+            .check(&QueryConfig::new(QueryType::TestMultiply, FieldType::Fp31, 1).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "web-app")]
+    fn parse() {
+        let policy: PrivacyPolicy = toml::from_str(
+            r"
+            max_oprf_padding_epsilon = 5.0
+            min_matchkey_cardinality_cap = 10
+            ",
+        )
+        .unwrap();
+        assert_eq!(policy.max_oprf_padding_epsilon, Some(5.0));
+        assert_eq!(policy.min_matchkey_cardinality_cap, Some(10));
+        assert_eq!(policy.max_aggregation_padding_delta, None);
+
+        toml::from_str::<PrivacyPolicy>("max_epsilon = 1.0").unwrap_err();
+    }
+
+    #[test]
+    fn enforces_bounds() {
+        let policy = PrivacyPolicy {
+            max_oprf_padding_epsilon: Some(5.0),
+            min_matchkey_cardinality_cap: Some(10),
+            max_aggregation_padding_delta: Some(1e-6),
+            ..Default::default()
+        };
+
+        policy.check(&ipa_query(IpaQueryConfig::default())).unwrap();
+
+        assert!(matches!(
+            policy.check(&ipa_query(IpaQueryConfig {
+                oprf_padding_epsilon: 10.0,
+                ..Default::default()
+            })),
+            Err(PolicyViolation::AboveMaximum {
+                parameter: "oprf_padding_epsilon",
+                ..
+            })
+        ));
+        assert!(matches!(
+            policy.check(&ipa_query(IpaQueryConfig {
+                matchkey_cardinality_cap: 3,
+                ..Default::default()
+            })),
+            Err(PolicyViolation::BelowMinimum {
+                parameter: "matchkey_cardinality_cap",
+                requested: 3,
+                limit: 10,
+            })
+        ));
+        assert!(matches!(
+            policy.check(
+                &QueryConfig::new(
+                    QueryType::SemiHonestHybrid(HybridQueryParams {
+                        aggregation_padding_delta: 1e-4,
+                        ..Default::default()
+                    }),
+                    FieldType::Fp32BitPrime,
+                    1,
+                )
+                .unwrap()
+            ),
+            Err(PolicyViolation::AboveMaximum {
+                parameter: "aggregation_padding_delta",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn checks_default_padding() {
+        let lift = QueryConfig::new(
+            QueryType::SemiHonestLift(LiftQueryParams::default()),
+            FieldType::Fp32BitPrime,
+            1,
+        )
+        .unwrap();
+
+        PrivacyPolicy::default().check(&lift).unwrap();
+        assert!(matches!(
+            PrivacyPolicy {
+                max_oprf_padding_epsilon: Some(1.0),
+                ..Default::default()
+            }
+            .check(&lift),
+            Err(PolicyViolation::AboveMaximum {
+                parameter: "oprf_padding_epsilon",
+                ..
+            })
+        ));
+    }
+}
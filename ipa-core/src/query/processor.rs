@@ -5,6 +5,7 @@ use std::{
 
 use futures::{future::try_join, stream};
 use serde::Serialize;
+use tokio::sync::oneshot;
 
 use crate::{
     error::Error as ProtocolError,
@@ -18,8 +19,9 @@ use crate::{
     protocol::QueryId,
     query::{
         executor,
-        state::{QueryState, QueryStatus, RemoveQuery, RunningQueries, StateError},
-        CompletionHandle, ProtocolResult,
+        state::{QueryState, QueryStatus, RemoveQuery, RunningQueries, RunningQuery, StateError},
+        BudgetCharge, BudgetError, BudgetLedger, BudgetLedgerStatus, CompletionHandle, EvidenceLog,
+        PolicyViolation, PrivacyPolicy, ProtocolResult,
     },
     sync::Arc,
     utils::NonZeroU32PowerOfTwo,
@@ -46,6 +48,9 @@ pub struct Processor {
     queries: RunningQueries,
     key_registry: Arc<KeyRegistry<PrivateKeyOnly>>,
     active_work: Option<NonZeroU32PowerOfTwo>,
+    privacy_policy: PrivacyPolicy,
+    budget_ledger: Option<Arc<BudgetLedger>>,
+    evidence_log: Option<Arc<EvidenceLog>>,
     runtime: IpaRuntime,
 }
 
@@ -55,6 +60,9 @@ impl Default for Processor {
             queries: RunningQueries::default(),
             key_registry: Arc::new(KeyRegistry::<PrivateKeyOnly>::empty()),
             active_work: None,
+            privacy_policy: PrivacyPolicy::default(),
+            budget_ledger: None,
+            evidence_log: None,
             runtime: IpaRuntime::current(),
         }
     }
@@ -65,6 +73,10 @@ pub enum NewQueryError {
     #[error(transparent)]
     State(#[from] StateError),
     #[error(transparent)]
+    PolicyViolation(#[from] PolicyViolation),
+    #[error(transparent)]
+    Budget(#[from] BudgetError),
+    #[error(transparent)]
     MpcTransport(#[from] MpcTransportError),
 }
 
@@ -75,6 +87,10 @@ pub enum PrepareQueryError {
     #[error("Query is already running")]
     AlreadyRunning,
     #[error(transparent)]
+    PolicyViolation(#[from] PolicyViolation),
+    #[error(transparent)]
+    Budget(#[from] BudgetError),
+    #[error(transparent)]
     StateError {
         #[from]
         source: StateError,
@@ -122,18 +138,26 @@ impl Processor {
     pub fn new(
         key_registry: KeyRegistry<PrivateKeyOnly>,
         active_work: Option<NonZeroU32PowerOfTwo>,
+        privacy_policy: PrivacyPolicy,
+        budget_ledger: Option<Arc<BudgetLedger>>,
+        evidence_log: Option<Arc<EvidenceLog>>,
         runtime: IpaRuntime,
     ) -> Self {
         Self {
             queries: RunningQueries::default(),
             key_registry: Arc::new(key_registry),
             active_work,
+            privacy_policy,
+            budget_ledger,
+            evidence_log,
             runtime,
         }
     }
 
     /// Upon receiving a new query request:
+    /// * processor checks that the query satisfies its privacy policy
     /// * processor generates new query id
+    /// * reserves the privacy budget this query is going to spend
     /// * assigns roles to helpers in the ring.
     ///     Helper that received new query request becomes `Role::H1` (aka coordinator).
     ///     The coordinator is in theory free to choose helpers for `Role::H2` and `Role::H3`
@@ -146,17 +170,20 @@ impl Processor {
     /// * returns query configuration
     ///
     /// ## Errors
-    /// When other peers failed to acknowledge this query
+    /// When the query violates the privacy policy of this helper, there is not enough privacy
+    /// budget left to run it or other peers failed to acknowledge this query
     #[allow(clippy::missing_panics_doc)]
     pub async fn new_query(
         &self,
         transport: MpcTransportImpl,
         req: QueryConfig,
     ) -> Result<PrepareQuery, NewQueryError> {
+        self.privacy_policy.check(&req)?;
         let query_id = QueryId;
         let handle = self.queries.handle(query_id);
-        handle.set_state(QueryState::Preparing(req))?;
+        handle.set_state(QueryState::Preparing(req.clone()))?;
         let guard = handle.remove_query_on_drop();
+        let reservation = self.reserve_budget(&req)?;
 
         let id = transport.identity();
         let [right, left] = id.others();
@@ -166,7 +193,7 @@ impl Processor {
 
         let prepare_request = PrepareQuery {
             query_id,
-            config: req,
+            config: req.clone(),
             roles: roles.clone(),
         };
 
@@ -181,17 +208,21 @@ impl Processor {
         handle.set_state(QueryState::AwaitingInputs(query_id, req, roles))?;
 
         guard.restore();
+        reservation.keep();
         Ok(prepare_request)
     }
 
     /// On prepare, each follower:
     /// * ensures that it is not the leader on this query
+    /// * query satisfies its privacy policy
     /// * query is not registered yet
+    /// * reserves the privacy budget this query is going to spend
     /// * creates gateway and network
     /// * registers query
     ///
     /// ## Errors
-    /// if query is already running or this helper cannot be a follower in it
+    /// if query is already running, violates the privacy policy of this helper, exceeds the
+    /// remaining privacy budget or this helper cannot be a follower in it
     pub fn prepare(
         &self,
         transport: &MpcTransportImpl,
@@ -202,16 +233,19 @@ impl Processor {
         if my_role == Role::H1 {
             return Err(PrepareQueryError::WrongTarget);
         }
+        self.privacy_policy.check(&req.config)?;
         let handle = self.queries.handle(req.query_id);
         if handle.status().is_some() {
             return Err(PrepareQueryError::AlreadyRunning);
         }
+        let reservation = self.reserve_budget(&req.config)?;
 
         handle.set_state(QueryState::AwaitingInputs(
             req.query_id,
             req.config,
             req.roles,
         ))?;
+        reservation.keep();
 
         Ok(())
     }
@@ -251,16 +285,21 @@ impl Processor {
                         mpc_transport,
                         shard_transport,
                     );
-                    queries.insert(
-                        input.query_id,
-                        QueryState::Running(executor::execute(
-                            &self.runtime,
-                            config,
-                            Arc::clone(&self.key_registry),
-                            gateway,
-                            input.input_stream,
-                        )),
+                    let charge = self.budget_charge(&config);
+                    let mut running = executor::execute(
+                        &self.runtime,
+                        config,
+                        Arc::clone(&self.key_registry),
+                        gateway,
+                        input.input_stream,
                     );
+                    if let Some(log) = &self.evidence_log {
+                        running = record_evidence(&self.runtime, running, Arc::clone(log));
+                    }
+                    if let Some((ledger, charge)) = charge {
+                        running = settle_budget(&self.runtime, running, ledger, charge);
+                    }
+                    queries.insert(input.query_id, QueryState::Running(running));
                     Ok(())
                 } else {
                     let error = StateError::InvalidState {
@@ -350,12 +389,146 @@ impl Processor {
             return Err(QueryKillStatus::NoSuchQuery(query_id));
         };
 
-        if let QueryState::Running(handle) = state {
-            handle.join_handle.abort();
+        match state {
+            QueryState::Running(handle) => handle.join_handle.abort(),
+            QueryState::AwaitingInputs(_, config, _) => {
+                if let Some((ledger, charge)) = self.budget_charge(&config) {
+                    ledger.release(&charge);
+                }
+            }
+            _ => {}
         }
 
         Ok(QueryKilled(query_id))
     }
+
+    /// Returns the state of the privacy budget ledger kept by this helper.
+    #[must_use]
+    pub fn budget_status(&self) -> BudgetLedgerStatus {
+        self.budget_ledger
+            .as_deref()
+            .map(BudgetLedgerStatus::from)
+            .unwrap_or_default()
+    }
+
+    fn reserve_budget(&self, config: &QueryConfig) -> Result<ReleaseBudget<'_>, BudgetError> {
+        let Some(ledger) = self.budget_ledger.as_deref() else {
+            return Ok(ReleaseBudget { inner: None });
+        };
+        let charge = BudgetCharge::for_query(config)?;
+        ledger.reserve(&charge)?;
+
+        Ok(ReleaseBudget {
+            inner: Some((ledger, charge)),
+        })
+    }
+
+    /// Returns the charge for a query that has already been accepted by this helper, along with
+    /// the ledger it is recorded in.
+    fn budget_charge(&self, config: &QueryConfig) -> Option<(Arc<BudgetLedger>, BudgetCharge)> {
+        let ledger = self.budget_ledger.as_ref()?;
+        // budget was reserved when this query was accepted, so it must be valid
+        let charge = BudgetCharge::for_query(config)
+            .expect("accepted query must have a valid budget charge");
+
+        Some((Arc::clone(ledger), charge))
+    }
+}
+
+/// RAII guard that returns the reserved budget back to the ledger, unless the query that
+/// reserved it has been accepted.
+struct ReleaseBudget<'a> {
+    inner: Option<(&'a BudgetLedger, BudgetCharge)>,
+}
+
+impl ReleaseBudget<'_> {
+    fn keep(mut self) {
+        self.inner.take();
+    }
+}
+
+impl Drop for ReleaseBudget<'_> {
+    fn drop(&mut self) {
+        if let Some((ledger, charge)) = self.inner.take() {
+            ledger.release(&charge);
+        }
+    }
+}
+
+/// Debits the budget reserved by a query once it produces a result. If the query fails or gets
+/// aborted, the reservation is released instead.
+fn settle_budget(
+    runtime: &IpaRuntime,
+    query: RunningQuery,
+    ledger: Arc<BudgetLedger>,
+    charge: BudgetCharge,
+) -> RunningQuery {
+    let RunningQuery {
+        result,
+        join_handle,
+    } = query;
+    let (tx, rx) = oneshot::channel();
+
+    // this task finishes together with the query, so there is no need to keep its handle
+    drop(runtime.spawn(async move {
+        let Ok(result) = result.await else {
+            // query task has been aborted
+            ledger.release(&charge);
+            return;
+        };
+        if result.is_ok() {
+            if let Err(e) = ledger.debit(&charge) {
+                tracing::error!("failed to record privacy budget spent by the query: {e}");
+            }
+        } else {
+            ledger.release(&charge);
+        }
+        // query may have been killed in the meantime
+        let _ = tx.send(result);
+    }));
+
+    RunningQuery {
+        result: rx,
+        join_handle,
+    }
+}
+
+/// Writes the evidence of a failed malicious security check to the log before the query result
+/// is reported back.
+fn record_evidence(
+    runtime: &IpaRuntime,
+    query: RunningQuery,
+    log: Arc<EvidenceLog>,
+) -> RunningQuery {
+    let RunningQuery {
+        result,
+        join_handle,
+    } = query;
+    let (tx, rx) = oneshot::channel();
+
+    // this task finishes together with the query, so there is no need to keep its handle
+    drop(runtime.spawn(async move {
+        let Ok(result) = result.await else {
+            // query task has been aborted
+            return;
+        };
+        if let Some(failure) = result
+            .as_ref()
+            .err()
+            .and_then(ProtocolError::validation_failure)
+        {
+            if let Err(e) = log.record(failure) {
+                tracing::error!("failed to record the evidence of a validation failure: {e}");
+            }
+        }
+        // query may have been killed in the meantime
+        let _ = tx.send(result);
+    }));
+
+    RunningQuery {
+        result: rx,
+        join_handle,
+    }
 }
 
 #[derive(Clone, Serialize)]
@@ -376,16 +549,22 @@ mod tests {
     use tokio::sync::Barrier;
 
     use crate::{
+        executor::IpaRuntime,
         ff::FieldType,
         helpers::{
             make_owned_handler,
-            query::{PrepareQuery, QueryConfig, QueryType::TestMultiply},
+            query::{
+                IpaQueryConfig, PrepareQuery, QueryConfig,
+                QueryType::{SemiHonestOprfIpa, TestMultiply},
+            },
             ApiError, HandlerBox, HelperIdentity, HelperResponse, InMemoryMpcNetwork,
             RequestHandler, RoleAssignment, Transport,
         },
+        hpke::KeyRegistry,
         protocol::QueryId,
         query::{
-            processor::Processor, state::StateError, NewQueryError, PrepareQueryError, QueryStatus,
+            processor::Processor, state::StateError, BudgetEntry, BudgetError, BudgetLedger,
+            NewQueryError, PolicyViolation, PrepareQueryError, PrivacyPolicy, QueryStatus,
         },
     };
 
@@ -436,7 +615,7 @@ mod tests {
         let p0 = Processor::default();
         let request = test_multiply_config();
 
-        let qc_future = p0.new_query(t0, request);
+        let qc_future = p0.new_query(t0, request.clone());
         pin_mut!(qc_future);
 
         // poll future once to trigger query status change
@@ -474,7 +653,7 @@ mod tests {
         let request = test_multiply_config();
 
         let _qc = p0
-            .new_query(Transport::clone_ref(&t0), request)
+            .new_query(Transport::clone_ref(&t0), request.clone())
             .await
             .unwrap();
         assert!(matches!(
@@ -483,6 +662,109 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn rejects_query_violating_privacy_policy() {
+        let handlers =
+            array::from_fn(|_| prepare_query_handler(|_| async { Ok(HelperResponse::ok()) }));
+        let network =
+            InMemoryMpcNetwork::new(handlers.each_ref().map(HandlerBox::owning_ref).map(Some));
+        let [t0, _, _] = network.transports();
+        let p0 = Processor::new(
+            KeyRegistry::empty(),
+            None,
+            PrivacyPolicy {
+                max_oprf_padding_epsilon: Some(1.0),
+                ..Default::default()
+            },
+            None,
+            None,
+            IpaRuntime::current(),
+        );
+        let request = QueryConfig::new(
+            SemiHonestOprfIpa(IpaQueryConfig {
+                oprf_padding_epsilon: 5.0,
+                ..Default::default()
+            }),
+            FieldType::Fp32BitPrime,
+            1,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            p0.new_query(t0, request).await,
+            Err(NewQueryError::PolicyViolation(
+                PolicyViolation::AboveMaximum { .. }
+            )),
+        ));
+        assert!(p0.query_status(QueryId).is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_query_exceeding_privacy_budget() {
+        let handlers =
+            array::from_fn(|_| prepare_query_handler(|_| async { Ok(HelperResponse::ok()) }));
+        let network =
+            InMemoryMpcNetwork::new(handlers.each_ref().map(HandlerBox::owning_ref).map(Some));
+        let [t0, _, _] = network.transports();
+        let p0 = Processor::new(
+            KeyRegistry::empty(),
+            None,
+            PrivacyPolicy::default(),
+            Some(Arc::new(BudgetLedger::new(2.0))),
+            None,
+            IpaRuntime::current(),
+        );
+        // padding spends 0.5 on top of the output epsilon
+        let request = |epsilon: f64, conversion_site: Option<&str>| {
+            QueryConfig::new(
+                SemiHonestOprfIpa(IpaQueryConfig {
+                    epsilon,
+                    oprf_padding_epsilon: 0.25,
+                    aggregation_padding_epsilon: 0.25,
+                    conversion_site: conversion_site.map(ToString::to_string),
+                    epoch: Some(1),
+                    ..Default::default()
+                }),
+                FieldType::Fp32BitPrime,
+                1,
+            )
+            .unwrap()
+        };
+
+        assert!(matches!(
+            p0.new_query(t0.clone_ref(), request(0.5, None)).await,
+            Err(NewQueryError::Budget(BudgetError::MissingKey)),
+        ));
+
+        p0.new_query(t0.clone_ref(), request(0.5, Some("shop.example")))
+            .await
+            .unwrap();
+        let entry = |reserved: f64| BudgetEntry {
+            conversion_site: "shop.example".to_string(),
+            epoch: 1,
+            spent: 0.0,
+            reserved,
+            remaining: 2.0 - reserved,
+        };
+        assert_eq!(vec![entry(1.0)], p0.budget_status().entries);
+        // killed queries do not spend budget
+        p0.kill(QueryId).unwrap();
+        assert_eq!(vec![entry(0.0)], p0.budget_status().entries);
+
+        assert!(matches!(
+            p0.new_query(t0.clone_ref(), request(1.75, Some("shop.example")))
+                .await,
+            Err(NewQueryError::Budget(BudgetError::Exhausted { .. })),
+        ));
+        assert!(p0.query_status(QueryId).is_err());
+
+        // a ledger can't price test queries, so they are not accepted either
+        assert!(matches!(
+            p0.new_query(t0, test_multiply_config()).await,
+            Err(NewQueryError::Budget(BudgetError::Unpriced(_))),
+        ));
+    }
+
     #[tokio::test]
     async fn prepare_error() {
         let h2 = respond_ok();
@@ -518,7 +800,9 @@ mod tests {
         let [t0, _, _] = network.transports();
         let p0 = Processor::default();
         let request = test_multiply_config();
-        p0.new_query(t0.clone_ref(), request).await.unwrap_err();
+        p0.new_query(t0.clone_ref(), request.clone())
+            .await
+            .unwrap_err();
 
         assert!(matches!(
             p0.new_query(t0, request).await.unwrap_err(),
@@ -697,7 +981,7 @@ mod tests {
                 boolean_array::{BA20, BA3, BA8},
                 Fp31, U128Conversions,
             },
-            helpers::query::{IpaQueryConfig, QueryType},
+            helpers::query::{DpMechanismKind, IpaQueryConfig, QueryType},
             protocol::ipa_prf::OPRFIPAInputRow,
             secret_sharing::replicated::semi_honest,
             test_fixture::{ipa::TestRawDataRecord, Reconstruct, TestApp},
@@ -819,7 +1103,10 @@ mod tests {
                             attribution_window_seconds: None,
                             with_dp: 0,
                             epsilon: 5.0,
+                            dp_mechanism: DpMechanismKind::DiscreteLaplace,
+                            delta: 1e-6,
                             plaintext_match_keys: true,
+                            ..Default::default()
                         }),
                     },
                 )
@@ -0,0 +1,192 @@
+use futures::TryStreamExt;
+
+use crate::{
+    error::Error,
+    ff::boolean_array::{BA20, BA32, BA8},
+    helpers::{
+        query::{FeatureLabelQueryParams, NoisyHistogram, OutputDp, QuerySize},
+        BodyStream, RecordsStream,
+    },
+    protocol::{
+        context::{Context, SemiHonestContext},
+        ipa_prf::prf_sharding::feature_label_dot_product::{
+            feature_label_dot_product, FeatureLabelInputRow,
+        },
+        step::ProtocolStep::IpaPrf,
+    },
+    query::DpQueryOutput,
+    secret_sharing::replicated::semi_honest::AdditiveShare as Replicated,
+};
+
+/// Number of features in each source event.
+pub const FEATURE_COUNT: usize = 16;
+
+/// Bound on the sum of a single feature vector. `2^12` is the smallest power of two that is not
+/// less than `FEATURE_COUNT * (2^8 - 1)`.
+const SS_BITS: usize = 12;
+
+pub type FeatureLabelQueryInputRow = FeatureLabelInputRow<BA8, BA20, FEATURE_COUNT>;
+
+/// Computes the sum of the feature vectors of source events that received attribution.
+///
+/// Only plaintext match keys are supported, and the row layout does not fit the malicious
+/// shuffle, so this query only runs with semi-honest security.
+pub struct FeatureLabelDotProductQuery {
+    config: FeatureLabelQueryParams,
+}
+
+impl FeatureLabelDotProductQuery {
+    pub fn new(config: FeatureLabelQueryParams) -> Self {
+        Self { config }
+    }
+
+    #[tracing::instrument("feature_label_dot_product_query", skip_all, fields(sz=%query_size))]
+    pub async fn execute(
+        self,
+        ctx: SemiHonestContext<'_>,
+        query_size: QuerySize,
+        input_stream: BodyStream,
+    ) -> Result<DpQueryOutput<Replicated<BA32>>, Error> {
+        let Self { config } = self;
+        tracing::info!("New query: {config:?}");
+        let ctx = ctx.narrow(&IpaPrf);
+        let sz = usize::from(query_size);
+
+        let mut input = RecordsStream::<FeatureLabelQueryInputRow, _>::new(input_stream)
+            .try_concat()
+            .await?;
+        input.truncate(sz);
+
+        let dp_params = config
+            .dp_mechanism()
+            .map_err(|e| Error::InvalidQueryParameter(Box::new(e)))?;
+        tracing::info!("Applying DP mechanism: {dp_params:?}");
+
+        let padding_params = config.padding_params();
+
+        let values = feature_label_dot_product::<_, BA8, BA20, BA32, SS_BITS, FEATURE_COUNT>(
+            ctx,
+            input,
+            dp_params,
+            padding_params,
+        )
+        .await?;
+
+        Ok(DpQueryOutput {
+            values,
+            dp: OutputDp {
+                mechanism: dp_params,
+                histograms: vec![NoisyHistogram {
+                    mechanism: dp_params,
+                    sensitivity: 1 << SS_BITS,
+                    dimensions: u32::try_from(FEATURE_COUNT).unwrap(),
+                    suppression_threshold: None,
+                }],
+            },
+        })
+    }
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use std::iter::zip;
+
+    use generic_array::GenericArray;
+    use typenum::Unsigned;
+
+    use super::{FeatureLabelDotProductQuery, FeatureLabelQueryInputRow, FEATURE_COUNT};
+    use crate::{
+        ff::{Serializable, U128Conversions},
+        helpers::{
+            query::{FeatureLabelQueryParams, QuerySize},
+            BodyStream,
+        },
+        secret_sharing::IntoShares,
+        test_fixture::{
+            feature_label::{feature_label_dot_product_in_the_clear, TestFeatureLabelRecord},
+            join3v, Reconstruct, TestWorld,
+        },
+    };
+
+    #[tokio::test]
+    async fn plaintext_match_keys() {
+        let features = |base: u32| -> Vec<u32> {
+            (0..u32::try_from(FEATURE_COUNT).unwrap())
+                .map(|i| (base + 7 * i) % 256)
+                .collect()
+        };
+        let records = vec![
+            TestFeatureLabelRecord {
+                timestamp: 0,
+                user_id: 12345,
+                is_trigger_report: false,
+                features: features(3),
+            },
+            TestFeatureLabelRecord {
+                timestamp: 4,
+                user_id: 68362,
+                is_trigger_report: false,
+                features: features(100),
+            },
+            TestFeatureLabelRecord {
+                timestamp: 10,
+                user_id: 12345,
+                is_trigger_report: true,
+                features: vec![0; FEATURE_COUNT],
+            },
+            TestFeatureLabelRecord {
+                timestamp: 12,
+                user_id: 68362,
+                is_trigger_report: true,
+                features: vec![0; FEATURE_COUNT],
+            },
+            TestFeatureLabelRecord {
+                timestamp: 20,
+                user_id: 68362,
+                is_trigger_report: false,
+                features: features(250),
+            },
+        ];
+        let expected = feature_label_dot_product_in_the_clear(&records, FEATURE_COUNT);
+
+        let query_size = QuerySize::try_from(records.len()).unwrap();
+        let row_size = <FeatureLabelQueryInputRow as Serializable>::Size::USIZE;
+        let mut buffers: [_; 3] = std::array::from_fn(|_| Vec::new());
+        let shares: [Vec<FeatureLabelQueryInputRow>; 3] = records.into_iter().share();
+        for (buf, shares) in zip(&mut buffers, shares) {
+            for share in shares {
+                let mut row = GenericArray::default();
+                share.serialize(&mut row);
+                assert_eq!(row.len(), row_size);
+                buf.extend_from_slice(&row);
+            }
+        }
+
+        let world = TestWorld::default();
+        let contexts = world.contexts();
+        #[allow(clippy::large_futures)]
+        let results = join3v(buffers.into_iter().zip(contexts).map(|(buffer, ctx)| {
+            let query_config = FeatureLabelQueryParams {
+                with_dp: 0,
+                epsilon: 5.0,
+                ..Default::default()
+            };
+            FeatureLabelDotProductQuery::new(query_config).execute(
+                ctx,
+                query_size,
+                BodyStream::from(buffer),
+            )
+        }))
+        .await;
+
+        assert_eq!(
+            results
+                .map(|output| output.values)
+                .reconstruct()
+                .iter()
+                .map(|v| u32::try_from(v.as_u128()).unwrap())
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+}
@@ -9,17 +9,17 @@ use crate::{
         U128Conversions,
     },
     helpers::{
-        query::{DpMechanism, HybridQueryParams, QuerySize},
+        query::{HybridQueryParams, NoisyHistogram, OutputDp, QuerySize},
         BodyStream, LengthDelimitedStream,
     },
     hpke::PrivateKeyRegistry,
     protocol::{
         context::{ShardedContext, UpgradableContext},
         hybrid::{hybrid_protocol, step::HybridStep},
-        ipa_prf::{oprf_padding::PaddingParameters, shuffle::Shuffle},
+        ipa_prf::shuffle::Shuffle,
         step::ProtocolStep::Hybrid,
     },
-    query::runner::reshard_tag::reshard_aad,
+    query::{check_conversion_info, runner::reshard_tag::reshard_aad, DpQueryOutput},
     report::{
         hybrid::{
             EncryptedHybridReport, IndistinguishableHybridReport, UniqueTag, UniqueTagValidator,
@@ -65,7 +65,7 @@ where
         ctx: C,
         query_size: QuerySize,
         input_stream: BodyStream,
-    ) -> Result<Vec<Replicated<HV>>, Error> {
+    ) -> Result<DpQueryOutput<Replicated<HV>>, Error> {
         let Self {
             config,
             key_registry,
@@ -82,6 +82,8 @@ where
                 "Hybrid queries do not currently support plaintext match keys".to_string(),
             ));
         }
+        check_conversion_info(&config, &hybrid_info.conversion)
+            .map_err(|e| Error::InvalidQueryParameter(Box::new(e)))?;
 
         let stream = LengthDelimitedStream::<EncryptedHybridReport<BA8, BA3>, _>::new(input_stream)
             .map_err(Into::<Error>::into)
@@ -115,31 +117,41 @@ where
         let indistinguishable_reports: Vec<IndistinguishableHybridReport<BA8, BA3>> =
             decrypted_reports.into_iter().map(Into::into).collect();
 
-        let dp_params: DpMechanism = match config.with_dp {
-            0 => DpMechanism::NoDp,
-            _ => DpMechanism::DiscreteLaplace {
-                epsilon: config.epsilon,
-            },
-        };
-
-        #[cfg(feature = "relaxed-dp")]
-        let padding_params = PaddingParameters::relaxed();
-        #[cfg(not(feature = "relaxed-dp"))]
-        let padding_params = PaddingParameters::default();
-
-        match config.per_user_credit_cap {
-            1 => hybrid_protocol::<_, BA8, BA3, HV, 1, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await,
-            2 | 4 => hybrid_protocol::<_, BA8, BA3, HV, 2, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await,
-            8 => hybrid_protocol::<_, BA8, BA3, HV, 3, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await,
-            16 => hybrid_protocol::<_, BA8, BA3, HV, 4, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await,
-            32 => hybrid_protocol::<_, BA8, BA3, HV, 5, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await,
-            64 => hybrid_protocol::<_, BA8, BA3, HV, 6, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await,
-            128 => hybrid_protocol::<_, BA8, BA3, HV, 7, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await,
+        let dp_params = config
+            .dp_mechanism()
+            .map_err(|e| Error::InvalidQueryParameter(Box::new(e)))?;
+        tracing::info!("Applying DP mechanism: {dp_params:?}");
+
+        let padding_params = config.padding_params();
+
+        // Saturating sums have `SS_BITS` bits, which caps the contribution of a user at
+        // `2^SS_BITS`.
+        let (ss_bits, values) = match config.per_user_credit_cap {
+            1 => (1, hybrid_protocol::<_, BA8, BA3, HV, 1, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await),
+            2 | 4 => (2, hybrid_protocol::<_, BA8, BA3, HV, 2, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await),
+            8 => (3, hybrid_protocol::<_, BA8, BA3, HV, 3, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await),
+            16 => (4, hybrid_protocol::<_, BA8, BA3, HV, 4, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await),
+            32 => (5, hybrid_protocol::<_, BA8, BA3, HV, 5, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await),
+            64 => (6, hybrid_protocol::<_, BA8, BA3, HV, 6, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await),
+            128 => (7, hybrid_protocol::<_, BA8, BA3, HV, 7, 256>(ctx, indistinguishable_reports, dp_params, padding_params).await),
             _ => panic!(
                 "Invalid value specified for per-user cap: {:?}. Must be one of 1, 2, 4, 8, 16, 32, 64, or 128.",
                 config.per_user_credit_cap
             ),
-        }
+        };
+
+        Ok(DpQueryOutput {
+            values: values?,
+            dp: OutputDp {
+                mechanism: dp_params,
+                histograms: vec![NoisyHistogram {
+                    mechanism: dp_params,
+                    sensitivity: 2_u32.pow(ss_bits),
+                    dimensions: 256,
+                    suppression_threshold: None,
+                }],
+            },
+        })
     }
 }
 
@@ -156,7 +168,7 @@ mod tests {
             U128Conversions,
         },
         helpers::{
-            query::{HybridQueryParams, QuerySize},
+            query::{DpMechanismKind, HybridQueryParams, QuerySize},
             BodyStream,
         },
         hpke::{KeyPair, KeyRegistry},
@@ -291,7 +303,10 @@ mod tests {
                             max_breakdown_key: 3,
                             with_dp: 0,
                             epsilon: 5.0,
+                            dp_mechanism: DpMechanismKind::DiscreteLaplace,
+                            delta: 1e-6,
                             plaintext_match_keys: false,
+                            ..Default::default()
                         };
                         let input = BodyStream::from(buffer);
 
@@ -310,9 +325,9 @@ mod tests {
             .chunks(3)
             .map(|chunk| {
                 [
-                    chunk[0].as_ref().unwrap().clone(),
-                    chunk[1].as_ref().unwrap().clone(),
-                    chunk[2].as_ref().unwrap().clone(),
+                    chunk[0].as_ref().unwrap().values.clone(),
+                    chunk[1].as_ref().unwrap().values.clone(),
+                    chunk[2].as_ref().unwrap().values.clone(),
                 ]
             })
             .collect();
@@ -381,7 +396,10 @@ mod tests {
                             max_breakdown_key: 3,
                             with_dp: 0,
                             epsilon: 5.0,
+                            dp_mechanism: DpMechanismKind::DiscreteLaplace,
+                            delta: 1e-6,
                             plaintext_match_keys: false,
+                            ..Default::default()
                         };
                         let input = BodyStream::from(buffer);
 
@@ -434,7 +452,10 @@ mod tests {
                             max_breakdown_key: 3,
                             with_dp: 0,
                             epsilon: 5.0,
+                            dp_mechanism: DpMechanismKind::DiscreteLaplace,
+                            delta: 1e-6,
                             plaintext_match_keys: true,
+                            ..Default::default()
                         };
                         let input = BodyStream::from(buffer);
 
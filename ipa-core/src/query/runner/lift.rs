@@ -0,0 +1,231 @@
+use futures::TryStreamExt;
+
+use crate::{
+    error::Error,
+    ff::boolean_array::{BA20, BA3, BA32, BA5},
+    helpers::{
+        query::{LiftQueryParams, NoisyHistogram, OutputDp, QuerySize},
+        BodyStream, RecordsStream,
+    },
+    protocol::{
+        context::{Context, SemiHonestContext},
+        ipa_prf::{
+            prf_sharding::lift::{lift, DP_RELEASES},
+            OPRFIPAInputRow,
+        },
+        step::ProtocolStep::IpaPrf,
+    },
+    query::DpQueryOutput,
+    secret_sharing::replicated::semi_honest::AdditiveShare as Replicated,
+};
+
+/// The per-user cap of the lift query is `2^SS_BITS`.
+const SS_BITS: usize = 3;
+
+/// Only bit 0 of the breakdown key is used, but the aggregation is only implemented for wider
+/// breakdown keys.
+const BREAKDOWN_COUNT: usize = 32;
+
+pub type LiftQueryInputRow = OPRFIPAInputRow<BA5, BA3, BA20>;
+
+/// Computes the DP-noised totals and counts of the conversions attributed to the control and to
+/// the test arm, as `[control_total, control_count, test_total, test_count]`.
+///
+/// Only plaintext match keys are supported, so this query only runs with semi-honest security.
+pub struct LiftQuery {
+    config: LiftQueryParams,
+}
+
+impl LiftQuery {
+    pub fn new(config: LiftQueryParams) -> Self {
+        Self { config }
+    }
+
+    #[tracing::instrument("lift_query", skip_all, fields(sz=%query_size))]
+    pub async fn execute(
+        self,
+        ctx: SemiHonestContext<'_>,
+        query_size: QuerySize,
+        input_stream: BodyStream,
+    ) -> Result<DpQueryOutput<Replicated<BA32>>, Error> {
+        let Self { config } = self;
+        tracing::info!("New query: {config:?}");
+        let ctx = ctx.narrow(&IpaPrf);
+        let sz = usize::from(query_size);
+
+        let mut input = RecordsStream::<LiftQueryInputRow, _>::new(input_stream)
+            .try_concat()
+            .await?;
+        input.truncate(sz);
+
+        let dp_params = config
+            .dp_mechanism()
+            .map_err(|e| Error::InvalidQueryParameter(Box::new(e)))?;
+        tracing::info!("Applying DP mechanism: {dp_params:?}");
+
+        let padding_params = config.padding_params();
+
+        let values = lift::<_, BA5, BA3, BA32, BA20, SS_BITS, BREAKDOWN_COUNT>(
+            ctx,
+            input,
+            config.attribution_window_seconds,
+            dp_params,
+            padding_params,
+        )
+        .await?;
+
+        Ok(DpQueryOutput {
+            values,
+            dp: OutputDp {
+                mechanism: dp_params,
+                // the totals and the counts are noised together
+                histograms: vec![NoisyHistogram {
+                    mechanism: dp_params.split_budget(DP_RELEASES),
+                    sensitivity: 1 << SS_BITS,
+                    dimensions: u32::try_from(BREAKDOWN_COUNT).unwrap(),
+                    suppression_threshold: None,
+                }],
+            },
+        })
+    }
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use std::iter::zip;
+
+    use generic_array::GenericArray;
+    use typenum::Unsigned;
+
+    use super::{LiftQuery, LiftQueryInputRow, SS_BITS};
+    use crate::{
+        ff::{Serializable, U128Conversions},
+        helpers::{
+            query::{
+                DpMechanism, DpMechanismKind, LiftQueryParams, NoisyHistogram, OutputDp, QuerySize,
+            },
+            BodyStream,
+        },
+        secret_sharing::IntoShares,
+        test_fixture::{
+            ipa::TestRawDataRecord, join3v, lift::lift_in_the_clear, Reconstruct, TestWorld,
+        },
+    };
+
+    #[tokio::test]
+    async fn plaintext_match_keys() {
+        let record = |timestamp: u64,
+                      user_id: u64,
+                      is_trigger_report: bool,
+                      breakdown_key: u32,
+                      trigger_value: u32| TestRawDataRecord {
+            timestamp,
+            user_id,
+            is_trigger_report,
+            breakdown_key,
+            trigger_value,
+        };
+        let records = vec![
+            record(0, 12345, false, 1, 0),
+            record(5, 12345, true, 0, 5),
+            record(0, 68362, false, 0, 0),
+            record(10, 68362, true, 0, 3),
+            record(20, 68362, true, 0, 6),
+            record(0, 77777, true, 0, 2),
+        ];
+        let expected = lift_in_the_clear(&records, 1 << SS_BITS, None);
+
+        let query_size = QuerySize::try_from(records.len()).unwrap();
+        let row_size = <LiftQueryInputRow as Serializable>::Size::USIZE;
+        let mut buffers: [_; 3] = std::array::from_fn(|_| Vec::new());
+        let shares: [Vec<LiftQueryInputRow>; 3] = records.into_iter().share();
+        for (buf, shares) in zip(&mut buffers, shares) {
+            for share in shares {
+                let mut row = GenericArray::default();
+                share.serialize(&mut row);
+                assert_eq!(row.len(), row_size);
+                buf.extend_from_slice(&row);
+            }
+        }
+
+        let world = TestWorld::default();
+        let contexts = world.contexts();
+        #[allow(clippy::large_futures)]
+        let results = join3v(buffers.into_iter().zip(contexts).map(|(buffer, ctx)| {
+            let query_config = LiftQueryParams {
+                attribution_window_seconds: None,
+                with_dp: 0,
+                epsilon: 5.0,
+                ..Default::default()
+            };
+            LiftQuery::new(query_config).execute(ctx, query_size, BodyStream::from(buffer))
+        }))
+        .await;
+
+        assert_eq!(
+            results
+                .map(|output| output.values)
+                .reconstruct()
+                .iter()
+                .map(|v| u32::try_from(v.as_u128()).unwrap())
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn returns_dp_mechanism() {
+        let records = vec![TestRawDataRecord {
+            timestamp: 0,
+            user_id: 12345,
+            is_trigger_report: false,
+            breakdown_key: 1,
+            trigger_value: 0,
+        }];
+        let query_size = QuerySize::try_from(records.len()).unwrap();
+        let mut buffers: [_; 3] = std::array::from_fn(|_| Vec::new());
+        let shares: [Vec<LiftQueryInputRow>; 3] = records.into_iter().share();
+        for (buf, shares) in zip(&mut buffers, shares) {
+            for share in shares {
+                let mut row = GenericArray::default();
+                share.serialize(&mut row);
+                buf.extend_from_slice(&row);
+            }
+        }
+
+        let world = TestWorld::default();
+        let contexts = world.contexts();
+        #[allow(clippy::large_futures)]
+        let results = join3v(buffers.into_iter().zip(contexts).map(|(buffer, ctx)| {
+            let query_config = LiftQueryParams {
+                with_dp: 1,
+                epsilon: 4.0,
+                dp_mechanism: DpMechanismKind::DiscreteGaussian,
+                ..Default::default()
+            };
+            LiftQuery::new(query_config).execute(ctx, query_size, BodyStream::from(buffer))
+        }))
+        .await;
+
+        for output in results {
+            assert_eq!(
+                output.dp,
+                OutputDp {
+                    mechanism: DpMechanism::DiscreteGaussian {
+                        epsilon: 4.0,
+                        delta: DpMechanism::DEFAULT_DELTA,
+                    },
+                    histograms: vec![NoisyHistogram {
+                        mechanism: DpMechanism::DiscreteGaussian {
+                            epsilon: 2.0,
+                            delta: DpMechanism::DEFAULT_DELTA / 2.0,
+                        },
+                        sensitivity: 8,
+                        dimensions: 32,
+                        suppression_threshold: None,
+                    }],
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use futures::TryStreamExt;
+
+use crate::{
+    error::Error,
+    ff::boolean_array::BA16,
+    helpers::{
+        query::{LogisticRegressionQueryParams, NoisyHistogram, OutputDp, QuerySize},
+        BodyStream, RecordsStream,
+    },
+    protocol::{
+        context::{Context, SemiHonestContext},
+        ipa_prf::logistic_regression::{logistic_regression, LogisticRegressionInputRow},
+        step::ProtocolStep::IpaPrf,
+    },
+    query::DpQueryOutput,
+    secret_sharing::replicated::semi_honest::AdditiveShare as Replicated,
+};
+
+/// Number of features in each example.
+pub const FEATURE_COUNT: usize = 16;
+
+/// Bound on the gradient contribution of a single example. Each of the `FEATURE_COUNT` features
+/// contributes a 9-bit value, so `2^13` bounds their sum.
+const SS_BITS: usize = 13;
+
+pub type LogisticRegressionQueryInputRow = LogisticRegressionInputRow<FEATURE_COUNT>;
+
+/// Trains a logistic regression model over attributed examples, and reveals the DP-noised weights.
+pub struct LogisticRegressionQuery {
+    config: LogisticRegressionQueryParams,
+}
+
+impl LogisticRegressionQuery {
+    pub fn new(config: LogisticRegressionQueryParams) -> Self {
+        Self { config }
+    }
+
+    #[tracing::instrument("logistic_regression_query", skip_all, fields(sz=%query_size))]
+    pub async fn execute(
+        self,
+        ctx: SemiHonestContext<'_>,
+        query_size: QuerySize,
+        input_stream: BodyStream,
+    ) -> Result<DpQueryOutput<Replicated<BA16>>, Error> {
+        let Self { config } = self;
+        tracing::info!("New query: {config:?}");
+        let ctx = ctx.narrow(&IpaPrf);
+        let sz = usize::from(query_size);
+
+        let mut input = RecordsStream::<LogisticRegressionQueryInputRow, _>::new(input_stream)
+            .try_concat()
+            .await?;
+        input.truncate(sz);
+
+        let dp_params = config
+            .dp_mechanism()
+            .map_err(|e| Error::InvalidQueryParameter(Box::new(e)))?;
+        tracing::info!("Applying DP mechanism: {dp_params:?}");
+
+        let padding_params = config.padding_params();
+
+        let values = logistic_regression::<_, SS_BITS, FEATURE_COUNT>(
+            ctx,
+            input,
+            usize::try_from(config.iterations).unwrap(),
+            usize::try_from(config.learning_rate_shift).unwrap(),
+            dp_params,
+            padding_params,
+        )
+        .await?;
+
+        Ok(DpQueryOutput {
+            values,
+            dp: OutputDp {
+                mechanism: dp_params,
+                // every iteration releases a noised gradient
+                histograms: vec![
+                    NoisyHistogram {
+                        mechanism: dp_params.split_budget(config.iterations),
+                        sensitivity: 1 << SS_BITS,
+                        dimensions: u32::try_from(FEATURE_COUNT).unwrap(),
+                        suppression_threshold: None,
+                    };
+                    usize::try_from(config.iterations).unwrap()
+                ],
+            },
+        })
+    }
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use std::iter::zip;
+
+    use generic_array::GenericArray;
+    use typenum::Unsigned;
+
+    use super::{LogisticRegressionQuery, LogisticRegressionQueryInputRow, FEATURE_COUNT};
+    use crate::{
+        ff::{Serializable, U128Conversions},
+        helpers::{
+            query::{LogisticRegressionQueryParams, QuerySize},
+            BodyStream,
+        },
+        secret_sharing::IntoShares,
+        test_fixture::{
+            join3v,
+            logistic_regression::{logistic_regression_in_the_clear, TestLogisticRegressionRecord},
+            Reconstruct, TestWorld,
+        },
+    };
+
+    #[tokio::test]
+    async fn train() {
+        let query_config = LogisticRegressionQueryParams {
+            iterations: 2,
+            learning_rate_shift: 2,
+            with_dp: 0,
+            epsilon: 5.0,
+            ..Default::default()
+        };
+        let records = (0..8_u8)
+            .map(|i| TestLogisticRegressionRecord {
+                user_id: u64::from(i),
+                features: (0..u8::try_from(FEATURE_COUNT).unwrap())
+                    .map(|j| i.wrapping_mul(37).wrapping_add(j.wrapping_mul(11)))
+                    .collect(),
+                label: i % 3 == 0,
+            })
+            .collect::<Vec<_>>();
+        let expected = logistic_regression_in_the_clear(&records, FEATURE_COUNT, 2, 2);
+
+        let query_size = QuerySize::try_from(records.len()).unwrap();
+        let row_size = <LogisticRegressionQueryInputRow as Serializable>::Size::USIZE;
+        let mut buffers: [_; 3] = std::array::from_fn(|_| Vec::new());
+        let shares: [Vec<LogisticRegressionQueryInputRow>; 3] = records.into_iter().share();
+        for (buf, shares) in zip(&mut buffers, shares) {
+            for share in shares {
+                let mut row = GenericArray::default();
+                share.serialize(&mut row);
+                assert_eq!(row.len(), row_size);
+                buf.extend_from_slice(&row);
+            }
+        }
+
+        let world = TestWorld::default();
+        let contexts = world.contexts();
+        #[allow(clippy::large_futures)]
+        let results = join3v(buffers.into_iter().zip(contexts).map(|(buffer, ctx)| {
+            LogisticRegressionQuery::new(query_config.clone()).execute(
+                ctx,
+                query_size,
+                BodyStream::from(buffer),
+            )
+        }))
+        .await;
+
+        assert_eq!(
+            results
+                .map(|output| output.values)
+                .reconstruct()
+                .iter()
+                .map(|w| i16::from_le_bytes(u16::try_from(w.as_u128()).unwrap().to_le_bytes()))
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+}
@@ -1,17 +1,31 @@
 #[cfg(any(test, feature = "cli", feature = "test-fixture"))]
 mod add_in_prime_field;
+mod feature_label;
 mod hybrid;
+mod lift;
+mod logistic_regression;
 mod oprf_ipa;
+mod reach_frequency;
 mod reshard_tag;
 #[cfg(any(test, feature = "cli", feature = "test-fixture"))]
+mod sharded_shuffle;
+#[cfg(any(test, feature = "cli", feature = "test-fixture"))]
 mod test_multiply;
 
 #[cfg(any(test, feature = "cli", feature = "test-fixture"))]
 pub(super) use add_in_prime_field::execute as test_add_in_prime_field;
 #[cfg(any(test, feature = "cli", feature = "test-fixture"))]
+pub(super) use sharded_shuffle::execute_sharded_shuffle;
+#[cfg(any(test, feature = "cli", feature = "test-fixture"))]
 pub(super) use test_multiply::execute_test_multiply;
 
-pub use self::oprf_ipa::OprfIpaQuery;
+pub use self::{
+    feature_label::FeatureLabelDotProductQuery,
+    lift::LiftQuery,
+    logistic_regression::LogisticRegressionQuery,
+    oprf_ipa::{OprfIpaQuery, ShardedOprfIpaQuery},
+    reach_frequency::ReachFrequencyQuery,
+};
 use crate::{error::Error, query::ProtocolResult};
 
 pub(super) type QueryResult = Result<Box<dyn ProtocolResult>, Error>;
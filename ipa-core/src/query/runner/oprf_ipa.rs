@@ -7,27 +7,30 @@ use crate::{
     error::{Error, LengthError},
     ff::{
         boolean::Boolean,
-        boolean_array::{BooleanArray, BA20, BA3, BA8},
+        boolean_array::{BooleanArray, BA20, BA3, BA64, BA8},
         curve_points::RP25519,
         ec_prime_field::Fp25519,
         Field, Serializable, U128Conversions,
     },
     helpers::{
-        query::{DpMechanism, IpaQueryConfig, QuerySize},
+        query::{DpMechanism, IpaQueryConfig, NoisyHistogram, OutputDp, QuerySize},
         BodyStream, LengthDelimitedStream, RecordsStream,
     },
     hpke::PrivateKeyRegistry,
     protocol::{
         basics::{BooleanArrayMul, Reveal, ShareKnownValue},
-        context::{DZKPUpgraded, MacUpgraded, UpgradableContext},
+        context::{Context, DZKPUpgraded, MacUpgraded, ShardedContext, UpgradableContext},
+        dp::suppress_sparse_buckets,
         ipa_prf::{
-            oprf_ipa, oprf_padding::PaddingParameters, prf_eval::PrfSharing, OPRFIPAInputRow,
+            oprf_ipa, prf_eval::PrfSharing, prf_sharding::PrfShardedIpaInputRow, sharded_oprf_ipa,
+            shuffle::ShardedShuffle, trigger_value_cap::per_user_sensitivity, OPRFIPAInputRow,
             Shuffle, AGG_CHUNK, CONV_CHUNK, PRF_CHUNK, SORT_CHUNK,
         },
         prss::FromPrss,
         step::ProtocolStep::IpaPrf,
         BooleanProtocols,
     },
+    query::{check_trigger_report, DpQueryOutput},
     report::{EncryptedOprfReport, EventType},
     secret_sharing::{
         replicated::semi_honest::{AdditiveShare as Replicated, AdditiveShare},
@@ -71,7 +74,9 @@ where
     Replicated<BA8>: BooleanArrayMul<DZKPUpgraded<C>>
         + Reveal<DZKPUpgraded<C>, Output = <BA8 as Vectorizable<1>>::Array>,
     Replicated<BA20>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<BA64>: BooleanArrayMul<DZKPUpgraded<C>>,
     Replicated<BA3>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<HV>: BooleanArrayMul<DZKPUpgraded<C>>,
     Vec<Replicated<HV>>:
         for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, 256>>, Error = LengthError>,
     BitDecomposed<AdditiveShare<Boolean, 256>>:
@@ -83,7 +88,7 @@ where
         ctx: C,
         query_size: QuerySize,
         input_stream: BodyStream,
-    ) -> Result<Vec<Replicated<HV>>, Error> {
+    ) -> Result<DpQueryOutput<Replicated<HV>>, Error> {
         let Self {
             config,
             key_registry,
@@ -91,99 +96,289 @@ where
         } = self;
         tracing::info!("New query: {config:?}");
         let ctx = ctx.narrow(&IpaPrf);
-        let sz = usize::from(query_size);
-
-        let input = if config.plaintext_match_keys {
-            let mut v = RecordsStream::<OPRFIPAInputRow<BA8, BA3, BA20>, _>::new(input_stream)
-                .try_concat()
-                .await?;
-            v.truncate(sz);
-            v
-        } else {
-            LengthDelimitedStream::<EncryptedOprfReport<BA8, BA3, BA20, _>, _>::new(input_stream)
-                .map_err(Into::<Error>::into)
-                .map_ok(|enc_reports| {
-                    iter(enc_reports.into_iter().map(|enc_report| {
-                        enc_report
-                            .decrypt(key_registry.as_ref())
-                            .map_err(Into::<Error>::into)
-                    }))
-                })
-                .try_flatten()
-                .take(sz)
-                .zip(repeat(ctx.clone()))
-                .map(|(res, ctx)| {
-                    res.map(|report| {
-                        let is_trigger = Replicated::<Boolean>::share_known_value(
-                            &ctx,
-                            match report.event_type {
-                                EventType::Source => Boolean::ZERO,
-                                EventType::Trigger => Boolean::ONE,
-                            },
-                        );
-
-                        OPRFIPAInputRow {
-                            timestamp: report.timestamp,
-                            match_key: report.match_key,
-                            is_trigger,
-                            breakdown_key: report.breakdown_key,
-                            trigger_value: report.trigger_value,
-                        }
-                    })
-                })
-                .try_collect::<Vec<_>>()
-                .await?
-        };
+        let input = read_input(
+            &ctx,
+            &config,
+            key_registry.as_ref(),
+            query_size,
+            input_stream,
+        )
+        .await?;
 
         let aws = config.attribution_window_seconds;
-        let dp_params: DpMechanism = match config.with_dp {
-            0 => DpMechanism::NoDp,
-            _ => DpMechanism::DiscreteLaplace {
-                epsilon: config.epsilon,
-            },
-        };
+        let tvc = config.trigger_value_cap;
+        let ubk = unattributed_breakdown_key(&config)?;
+        let dp_params = config
+            .dp_mechanism()
+            .map_err(|e| Error::InvalidQueryParameter(Box::new(e)))?;
+        tracing::info!("Applying DP mechanism: {dp_params:?}");
 
-        #[cfg(feature = "relaxed-dp")]
-        let padding_params = PaddingParameters::relaxed();
-        #[cfg(not(feature = "relaxed-dp"))]
-        let padding_params = PaddingParameters::default();
-        match config.per_user_credit_cap {
-            1 => oprf_ipa::<_, BA8, BA3, HV, BA20, 1, 256>(ctx, input, aws, dp_params, padding_params).await,
-            2 | 4 => oprf_ipa::<_, BA8, BA3, HV, BA20, 2, 256>(ctx, input, aws, dp_params, padding_params).await,
-            8 => oprf_ipa::<_, BA8, BA3, HV, BA20, 3, 256>(ctx, input, aws, dp_params, padding_params).await,
-            16 => oprf_ipa::<_, BA8, BA3, HV, BA20, 4, 256>(ctx, input, aws, dp_params, padding_params).await,
-            32 => oprf_ipa::<_, BA8, BA3, HV, BA20, 5, 256>(ctx, input, aws, dp_params, padding_params).await,
-            64 => oprf_ipa::<_, BA8, BA3, HV, BA20, 6, 256>(ctx, input, aws, dp_params, padding_params).await,
-            128 => oprf_ipa::<_, BA8, BA3, HV, BA20, 7, 256>(ctx, input, aws, dp_params, padding_params).await,
+        let padding_params = config.padding_params();
+        let suppression_ctx = ctx.clone();
+        // Saturating sums in attribution have `SS_BITS` bits, which caps the contribution of a
+        // user at `2^SS_BITS`.
+        let (ss_bits, values) = match config.per_user_credit_cap {
+            1 => (1, oprf_ipa::<_, BA8, BA3, HV, BA20, 1, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            2 | 4 => (2, oprf_ipa::<_, BA8, BA3, HV, BA20, 2, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            8 => (3, oprf_ipa::<_, BA8, BA3, HV, BA20, 3, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            16 => (4, oprf_ipa::<_, BA8, BA3, HV, BA20, 4, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            32 => (5, oprf_ipa::<_, BA8, BA3, HV, BA20, 5, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            64 => (6, oprf_ipa::<_, BA8, BA3, HV, BA20, 6, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            128 => (7, oprf_ipa::<_, BA8, BA3, HV, BA20, 7, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
             _ => panic!(
                 "Invalid value specified for per-user cap: {:?}. Must be one of 1, 2, 4, 8, 16, 32, 64, or 128.",
                 config.per_user_credit_cap
             ),
+        };
+
+        with_output_dp(suppression_ctx, &config, dp_params, ss_bits, values?).await
+    }
+}
+
+/// Runs OPRF IPA on a helper that is split into several shards. Each shard reads the part of
+/// the input that was sent to it. Only the leader shard returns the histogram, other shards
+/// return an empty one. See [`sharded_oprf_ipa`] for details.
+pub struct ShardedOprfIpaQuery<C, HV, R: PrivateKeyRegistry> {
+    config: IpaQueryConfig,
+    key_registry: Arc<R>,
+    phantom_data: PhantomData<(C, HV)>,
+}
+
+impl<C, HV, R: PrivateKeyRegistry> ShardedOprfIpaQuery<C, HV, R> {
+    pub fn new(config: IpaQueryConfig, key_registry: Arc<R>) -> Self {
+        Self {
+            config,
+            key_registry,
+            phantom_data: PhantomData,
         }
     }
 }
 
+#[allow(clippy::too_many_lines)]
+impl<C, HV, R> ShardedOprfIpaQuery<C, HV, R>
+where
+    C: UpgradableContext + ShardedContext + ShardedShuffle,
+    DZKPUpgraded<C>: ShardedContext,
+    HV: BooleanArray + U128Conversions,
+    R: PrivateKeyRegistry,
+    Replicated<Boolean>: Serializable + ShareKnownValue<C, Boolean>,
+    Replicated<Boolean>: BooleanProtocols<DZKPUpgraded<C>>,
+    Replicated<Boolean, 256>: BooleanProtocols<DZKPUpgraded<C>, 256>,
+    Replicated<Boolean, AGG_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, AGG_CHUNK>,
+    Replicated<Boolean, CONV_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, CONV_CHUNK>,
+    Replicated<Boolean, SORT_CHUNK>: BooleanProtocols<DZKPUpgraded<C>, SORT_CHUNK>,
+    Replicated<Fp25519, PRF_CHUNK>:
+        PrfSharing<MacUpgraded<C, Fp25519>, PRF_CHUNK, Field = Fp25519> + FromPrss,
+    Replicated<RP25519, PRF_CHUNK>:
+        Reveal<MacUpgraded<C, Fp25519>, Output = <RP25519 as Vectorizable<PRF_CHUNK>>::Array>,
+    Replicated<BA8>: BooleanArrayMul<DZKPUpgraded<C>>
+        + Reveal<DZKPUpgraded<C>, Output = <BA8 as Vectorizable<1>>::Array>,
+    Replicated<BA20>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<BA64>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<BA3>: BooleanArrayMul<DZKPUpgraded<C>>,
+    Replicated<HV>: BooleanArrayMul<DZKPUpgraded<C>> + Serializable,
+    PrfShardedIpaInputRow<BA8, BA3, BA20>: Serializable,
+    Vec<Replicated<HV>>:
+        for<'a> TransposeFrom<&'a BitDecomposed<Replicated<Boolean, 256>>, Error = LengthError>,
+    BitDecomposed<AdditiveShare<Boolean, 256>>:
+        for<'a> TransposeFrom<&'a [AdditiveShare<HV>; 256], Error = Infallible>,
+{
+    #[tracing::instrument("sharded_oprf_ipa_query", skip_all, fields(sz=%query_size))]
+    pub async fn execute(
+        self,
+        ctx: C,
+        query_size: QuerySize,
+        input_stream: BodyStream,
+    ) -> Result<DpQueryOutput<Replicated<HV>>, Error> {
+        let Self {
+            config,
+            key_registry,
+            phantom_data: _,
+        } = self;
+        tracing::info!("New sharded query: {config:?}");
+        let ctx = ctx.narrow(&IpaPrf);
+        let input = read_input(
+            &ctx,
+            &config,
+            key_registry.as_ref(),
+            query_size,
+            input_stream,
+        )
+        .await?;
+
+        let aws = config.attribution_window_seconds;
+        let tvc = config.trigger_value_cap;
+        let ubk = unattributed_breakdown_key(&config)?;
+        let dp_params = config
+            .dp_mechanism()
+            .map_err(|e| Error::InvalidQueryParameter(Box::new(e)))?;
+        tracing::info!("Applying DP mechanism: {dp_params:?}");
+
+        let padding_params = config.padding_params();
+        let suppression_ctx = ctx.clone();
+        let (ss_bits, values) = match config.per_user_credit_cap {
+            1 => (1, sharded_oprf_ipa::<_, BA8, BA3, HV, BA20, 1, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            2 | 4 => (2, sharded_oprf_ipa::<_, BA8, BA3, HV, BA20, 2, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            8 => (3, sharded_oprf_ipa::<_, BA8, BA3, HV, BA20, 3, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            16 => (4, sharded_oprf_ipa::<_, BA8, BA3, HV, BA20, 4, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            32 => (5, sharded_oprf_ipa::<_, BA8, BA3, HV, BA20, 5, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            64 => (6, sharded_oprf_ipa::<_, BA8, BA3, HV, BA20, 6, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            128 => (7, sharded_oprf_ipa::<_, BA8, BA3, HV, BA20, 7, 256>(ctx, input, aws, tvc, ubk, dp_params, padding_params).await),
+            _ => panic!(
+                "Invalid value specified for per-user cap: {:?}. Must be one of 1, 2, 4, 8, 16, 32, 64, or 128.",
+                config.per_user_credit_cap
+            ),
+        };
+
+        // Non-leader shards have no histogram, so they have nothing to suppress either.
+        with_output_dp(suppression_ctx, &config, dp_params, ss_bits, values?).await
+    }
+}
+
+/// Reads the reports sent to this helper, decrypting them unless the query uses plaintext match
+/// keys. Trigger reports must belong to the site and epoch of the query.
+async fn read_input<C, R>(
+    ctx: &C,
+    config: &IpaQueryConfig,
+    key_registry: &R,
+    query_size: QuerySize,
+    input_stream: BodyStream,
+) -> Result<Vec<OPRFIPAInputRow<BA8, BA3, BA20>>, Error>
+where
+    C: Context,
+    R: PrivateKeyRegistry,
+    Replicated<Boolean>: ShareKnownValue<C, Boolean>,
+{
+    let sz = usize::from(query_size);
+
+    if config.plaintext_match_keys {
+        let mut v = RecordsStream::<OPRFIPAInputRow<BA8, BA3, BA20>, _>::new(input_stream)
+            .try_concat()
+            .await?;
+        v.truncate(sz);
+        Ok(v)
+    } else {
+        LengthDelimitedStream::<EncryptedOprfReport<BA8, BA3, BA20, _>, _>::new(input_stream)
+            .map_err(Into::<Error>::into)
+            .map_ok(|enc_reports| {
+                iter(enc_reports.into_iter().map(|enc_report| {
+                    let report = enc_report
+                        .decrypt(key_registry)
+                        .map_err(Into::<Error>::into)?;
+                    if report.event_type == EventType::Trigger {
+                        check_trigger_report(config, &report.site_domain, report.epoch)
+                            .map_err(|e| Error::InvalidQueryParameter(Box::new(e)))?;
+                    }
+                    Ok::<_, Error>(report)
+                }))
+            })
+            .try_flatten()
+            .take(sz)
+            .zip(repeat(ctx.clone()))
+            .map(|(res, ctx)| {
+                res.map(|report| {
+                    let is_trigger = Replicated::<Boolean>::share_known_value(
+                        &ctx,
+                        match report.event_type {
+                            EventType::Source => Boolean::ZERO,
+                            EventType::Trigger => Boolean::ONE,
+                        },
+                    );
+
+                    OPRFIPAInputRow {
+                        timestamp: report.timestamp,
+                        match_key: report.match_key,
+                        is_trigger,
+                        breakdown_key: report.breakdown_key,
+                        trigger_value: report.trigger_value,
+                    }
+                })
+            })
+            .try_collect::<Vec<_>>()
+            .await
+    }
+}
+
+/// Unattributed trigger values go to the breakdown right after the last one in use.
+fn unattributed_breakdown_key(config: &IpaQueryConfig) -> Result<Option<BA8>, Error> {
+    if !config.unattributed_bucket {
+        return Ok(None);
+    }
+    if config.max_breakdown_key >= 256 {
+        return Err(Error::InvalidQueryParameter(
+            "the unattributed bucket needs max_breakdown_key to be below 256".into(),
+        ));
+    }
+    Ok(Some(BA8::truncate_from(config.max_breakdown_key)))
+}
+
+/// Describes the DP noise in `values` and, if the query asks for it, suppresses sparse buckets
+/// before the histogram is revealed, so the report collector never learns their noisy values.
+async fn with_output_dp<C, HV>(
+    ctx: C,
+    config: &IpaQueryConfig,
+    dp_params: DpMechanism,
+    ss_bits: u32,
+    values: Vec<Replicated<HV>>,
+) -> Result<DpQueryOutput<Replicated<HV>>, Error>
+where
+    C: UpgradableContext,
+    HV: BooleanArray + U128Conversions,
+    Replicated<Boolean>: BooleanProtocols<DZKPUpgraded<C>>,
+    Replicated<HV>: BooleanArrayMul<DZKPUpgraded<C>>,
+{
+    let mut histogram = NoisyHistogram {
+        mechanism: dp_params,
+        sensitivity: per_user_sensitivity(2_u32.pow(ss_bits), config.trigger_value_cap),
+        dimensions: 256,
+        suppression_threshold: None,
+    };
+    let values = match (config.suppression_threshold_stds, histogram.noise()?) {
+        (Some(threshold_stds), Some(noise)) => {
+            histogram.suppression_threshold = Some(
+                noise
+                    .suppression_threshold(threshold_stds)
+                    .map_err(|e| Error::InvalidQueryParameter(Box::new(e)))?,
+            );
+            suppress_sparse_buckets(ctx, values, &noise, threshold_stds).await?
+        }
+        _ => values,
+    };
+
+    Ok(DpQueryOutput {
+        values,
+        dp: OutputDp {
+            mechanism: dp_params,
+            histograms: vec![histogram],
+        },
+    })
+}
+
 #[cfg(all(test, unit_test))]
 mod tests {
     use std::{iter::zip, sync::Arc};
 
+    use futures::future::join_all;
     use rand::rngs::StdRng;
     use rand_core::SeedableRng;
 
     use crate::{
+        error::Error,
         ff::{
             boolean_array::{BA16, BA20, BA3, BA8},
             U128Conversions,
         },
         helpers::{
-            query::{IpaQueryConfig, QuerySize},
+            query::{DpMechanismKind, IpaQueryConfig, QuerySize},
             BodyStream,
         },
         hpke::{KeyPair, KeyRegistry},
-        query::runner::OprfIpaQuery,
+        query::runner::{OprfIpaQuery, ShardedOprfIpaQuery},
         report::{OprfReport, DEFAULT_KEY_ID},
         secret_sharing::IntoShares,
-        test_fixture::{ipa::TestRawDataRecord, join3v, Reconstruct, TestWorld},
+        test_fixture::{
+            ipa::TestRawDataRecord, join3v, Reconstruct, TestWorld, TestWorldConfig, WithShards,
+        },
     };
 
     #[tokio::test]
@@ -262,7 +457,10 @@ mod tests {
                 max_breakdown_key: 3,
                 with_dp: 0,
                 epsilon: 5.0,
+                dp_mechanism: DpMechanismKind::DiscreteLaplace,
+                delta: 1e-6,
                 plaintext_match_keys: false,
+                ..Default::default()
             };
             let input = BodyStream::from(buffer);
 
@@ -275,7 +473,175 @@ mod tests {
         .await;
 
         assert_eq!(
-            results.reconstruct()[0..3]
+            results.map(|output| output.values).reconstruct()[0..3]
+                .iter()
+                .map(U128Conversions::as_u128)
+                .collect::<Vec<u128>>(),
+            EXPECTED
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_reports_of_another_epoch() {
+        let records = vec![
+            TestRawDataRecord {
+                timestamp: 0,
+                user_id: 12345,
+                is_trigger_report: false,
+                breakdown_key: 2,
+                trigger_value: 0,
+            },
+            TestRawDataRecord {
+                timestamp: 10,
+                user_id: 12345,
+                is_trigger_report: true,
+                breakdown_key: 0,
+                trigger_value: 5,
+            },
+        ];
+        let query_size = QuerySize::try_from(records.len()).unwrap();
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let key_registry = Arc::new(KeyRegistry::<KeyPair>::random(1, &mut rng));
+        let mut buffers: [_; 3] = std::array::from_fn(|_| Vec::new());
+        let shares: [Vec<OprfReport<BA8, BA3, BA20>>; 3] = records.into_iter().share();
+        for (buf, shares) in zip(&mut buffers, shares) {
+            for share in shares {
+                share
+                    .delimited_encrypt_to(DEFAULT_KEY_ID, key_registry.as_ref(), &mut rng, buf)
+                    .unwrap();
+            }
+        }
+
+        let world = TestWorld::default();
+        let contexts = world.contexts();
+        #[allow(clippy::large_futures)]
+        let results = join_all(buffers.into_iter().zip(contexts).map(|(buffer, ctx)| {
+            // test reports are issued in epoch 1
+            let query_config = IpaQueryConfig {
+                max_breakdown_key: 3,
+                epoch: Some(2),
+                ..Default::default()
+            };
+
+            OprfIpaQuery::<_, BA16, KeyRegistry<KeyPair>>::new(
+                query_config,
+                Arc::clone(&key_registry),
+            )
+            .execute(ctx, query_size, BodyStream::from(buffer))
+        }))
+        .await;
+
+        for result in results {
+            assert!(matches!(result, Err(Error::InvalidQueryParameter(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn sharded_encrypted_reports() {
+        const SHARDS: usize = 2;
+        const EXPECTED: &[u128] = &[0, 8, 5];
+
+        let records: Vec<TestRawDataRecord> = vec![
+            TestRawDataRecord {
+                timestamp: 0,
+                user_id: 12345,
+                is_trigger_report: false,
+                breakdown_key: 2,
+                trigger_value: 0,
+            },
+            TestRawDataRecord {
+                timestamp: 4,
+                user_id: 68362,
+                is_trigger_report: false,
+                breakdown_key: 1,
+                trigger_value: 0,
+            },
+            TestRawDataRecord {
+                timestamp: 10,
+                user_id: 12345,
+                is_trigger_report: true,
+                breakdown_key: 0,
+                trigger_value: 5,
+            },
+            TestRawDataRecord {
+                timestamp: 12,
+                user_id: 68362,
+                is_trigger_report: true,
+                breakdown_key: 0,
+                trigger_value: 2,
+            },
+            TestRawDataRecord {
+                timestamp: 20,
+                user_id: 68362,
+                is_trigger_report: false,
+                breakdown_key: 1,
+                trigger_value: 0,
+            },
+            TestRawDataRecord {
+                timestamp: 30,
+                user_id: 68362,
+                is_trigger_report: true,
+                breakdown_key: 1,
+                trigger_value: 7,
+            },
+        ];
+        let query_size = QuerySize::try_from(records.len()).unwrap();
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let key_registry = Arc::new(KeyRegistry::<KeyPair>::random(1, &mut rng));
+
+        // Report `i` goes to shard `i mod SHARDS` of every helper.
+        let mut buffers: [Vec<_>; 3] =
+            std::array::from_fn(|_| (0..SHARDS).map(|_| Vec::new()).collect());
+        let shares: [Vec<OprfReport<BA8, BA3, BA20>>; 3] = records.into_iter().share();
+        for (helper_buffers, shares) in zip(&mut buffers, shares) {
+            for (i, share) in shares.into_iter().enumerate() {
+                share
+                    .delimited_encrypt_to(
+                        DEFAULT_KEY_ID,
+                        key_registry.as_ref(),
+                        &mut rng,
+                        &mut helper_buffers[i % SHARDS],
+                    )
+                    .unwrap();
+            }
+        }
+
+        let world = TestWorld::<WithShards<SHARDS>>::with_shards(TestWorldConfig::default());
+        let contexts = world.malicious_contexts();
+        #[allow(clippy::large_futures)]
+        let results = join_all(
+            zip(buffers, contexts).flat_map(|(helper_buffers, contexts)| {
+                zip(helper_buffers, contexts).map(|(buffer, ctx)| {
+                    let query_config = IpaQueryConfig {
+                        per_user_credit_cap: 8,
+                        max_breakdown_key: 3,
+                        with_dp: 0,
+                        plaintext_match_keys: false,
+                        ..Default::default()
+                    };
+
+                    ShardedOprfIpaQuery::<_, BA16, KeyRegistry<KeyPair>>::new(
+                        query_config,
+                        Arc::clone(&key_registry),
+                    )
+                    .execute(ctx, query_size, BodyStream::from(buffer))
+                })
+            }),
+        )
+        .await
+        .into_iter()
+        .map(|result| result.unwrap().values)
+        .collect::<Vec<_>>();
+
+        // Results are grouped by helper, then by shard. Only the leader shard has the histogram.
+        let leader_results: [_; 3] = std::array::from_fn(|helper| results[helper * SHARDS].clone());
+        assert!((0..3)
+            .flat_map(|helper| (1..SHARDS).map(move |shard| helper * SHARDS + shard))
+            .all(|i| results[i].is_empty()));
+        assert_eq!(
+            leader_results.reconstruct()[0..3]
                 .iter()
                 .map(U128Conversions::as_u128)
                 .collect::<Vec<u128>>(),
@@ -0,0 +1,192 @@
+use futures::TryStreamExt;
+
+use crate::{
+    error::Error,
+    ff::boolean_array::{BA20, BA3, BA32, BA5},
+    helpers::{
+        query::{NoisyHistogram, OutputDp, QuerySize, ReachFrequencyQueryParams},
+        BodyStream, RecordsStream,
+    },
+    protocol::{
+        context::{Context, SemiHonestContext},
+        ipa_prf::{
+            prf_sharding::reach_frequency::{reach_frequency, DP_RELEASES, MAX_FREQUENCY_CAP},
+            OPRFIPAInputRow,
+        },
+        step::ProtocolStep::IpaPrf,
+    },
+    query::DpQueryOutput,
+    secret_sharing::replicated::semi_honest::AdditiveShare as Replicated,
+};
+
+/// Number of breakdowns the reach is computed for.
+pub const BREAKDOWN_COUNT: usize = 32;
+
+/// Bound on the contribution of a single user to the reach, which is one per breakdown.
+const SS_BITS: usize = 5;
+
+pub type ReachFrequencyQueryInputRow = OPRFIPAInputRow<BA5, BA3, BA20>;
+
+/// Computes the DP-noised reach of every breakdown, followed by the DP-noised frequency
+/// distribution of impressions per user.
+///
+/// Only plaintext match keys are supported, so this query only runs with semi-honest security.
+pub struct ReachFrequencyQuery {
+    config: ReachFrequencyQueryParams,
+}
+
+impl ReachFrequencyQuery {
+    pub fn new(config: ReachFrequencyQueryParams) -> Self {
+        Self { config }
+    }
+
+    #[tracing::instrument("reach_frequency_query", skip_all, fields(sz=%query_size))]
+    pub async fn execute(
+        self,
+        ctx: SemiHonestContext<'_>,
+        query_size: QuerySize,
+        input_stream: BodyStream,
+    ) -> Result<DpQueryOutput<Replicated<BA32>>, Error> {
+        let Self { config } = self;
+        tracing::info!("New query: {config:?}");
+        let ctx = ctx.narrow(&IpaPrf);
+        let sz = usize::from(query_size);
+
+        let mut input = RecordsStream::<ReachFrequencyQueryInputRow, _>::new(input_stream)
+            .try_concat()
+            .await?;
+        input.truncate(sz);
+
+        let dp_params = config
+            .dp_mechanism()
+            .map_err(|e| Error::InvalidQueryParameter(Box::new(e)))?;
+        tracing::info!("Applying DP mechanism: {dp_params:?}");
+
+        let padding_params = config.padding_params();
+
+        let values = reach_frequency::<
+            _,
+            BA5,
+            BA3,
+            BA32,
+            BA20,
+            SS_BITS,
+            BREAKDOWN_COUNT,
+            MAX_FREQUENCY_CAP,
+        >(
+            ctx,
+            input,
+            usize::try_from(config.frequency_cap).unwrap(),
+            dp_params,
+            padding_params,
+        )
+        .await?;
+
+        Ok(DpQueryOutput {
+            values,
+            dp: OutputDp {
+                mechanism: dp_params,
+                histograms: vec![
+                    NoisyHistogram {
+                        mechanism: dp_params.split_budget(DP_RELEASES),
+                        sensitivity: 1 << SS_BITS,
+                        dimensions: u32::try_from(BREAKDOWN_COUNT).unwrap(),
+                        suppression_threshold: None,
+                    },
+                    // every user is counted once in the frequency distribution
+                    NoisyHistogram {
+                        mechanism: dp_params.split_budget(DP_RELEASES),
+                        sensitivity: 1,
+                        dimensions: u32::try_from(MAX_FREQUENCY_CAP).unwrap(),
+                        suppression_threshold: None,
+                    },
+                ],
+            },
+        })
+    }
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use std::iter::zip;
+
+    use generic_array::GenericArray;
+    use typenum::Unsigned;
+
+    use super::{ReachFrequencyQuery, ReachFrequencyQueryInputRow, BREAKDOWN_COUNT};
+    use crate::{
+        ff::{Serializable, U128Conversions},
+        helpers::{
+            query::{QuerySize, ReachFrequencyQueryParams},
+            BodyStream,
+        },
+        secret_sharing::IntoShares,
+        test_fixture::{
+            ipa::TestRawDataRecord, join3v, reach_frequency::reach_frequency_in_the_clear,
+            Reconstruct, TestWorld,
+        },
+    };
+
+    #[tokio::test]
+    async fn plaintext_match_keys() {
+        let record =
+            |user_id: u64, is_trigger_report: bool, breakdown_key: u32| TestRawDataRecord {
+                timestamp: 0,
+                user_id,
+                is_trigger_report,
+                breakdown_key,
+                trigger_value: 0,
+            };
+        let records = vec![
+            record(12345, false, 1),
+            record(68362, false, 1),
+            record(12345, false, 4),
+            record(12345, true, 0),
+            record(68362, false, 1),
+            record(77777, false, 30),
+        ];
+        let frequency_cap = 2;
+        let expected = reach_frequency_in_the_clear(&records, BREAKDOWN_COUNT, frequency_cap);
+
+        let query_size = QuerySize::try_from(records.len()).unwrap();
+        let row_size = <ReachFrequencyQueryInputRow as Serializable>::Size::USIZE;
+        let mut buffers: [_; 3] = std::array::from_fn(|_| Vec::new());
+        let shares: [Vec<ReachFrequencyQueryInputRow>; 3] = records.into_iter().share();
+        for (buf, shares) in zip(&mut buffers, shares) {
+            for share in shares {
+                let mut row = GenericArray::default();
+                share.serialize(&mut row);
+                assert_eq!(row.len(), row_size);
+                buf.extend_from_slice(&row);
+            }
+        }
+
+        let world = TestWorld::default();
+        let contexts = world.contexts();
+        #[allow(clippy::large_futures)]
+        let results = join3v(buffers.into_iter().zip(contexts).map(|(buffer, ctx)| {
+            let query_config = ReachFrequencyQueryParams {
+                frequency_cap: u32::try_from(frequency_cap).unwrap(),
+                with_dp: 0,
+                epsilon: 5.0,
+                ..Default::default()
+            };
+            ReachFrequencyQuery::new(query_config).execute(
+                ctx,
+                query_size,
+                BodyStream::from(buffer),
+            )
+        }))
+        .await;
+
+        assert_eq!(
+            results
+                .map(|output| output.values)
+                .reconstruct()
+                .iter()
+                .map(|v| u32::try_from(v.as_u128()).unwrap())
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+}
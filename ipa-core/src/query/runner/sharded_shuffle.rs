@@ -0,0 +1,90 @@
+use futures::TryStreamExt;
+
+use crate::{
+    error::Error,
+    ff::boolean_array::BA32,
+    helpers::{BodyStream, Gateway, RecordsStream},
+    protocol::{
+        context::{Context, ShardedSemiHonestContext},
+        ipa_prf::shuffle::ShardedShuffle,
+        prss::Endpoint as PrssEndpoint,
+        step::ProtocolStep,
+    },
+    query::runner::QueryResult,
+    secret_sharing::replicated::semi_honest::AdditiveShare as Replicated,
+};
+
+/// Shuffles the input across all the shards of this helper. This query exists to exercise both
+/// helper-to-helper and shard-to-shard communication channels in sharded deployments.
+///
+/// Every shard returns its part of the shuffled input. Shards do not necessarily return the same
+/// number of rows they received.
+#[tracing::instrument("sharded_shuffle", skip_all)]
+pub async fn execute_sharded_shuffle<'a>(
+    prss: &'a PrssEndpoint,
+    gateway: &'a Gateway,
+    input: BodyStream,
+) -> QueryResult {
+    let ctx = ShardedSemiHonestContext::new_sharded(prss, gateway, gateway.shard_config())
+        .narrow(&ProtocolStep::ShardedShuffle);
+    Ok(Box::new(execute_internal(ctx, input).await?))
+}
+
+async fn execute_internal<C: ShardedShuffle>(
+    ctx: C,
+    input_stream: BodyStream,
+) -> Result<Vec<Replicated<BA32>>, Error> {
+    let input = RecordsStream::<Replicated<BA32>, _>::new(input_stream)
+        .try_concat()
+        .await?;
+
+    ctx.sharded_shuffle::<Replicated<BA32>, _>(input).await
+}
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use generic_array::GenericArray;
+    use typenum::Unsigned;
+
+    use super::*;
+    use crate::{
+        ff::{Serializable, U128Conversions},
+        test_executor::run,
+        test_fixture::{Reconstruct, Runner, TestWorld, TestWorldConfig, WithShards},
+    };
+
+    fn to_body_stream(shares: Vec<Replicated<BA32>>) -> BodyStream {
+        const SIZE: usize = <Replicated<BA32> as Serializable>::Size::USIZE;
+        shares
+            .into_iter()
+            .flat_map(|share| {
+                let mut slice = [0_u8; SIZE];
+                share.serialize(GenericArray::from_mut_slice(&mut slice));
+                slice
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    #[test]
+    fn permutes_input() {
+        run(|| async {
+            const SHARDS: usize = 3;
+            let world = TestWorld::<WithShards<SHARDS>>::with_shards(TestWorldConfig::default());
+            let input = (0..20_u128).map(BA32::truncate_from).collect::<Vec<_>>();
+
+            let mut result = world
+                .semi_honest(input.clone().into_iter(), |ctx, shares| async move {
+                    execute_internal(ctx, to_body_stream(shares)).await.unwrap()
+                })
+                .await
+                .into_iter()
+                .flat_map(|shard_result| shard_result.reconstruct())
+                .collect::<Vec<_>>();
+
+            assert_ne!(input, result);
+            result.sort_by_key(U128Conversions::as_u128);
+            assert_eq!(input, result);
+        });
+    }
+}
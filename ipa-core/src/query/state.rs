@@ -33,6 +33,8 @@ pub enum QueryStatus {
     AwaitingCompletion,
     /// Query has finished and results are available.
     Completed,
+    /// Query has finished with an error. Requesting its results returns that error.
+    Failed,
 }
 
 impl From<&QueryState> for QueryStatus {
@@ -43,7 +45,8 @@ impl From<&QueryState> for QueryStatus {
             QueryState::AwaitingInputs(_, _, _) => QueryStatus::AwaitingInputs,
             QueryState::Running(_) => QueryStatus::Running,
             QueryState::AwaitingCompletion => QueryStatus::AwaitingCompletion,
-            QueryState::Completed(_) => QueryStatus::Completed,
+            QueryState::Completed(Ok(_)) => QueryStatus::Completed,
+            QueryState::Completed(Err(_)) => QueryStatus::Failed,
         }
     }
 }
@@ -226,3 +229,21 @@ impl Drop for RemoveQuery<'_> {
         }
     }
 }
+
+#[cfg(all(test, unit_test))]
+mod tests {
+    use super::{QueryState, QueryStatus};
+    use crate::{error::Error, ff::Fp31};
+
+    #[test]
+    fn completed_status_reflects_result() {
+        assert_eq!(
+            QueryStatus::Completed,
+            QueryStatus::from(&QueryState::Completed(Ok(Box::new(Vec::<Fp31>::new()))))
+        );
+        assert_eq!(
+            QueryStatus::Failed,
+            QueryStatus::from(&QueryState::Completed(Err(Error::Internal)))
+        );
+    }
+}
@@ -200,7 +200,27 @@ pub struct EncryptedOprfReportStreams {
 ///  `EncryptedOprfReports` formated at newline delimited hex.
 impl From<[&PathBuf; 3]> for EncryptedOprfReportStreams {
     fn from(files: [&PathBuf; 3]) -> Self {
-        let mut buffers: [_; 3] = std::array::from_fn(|_| Vec::new());
+        Self::from_files_sharded(files, 1).pop().unwrap()
+    }
+}
+
+impl EncryptedOprfReportStreams {
+    /// Reads `EncryptedOprfReports` from 3 files formatted as newline delimited hex, and splits
+    /// them round-robin between `shard_count` shards of every helper. Report `i` goes to shard
+    /// `i mod shard_count`, so that every helper gets its share of a given report on the same
+    /// shard.
+    ///
+    /// Reports are encrypted, so there is no way to split them by any of their fields.
+    ///
+    /// ## Panics
+    /// If any of the files cannot be read or is corrupt, if they do not have the same number of
+    /// reports, or if `shard_count` is zero.
+    #[must_use]
+    pub fn from_files_sharded(files: [&PathBuf; 3], shard_count: usize) -> Vec<Self> {
+        assert!(shard_count > 0, "at least one shard is required");
+        let mut buffers: Vec<[_; 3]> = (0..shard_count)
+            .map(|_| std::array::from_fn(|_| Vec::new()))
+            .collect();
         let mut query_sizes: [usize; 3] = [0, 0, 0];
         for (i, path) in files.iter().enumerate() {
             let file =
@@ -212,13 +232,14 @@ impl From<[&PathBuf; 3]> for EncryptedOprfReportStreams {
                         .trim(),
                 )
                 .expect("Unable to read line. {file:?} is likely corrupt");
-                buffers[i].put_u16_le(
+                let buffer = &mut buffers[query_sizes[i] % shard_count][i];
+                buffer.put_u16_le(
                     encrypted_report_bytes
                         .len()
                         .try_into()
                         .expect("Unable to read line. {file:?} is likely corrupt"),
                 );
-                buffers[i].put_slice(encrypted_report_bytes.as_slice());
+                buffer.put_slice(encrypted_report_bytes.as_slice());
                 query_sizes[i] += 1;
             }
         }
@@ -227,11 +248,17 @@ impl From<[&PathBuf; 3]> for EncryptedOprfReportStreams {
         assert_eq!(query_sizes[0], query_sizes[1]);
         assert_eq!(query_sizes[1], query_sizes[2]);
 
-        Self {
-            streams: buffers.map(BodyStream::from),
-            // without loss of generality, set query length to length of first input size
-            query_size: query_sizes[0],
-        }
+        // without loss of generality, set query length to length of first input size
+        let query_size = query_sizes[0];
+        buffers
+            .into_iter()
+            .enumerate()
+            .map(|(shard, buffers)| Self {
+                streams: buffers.map(BodyStream::from),
+                query_size: query_size / shard_count
+                    + usize::from(shard < query_size % shard_count),
+            })
+            .collect()
     }
 }
 // TODO: If we are parsing reports from CSV files, we may also want an owned version of EncryptedReport.
@@ -614,10 +614,16 @@ impl_transpose_shares_bool_to_ba!(BA32, 32, 256, test_transpose_shares_bool_to_b
 impl_transpose_shares_bool_to_ba_small!(BA8, 8, 32, test_transpose_shares_bool_to_ba_8x32);
 // added to support HV = BA32 to hold results when adding Binomial noise
 impl_transpose_shares_bool_to_ba_small!(BA32, 32, 32, test_transpose_shares_bool_to_ba_32x32);
+// Usage: feature-label dot product query output. M = HV bits, N = number of features.
+impl_transpose_shares_bool_to_ba!(BA32, 32, 16, test_transpose_shares_bool_to_ba_32x16);
 
 // Usage: Aggregation output tests
 impl_transpose_shares_bool_to_ba_small!(BA8, 8, 8, test_transpose_shares_bool_to_ba_8x8);
 
+// Usage: reach and frequency output. M = HV bits, N = number of frequency buckets.
+impl_transpose_shares_bool_to_ba_small!(BA16, 16, 8, test_transpose_shares_bool_to_ba_16x8);
+impl_transpose_shares_bool_to_ba_small!(BA32, 32, 8, test_transpose_shares_bool_to_ba_32x8);
+
 // Usage: Binomial Noise Gen
 impl_transpose_shares_bool_to_ba!(BA16, 16, 16, test_transpose_shares_bool_to_ba_16x16);
 
@@ -795,6 +801,10 @@ impl_transpose_shares_ba_to_bool!(BA32, 32, 32, test_transpose_shares_ba_to_bool
 impl_transpose_shares_ba_to_bool!(BA16, 256, 16, test_transpose_shares_ba_to_bool_256x16);
 impl_transpose_shares_ba_to_bool!(BA16, 32, 16, test_transpose_shares_ba_to_bool_32x16);
 impl_transpose_shares_ba_to_bool_small!(BA8, 16, 8, test_transpose_shares_ba_to_bool_16x8);
+impl_transpose_shares_ba_to_bool!(BA32, 16, 32, test_transpose_shares_ba_to_bool_16x32);
+// M = number of frequency buckets of the reach and frequency query, N = OV bits.
+impl_transpose_shares_ba_to_bool_small!(BA16, 8, 16, test_transpose_shares_ba_to_bool_8x16);
+impl_transpose_shares_ba_to_bool_small!(BA32, 8, 32, test_transpose_shares_ba_to_bool_8x32);
 
 // Special transpose used for "aggregation intermediate". See [`aggregate_contributions`] for
 // additional details.
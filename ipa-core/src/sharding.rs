@@ -34,6 +34,12 @@ impl ShardedHelperIdentity {
     }
 }
 
+impl Display for ShardedHelperIdentity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/shard {}", self.helper_identity, self.shard_index)
+    }
+}
+
 /// A unique zero-based index of the helper shard.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ShardIndex(pub u32);
@@ -150,6 +156,17 @@ pub trait ShardConfiguration {
     /// same number of shards.
     fn shard_count(&self) -> ShardIndex;
 
+    /// Returns the index of the leader shard. Protocols that produce a single result for the
+    /// whole helper collect it on this shard.
+    fn leader(&self) -> ShardIndex {
+        ShardIndex::FIRST
+    }
+
+    /// Returns `true` if the current shard is the leader shard.
+    fn is_leader(&self) -> bool {
+        self.shard_id() == self.leader()
+    }
+
     /// Returns an iterator that yields shard indices for all shards present in the system, except
     /// this one. Shards are yielded in ascending order.
     ///
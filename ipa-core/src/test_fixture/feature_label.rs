@@ -0,0 +1,102 @@
+use std::{collections::HashMap, iter::zip};
+
+use crate::{
+    ff::{
+        boolean::Boolean,
+        boolean_array::{BooleanArray, BA64},
+        U128Conversions,
+    },
+    protocol::ipa_prf::prf_sharding::feature_label_dot_product::FeatureLabelInputRow,
+    rand::Rng,
+    secret_sharing::{
+        replicated::{semi_honest::AdditiveShare as Replicated, ReplicatedSecretSharing},
+        IntoShares,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestFeatureLabelRecord {
+    pub timestamp: u64,
+    pub user_id: u64,
+    pub is_trigger_report: bool,
+    /// Ignored for trigger reports.
+    pub features: Vec<u32>,
+}
+
+/// Executes the feature-label dot product in the clear, that is without any MPC helpers involved
+/// in the computation. Useful to validate the output of the MPC protocol, ignoring the DP noise
+/// it may add.
+///
+/// For every user, the most recent source report that is followed by a trigger report contributes
+/// its features to the output. A trigger report with the same timestamp as a source report is
+/// considered to come after it.
+///
+/// ## Panics
+/// If a source report does not have exactly `feature_count` features.
+#[must_use]
+pub fn feature_label_dot_product_in_the_clear(
+    input: &[TestFeatureLabelRecord],
+    feature_count: usize,
+) -> Vec<u32> {
+    let mut user_events = HashMap::<_, Vec<_>>::new();
+    for row in input {
+        user_events.entry(row.user_id).or_default().push(row);
+    }
+
+    let mut output = vec![0; feature_count];
+    for records_per_user in user_events.values_mut() {
+        records_per_user.sort_by_key(|r| (r.timestamp, r.is_trigger_report));
+
+        let mut seen_trigger = false;
+        for record in records_per_user.iter().rev() {
+            if record.is_trigger_report {
+                seen_trigger = true;
+            } else if seen_trigger {
+                assert_eq!(record.features.len(), feature_count);
+                zip(output.iter_mut(), &record.features).for_each(|(acc, f)| *acc += f);
+                break;
+            }
+        }
+    }
+
+    output
+}
+
+impl<FV, TS, const B: usize> IntoShares<FeatureLabelInputRow<FV, TS, B>> for TestFeatureLabelRecord
+where
+    FV: BooleanArray + U128Conversions + IntoShares<Replicated<FV>>,
+    TS: BooleanArray + U128Conversions + IntoShares<Replicated<TS>>,
+{
+    fn share_with<R: Rng>(self, rng: &mut R) -> [FeatureLabelInputRow<FV, TS, B>; 3] {
+        let is_trigger = Replicated::new(
+            Boolean::from(self.is_trigger_report),
+            Boolean::from(self.is_trigger_report),
+        );
+        let match_key = BA64::try_from(u128::from(self.user_id))
+            .unwrap()
+            .share_with(rng);
+        let timestamp = TS::try_from(u128::from(self.timestamp))
+            .unwrap()
+            .share_with(rng);
+        let features: [FV; B] = if self.is_trigger_report {
+            [FV::ZERO; B]
+        } else {
+            assert_eq!(self.features.len(), B);
+            std::array::from_fn(|i| FV::try_from(u128::from(self.features[i])).unwrap())
+        };
+        let feature_vector: [[Replicated<FV>; B]; 3] = features.share_with(rng);
+
+        zip(zip(match_key, timestamp), feature_vector)
+            .map(
+                |((match_key, timestamp), feature_vector)| FeatureLabelInputRow {
+                    match_key,
+                    is_trigger: is_trigger.clone(),
+                    timestamp,
+                    feature_vector,
+                },
+            )
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap()
+    }
+}
@@ -9,8 +9,11 @@ use crate::{
     helpers::query::{DpMechanism, IpaQueryConfig},
     protocol::{
         dp::NoiseParams,
-        ipa_prf::oprf_padding::{insecure::OPRFPaddingDp, PaddingParameters},
-        ipa_prf::OPRFIPAInputRow,
+        ipa_prf::oprf_padding::{
+            insecure::{DiscreteGaussianDp, OPRFPaddingDp},
+            PaddingParameters,
+        },
+        ipa_prf::{trigger_value_cap::per_user_sensitivity, OPRFIPAInputRow},
     },
     secret_sharing::{
         replicated::{
@@ -70,32 +73,27 @@ fn insert_sorted(user_records: &mut Vec<TestRawDataRecord>, record: TestRawDataR
 ///
 /// ## Panics
 /// Will panic if you run in on Intel 80286 or any other 16 bit hardware.
+#[must_use]
 pub fn ipa_in_the_clear(
     input: &[TestRawDataRecord],
     per_user_cap: u32,
     attribution_window: Option<NonZeroU32>,
     max_breakdown: u32,
+    unattributed_bucket: bool,
     order: &CappingOrder,
 ) -> Vec<u32> {
-    // build a view that is convenient for attribution. match key -> events sorted by timestamp
-    // that is more memory intensive, but should be faster to compute. We can always opt-out and
-    // execute IPA in place
-    let mut user_events = HashMap::new();
-    for row in input {
-        insert_sorted(
-            user_events.entry(row.user_id).or_insert_with(Vec::new),
-            row.clone(),
-        );
-    }
-
-    let mut breakdowns = vec![0u32; usize::try_from(max_breakdown).unwrap()];
-    for records_per_user in user_events.values() {
+    // The unattributed bucket, if any, comes right after the last breakdown.
+    let unattributed_breakdown = unattributed_bucket.then_some(max_breakdown);
+    let mut breakdowns =
+        vec![0u32; usize::try_from(max_breakdown).unwrap() + usize::from(unattributed_bucket)];
+    for records_per_user in user_events(input).values() {
         let rev_records = records_per_user.iter().rev();
         update_expected_output_for_user(
             rev_records,
             &mut breakdowns,
             per_user_cap,
             attribution_window,
+            unattributed_breakdown,
             order,
         );
     }
@@ -103,6 +101,20 @@ pub fn ipa_in_the_clear(
     breakdowns
 }
 
+/// Builds a view of the input that is convenient for attribution: match key -> events sorted by
+/// timestamp. That is more memory intensive, but should be faster to compute. We can always opt-out
+/// and execute IPA in place.
+pub(super) fn user_events(input: &[TestRawDataRecord]) -> HashMap<u64, Vec<TestRawDataRecord>> {
+    let mut user_events = HashMap::new();
+    for row in input {
+        insert_sorted(
+            user_events.entry(row.user_id).or_insert_with(Vec::new),
+            row.clone(),
+        );
+    }
+    user_events
+}
+
 pub enum CappingOrder {
     CapOldestFirst,
     CapMostRecentFirst,
@@ -116,8 +128,70 @@ fn update_expected_output_for_user<'a, I: IntoIterator<Item = &'a TestRawDataRec
     expected_results: &mut [u32],
     per_user_cap: u32,
     attribution_window_seconds: Option<NonZeroU32>,
+    unattributed_breakdown: Option<u32>,
     order: &CappingOrder,
 ) {
+    let credited_triggers = credited_triggers(
+        records_for_user,
+        attribution_window_seconds,
+        unattributed_breakdown,
+    );
+
+    match order {
+        CappingOrder::CapOldestFirst => {
+            update_breakdowns(credited_triggers, expected_results, per_user_cap);
+        }
+        CappingOrder::CapMostRecentFirst => update_breakdowns(
+            credited_triggers.into_iter().rev(),
+            expected_results,
+            per_user_cap,
+        ),
+    }
+}
+
+/// Pairs the value of every trigger report of a user with the breakdown it is credited to. That
+/// is the breakdown of the source report it is attributed to (see [`attributed_triggers`]), or
+/// `unattributed_breakdown` if it is not attributed. Trigger reports that are not attributed are
+/// skipped if there is no `unattributed_breakdown`.
+///
+/// Assumes records all belong to the same user, and are in reverse chronological order. The output
+/// is in reverse chronological order too.
+fn credited_triggers<'a, I: IntoIterator<Item = &'a TestRawDataRecord>>(
+    records_for_user: I,
+    attribution_window_seconds: Option<NonZeroU32>,
+    unattributed_breakdown: Option<u32>,
+) -> Vec<(u32, u32)> {
+    let records = records_for_user.into_iter().collect::<Vec<_>>();
+    let mut attributed = attributed_triggers(records.iter().copied(), attribution_window_seconds)
+        .into_iter()
+        .peekable();
+    let mut credited_triggers = Vec::new();
+    for record in records.iter().filter(|record| record.is_trigger_report) {
+        match attributed.peek() {
+            Some((trigger_report, source_report)) if std::ptr::eq(*trigger_report, *record) => {
+                credited_triggers.push((record.trigger_value, source_report.breakdown_key));
+                attributed.next();
+            }
+            _ => {
+                if let Some(breakdown) = unattributed_breakdown {
+                    credited_triggers.push((record.trigger_value, breakdown));
+                }
+            }
+        }
+    }
+
+    credited_triggers
+}
+
+/// Pairs every trigger report of a user with the most recent source report that precedes it,
+/// skipping trigger reports that fall outside of the attribution window.
+///
+/// Assumes records all belong to the same user, and are in reverse chronological order. The output
+/// is in reverse chronological order too.
+pub(super) fn attributed_triggers<'a, I: IntoIterator<Item = &'a TestRawDataRecord>>(
+    records_for_user: I,
+    attribution_window_seconds: Option<NonZeroU32>,
+) -> Vec<(&'a TestRawDataRecord, &'a TestRawDataRecord)> {
     let within_window = |value: u64| -> bool {
         if let Some(window) = attribution_window_seconds {
             value <= u64::from(window.get())
@@ -149,28 +223,18 @@ fn update_expected_output_for_user<'a, I: IntoIterator<Item = &'a TestRawDataRec
         }
     }
 
-    match order {
-        CappingOrder::CapOldestFirst => {
-            update_breakdowns(attributed_triggers, expected_results, per_user_cap);
-        }
-        CappingOrder::CapMostRecentFirst => update_breakdowns(
-            attributed_triggers.into_iter().rev(),
-            expected_results,
-            per_user_cap,
-        ),
-    }
+    attributed_triggers
 }
 
-fn update_breakdowns<'a, I>(attributed_triggers: I, expected_results: &mut [u32], per_user_cap: u32)
+fn update_breakdowns<I>(credited_triggers: I, expected_results: &mut [u32], per_user_cap: u32)
 where
-    I: IntoIterator<Item = (&'a TestRawDataRecord, &'a TestRawDataRecord)>,
+    I: IntoIterator<Item = (u32, u32)>,
 {
     let mut total_contribution = 0;
-    for (trigger_report, source_report) in attributed_triggers {
+    for (trigger_value, breakdown_key) in credited_triggers {
         let delta_to_per_user_cap = per_user_cap - total_contribution;
-        let capped_contribution =
-            std::cmp::min(delta_to_per_user_cap, trigger_report.trigger_value);
-        let bk: usize = source_report.breakdown_key.try_into().unwrap();
+        let capped_contribution = std::cmp::min(delta_to_per_user_cap, trigger_value);
+        let bk: usize = breakdown_key.try_into().unwrap();
         expected_results[bk] += capped_contribution;
         total_contribution += capped_contribution;
     }
@@ -202,12 +266,11 @@ pub async fn test_oprf_ipa<F>(
     };
 
     let aws = config.attribution_window_seconds;
-    let dp_params: DpMechanism = match config.with_dp {
-        0 => DpMechanism::NoDp,
-        _ => DpMechanism::DiscreteLaplace {
-            epsilon: config.epsilon,
-        },
-    };
+    let tvc = config.trigger_value_cap;
+    let ubk = config
+        .unattributed_bucket
+        .then_some(config.max_breakdown_key);
+    let dp_params = config.dp_mechanism().unwrap();
     let padding_params = PaddingParameters::default();
     let result: Vec<_> = if config.per_user_credit_cap == 256 {
         // Note that many parameters are different in this case, not just the credit cap.
@@ -215,7 +278,7 @@ pub async fn test_oprf_ipa<F>(
         world.semi_honest(
             records.into_iter(),
             |ctx, input_rows: Vec<OPRFIPAInputRow<BA5, BA8, BA20>>| async move {
-                oprf_ipa::<_, BA5, BA8, BA32, BA20, 8, 32>(ctx, input_rows, aws, dp_params, padding_params)
+                oprf_ipa::<_, BA5, BA8, BA32, BA20, 8, 32>(ctx, input_rows, aws, tvc, ubk.map(BA5::truncate_from), dp_params, padding_params)
                     .await
                     .unwrap()
             },
@@ -227,19 +290,19 @@ pub async fn test_oprf_ipa<F>(
             |ctx, input_rows: Vec<OPRFIPAInputRow<BA8, BA3, BA20>>| async move {
 
                 match config.per_user_credit_cap {
-                    8 => oprf_ipa::<_, BA8, BA3, BA32, BA20, 3, 256>(ctx, input_rows, aws, dp_params, padding_params)
+                    8 => oprf_ipa::<_, BA8, BA3, BA32, BA20, 3, 256>(ctx, input_rows, aws, tvc, ubk.map(BA8::truncate_from), dp_params, padding_params)
                     .await
                     .unwrap(),
-                    16 => oprf_ipa::<_, BA8, BA3, BA32, BA20, 4, 256>(ctx, input_rows, aws, dp_params, padding_params)
+                    16 => oprf_ipa::<_, BA8, BA3, BA32, BA20, 4, 256>(ctx, input_rows, aws, tvc, ubk.map(BA8::truncate_from), dp_params, padding_params)
                     .await
                     .unwrap(),
-                    32 => oprf_ipa::<_, BA8, BA3, BA32, BA20, 5, 256>(ctx, input_rows, aws, dp_params, padding_params)
+                    32 => oprf_ipa::<_, BA8, BA3, BA32, BA20, 5, 256>(ctx, input_rows, aws, tvc, ubk.map(BA8::truncate_from), dp_params, padding_params)
                     .await
                     .unwrap(),
-                    64 => oprf_ipa::<_, BA8, BA3, BA32, BA20, 6, 256>(ctx, input_rows, aws, dp_params, padding_params)
+                    64 => oprf_ipa::<_, BA8, BA3, BA32, BA20, 6, 256>(ctx, input_rows, aws, tvc, ubk.map(BA8::truncate_from), dp_params, padding_params)
                     .await
                     .unwrap(),
-                    128 => oprf_ipa::<_, BA8, BA3, BA32, BA20, 7, 256>(ctx, input_rows, aws, dp_params, padding_params)
+                    128 => oprf_ipa::<_, BA8, BA3, BA32, BA20, 7, 256>(ctx, input_rows, aws, tvc, ubk.map(BA8::truncate_from), dp_params, padding_params)
                     .await
                     .unwrap(),
                     _ =>
@@ -262,18 +325,19 @@ pub async fn test_oprf_ipa<F>(
     //TODO(richaj): To be removed once the function supports non power of 2 breakdowns
     let _ = result.split_off(expected_results.len());
 
+    let sensitivity = per_user_sensitivity(config.per_user_credit_cap, tvc);
     match dp_params {
         DpMechanism::NoDp => {
             assert_eq!(result, expected_results);
         }
-        DpMechanism::Binomial { epsilon } => {
+        DpMechanism::Binomial { epsilon, delta } => {
             let noise_params = NoiseParams {
                 epsilon,
-                delta: 1e-6,
-                per_user_credit_cap: config.per_user_credit_cap,
-                ell_1_sensitivity: f64::from(config.per_user_credit_cap),
-                ell_2_sensitivity: f64::from(config.per_user_credit_cap),
-                ell_infty_sensitivity: f64::from(config.per_user_credit_cap),
+                delta,
+                per_user_credit_cap: sensitivity,
+                ell_1_sensitivity: f64::from(sensitivity),
+                ell_2_sensitivity: f64::from(sensitivity),
+                ell_infty_sensitivity: f64::from(sensitivity),
                 dimensions: 256.0, // matches hard coded dimension in oprf_ipa.rs/execute
                 ..Default::default()
             };
@@ -288,9 +352,9 @@ pub async fn test_oprf_ipa<F>(
                 );
             }
         }
-        DpMechanism::DiscreteLaplace { epsilon } => {
+        DpMechanism::DiscreteLaplace { epsilon, delta } => {
             let truncated_discrete_laplace =
-                OPRFPaddingDp::new(epsilon, 1e-6, config.per_user_credit_cap).unwrap();
+                OPRFPaddingDp::new(epsilon, delta, sensitivity).unwrap();
 
             let (_, std) = truncated_discrete_laplace.mean_and_std();
             let tolerance_factor = 12.0;
@@ -314,6 +378,30 @@ pub async fn test_oprf_ipa<F>(
                 );
             }
         }
+        DpMechanism::DiscreteGaussian { epsilon, delta } => {
+            let discrete_gaussian =
+                DiscreteGaussianDp::new(epsilon, delta, f64::from(sensitivity)).unwrap();
+
+            // Each of the three pairs of helpers adds an independent sample.
+            let std = 3.0_f64.sqrt() * discrete_gaussian.std();
+            let tolerance_factor = 12.0;
+
+            assert_eq!(result.len(), expected_results.len());
+
+            for (&sample, &expected) in std::iter::zip(result.iter(), expected_results.iter()) {
+                // Negative noise wraps around in BA32, see the Laplace case above.
+                let sample_shifted = if f64::from(sample) > 2.0_f64.powf(31.0) {
+                    f64::from(sample) - 2.0_f64.powf(32.0)
+                } else {
+                    f64::from(sample)
+                };
+                assert!(
+                    (sample_shifted - f64::from(expected)).abs() < tolerance_factor * std,
+                    "DP result was not within {tolerance_factor} times the standard deviation of a\
+                    Discrete Gaussian from what was expected"
+                );
+            }
+        }
     }
 }
 
@@ -397,4 +485,36 @@ mod tests {
             assert_ne!(counts6[i], 0);
         }
     }
+
+    #[test]
+    fn unattributed_bucket_in_the_clear() {
+        let record = |timestamp, user_id, is_trigger_report, breakdown_key, trigger_value| {
+            TestRawDataRecord {
+                timestamp,
+                user_id,
+                is_trigger_report,
+                breakdown_key,
+                trigger_value,
+            }
+        };
+        let input = [
+            record(0, 1, true, 0, 3),
+            record(10, 1, false, 1, 0),
+            record(20, 1, true, 0, 2),
+            record(0, 2, true, 0, 4),
+            record(0, 3, false, 0, 0),
+            record(100, 3, true, 0, 5),
+        ];
+
+        let window = NonZeroU32::new(50);
+        let order = CappingOrder::CapOldestFirst;
+        assert_eq!(
+            ipa_in_the_clear(&input, 8, window, 2, false, &order),
+            vec![0, 2]
+        );
+        assert_eq!(
+            ipa_in_the_clear(&input, 8, window, 2, true, &order),
+            vec![0, 2, 3 + 4 + 5]
+        );
+    }
 }
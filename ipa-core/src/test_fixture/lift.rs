@@ -0,0 +1,44 @@
+use std::num::NonZeroU32;
+
+use crate::test_fixture::ipa::{attributed_triggers, user_events, TestRawDataRecord};
+
+/// Executes the lift query in the clear, that is without any MPC helpers involved in the
+/// computation. Useful to validate the output of the MPC protocol, ignoring the DP noise it may
+/// add.
+///
+/// Bit 0 of the breakdown key of a source report is its treatment arm (0 for control, 1 for
+/// test). Trigger reports are attributed and capped in the same way as in IPA, and then
+/// aggregated by the arm of the source report they were attributed to. The output is
+/// `[control_total, control_count, test_total, test_count]`, where the count is the number of
+/// attributed trigger reports that kept a non-zero value after capping.
+///
+/// ## Panics
+/// Will panic if you run in on Intel 80286 or any other 16 bit hardware.
+#[must_use]
+pub fn lift_in_the_clear(
+    input: &[TestRawDataRecord],
+    per_user_cap: u32,
+    attribution_window: Option<NonZeroU32>,
+) -> Vec<u32> {
+    let mut output = vec![0; 4];
+    for records_per_user in user_events(input).values() {
+        let attributed = attributed_triggers(records_per_user.iter().rev(), attribution_window);
+
+        // Like the MPC protocol, contributions are capped in chronological order.
+        let mut total_contribution = 0;
+        for (trigger_report, source_report) in attributed.into_iter().rev() {
+            let capped_contribution = std::cmp::min(
+                per_user_cap - total_contribution,
+                trigger_report.trigger_value,
+            );
+            if capped_contribution > 0 {
+                let arm = usize::try_from(source_report.breakdown_key & 1).unwrap();
+                output[2 * arm] += capped_contribution;
+                output[2 * arm + 1] += 1;
+            }
+            total_contribution += capped_contribution;
+        }
+    }
+
+    output
+}
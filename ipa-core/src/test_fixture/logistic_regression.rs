@@ -0,0 +1,111 @@
+use std::{collections::HashSet, iter::zip};
+
+use crate::{
+    ff::{
+        boolean::Boolean,
+        boolean_array::{BA64, BA8},
+        U128Conversions,
+    },
+    protocol::ipa_prf::logistic_regression::LogisticRegressionInputRow,
+    rand::Rng,
+    secret_sharing::{replicated::semi_honest::AdditiveShare as Replicated, IntoShares},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestLogisticRegressionRecord {
+    pub user_id: u64,
+    /// Features in units of `2^-8`.
+    pub features: Vec<u8>,
+    pub label: bool,
+}
+
+/// The approximation of the sigmoid function computed by the MPC protocol. The input is in
+/// units of `2^-4` and the output in units of `2^-8`.
+fn sigmoid(x: i64) -> i64 {
+    match x {
+        i64::MIN..=-113 => 0,
+        -112..=-97 => 1,
+        -96..=-81 => 2 + ((x + 96) >> 3),
+        -80..=-65 => 4 + ((x + 80) >> 2),
+        -64..=-49 => 8 + ((x + 64) >> 1),
+        -48..=-33 => 16 + (x + 48),
+        -32..=-17 => 32 + ((x + 32) << 1),
+        -16..=15 => 64 + ((x + 16) << 2),
+        16..=31 => 192 + ((x - 16) << 1),
+        32..=47 => 224 + (x - 32),
+        48..=63 => 240 + ((x - 48) >> 1),
+        64..=79 => 248 + ((x - 64) >> 2),
+        80..=95 => 252 + ((x - 80) >> 3),
+        96..=111 => 254,
+        _ => 255,
+    }
+}
+
+/// Trains a logistic regression model in the clear, that is without any MPC helpers involved in
+/// the computation. It uses the same fixed-point arithmetic as the MPC protocol, so without DP
+/// noise the weights it returns are identical.
+///
+/// Only the first record of each user is used. The MPC protocol keeps an arbitrary record of each
+/// user, so the results only match if every user's records have the same features and label.
+///
+/// ## Panics
+/// If a record does not have exactly `feature_count` features.
+#[must_use]
+pub fn logistic_regression_in_the_clear(
+    input: &[TestLogisticRegressionRecord],
+    feature_count: usize,
+    iterations: usize,
+    learning_rate_shift: usize,
+) -> Vec<i16> {
+    let mut users = HashSet::new();
+    let input = input
+        .iter()
+        .filter(|record| users.insert(record.user_id))
+        .collect::<Vec<_>>();
+
+    let mut weights = vec![0_i16; feature_count];
+    for _ in 0..iterations {
+        let mut gradients = vec![0_i64; feature_count];
+        for record in &input {
+            assert_eq!(record.features.len(), feature_count);
+            let dot_product: i64 = zip(&weights, &record.features)
+                .map(|(&w, &x)| i64::from(w) * i64::from(x))
+                .sum();
+            let prediction = sigmoid((dot_product >> 12).clamp(-128, 127));
+            let error = prediction - (i64::from(record.label) << 8);
+            for (gradient, &x) in zip(&mut gradients, &record.features) {
+                *gradient += (error * i64::from(x)) >> 8;
+            }
+        }
+        for (weight, gradient) in zip(&mut weights, gradients) {
+            // Weights saturate, like in the MPC protocol.
+            let step =
+                (-gradient >> learning_rate_shift).clamp(i64::from(i16::MIN), i64::from(i16::MAX));
+            *weight = weight.saturating_add(i16::try_from(step).unwrap());
+        }
+    }
+
+    weights
+}
+
+impl<const B: usize> IntoShares<LogisticRegressionInputRow<B>> for TestLogisticRegressionRecord {
+    fn share_with<R: Rng>(self, rng: &mut R) -> [LogisticRegressionInputRow<B>; 3] {
+        assert_eq!(self.features.len(), B);
+        let features: [BA8; B] = std::array::from_fn(|i| BA8::truncate_from(self.features[i]));
+        let features: [[Replicated<BA8>; B]; 3] = features.share_with(rng);
+        let label: [Replicated<Boolean>; 3] = Boolean::from(self.label).share_with(rng);
+        let match_key: [Replicated<BA64>; 3] = BA64::truncate_from(self.user_id).share_with(rng);
+
+        zip(match_key, zip(features, label))
+            .map(
+                |(match_key, (features, label))| LogisticRegressionInputRow {
+                    match_key,
+                    features,
+                    label,
+                },
+            )
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap()
+    }
+}
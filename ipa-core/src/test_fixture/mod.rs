@@ -11,11 +11,15 @@ mod app;
 #[cfg(feature = "in-memory-infra")]
 pub mod circuit;
 mod event_gen;
+pub mod feature_label;
 pub mod hybrid;
 pub mod hybrid_event_gen;
 pub mod ipa;
+pub mod lift;
 pub mod logging;
+pub mod logistic_regression;
 pub mod metrics;
+pub mod reach_frequency;
 #[cfg(feature = "in-memory-infra")]
 mod test_gate;
 
@@ -0,0 +1,39 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::test_fixture::ipa::TestRawDataRecord;
+
+/// Executes the reach and frequency query in the clear, that is without any MPC helpers involved
+/// in the computation. Useful to validate the output of the MPC protocol, ignoring the DP noise
+/// it may add.
+///
+/// The output has `max_breakdown` reach values, followed by the number of users that saw
+/// `1, 2, ..., frequency_cap` impressions. Users that saw more than `frequency_cap` impressions are
+/// counted in the last bucket. Trigger reports are ignored.
+///
+/// ## Panics
+/// If a source report has a breakdown key that is not less than `max_breakdown`.
+#[must_use]
+pub fn reach_frequency_in_the_clear(
+    input: &[TestRawDataRecord],
+    max_breakdown: usize,
+    frequency_cap: usize,
+) -> Vec<u32> {
+    let mut user_breakdowns = HashMap::<_, Vec<_>>::new();
+    for row in input.iter().filter(|row| !row.is_trigger_report) {
+        user_breakdowns
+            .entry(row.user_id)
+            .or_default()
+            .push(usize::try_from(row.breakdown_key).unwrap());
+    }
+
+    let mut output = vec![0; max_breakdown + frequency_cap];
+    for breakdowns in user_breakdowns.values() {
+        for breakdown in breakdowns.iter().collect::<HashSet<_>>() {
+            assert!(*breakdown < max_breakdown);
+            output[*breakdown] += 1;
+        }
+        output[max_breakdown + breakdowns.len().min(frequency_cap) - 1] += 1;
+    }
+
+    output
+}
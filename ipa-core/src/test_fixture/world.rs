@@ -243,9 +243,11 @@ impl<const SHARDS: usize, D: Distribute> TestWorld<WithShards<SHARDS, D>> {
     /// Panics if world has more or less than 3 gateways/participants
     #[must_use]
     pub fn malicious_contexts(&self) -> [Vec<ShardedMaliciousContext<'_>>; 3] {
+        // All shards must use the same gate, otherwise they can't talk to each other.
+        let gate = &self.next_gate();
         self.shards()
             .iter()
-            .map(|shard| shard.malicious_contexts(&self.next_gate()))
+            .map(|shard| shard.malicious_contexts(gate))
             .fold([Vec::new(), Vec::new(), Vec::new()], |mut acc, contexts| {
                 // Distribute contexts into the respective vectors.
                 for (vec, context) in acc.iter_mut().zip(contexts.iter()) {
@@ -254,6 +256,15 @@ impl<const SHARDS: usize, D: Distribute> TestWorld<WithShards<SHARDS, D>> {
                 acc
             })
     }
+
+    /// Returns the gateway used by `role` on the given shard.
+    ///
+    /// # Panics
+    /// If `shard` is not a valid shard index for this world.
+    #[must_use]
+    pub fn gateway(&self, shard: ShardIndex, role: Role) -> &Gateway {
+        &self.shards[usize::from(shard)].gateways[role]
+    }
 }
 
 /// Backward-compatible API for tests that don't use sharding.
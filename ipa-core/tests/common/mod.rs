@@ -206,6 +206,87 @@ pub fn test_add_in_prime_field(config_dir: &Path, https: bool, count: u32) {
     test_mpc.wait().unwrap_status();
 }
 
+/// Spawns `SHARDS` shards of each helper. Every shard gets its own helper network, generated by
+/// `test-setup` in the `shard{index}` subdirectory of `config_path`. Shards of the same helper are
+/// connected to each other over plain HTTP.
+pub fn spawn_shards<const SHARDS: usize>(config_path: &Path) -> Vec<TerminateOnDrop> {
+    let mpc_sockets: [_; SHARDS] =
+        array::from_fn(|shard| test_setup(&config_path.join(format!("shard{shard}"))));
+    let shard_sockets: [[_; SHARDS]; 3] =
+        array::from_fn(|_| array::from_fn(|_| TcpListener::bind("127.0.0.1:0").unwrap()));
+
+    for (id, sockets) in zip([1, 2, 3], &shard_sockets) {
+        let peers = sockets
+            .iter()
+            .map(|socket| {
+                let port = socket.local_addr().unwrap().port();
+                format!("[[peers]]\nurl = \"localhost:{port}\"\n")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        std::fs::write(config_path.join(format!("h{id}_shards.toml")), peers).unwrap();
+    }
+
+    (0..SHARDS)
+        .flat_map(|shard| {
+            let shard_sockets = &shard_sockets;
+            zip([1, 2, 3], &mpc_sockets[shard]).map(move |(id, socket)| {
+                let shard_socket = &shard_sockets[id - 1][shard];
+                let mut command = Command::new(HELPER_BIN);
+                command
+                    .args(["-i", &id.to_string()])
+                    .args([
+                        "--network".into(),
+                        config_path.join(format!("shard{shard}/network.toml")),
+                    ])
+                    .args([
+                        "--shard-network".into(),
+                        config_path.join(format!("h{id}_shards.toml")),
+                    ])
+                    .args(["--shard-index", &shard.to_string()])
+                    .args(["--shard-count", &SHARDS.to_string()])
+                    .arg("--disable-https")
+                    .silent();
+
+                command.preserved_fds(vec![socket.as_raw_fd(), shard_socket.as_raw_fd()]);
+                command.args(["--server-socket-fd", &socket.as_raw_fd().to_string()]);
+                command.args([
+                    "--shard-server-socket-fd",
+                    &shard_socket.as_raw_fd().to_string(),
+                ]);
+
+                let mut child = command.spawn().unwrap();
+                if let Ok(Some(status)) = child.try_wait() {
+                    panic!("Helper binary terminated early with status = {status}");
+                }
+
+                child.terminate_on_drop()
+            })
+        })
+        .collect::<Vec<_>>()
+}
+
+pub fn test_sharded_shuffle<const SHARDS: usize>(count: u32) {
+    let dir = TempDir::new_delete_on_drop();
+    let path = dir.path();
+
+    println!("generating configuration in {}", path.display());
+    let _helpers = spawn_shards::<SHARDS>(path);
+
+    let mut command = Command::new(TEST_MPC_BIN);
+    command
+        .args(["--wait", "2"])
+        .args(["--generate", &count.to_string()])
+        .arg("--disable-https")
+        .silent()
+        .arg("sharded-shuffle")
+        .arg("--shard-networks")
+        .args((0..SHARDS).map(|shard| path.join(format!("shard{shard}/network.toml"))));
+
+    let test_mpc = command.spawn().unwrap().terminate_on_drop();
+    test_mpc.wait().unwrap_status();
+}
+
 pub fn test_network<T: NetworkTest>(https: bool) {
     let dir = TempDir::new_delete_on_drop();
     let path = dir.path();
@@ -330,6 +411,32 @@ pub fn test_ipa_with_config(
         ]);
     }
 
+    command
+        .args([
+            "--oprf-padding-epsilon",
+            &config.oprf_padding_epsilon.to_string(),
+        ])
+        .args([
+            "--oprf-padding-delta",
+            &config.oprf_padding_delta.to_string(),
+        ])
+        .args([
+            "--matchkey-cardinality-cap",
+            &config.matchkey_cardinality_cap.to_string(),
+        ])
+        .args([
+            "--aggregation-padding-epsilon",
+            &config.aggregation_padding_epsilon.to_string(),
+        ])
+        .args([
+            "--aggregation-padding-delta",
+            &config.aggregation_padding_delta.to_string(),
+        ])
+        .args([
+            "--aggregation-padding-sensitivity",
+            &config.aggregation_padding_sensitivity.to_string(),
+        ]);
+
     if !https {
         // No reason that match key encryption needs to be coupled with helper-to-helper TLS, but
         // currently it is.
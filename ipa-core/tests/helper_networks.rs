@@ -3,8 +3,8 @@ mod common;
 use std::{array, net::TcpListener, path::Path, process::Command};
 
 use common::{
-    spawn_helpers, tempdir::TempDir, test_ipa, test_multiply, test_network, CommandExt,
-    UnwrapStatusExt, HELPER_BIN,
+    spawn_helpers, tempdir::TempDir, test_ipa, test_multiply, test_network, test_sharded_shuffle,
+    CommandExt, UnwrapStatusExt, HELPER_BIN,
 };
 use ipa_core::{cli::CliPaths, helpers::HelperIdentity, test_fixture::ipa::IpaSecurityModel};
 
@@ -28,6 +28,12 @@ fn http_network_add() {
     test_network::<AddInPrimeField<10>>(false);
 }
 
+#[test]
+#[cfg(all(test, web_test))]
+fn http_sharded_shuffle() {
+    test_sharded_shuffle::<2>(20);
+}
+
 /// This reproduces the faulty behaviour described in #ipa/1141 and should fail
 /// if the fix is not in place.
 #[test]
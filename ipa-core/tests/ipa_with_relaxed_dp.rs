@@ -3,7 +3,7 @@ mod common;
 
 use std::num::NonZeroU32;
 
-use common::{test_ipa, test_ipa_with_config};
+use common::test_ipa_with_config;
 use ipa_core::{helpers::query::IpaQueryConfig, test_fixture::ipa::IpaSecurityModel};
 
 fn build_config() -> IpaQueryConfig {
@@ -11,6 +11,12 @@ fn build_config() -> IpaQueryConfig {
         per_user_credit_cap: 8,
         attribution_window_seconds: NonZeroU32::new(0),
         with_dp: 0,
+        oprf_padding_epsilon: 10.0,
+        oprf_padding_delta: 1e-4,
+        matchkey_cardinality_cap: 3,
+        aggregation_padding_epsilon: 10.0,
+        aggregation_padding_delta: 1e-4,
+        aggregation_padding_sensitivity: 3,
         ..Default::default()
     }
 }
@@ -44,5 +50,5 @@ fn relaxed_dp_malicious() {
 #[test]
 #[cfg(all(test, web_test))]
 fn relaxed_dp_https_malicious_ipa() {
-    test_ipa(IpaSecurityModel::Malicious, true, true);
+    test_ipa_with_config(IpaSecurityModel::Malicious, true, build_config(), true);
 }